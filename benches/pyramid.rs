@@ -0,0 +1,141 @@
+//! Times the steps of the pyramid demo.
+//!
+//! ```text
+//! cargo bench --bench pyramid
+//! ```
+//!
+//! Also times the pair queries of the default broad phase on the boxes of the
+//! pyramid, which compare several boxes at once with `--features simd`, the
+//! broad phase of a few thousand scattered bodies and the narrow
+//! phase of a few thousand overlapping ones, which run on all cores with
+//! `--features parallel`.
+
+use std::time::Instant;
+use sylt_2d::body::Body;
+use sylt_2d::broad_phase::{BroadPhaseMode, Broadphase, BruteForce};
+use sylt_2d::math_utils::Vec2;
+use sylt_2d::world::World;
+
+const ROWS: usize = 20;
+const STEPS: u32 = 600;
+const QUERIES: u32 = 20000;
+const SCATTERED: usize = 5000;
+const GRID: usize = 70;
+
+fn pyramid() -> World {
+    let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+    let mut ground = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
+    ground.position = Vec2::new(0.0, -10.0);
+    ground.friction = 0.2;
+    world.add_body(ground);
+    for row in 0..ROWS {
+        for column in 0..ROWS - row {
+            let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            block.friction = 0.2;
+            block.position = Vec2::new(
+                column as f32 * 1.125 + row as f32 * 0.5625 - 10.0,
+                row as f32 * 1.0 + 0.5,
+            );
+            world.add_body(block);
+        }
+    }
+    world
+}
+
+// Falling bodies spread over a large area, few of them touching.
+fn scattered() -> World {
+    let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+    world.world_context.broad_phase = BroadPhaseMode::SweepAndPrune;
+    for i in 0..SCATTERED {
+        let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        block.position = Vec2::new((i % 100) as f32 * 1.5, (i / 100) as f32 * 1.5);
+        world.add_body(block);
+    }
+    world
+}
+
+// Weightless boxes overlapping their neighbours, every pair colliding. They
+// are not pushed apart, so the pairs stay.
+fn overlapping() -> World {
+    let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+    world.world_context.position_correction = false;
+    world.world_context.broad_phase = BroadPhaseMode::SweepAndPrune;
+    for i in 0..GRID * GRID {
+        let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        block.position = Vec2::new((i % GRID) as f32 * 0.9, (i / GRID) as f32 * 0.9);
+        world.add_body(block);
+    }
+    world
+}
+
+fn main() {
+    let mut world = pyramid();
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        world.step(1.0 / 60.0).unwrap();
+    }
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    println!(
+        "pyramid of {} rows: {:.3} ms per step",
+        ROWS,
+        elapsed / STEPS as f64
+    );
+    let stats = world.last_step_stats();
+    println!(
+        "last step: broad phase {:.3} ms, solve {:.3} ms, integrate {:.3} ms, {} contacts",
+        stats.broadphase_ms, stats.solve_ms, stats.integrate_ms, stats.contacts
+    );
+
+    // The pair queries alone, on the boxes of the settled pyramid
+    let mut brute_force = BruteForce::new();
+    for body in world.iter_bodies() {
+        brute_force.add(body.id, body.aabb());
+    }
+    let mut pairs = Vec::new();
+    let start = Instant::now();
+    for _ in 0..QUERIES {
+        brute_force.query_pairs(&mut pairs);
+    }
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    println!(
+        "{} pairs, simd {}: brute force query {:.4} ms",
+        pairs.len(),
+        if cfg!(feature = "simd") { "on" } else { "off" },
+        elapsed / QUERIES as f64
+    );
+
+    let mut world = scattered();
+    let mut broadphase_ms = 0.0;
+    for _ in 0..STEPS / 10 {
+        world.step(1.0 / 60.0).unwrap();
+        broadphase_ms += world.last_step_stats().broadphase_ms;
+    }
+    println!(
+        "{} scattered bodies, parallel {}: broad phase {:.3} ms per step",
+        SCATTERED,
+        if cfg!(feature = "parallel") {
+            "on"
+        } else {
+            "off"
+        },
+        broadphase_ms / (STEPS / 10) as f32
+    );
+
+    let mut world = overlapping();
+    let mut broadphase_ms = 0.0;
+    for _ in 0..STEPS / 10 {
+        world.step(1.0 / 60.0).unwrap();
+        broadphase_ms += world.last_step_stats().broadphase_ms;
+    }
+    println!(
+        "{} overlapping bodies, {} pairs, parallel {}: broad and narrow phase {:.3} ms per step",
+        GRID * GRID,
+        world.last_step_stats().pairs_tested,
+        if cfg!(feature = "parallel") {
+            "on"
+        } else {
+            "off"
+        },
+        broadphase_ms / (STEPS / 10) as f32
+    );
+}
@@ -5,11 +5,12 @@ use nannou_egui::{
     Egui,
 };
 use sylt_2d::{
-    arbiter::Contact,
+    arbiter::ContactInfo,
     body::{Body, Shape},
     collide::collide,
     collide_polygon::collide_polygons,
     math_utils::Vec2,
+    narrowphase::NarrowphaseTable,
 };
 
 fn main() {
@@ -27,7 +28,7 @@ struct Model {
     egui: Egui,
     settings: EguiSettings,
     load_demo_flag: bool,
-    contacts: Vec<Contact>,
+    contacts: Vec<ContactInfo>,
     bodies: Vec<Body>,
     is_first_frame: bool,
 }
@@ -51,7 +52,7 @@ fn model(app: &App) -> Model {
             color: WHITE,
         },
         load_demo_flag: false,
-        contacts: Vec::<Contact>::with_capacity(2),
+        contacts: Vec::<ContactInfo>::with_capacity(2),
         bodies: Vec::<Body>::with_capacity(2),
         is_first_frame: true,
     }
@@ -161,8 +162,8 @@ fn demo7(_model: &mut Model) {
         Vec2 { x: 0.95, y: 0.31 },   // Top-right vertex
     ];
 
-    let pentagon_body = Body::new_polygon(pentagon, 1.0);
-    let hexagon_body = Body::new_polygon(hexagon, 1.0);
+    let pentagon_body = Body::new_polygon(pentagon, 1.0).expect("pentagon is convex");
+    let hexagon_body = Body::new_polygon(hexagon, 1.0).expect("hexagon is convex");
 
     _model.bodies.push(pentagon_body.clone());
     _model.bodies.push(hexagon_body.clone());
@@ -185,7 +186,7 @@ fn demo8(_model: &mut Model) {
     box_a.position = pos_a;
     box_a.rotation = 45.0_f32.to_radians();
 
-    let hexagon_body = Body::new_polygon(hexagon, 1.0);
+    let hexagon_body = Body::new_polygon(hexagon, 1.0).expect("hexagon is convex");
 
     _model.bodies.push(box_a.clone());
     _model.bodies.push(hexagon_body.clone());
@@ -275,14 +276,7 @@ fn key_pressed(_app: &App, model: &mut Model, key: Key) {
             model.contacts.clear();
             let body1 = model.bodies.first().unwrap();
             let body2 = model.bodies.get(1).unwrap();
-            match (body1.shape, body2.shape) {
-                (Shape::Box, Shape::Box) => {
-                    let _ = collide(&mut model.contacts, body1, body2);
-                }
-                _ => {
-                    let _ = collide_polygons(&mut model.contacts, body1, body2);
-                }
-            }
+            let _ = NarrowphaseTable::builtin().collide(&mut model.contacts, body1, body2);
             println!("Contacts {:?}", model.contacts);
         }
         _other_key => {}
@@ -315,29 +309,58 @@ fn view(app: &App, _model: &Model, frame: Frame) {
                     .x_y(body.position.x, body.position.y)
                     .points(tuples);
             }
-        }
-    }
-
-    for contact in _model.contacts.iter() {
-        match contact {
-            Some(contact) => {
+            Shape::Capsule => {
+                let vertices = body.world_vertices();
+                draw.line()
+                    .start(pt2(vertices[0].x, vertices[0].y))
+                    .end(pt2(vertices[1].x, vertices[1].y))
+                    .weight(2.0 * body.radius)
+                    .caps_round()
+                    .color(if num == 0 { DARKSEAGREEN } else { ORCHID });
+            }
+            Shape::Circle => {
                 draw.ellipse()
-                    .x_y(contact.position.x, contact.position.y)
-                    .radius(0.1)
-                    .color(settings.color);
-                draw.arrow()
-                    .start(pt2(contact.position.x, contact.position.y))
+                    .x_y(body.position.x, body.position.y)
+                    .radius(body.radius)
+                    .color(if num == 0 { DARKSEAGREEN } else { ORCHID });
+                draw.line()
+                    .start(pt2(body.position.x, body.position.y))
                     .end(pt2(
-                        contact.position.x + contact.normal.x,
-                        contact.position.y + contact.normal.y,
+                        body.position.x + body.radius * body.rotation.cos(),
+                        body.position.y + body.radius * body.rotation.sin(),
                     ))
                     .weight(0.05)
-                    .color(LIGHTSALMON);
+                    .color(SLATEGREY);
+            }
+            Shape::Chain | Shape::Heightfield => {
+                let points: Vec<Point2> = body
+                    .world_vertices()
+                    .into_iter()
+                    .map(|point| pt2(point.x, point.y))
+                    .collect();
+                draw.polyline()
+                    .weight(0.1)
+                    .points(points)
+                    .color(if num == 0 { DARKSEAGREEN } else { ORCHID });
             }
-            None => (),
         }
     }
 
+    for contact in _model.contacts.iter() {
+        draw.ellipse()
+            .x_y(contact.position.x, contact.position.y)
+            .radius(0.1)
+            .color(settings.color);
+        draw.arrow()
+            .start(pt2(contact.position.x, contact.position.y))
+            .end(pt2(
+                contact.position.x + contact.normal.x,
+                contact.position.y + contact.normal.y,
+            ))
+            .weight(0.05)
+            .color(LIGHTSALMON);
+    }
+
     /*if !_model.clipped_vertices.is_empty() {
         let clipped = _model.clipped_vertices.clone();
         draw.polygon()
@@ -1,9 +1,11 @@
 use nannou::prelude::*;
 use nannou_egui::{self, egui, Egui};
 use sylt_2d::body::{Body, ConvexPolygon, Shape};
-use sylt_2d::joint::Joint;
-use sylt_2d::math_utils::{Mat2x2, Vec2};
-use sylt_2d::world::World;
+use sylt_2d::joint::{Joint, JointBuilder, JointKind};
+use sylt_2d::math_utils::Vec2;
+use sylt_2d::scene::Scene;
+use sylt_2d::stepper::FixedStepper;
+use sylt_2d::world::{FloorFriction, World};
 fn main() {
     nannou::app(model).update(update).run();
 }
@@ -17,6 +19,7 @@ struct EguiSettings {
 struct Model {
     _window: window::Id,
     time_step: f32,
+    stepper: FixedStepper,
     demo_index: u32,
     world: World,
     bomb: bool,
@@ -43,6 +46,7 @@ fn model(app: &App) -> Model {
         demo_index: 0,
         bomb: false,
         time_step: 1.0 / 60.0,
+        stepper: FixedStepper::new(1.0 / 60.0),
         egui,
         settings: EguiSettings {
             scale: 18.0,
@@ -60,6 +64,7 @@ fn launch_bomb(model: &mut Model) {
     bomb.rotation = random_range(-1.5, 1.5);
     bomb.velocity = bomb.position * -1.5;
     bomb.angular_velocity = random_range(-20.0, 20.0);
+    bomb.bullet = true;
     model.world.add_body(bomb.clone());
 }
 
@@ -90,8 +95,8 @@ fn demo1(_model: &mut Model) {
         Vec2 { x: 0.59, y: -0.81 },  // Bottom-right vertex
         Vec2 { x: 0.95, y: 0.31 },   // Top-right vertex
     ];
-    let mut pentagon_body = Body::new_polygon(pentagon, 2.0);
-    let mut hexagon_body = Body::new_polygon(hexagon, 2.0);
+    let mut pentagon_body = Body::new_polygon(pentagon, 2.0).expect("pentagon is convex");
+    let mut hexagon_body = Body::new_polygon(hexagon, 2.0).expect("hexagon is convex");
     pentagon_body.position = Vec2::new(0.0, 5.0);
     pentagon_body.friction = 100.0;
     hexagon_body.position = Vec2::new(5.0, 4.0);
@@ -101,21 +106,9 @@ fn demo1(_model: &mut Model) {
 }
 
 fn demo2(model: &mut Model) {
-    // Simple Pendulum
-    let mut body1 = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
-    body1.friction = 0.2;
-    body1.position = Vec2::new(0.0, -0.5 * body1.width.y);
-    body1.rotation = 0.0;
-    model.world.add_body(body1.clone());
-
-    let mut body2 = Body::new(Vec2::new(1.0, 1.0), 100.0);
-    body2.friction = 0.2;
-    body2.position = Vec2::new(9.0, 11.0);
-    body2.rotation = 0.0;
-    model.world.add_body(body2.clone());
-
-    let joint = Joint::new(body1, body2, Vec2::new(0.0, 11.0), &model.world);
-    model.world.add_joint(joint);
+    // Simple Pendulum, loaded from a scene file
+    let scene = Scene::from_ron(include_str!("../scenes/pendulum.ron")).expect("scene is valid");
+    scene.populate(&mut model.world).expect("scene is valid");
 }
 
 fn demo3(model: &mut Model) {
@@ -155,37 +148,20 @@ fn demo4(model: &mut Model) {
 }
 
 fn demo5(model: &mut Model) {
-    // Pyramid
-    let mut ground = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
-    ground.friction = 0.2;
-    ground.position = Vec2::new(0.0, -0.5 * ground.width.y);
-    model.world.add_body(ground.clone());
-
-    let mut x = Vec2::new(-6.0, 0.75);
-    for i in 0..12 {
-        let mut y = x;
-        for _j in i..12 {
-            let mut body = Body::new(Vec2::new(1.0, 1.0), 10.0);
-            body.friction = 0.2;
-            body.position = y;
-            model.world.add_body(body.clone());
-
-            y.x += 1.125;
-        }
-        x.x += 0.5625;
-        x.y += 2.0;
-    }
+    // Pyramid, spawned from a scene file
+    let scene = Scene::from_toml(include_str!("../scenes/pyramid.toml")).expect("scene is valid");
+    scene.populate(&mut model.world).expect("scene is valid");
 }
 
 fn demo6(model: &mut Model) {
     // A Teeter
     let mut body1 = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
     body1.position = Vec2::new(0.0, -0.5 * body1.width.y);
-    model.world.add_body(body1.clone());
+    let body1 = model.world.add_body(body1);
 
     let mut body2 = Body::new(Vec2::new(12.0, 0.25), 10.0);
     body2.position = Vec2::new(0.0, 3.0);
-    model.world.add_body(body2.clone());
+    let body2 = model.world.add_body(body2);
 
     let mut body3 = Body::new(Vec2::new(0.5, 0.5), 2.0);
     body3.position = Vec2::new(-5.0, 5.0);
@@ -199,7 +175,8 @@ fn demo6(model: &mut Model) {
     body5.position = Vec2::new(5.5, 15.0);
     model.world.add_body(body5.clone());
 
-    let joint = Joint::new(body1, body2, Vec2::new(0.0, 3.0), &model.world);
+    let joint = Joint::new(body1, body2, Vec2::new(0.0, 3.0), &model.world)
+        .expect("bodies are in the world");
     model.world.add_joint(joint);
 }
 
@@ -207,7 +184,7 @@ fn demo7(model: &mut Model) {
     let mut ground = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
     ground.friction = 0.2;
     ground.position = Vec2::new(0.0, -0.5 * ground.width.y);
-    model.world.add_body(ground.clone());
+    let ground = model.world.add_body(ground);
 
     let num_planks = 15;
     let mass = 10.0;
@@ -224,14 +201,15 @@ fn demo7(model: &mut Model) {
         let mut plank = Body::new(Vec2::new(1.0, 0.25), mass);
         plank.friction = 0.2;
         plank.position = Vec2::new(-8.5 + 1.25 * i as f32, 5.0);
-        model.world.add_body(plank.clone());
+        let plank = model.world.add_body(plank);
 
         let mut joint = Joint::new(
-            plank.clone(),
-            ground.clone(),
+            plank,
+            ground,
             Vec2::new(-9.125 + 1.25 * i as f32, 5.0),
             &model.world,
-        );
+        )
+        .expect("bodies are in the world");
         joint.softness = softness;
         joint.bias_factor = bias_factor;
         model.world.add_joint(joint);
@@ -242,7 +220,7 @@ fn demo7(model: &mut Model) {
 fn demo8(model: &mut Model) {
     let mut b1 = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
     b1.position = Vec2::new(0.0, -0.5 * b1.width.y);
-    model.world.add_body(b1.clone());
+    let b1 = model.world.add_body(b1);
 
     let mut b = Body::new(Vec2::new(12.0, 0.5), f32::MAX);
     b.position = Vec2::new(-1.5, 10.0);
@@ -262,37 +240,41 @@ fn demo8(model: &mut Model) {
 
     let mut b2 = Body::new(Vec2::new(0.5, 3.0), f32::MAX);
     b2.position = Vec2::new(-7.0, 4.0);
-    model.world.add_body(b2.clone());
+    let b2 = model.world.add_body(b2);
 
     let mut b3 = Body::new(Vec2::new(12.0, 0.25), 10.0);
     b3.position = Vec2::new(-0.9, 1.0);
-    model.world.add_body(b3.clone());
+    let b3 = model.world.add_body(b3);
 
-    let joint1 = Joint::new(b1.clone(), b3, Vec2::new(-2.0, 3.0), &model.world);
+    let joint1 =
+        Joint::new(b1, b3, Vec2::new(-2.0, 3.0), &model.world).expect("bodies are in the world");
     model.world.add_joint(joint1);
 
     let mut b4 = Body::new(Vec2::new(0.5, 0.5), 16.0);
     b4.position = Vec2::new(-10.0, 15.0);
     b4.rotation = 0.0;
     b4.friction = 0.2;
-    model.world.add_body(b4.clone());
+    let b4 = model.world.add_body(b4);
 
-    let joint2 = Joint::new(b2, b4, Vec2::new(-7.0, 15.0), &model.world);
+    let joint2 =
+        Joint::new(b2, b4, Vec2::new(-7.0, 15.0), &model.world).expect("bodies are in the world");
     model.world.add_joint(joint2);
 
     let mut b5 = Body::new(Vec2::new(2.0, 2.0), 10.0);
     b5.position = Vec2::new(6.0, 2.5);
     b5.friction = 0.1;
-    model.world.add_body(b5.clone());
+    let b5 = model.world.add_body(b5);
 
-    let joint3 = Joint::new(b1, b5.clone(), Vec2::new(6.0, 2.6), &model.world);
+    let joint3 =
+        Joint::new(b1, b5, Vec2::new(6.0, 2.6), &model.world).expect("bodies are in the world");
     model.world.add_joint(joint3);
 
     let mut b6 = Body::new(Vec2::new(2.0, 0.2), 10.0);
     b6.position = Vec2::new(6.0, 3.6);
-    model.world.add_body(b6.clone());
+    let b6 = model.world.add_body(b6);
 
-    let joint4 = Joint::new(b5, b6, Vec2::new(7.0, 3.5), &model.world);
+    let joint4 =
+        Joint::new(b5, b6, Vec2::new(7.0, 3.5), &model.world).expect("bodies are in the world");
     model.world.add_joint(joint4);
 }
 
@@ -304,35 +286,26 @@ fn demo9(model: &mut Model) {
     ground.rotation = 0.0;
     model.world.add_body(ground.clone());
 
-    let mut b1 = ground;
-    let mass = 10.0;
-    let frequency_hz = 4.0;
-    let damping_ratio = 0.7;
-
-    let omega = 2.0 * std::f32::consts::PI * frequency_hz;
-    let d = 2.0 * mass * damping_ratio * omega;
-    let k = mass * omega * omega;
-
-    let time_step = model.time_step;
-    let softness = 1.0 / (d + time_step * k);
-    let bias_factor = time_step * k / (d + time_step * k);
-
     let y = 12.0;
-
-    for i in 0..15 {
-        let mut pendulum = Body::new(Vec2::new(0.75, 0.25), mass);
-        pendulum.friction = 0.2;
-        pendulum.position = Vec2::new(0.5 + i as f32, y);
-        pendulum.rotation = 0.0;
-        model.world.add_body(pendulum.clone());
-
-        let mut joint = Joint::new(b1, pendulum.clone(), Vec2::new(i as f32, y), &model.world);
-        joint.softness = softness;
-        joint.bias_factor = bias_factor;
-        model.world.add_joint(joint);
-
-        b1 = pendulum;
+    let links = model.world.create_chain(
+        Vec2::new(0.0, y),
+        Vec2::new(15.0, y),
+        15,
+        Vec2::new(0.75, 0.25),
+        10.0,
+        model.time_step,
+    );
+    for &link in &links {
+        model.world.body_mut(link).unwrap().friction = 0.2;
     }
+    // Hang it up with the same spring as the links
+    let spring = model.world.iter_joints().last().unwrap().clone();
+    JointBuilder::revolute(ground.handle(), links[0])
+        .anchor(Vec2::new(0.0, y))
+        .softness(spring.softness)
+        .bias_factor(spring.bias_factor)
+        .build(&mut model.world)
+        .unwrap();
 }
 
 // A Pawn and the pendulum
@@ -366,11 +339,13 @@ fn demo10(_model: &mut Model) {
     pawn_trunk.scale(2.0);
     let mut body1 = Body::new(Vec2::new(1000.0, 20.0), f32::MAX);
     body1.position = Vec2::new(0.0, -0.5 * body1.width.y);
-    _model.world.add_body(body1.clone());
+    let body1 = _model.world.add_body(body1);
 
-    let mut pentagon_body = Body::new_polygon(pentagon, 55.0);
-    let mut pawn_head = Body::new_polygon(pawn_head.get_vertices(), 10.0);
-    let mut pawn_body = Body::new_polygon(pawn_trunk.get_vertices(), 10.0);
+    let mut pentagon_body = Body::new_polygon(pentagon, 55.0).expect("pentagon is convex");
+    let mut pawn_head =
+        Body::new_polygon(pawn_head.get_vertices(), 10.0).expect("pawn head is convex");
+    let mut pawn_body =
+        Body::new_polygon(pawn_trunk.get_vertices(), 10.0).expect("pawn trunk is convex");
     pentagon_body.friction = 0.2;
     pentagon_body.position = Vec2::new(-9.0, 8.0);
     pentagon_body.rotation = 0.0;
@@ -380,16 +355,51 @@ fn demo10(_model: &mut Model) {
         pawn_body.position.x,
         pawn_body.position.y + 0.5 * pawn_body.width.y + 0.5 * pawn_head.width.y,
     );
-    _model.world.add_body(pentagon_body.clone());
-    _model.world.add_body(pawn_head.clone());
-    _model.world.add_body(pawn_body.clone());
-    let joint3 = Joint::new(pawn_head, pawn_body, Vec2::new(5.0, 3.0), &_model.world);
+    let pentagon_body = _model.world.add_body(pentagon_body);
+    let pawn_head = _model.world.add_body(pawn_head);
+    let pawn_body = _model.world.add_body(pawn_body);
+    let joint3 = Joint::new(pawn_head, pawn_body, Vec2::new(5.0, 3.0), &_model.world)
+        .expect("bodies are in the world");
     _model.world.add_joint(joint3);
 
-    let joint = Joint::new(body1, pentagon_body, Vec2::new(0.0, 11.0), &_model.world);
+    let joint = Joint::new(body1, pentagon_body, Vec2::new(0.0, 11.0), &_model.world)
+        .expect("bodies are in the world");
     _model.world.add_joint(joint);
 }
 
+// Pucks sliding on a table seen from above
+fn demo11(model: &mut Model) {
+    model.world.set_gravity(Vec2::default());
+    model.world.world_context.floor_friction = Some(FloorFriction::default());
+
+    let walls = [
+        (Vec2::new(0.0, 12.0), Vec2::new(40.0, 1.0)),
+        (Vec2::new(0.0, -12.0), Vec2::new(40.0, 1.0)),
+        (Vec2::new(-20.0, 0.0), Vec2::new(1.0, 25.0)),
+        (Vec2::new(20.0, 0.0), Vec2::new(1.0, 25.0)),
+    ];
+    for (position, size) in walls {
+        let mut wall = Body::new(size, f32::MAX);
+        wall.position = position;
+        model.world.add_body(wall);
+    }
+
+    for i in 0..5 {
+        let mut puck = Body::new(Vec2::new(1.5, 1.5), 5.0);
+        puck.friction = 0.3;
+        puck.restitution = 0.8;
+        puck.position = Vec2::new(5.0 + 2.0 * i as f32, -4.0 + 2.0 * i as f32);
+        model.world.add_body(puck);
+    }
+    let mut striker = Body::new(Vec2::new(2.0, 2.0), 10.0);
+    striker.friction = 0.3;
+    striker.restitution = 0.8;
+    striker.position = Vec2::new(-15.0, 0.0);
+    striker.velocity = Vec2::new(30.0, 2.0);
+    striker.angular_velocity = 5.0;
+    model.world.add_body(striker);
+}
+
 fn update(_app: &App, _model: &mut Model, _update: Update) {
     if _model.is_first_frame {
         let step = _model.world.step(_model.time_step);
@@ -400,7 +410,9 @@ fn update(_app: &App, _model: &mut Model, _update: Update) {
         load_demo(_model);
         _model.is_first_frame = false;
     }
-    let step = _model.world.step(_model.time_step);
+    // Physics runs at 60 Hz whatever the frame rate
+    let frame_time = _update.since_last.as_secs_f32();
+    let step = _model.stepper.advance(&mut _model.world, frame_time);
     if let Err(e) = step {
         eprintln!("Error: {}", e);
     }
@@ -431,6 +443,7 @@ fn update(_app: &App, _model: &mut Model, _update: Update) {
         "Demo 8: Dominos",
         "Demo 9: Multi-pendulum",
         "Demo 10: A Pawn and the pendulum",
+        "Demo 11: Top-down pucks",
     ];
     egui::Window::new("Settings").show(&ctx, |ui| {
         // Dropdown for selecting the demo
@@ -480,6 +493,8 @@ fn update(_app: &App, _model: &mut Model, _update: Update) {
 
 fn load_demo(model: &mut Model) {
     model.world.clear(); // Clear the current world bodies and joints
+    model.world.set_gravity(Vec2::new(0.0, -10.0));
+    model.world.world_context.floor_friction = None;
 
     match model.demo_index {
         0 => demo1(model),
@@ -492,6 +507,7 @@ fn load_demo(model: &mut Model) {
         7 => demo8(model),
         8 => demo9(model),
         9 => demo10(model),
+        10 => demo11(model),
         _ => {}
     }
 }
@@ -509,9 +525,12 @@ fn key_pressed(_app: &App, model: &mut Model, key: Key) {
             let _ = model.world.step(-model.time_step);
         }
         Key::Return => {
-            println!("Number of bodies {:?}", model.world.bodies.len());
-            println!("World Bodies: {:?}", model.world.bodies);
-            println!("{:?}", model.world.arbiters);
+            println!("Number of bodies {:?}", model.world.body_count());
+            println!(
+                "World Bodies: {:?}",
+                model.world.iter_bodies().collect::<Vec<_>>()
+            );
+            println!("{:?}", model.world.iter_arbiters().collect::<Vec<_>>());
         }
         _other_key => {}
     }
@@ -544,37 +563,69 @@ fn view(app: &App, _model: &Model, frame: Frame) {
                     .rotate(body.rotation)
                     .points(tuples);
             }
+            Shape::Capsule => {
+                let vertices = body.world_vertices();
+                draw.line()
+                    .start(pt2(vertices[0].x, vertices[0].y))
+                    .end(pt2(vertices[1].x, vertices[1].y))
+                    .weight(2.0 * body.radius)
+                    .caps_round()
+                    .color(if num == 0 { DARKSEAGREEN } else { ORCHID });
+            }
+            Shape::Circle => {
+                draw.ellipse()
+                    .x_y(body.position.x, body.position.y)
+                    .radius(body.radius)
+                    .color(if num == 0 { DARKSEAGREEN } else { ORCHID });
+                draw.line()
+                    .start(pt2(body.position.x, body.position.y))
+                    .end(pt2(
+                        body.position.x + body.radius * body.rotation.cos(),
+                        body.position.y + body.radius * body.rotation.sin(),
+                    ))
+                    .weight(0.05)
+                    .color(SLATEGREY);
+            }
+            Shape::Chain | Shape::Heightfield => {
+                let points: Vec<Point2> = body
+                    .world_vertices()
+                    .into_iter()
+                    .map(|point| pt2(point.x, point.y))
+                    .collect();
+                draw.polyline()
+                    .weight(0.1)
+                    .points(points)
+                    .color(if num == 0 { DARKSEAGREEN } else { ORCHID });
+            }
         }
     }
 
-    for (_, arbiter) in _model.world.arbiters.iter() {
-        for contact in arbiter.contacts.iter() {
-            match contact {
-                Some(contact) => {
-                    draw.ellipse()
-                        .x_y(contact.position.x, contact.position.y)
-                        .radius(0.1)
-                        .color(settings.color);
-                    draw.arrow()
-                        .start(pt2(contact.position.x, contact.position.y))
-                        .end(pt2(
-                            contact.position.x + contact.normal.x,
-                            contact.position.y + contact.normal.y,
-                        ))
-                        .weight(0.05)
-                        .color(LIGHTSALMON);
-                }
-                None => (),
-            }
+    for (_, arbiter) in _model.world.iter_arbiters() {
+        for contact in arbiter.manifold.points.iter() {
+            draw.ellipse()
+                .x_y(contact.position.x, contact.position.y)
+                .radius(0.1)
+                .color(settings.color);
+            draw.arrow()
+                .start(pt2(contact.position.x, contact.position.y))
+                .end(pt2(
+                    contact.position.x + contact.normal.x,
+                    contact.position.y + contact.normal.y,
+                ))
+                .weight(0.05)
+                .color(LIGHTSALMON);
         }
     }
-    for joint in _model.world.joints.iter() {
-        let x1 = joint.body_1.borrow().position;
-        let x2 = joint.body_2.borrow().position;
-        let r1 = Mat2x2::new_from_angle(joint.body_1.borrow().rotation);
-        let r2 = Mat2x2::new_from_angle(joint.body_2.borrow().rotation);
-        let p1 = x1 + r1 * joint.local_anchor_1;
-        let p2 = x2 + r2 * joint.local_anchor_2;
+    for joint in _model.world.iter_joints() {
+        let body_1 = _model.world.iter_bodies().find(|body| body.id == joint.body_1);
+        let body_2 = _model.world.iter_bodies().find(|body| body.id == joint.body_2);
+        let (Some(body_1), Some(body_2)) = (body_1, body_2) else {
+            continue;
+        };
+        let x1 = body_1.position;
+        let x2 = body_2.position;
+        let p1 = body_1.local_to_world(joint.local_anchor_1);
+        let p2 = body_2.local_to_world(joint.local_anchor_2);
         draw.line()
             .start(pt2(x1.x, x1.y))
             .end(pt2(p1.x, p1.y))
@@ -585,6 +636,21 @@ fn view(app: &App, _model: &Model, frame: Frame) {
             .end(pt2(p2.x, p2.y))
             .weight(0.05)
             .color(SLATEBLUE);
+        let ropes = match joint.kind() {
+            JointKind::Distance(_) => vec![(p1, p2)],
+            JointKind::Pulley(pulley) => {
+                let ground_anchors = pulley.ground_anchors;
+                vec![(ground_anchors.0, p1), (ground_anchors.1, p2)]
+            }
+            _ => vec![],
+        };
+        for (start, end) in ropes {
+            draw.line()
+                .start(pt2(start.x, start.y))
+                .end(pt2(end.x, end.y))
+                .weight(0.05)
+                .color(SLATEBLUE);
+        }
     }
     draw.to_frame(app, &frame).unwrap();
     _model.egui.draw_to_frame(&frame).unwrap();
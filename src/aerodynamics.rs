@@ -0,0 +1,195 @@
+use crate::body::Body;
+use crate::math_utils::{Cross, Mat2x2, Vec2};
+
+/// Flat-plate lift and drag model.
+///
+/// The plate lies along the local x axis of the body. The angle of attack is the
+/// angle between the plate and the velocity at the center of pressure:
+///
+/// * lift coefficient `C_L = lift_coefficient * sin(2 * alpha)`
+/// * drag coefficient `C_D = skin_drag + drag_coefficient * sin(alpha)^2`
+///
+/// Placing the center of pressure behind the center of mass makes the body turn
+/// into the wind like an arrow.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aerodynamics {
+    /// Area of the plate facing the air. Per meter of depth of the 2D world,
+    /// i.e. the chord length of the plate.
+    pub area: f32,
+    pub lift_coefficient: f32,
+    pub drag_coefficient: f32,
+    /// Drag when the plate moves edge-on.
+    pub skin_drag: f32,
+    /// Point where the forces are applied, in body coordinates.
+    pub center_of_pressure: Vec2,
+}
+
+impl Default for Aerodynamics {
+    fn default() -> Self {
+        Self {
+            area: 1.0,
+            lift_coefficient: 1.0,
+            drag_coefficient: 2.0,
+            skin_drag: 0.02,
+            center_of_pressure: Vec2::new(0.0, 0.0),
+        }
+    }
+}
+
+impl Aerodynamics {
+    /// Returns a flat plate with the given area and default coefficients.
+    pub fn flat_plate(area: f32) -> Self {
+        Self {
+            area,
+            ..Default::default()
+        }
+    }
+
+    /// Computes the aerodynamic force and the torque about the center of mass.
+    pub fn force_and_torque(&self, body: &Body, air_density: f32) -> (Vec2, f32) {
+        self.force_and_torque_in_wind(body, air_density, Vec2::default())
+    }
+
+    /// Like `force_and_torque`, with the air moving at `wind`.
+    pub fn force_and_torque_in_wind(
+        &self,
+        body: &Body,
+        air_density: f32,
+        wind: Vec2,
+    ) -> (Vec2, f32) {
+        let rot = Mat2x2::new_from_angle(body.rotation);
+        let r = rot * self.center_of_pressure;
+        let v = body.velocity + body.angular_velocity.cross(r) - wind;
+        let speed = v.length();
+        if speed < f32::EPSILON {
+            return (Vec2::default(), 0.0);
+        }
+        let dir = v * (1.0 / speed);
+        let chord = rot.col1;
+        let alpha = f32::atan2(chord.cross(dir), chord.dot(dir));
+
+        let c_l = self.lift_coefficient * f32::sin(2.0 * alpha);
+        let c_d = self.skin_drag + self.drag_coefficient * f32::sin(alpha).powi(2);
+        let q = 0.5 * air_density * speed * speed * self.area;
+
+        let lift = Vec2::new(-dir.y, dir.x) * (-c_l * q);
+        let drag = dir * (-c_d * q);
+        let force = lift + drag;
+        (force, r.cross(force))
+    }
+}
+
+/// Wind blowing through the world, see `WorldContext::wind`.
+///
+/// Pushes the bodies with a drag proportional to their velocity relative to the
+/// air. Bodies with an `Aerodynamics` model feel it through their model instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Wind {
+    /// Mean velocity of the air.
+    pub velocity: Vec2,
+    /// Largest change of the velocity by gusts, which swing around `velocity`.
+    pub gust: Vec2,
+    /// Gusts per second.
+    pub gust_frequency: f32,
+    /// Force per m/s of relative velocity, see `Body::wind_drag` to change it
+    /// for single bodies.
+    pub drag: f32,
+}
+
+impl Wind {
+    /// Returns a steady wind.
+    pub fn constant(velocity: Vec2, drag: f32) -> Self {
+        Self {
+            velocity,
+            drag,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the velocity of the air `time` seconds into the simulation.
+    pub fn velocity_at(&self, time: f32) -> Vec2 {
+        let phase = 2.0 * std::f32::consts::PI * self.gust_frequency * time;
+        self.velocity + self.gust * phase.sin()
+    }
+
+    /// Returns the force of the wind on the body at `time`.
+    pub fn force(&self, body: &Body, time: f32) -> Vec2 {
+        let drag = body.wind_drag.unwrap_or(self.drag);
+        (self.velocity_at(time) - body.velocity) * drag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_force_at_rest() {
+        let body = Body::new(Vec2::new(1.0, 0.1), 1.0);
+        let (force, torque) = Aerodynamics::flat_plate(1.0).force_and_torque(&body, 1.2);
+        assert_eq!(force, Vec2::default());
+        assert_eq!(torque, 0.0);
+    }
+
+    #[test]
+    fn test_broadside_drag_and_glide_lift() {
+        let model = Aerodynamics::flat_plate(1.0);
+        let mut body = Body::new(Vec2::new(1.0, 0.1), 1.0);
+
+        // Falling flat: pure drag opposing the motion
+        body.velocity = Vec2::new(0.0, -10.0);
+        let (force, _) = model.force_and_torque(&body, 1.2);
+        assert!(force.y > 0.0);
+        assert!(force.x.abs() < 1e-3);
+
+        // Gliding forward while sinking: lift pushes up and drag pushes back
+        body.velocity = Vec2::new(10.0, -1.0);
+        let (force, _) = model.force_and_torque(&body, 1.2);
+        assert!(force.y > 0.0);
+        assert!(force.x < 0.0);
+
+        // Edge-on motion only sees skin drag
+        body.velocity = Vec2::new(10.0, 0.0);
+        let (force, _) = model.force_and_torque(&body, 1.2);
+        assert!((force.x + 0.5 * 1.2 * 100.0 * model.skin_drag).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_arrow_turns_into_the_wind() {
+        let model = Aerodynamics {
+            center_of_pressure: Vec2::new(-0.4, 0.0),
+            ..Aerodynamics::flat_plate(1.0)
+        };
+        let mut body = Body::new(Vec2::new(1.0, 0.05), 1.0);
+        // Arrow pointing along +x but moving up and to the right
+        body.velocity = Vec2::new(10.0, 5.0);
+        let (_, torque) = model.force_and_torque(&body, 1.2);
+        assert!(torque > 0.0);
+    }
+
+    #[test]
+    fn test_wind() {
+        let wind = Wind {
+            velocity: Vec2::new(5.0, 0.0),
+            gust: Vec2::new(2.0, 0.0),
+            gust_frequency: 0.25,
+            drag: 0.5,
+        };
+        assert_eq!(wind.velocity_at(0.0), Vec2::new(5.0, 0.0));
+        assert_eq!(wind.velocity_at(1.0), Vec2::new(7.0, 0.0));
+
+        let mut body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        body.velocity = Vec2::new(1.0, 0.0);
+        assert_eq!(wind.force(&body, 0.0), Vec2::new(2.0, 0.0));
+        body.wind_drag = Some(0.0);
+        assert_eq!(wind.force(&body, 0.0), Vec2::default());
+
+        // A plate moving with the wind feels no force
+        body.velocity = Vec2::new(5.0, 0.0);
+        let (force, _) =
+            Aerodynamics::flat_plate(1.0).force_and_torque_in_wind(&body, 1.2, body.velocity);
+        assert_eq!(force, Vec2::default());
+    }
+}
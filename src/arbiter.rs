@@ -1,30 +1,13 @@
-use crate::body::Shape;
-use crate::collide_polygon::collide_polygons;
 use crate::math_utils::Cross;
+use crate::narrowphase::NarrowphaseTable;
+use crate::scratch::{ClipScratch, Scratch};
+use crate::solver::{PositionSolver, Softness, MAX_PUSHOUT_VELOCITY};
 use crate::world::WorldContext;
-use crate::{body::Body, collide::collide, math_utils::Vec2};
-use std::cell::RefCell;
-use std::fmt;
-use std::rc::Rc;
-
-#[derive(Debug)]
-pub enum ArbiterErrors {
-    NoOldContactFound,
-    NoNewContactFound,
-}
-
-impl fmt::Display for ArbiterErrors {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            ArbiterErrors::NoOldContactFound => write!(f, "No old contacts found."),
-            ArbiterErrors::NoNewContactFound => write!(f, "No new contacts found."),
-        }
-    }
-}
-
-impl std::error::Error for ArbiterErrors {}
+use crate::{body::Body, math_utils::Vec2};
+use std::ops::{Deref, DerefMut};
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EdgeNumbers {
     NoEdge = 0,
     Edge1,
@@ -34,6 +17,7 @@ pub enum EdgeNumbers {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Edges {
     pub in_edge_1: EdgeNumbers,
     pub out_edge_1: EdgeNumbers,
@@ -52,7 +36,18 @@ impl Default for Edges {
     }
 }
 
+impl Edges {
+    /// Packs the four edge numbers into a feature id.
+    pub fn key(&self) -> i32 {
+        (self.in_edge_1 as i32)
+            | (self.out_edge_1 as i32) << 4
+            | (self.in_edge_2 as i32) << 8
+            | (self.out_edge_2 as i32) << 12
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FeaturePair {
     pub edges: Edges,
     pub value: i32,
@@ -64,9 +59,8 @@ impl FeaturePair {
     }
 }
 
-pub type Contact = Option<ContactInfo>;
-
 #[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ContactInfo {
     pub position: Vec2,
     pub normal: Vec2,
@@ -79,10 +73,120 @@ pub struct ContactInfo {
     pub mass_normal: f32,
     pub mass_tangent: f32,
     pub bias: f32,
+    pub velocity_bias: f32, // separating velocity from restitution
+    pub max_pn: f32,        // largest normal impulse of the substeps
     pub feature: FeaturePair,
 }
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+/// Maximum number of contact points an arbiter keeps.
+pub const MAX_CONTACTS: usize = 2;
+
+/// Contact points of an arbiter, stored inline so arbiters never allocate.
+///
+/// Dereferences to a slice of the points that are in use.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Contacts {
+    points: [ContactInfo; MAX_CONTACTS],
+    len: usize,
+}
+
+impl Contacts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the points found by the narrowphase. If there are more than
+    /// `MAX_CONTACTS`, the deepest point and the one farthest away from it are kept.
+    pub fn from_points(points: &[ContactInfo]) -> Self {
+        let mut contacts = Self::new();
+        let Some(deepest) = points
+            .iter()
+            .min_by(|a, b| a.separation.total_cmp(&b.separation))
+        else {
+            return contacts;
+        };
+        if points.len() <= MAX_CONTACTS {
+            for point in points.iter() {
+                contacts.push(*point);
+            }
+            return contacts;
+        }
+        contacts.push(*deepest);
+        if let Some(farthest) = points.iter().max_by(|a, b| {
+            let distance_a = (a.position - deepest.position).length();
+            let distance_b = (b.position - deepest.position).length();
+            distance_a.total_cmp(&distance_b)
+        }) {
+            contacts.push(*farthest);
+        }
+        contacts
+    }
+
+    /// Adds a point. Returns false if the storage is already full.
+    pub fn push(&mut self, contact: ContactInfo) -> bool {
+        if self.len == MAX_CONTACTS {
+            return false;
+        }
+        self.points[self.len] = contact;
+        self.len += 1;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Deref for Contacts {
+    type Target = [ContactInfo];
+    fn deref(&self) -> &Self::Target {
+        &self.points[..self.len]
+    }
+}
+
+impl DerefMut for Contacts {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.points[..self.len]
+    }
+}
+
+/// Contact points of a touching pair together with their common normal.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifold {
+    pub points: Contacts,
+    /// Normal of the deepest point, pointing from the first body to the second.
+    pub normal: Vec2,
+}
+
+impl Manifold {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the points found by the narrowphase, see `Contacts::from_points`.
+    pub fn from_points(points: &[ContactInfo]) -> Self {
+        let points = Contacts::from_points(points);
+        let normal = points
+            .iter()
+            .min_by(|a, b| a.separation.total_cmp(&b.separation))
+            .map_or(Vec2::default(), |deepest| deepest.normal);
+        Self { points, normal }
+    }
+
+    /// Returns the number of contact points.
+    pub fn point_count(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArbiterKey {
     body1_id: usize,
     body2_id: usize,
@@ -90,210 +194,741 @@ pub struct ArbiterKey {
 
 impl ArbiterKey {
     pub fn new(body_1: &Body, body_2: &Body) -> Self {
-        if body_1.id < body_2.id {
-            Self {
-                body1_id: body_1.id,
-                body2_id: body_2.id,
-            }
-        } else {
-            Self {
-                body1_id: body_2.id,
-                body2_id: body_1.id,
-            }
+        Self::from_ids(body_1.id, body_2.id)
+    }
+
+    /// Returns the key of the pair of bodies with the given ids, in any order.
+    pub fn from_ids(id_1: usize, id_2: usize) -> Self {
+        Self {
+            body1_id: id_1.min(id_2),
+            body2_id: id_1.max(id_2),
+        }
+    }
+
+    /// Returns the ids of the two bodies, lower id first.
+    pub fn ids(&self) -> (usize, usize) {
+        (self.body1_id, self.body2_id)
+    }
+
+    /// Returns true if the body with the given id is part of this pair.
+    pub fn involves(&self, body_id: usize) -> bool {
+        self.body1_id == body_id || self.body2_id == body_id
+    }
+}
+
+/// How the frictions of two touching bodies combine, see
+/// `WorldContext::friction_combine`.
+///
+/// A body can override the rule for its contacts with `Body::friction_combine`,
+/// e.g. `Min` to keep ice slippery against rubber. If both bodies do, the rule
+/// with the higher `FrictionCombine::priority` wins. A pair of bodies can
+/// override both with `PhysicsPipeline::set_pair_friction_combine`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrictionCombine {
+    #[default]
+    GeometricMean,
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl FrictionCombine {
+    pub fn combine(self, a: f32, b: f32) -> f32 {
+        match self {
+            FrictionCombine::GeometricMean => f32::sqrt(a * b),
+            FrictionCombine::Average => 0.5 * (a + b),
+            FrictionCombine::Min => a.min(b),
+            FrictionCombine::Multiply => a * b,
+            FrictionCombine::Max => a.max(b),
+        }
+    }
+
+    /// Returns the rank deciding which rule applies when two touching bodies
+    /// ask for different ones. As in most engines, the means rank lowest and
+    /// `Max` highest.
+    pub fn priority(self) -> u8 {
+        match self {
+            FrictionCombine::GeometricMean => 0,
+            FrictionCombine::Average => 1,
+            FrictionCombine::Min => 2,
+            FrictionCombine::Multiply => 3,
+            FrictionCombine::Max => 4,
+        }
+    }
+}
+
+/// How the restitutions of two touching bodies combine, see
+/// `WorldContext::restitution_combine`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RestitutionCombine {
+    /// The bouncier body wins.
+    #[default]
+    Max,
+    Average,
+    Multiply,
+}
+
+impl RestitutionCombine {
+    pub fn combine(self, a: f32, b: f32) -> f32 {
+        match self {
+            RestitutionCombine::Max => a.max(b),
+            RestitutionCombine::Average => 0.5 * (a + b),
+            RestitutionCombine::Multiply => a * b,
         }
     }
 }
 
-#[derive(Debug)]
+/// Changes to a touching pair for one step, made by the pre-solve callback (see
+/// `PhysicsPipeline::set_pre_solve`). The normals are changed on the contact
+/// points of `Arbiter::manifold` instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactModification {
+    /// False lets the bodies pass through each other, e.g. a one-way platform.
+    pub enabled: bool,
+    /// Replaces the combined friction of the bodies.
+    pub friction: Option<f32>,
+    /// Replaces the rule combining the frictions of the bodies, set to the rule
+    /// of the pair from `PhysicsPipeline::set_pair_friction_combine` before the
+    /// callback runs.
+    pub friction_combine: Option<FrictionCombine>,
+    /// Replaces the combined restitution of the bodies.
+    pub restitution: Option<f32>,
+}
+
+impl Default for ContactModification {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            friction: None,
+            friction_combine: None,
+            restitution: None,
+        }
+    }
+}
+
+/// Contact constraint between two bodies. The body with the lower id is always
+/// the first body, and the solver methods expect the bodies in that order.
+#[derive(Debug, Clone, Copy)]
 pub struct Arbiter {
-    body1: Rc<RefCell<Body>>,
-    body2: Rc<RefCell<Body>>,
+    // storage indices of the two bodies for the current step
+    pub(crate) indices: (usize, usize),
     friction: f32,
-    pub num_contacts: i32,
-    pub contacts: Vec<Contact>,
+    // Overlap the soft solver leaves alone, from the world context
+    allowed_penetration: f32,
+    pub manifold: Manifold,
+    /// Reset at the start of every step before the pre-solve callback.
+    pub modification: ContactModification,
 }
 
 impl Arbiter {
-    pub fn new(body_1: Rc<RefCell<Body>>, body_2: Rc<RefCell<Body>>) -> Self {
-        let mut contacts = Vec::<Contact>::with_capacity(2);
+    pub fn new(body_1: &Body, body_2: &Body) -> Self {
+        Self::new_in(
+            body_1,
+            body_2,
+            NarrowphaseTable::builtin(),
+            &mut Scratch::new(),
+        )
+    }
 
-        if body_1.borrow().id > body_2.borrow().id {
-            body_1.swap(&body_2);
+    /// Like `new`, but collides the bodies with the narrow phase registered in
+    /// `narrowphase` and takes the temporaries of the narrow phase from `scratch`.
+    pub fn new_in(
+        body_1: &Body,
+        body_2: &Body,
+        narrowphase: &NarrowphaseTable,
+        scratch: &mut Scratch,
+    ) -> Self {
+        let (body_1, body_2) = if body_1.id > body_2.id {
+            (body_2, body_1)
+        } else {
+            (body_1, body_2)
         };
 
-        let num_contacts = match (body_1.borrow().shape, body_2.borrow().shape) {
-            (Shape::Box, Shape::Box) => collide(&mut contacts, &body_1.borrow(), &body_2.borrow()),
-            _ => collide_polygons(&mut contacts, &body_1.borrow(), &body_2.borrow()),
-        };
-        let friction = f32::sqrt(body_1.borrow().friction * body_2.borrow().friction);
+        let points = scratch.alloc_contacts(|points, clip| {
+            narrowphase.collide_in(points, clip, body_1, body_2);
+        });
+        Self::from_manifold(Manifold::from_points(points))
+    }
+
+    /// Arbiter of contact points found elsewhere, e.g. by a narrow phase running
+    /// on another thread.
+    pub fn from_manifold(manifold: Manifold) -> Self {
         Self {
-            body1: body_1,
-            body2: body_2,
-            friction,
-            num_contacts,
-            contacts,
+            indices: (0, 0),
+            friction: 0.0,
+            allowed_penetration: 0.0,
+            manifold,
+            modification: ContactModification::default(),
         }
     }
-    pub fn update(
+
+    /// Arbiter of a bullet and the body it hits during the step, with a
+    /// speculative contact at the impact, see `ccd::sweep_bullet`.
+    /// `normal` points from the first to the second body.
+    pub fn from_impact(point: Vec2, normal: Vec2, separation: f32) -> Self {
+        let contact = ContactInfo {
+            position: point,
+            normal,
+            separation,
+            ..Default::default()
+        };
+        Self::from_manifold(Manifold::from_points(&[contact]))
+    }
+
+    /// Collides the bodies again and updates the contact points in place (see
+    /// `update`), appending the new points to `contacts` as temporaries and
+    /// clipping in `clip`.
+    pub fn collide_into(
         &mut self,
-        new_contacts: &[Contact],
-        num_new_contacts: i32,
+        contacts: &mut Vec<ContactInfo>,
+        clip: &mut ClipScratch,
+        body_1: &Body,
+        body_2: &Body,
+        narrowphase: &NarrowphaseTable,
         world_context: &WorldContext,
-    ) -> Result<(), ArbiterErrors> {
-        let mut merged_contacts = Vec::<Contact>::with_capacity(2);
-
-        for new_contact in new_contacts.iter() {
-            let mut k = -1;
-            let new_contact_feature = new_contact.map(|contact| contact.feature.value);
-            if new_contact_feature.is_some() {
-                for (j, contact) in self.contacts.iter().enumerate() {
-                    if Some(contact.map(|contact| contact.feature.value))
-                        == Some(new_contact_feature)
-                    {
-                        k = j as i32;
-                        break;
-                    }
-                }
-                if k > -1 {
-                    let c_old =
-                        self.contacts[k as usize].ok_or(ArbiterErrors::NoOldContactFound)?;
-                    let mut new_contact_ = new_contact.ok_or(ArbiterErrors::NoNewContactFound)?;
-                    if world_context.warm_starting {
-                        new_contact_.pn = c_old.pn;
-                        new_contact_.pt = c_old.pt;
-                        new_contact_.pnb = c_old.pnb;
-                    } else {
-                        new_contact_.pn = 0.0;
-                        new_contact_.pt = 0.0;
-                        new_contact_.pnb = 0.0;
-                    }
-
-                    merged_contacts.push(Some(new_contact_));
+    ) {
+        let (body_1, body_2) = if body_1.id > body_2.id {
+            (body_2, body_1)
+        } else {
+            (body_1, body_2)
+        };
+        let start = contacts.len();
+        narrowphase.collide_in(contacts, clip, body_1, body_2);
+        let manifold = Manifold::from_points(&contacts[start..]);
+        self.update(&manifold, world_context);
+    }
+
+    /// Replaces the contact points, carrying the accumulated impulses of points
+    /// with a matching feature over to the new ones.
+    pub fn update(&mut self, new_manifold: &Manifold, world_context: &WorldContext) {
+        let mut merged_contacts = Contacts::new();
+
+        for new_contact in new_manifold.points.iter() {
+            let mut merged = *new_contact;
+            let old_contact = self
+                .manifold
+                .points
+                .iter()
+                .find(|contact| contact.feature.value == new_contact.feature.value);
+            if let Some(c_old) = old_contact {
+                if world_context.warm_starting {
+                    merged.pn = c_old.pn;
+                    merged.pt = c_old.pt;
+                    merged.pnb = c_old.pnb;
                 } else {
-                    merged_contacts.push(*new_contact);
+                    merged.pn = 0.0;
+                    merged.pt = 0.0;
+                    merged.pnb = 0.0;
                 }
             }
+            merged_contacts.push(merged);
         }
 
-        self.contacts = merged_contacts;
-        self.num_contacts = num_new_contacts;
-        Ok(())
+        self.manifold = Manifold {
+            points: merged_contacts,
+            normal: new_manifold.normal,
+        };
     }
-    pub fn pre_step(&mut self, inv_dt: f32, world_context: &WorldContext) {
-        let k_allowed_penetration = 0.01;
-        let k_bias_factor = if world_context.position_correction {
-            0.2
+    pub fn pre_step(
+        &mut self,
+        body1: &mut Body,
+        body2: &mut Body,
+        inv_dt: f32,
+        world_context: &WorldContext,
+    ) {
+        let k_allowed_penetration = world_context.allowed_penetration;
+        let k_bias_factor = if world_context.position_correction
+            && world_context.position_solver == PositionSolver::Baumgarte
+        {
+            world_context.bias_factor
         } else {
             0.0
         };
-        let mut body1 = self.body1.borrow_mut();
-        let mut body2 = self.body2.borrow_mut();
-        for contact in self.contacts.iter_mut() {
-            match contact {
-                Some(contact) => {
-                    let r1 = contact.position - body1.position;
-                    let r2 = contact.position - body2.position;
-
-                    // pre-compute normal mass , tangent mass, and bias
-                    let rn1 = r1.dot(contact.normal);
-                    let rn2 = r2.dot(contact.normal);
-                    let mut k_normal = body1.inv_mass + body2.inv_mass;
-                    k_normal += body1.inv_moi * (r1.dot(r1) - rn1 * rn1)
-                        + body2.inv_moi * (r2.dot(r2) - rn2 * rn2);
-                    contact.mass_normal = 1.0 / k_normal;
-
-                    let tangent = (contact.normal).cross(1.0);
-                    let rt1 = r1.dot(tangent);
-                    let rt2 = r2.dot(tangent);
-                    let mut k_tangent = body1.inv_mass + body2.inv_mass;
-                    k_tangent += body1.inv_moi * (r1.dot(r1) - rt1 * rt1)
-                        + body2.inv_moi * (r2.dot(r2) - rt2 * rt2);
-                    contact.mass_tangent = 1.0 / k_tangent;
-
-                    contact.bias = -k_bias_factor
-                        * inv_dt
-                        * f32::min(0.0, contact.separation + k_allowed_penetration);
-                    if world_context.accumulate_impulse {
-                        let p = contact.normal * contact.pn + tangent * contact.pt;
-                        body1.velocity = body1.velocity - p * body1.inv_mass;
-                        body1.angular_velocity -= body1.inv_moi * r1.cross(p);
-
-                        body2.velocity = body2.velocity + p * body2.inv_mass;
-                        body2.angular_velocity += body2.inv_moi * r2.cross(p);
-                    };
-                }
-                None => (),
+        self.friction = self.modification.friction.unwrap_or_else(|| {
+            friction(
+                world_context,
+                self.modification.friction_combine,
+                body1,
+                body2,
+            )
+        });
+        let restitution = self
+            .modification
+            .restitution
+            .unwrap_or_else(|| restitution(world_context, body1, body2));
+        for contact in self.manifold.points.iter_mut() {
+            let r1 = contact.position - body1.position;
+            let r2 = contact.position - body2.position;
+
+            // pre-compute normal mass , tangent mass, and bias
+            let tangent = (contact.normal).cross(1.0);
+            contact.mass_normal = effective_mass(body1, body2, r1, r2, contact.normal);
+            contact.mass_tangent = effective_mass(body1, body2, r1, r2, tangent);
+
+            contact.bias = if contact.separation > 0.0 {
+                // Speculative contact, only stops the bodies from overlapping
+                -contact.separation * inv_dt
+            } else {
+                -k_bias_factor * inv_dt * f32::min(0.0, contact.separation + k_allowed_penetration)
+            };
+            contact.velocity_bias = restitution_bias(
+                world_context,
+                restitution,
+                body1,
+                body2,
+                r1,
+                r2,
+                contact.normal,
+            );
+            if world_context.accumulate_impulse {
+                let p = contact.normal * contact.pn + tangent * contact.pt;
+                body1.velocity = body1.velocity - p * body1.inv_mass;
+                body1.angular_velocity -= body1.inv_moi * r1.cross(p);
+
+                body2.velocity = body2.velocity + p * body2.inv_mass;
+                body2.angular_velocity += body2.inv_moi * r2.cross(p);
+            };
+        }
+    }
+    pub fn apply_impulse(
+        &mut self,
+        body1: &mut Body,
+        body2: &mut Body,
+        world_context: &WorldContext,
+    ) {
+        for contact in self.manifold.points.iter_mut() {
+            contact.r1 = contact.position - body1.position;
+            contact.r2 = contact.position - body2.position;
+
+            // Relative velocity at contact
+            let dv = body2.velocity + body2.angular_velocity.cross(contact.r2)
+                - body1.velocity
+                - body1.angular_velocity.cross(contact.r1);
+
+            // Compute normal impulse
+            let vn = dv.dot(contact.normal);
+            let bias = f32::max(contact.bias, contact.velocity_bias);
+            let mut d_pn = contact.mass_normal * (-vn + bias);
+
+            if world_context.accumulate_impulse {
+                // Clamp accumulated impulse
+                let pn_0 = contact.pn;
+                contact.pn = f32::max(pn_0 + d_pn, 0.0);
+                d_pn = contact.pn - pn_0;
+            } else {
+                d_pn = 0.0_f32.max(d_pn);
+            };
+
+            // Apply contact impulse
+            let pn = contact.normal * d_pn;
+
+            body1.velocity = body1.velocity - pn * body1.inv_mass;
+            body1.angular_velocity -= body1.inv_moi * contact.r1.cross(pn);
+
+            body2.velocity = body2.velocity + pn * body2.inv_mass;
+            body2.angular_velocity += body2.inv_moi * contact.r2.cross(pn);
+
+            // Relative velocity at contact
+            let dv = body2.velocity + body2.angular_velocity.cross(contact.r2)
+                - body1.velocity
+                - body1.angular_velocity.cross(contact.r1);
+
+            let tangent = contact.normal.cross(1.0);
+            let vt = dv.dot(tangent);
+            let mut d_pt = contact.mass_tangent * -vt;
+            if world_context.accumulate_impulse {
+                // Compute friction impulse
+                let max_pt = self.friction * contact.pn;
+
+                // Clamp friction
+                let old_tangent_impulse = contact.pt;
+                contact.pt = f32::clamp(old_tangent_impulse + d_pt, -max_pt, max_pt);
+                d_pt = contact.pt - old_tangent_impulse;
+            } else {
+                let max_pt = self.friction * d_pn;
+                d_pt = f32::clamp(d_pt, -max_pt, max_pt);
+            };
+
+            // Apply contact impulse
+            let pt = tangent * d_pt;
+
+            body1.velocity = body1.velocity - pt * body1.inv_mass;
+            body1.angular_velocity -= body1.inv_moi * contact.r1.cross(pt);
+            body2.velocity = body2.velocity + pt * body2.inv_mass;
+            body2.angular_velocity += body2.inv_moi * contact.r2.cross(pt);
+        }
+    }
+
+    /// Prepares the contacts for the soft solver, see `Solver::Soft`. The
+    /// accumulated impulses are applied by `warm_start` in every substep.
+    pub fn prepare_soft(&mut self, body1: &Body, body2: &Body, world_context: &WorldContext) {
+        self.allowed_penetration = world_context.allowed_penetration;
+        self.friction = self.modification.friction.unwrap_or_else(|| {
+            friction(
+                world_context,
+                self.modification.friction_combine,
+                body1,
+                body2,
+            )
+        });
+        let restitution = self
+            .modification
+            .restitution
+            .unwrap_or_else(|| restitution(world_context, body1, body2));
+        for contact in self.manifold.points.iter_mut() {
+            contact.r1 = contact.position - body1.position;
+            contact.r2 = contact.position - body2.position;
+            let tangent = contact.normal.cross(1.0);
+            contact.mass_normal =
+                effective_mass(body1, body2, contact.r1, contact.r2, contact.normal);
+            contact.mass_tangent = effective_mass(body1, body2, contact.r1, contact.r2, tangent);
+            contact.velocity_bias = restitution_bias(
+                world_context,
+                restitution,
+                body1,
+                body2,
+                contact.r1,
+                contact.r2,
+                contact.normal,
+            );
+            contact.max_pn = 0.0;
+        }
+    }
+
+    /// Bounces the bodies of the soft solver apart after the substeps, which
+    /// only stop them from approaching. See `Body::restitution`.
+    pub fn apply_restitution(&mut self, body1: &mut Body, body2: &mut Body) {
+        for contact in self.manifold.points.iter_mut() {
+            // Only contacts that pushed during the step bounce
+            if contact.velocity_bias == 0.0 || contact.max_pn == 0.0 {
+                continue;
             }
+            let dv = body2.velocity + body2.angular_velocity.cross(contact.r2)
+                - body1.velocity
+                - body1.angular_velocity.cross(contact.r1);
+            let vn = dv.dot(contact.normal);
+            let d_pn = contact.mass_normal * (contact.velocity_bias - vn);
+            let pn_0 = contact.pn;
+            contact.pn = f32::max(pn_0 + d_pn, 0.0);
+            let pn = contact.normal * (contact.pn - pn_0);
+            body1.velocity = body1.velocity - pn * body1.inv_mass;
+            body1.angular_velocity -= body1.inv_moi * contact.r1.cross(pn);
+            body2.velocity = body2.velocity + pn * body2.inv_mass;
+            body2.angular_velocity += body2.inv_moi * contact.r2.cross(pn);
         }
     }
-    pub fn apply_impulse(&mut self, world_context: &WorldContext) {
-        let mut body1 = self.body1.borrow_mut();
-        let mut body2 = self.body2.borrow_mut();
-
-        for contact in self.contacts.iter_mut() {
-            match contact {
-                Some(contact) => {
-                    contact.r1 = contact.position - body1.position;
-                    contact.r2 = contact.position - body2.position;
-
-                    // Relative velocity at contact
-                    let dv = body2.velocity + body2.angular_velocity.cross(contact.r2)
-                        - body1.velocity
-                        - body1.angular_velocity.cross(contact.r1);
-
-                    // Compute normal impulse
-                    let vn = dv.dot(contact.normal);
-                    let mut d_pn = contact.mass_normal * (-vn + contact.bias);
-
-                    if world_context.accumulate_impulse {
-                        // Clamp accumulated impulse
-                        let pn_0 = contact.pn;
-                        contact.pn = f32::max(pn_0 + d_pn, 0.0);
-                        d_pn = contact.pn - pn_0;
-                    } else {
-                        d_pn = 0.0_f32.max(d_pn);
-                    };
-
-                    // Apply contact impulse
-                    let pn = contact.normal * d_pn;
-
-                    body1.velocity = body1.velocity - pn * body1.inv_mass;
-                    body1.angular_velocity -= body1.inv_moi * contact.r1.cross(pn);
-
-                    body2.velocity = body2.velocity + pn * body2.inv_mass;
-                    body2.angular_velocity += body2.inv_moi * contact.r2.cross(pn);
-
-                    // Relative velocity at contact
-                    let dv = body2.velocity + body2.angular_velocity.cross(contact.r2)
-                        - body1.velocity
-                        - body1.angular_velocity.cross(contact.r1);
-
-                    let tangent = contact.normal.cross(1.0);
-                    let vt = dv.dot(tangent);
-                    let mut d_pt = contact.mass_tangent * -vt;
-                    if world_context.accumulate_impulse {
-                        // Compute friction impulse
-                        let max_pt = self.friction * contact.pn;
-
-                        // Clamp friction
-                        let old_tangent_impulse = contact.pt;
-                        contact.pt = f32::clamp(old_tangent_impulse + d_pt, -max_pt, max_pt);
-                        d_pt = contact.pt - old_tangent_impulse;
-                    } else {
-                        let max_pt = self.friction * d_pn;
-                        d_pt = f32::clamp(d_pt, -max_pt, max_pt);
-                    };
-
-                    // Apply contact impulse
-                    let pt = tangent * d_pt;
-
-                    body1.velocity = body1.velocity - pt * body1.inv_mass;
-                    body1.angular_velocity -= body1.inv_moi * contact.r1.cross(pt);
-                    body2.velocity = body2.velocity + pt * body2.inv_mass;
-                    body2.angular_velocity += body2.inv_moi * contact.r2.cross(pt);
+
+    /// Applies the accumulated impulses of the contacts to the bodies.
+    pub fn warm_start(&self, body1: &mut Body, body2: &mut Body) {
+        for contact in self.manifold.points.iter() {
+            let p = contact.normal * contact.pn + contact.normal.cross(1.0) * contact.pt;
+            body1.velocity = body1.velocity - p * body1.inv_mass;
+            body1.angular_velocity -= body1.inv_moi * contact.r1.cross(p);
+            body2.velocity = body2.velocity + p * body2.inv_mass;
+            body2.angular_velocity += body2.inv_moi * contact.r2.cross(p);
+        }
+    }
+
+    /// Solves the contacts as soft constraints for one substep of `1 / inv_h`
+    /// seconds, given how far each body moved and turned since `prepare_soft`.
+    ///
+    /// Without `softness` the contacts only stop the bodies from approaching,
+    /// which is the relaxation pass removing the velocity added by the bias.
+    pub fn solve_soft(
+        &mut self,
+        body1: &mut Body,
+        body2: &mut Body,
+        offset1: (Vec2, f32),
+        offset2: (Vec2, f32),
+        softness: Option<Softness>,
+        inv_h: f32,
+    ) {
+        for contact in self.manifold.points.iter_mut() {
+            // Current separation, assuming small rotations
+            let d =
+                offset2.0 + offset2.1.cross(contact.r2) - offset1.0 - offset1.1.cross(contact.r1);
+            let separation = contact.separation + d.dot(contact.normal);
+            let (bias, softness) = match softness {
+                // Speculative contact, only stops the bodies from overlapping
+                _ if separation > 0.0 => (separation * inv_h, Softness::rigid()),
+                Some(softness) => {
+                    let overlap = f32::min(0.0, separation + self.allowed_penetration);
+                    let bias = f32::max(softness.bias_rate * overlap, -MAX_PUSHOUT_VELOCITY);
+                    (bias, softness)
                 }
-                None => (),
-            }
+                None => (0.0, Softness::rigid()),
+            };
+
+            let dv = body2.velocity + body2.angular_velocity.cross(contact.r2)
+                - body1.velocity
+                - body1.angular_velocity.cross(contact.r1);
+            let vn = dv.dot(contact.normal);
+            let d_pn = -contact.mass_normal * softness.mass_scale * (vn + bias)
+                - softness.impulse_scale * contact.pn;
+            let pn_0 = contact.pn;
+            contact.pn = f32::max(pn_0 + d_pn, 0.0);
+            contact.max_pn = contact.max_pn.max(contact.pn);
+            let pn = contact.normal * (contact.pn - pn_0);
+            body1.velocity = body1.velocity - pn * body1.inv_mass;
+            body1.angular_velocity -= body1.inv_moi * contact.r1.cross(pn);
+            body2.velocity = body2.velocity + pn * body2.inv_mass;
+            body2.angular_velocity += body2.inv_moi * contact.r2.cross(pn);
+
+            let dv = body2.velocity + body2.angular_velocity.cross(contact.r2)
+                - body1.velocity
+                - body1.angular_velocity.cross(contact.r1);
+            let tangent = contact.normal.cross(1.0);
+            let max_pt = self.friction * contact.pn;
+            let pt_0 = contact.pt;
+            contact.pt = f32::clamp(
+                pt_0 - contact.mass_tangent * dv.dot(tangent),
+                -max_pt,
+                max_pt,
+            );
+            let pt = tangent * (contact.pt - pt_0);
+            body1.velocity = body1.velocity - pt * body1.inv_mass;
+            body1.angular_velocity -= body1.inv_moi * contact.r1.cross(pt);
+            body2.velocity = body2.velocity + pt * body2.inv_mass;
+            body2.angular_velocity += body2.inv_moi * contact.r2.cross(pt);
+        }
+    }
+
+    /// Moves the bodies apart along the normal to reduce their overlap, see
+    /// `PositionSolver::NonlinearGaussSeidel`. `start1` and `start2` are the
+    /// positions and rotations of the bodies when the contacts were found.
+    ///
+    /// Each iteration removes `WorldContext::bias_factor` of the overlap beyond
+    /// `WorldContext::allowed_penetration`, at most
+    /// `WorldContext::max_correction`. Returns the smallest separation before
+    /// the correction.
+    pub fn solve_position(
+        &self,
+        body1: &mut Body,
+        body2: &mut Body,
+        start1: (Vec2, f32),
+        start2: (Vec2, f32),
+        world_context: &WorldContext,
+    ) -> f32 {
+        let k_baumgarte = world_context.bias_factor;
+        let k_max_correction = world_context.max_correction;
+        let mut min_separation = 0.0_f32;
+        for contact in self.manifold.points.iter() {
+            let r1 = contact.position - start1.0;
+            let r2 = contact.position - start2.0;
+            // Current separation, assuming small rotations
+            let d = body2.position - start2.0 + (body2.rotation - start2.1).cross(r2)
+                - (body1.position - start1.0)
+                - (body1.rotation - start1.1).cross(r1);
+            let separation = contact.separation + d.dot(contact.normal);
+            min_separation = min_separation.min(separation);
+
+            let correction = f32::clamp(
+                k_baumgarte * (separation + world_context.allowed_penetration),
+                -k_max_correction,
+                0.0,
+            );
+            let mass = effective_mass(body1, body2, r1, r2, contact.normal);
+            let p = contact.normal * (-correction * mass);
+            body1.position = body1.position - p * body1.inv_mass;
+            body1.rotation -= body1.inv_moi * r1.cross(p);
+            body2.position = body2.position + p * body2.inv_mass;
+            body2.rotation += body2.inv_moi * r2.cross(p);
+        }
+        min_separation
+    }
+
+    /// Lets a platform that carries riders hold a body standing on it with the
+    /// friction of the platform, whatever the friction of the rider.
+    ///
+    /// The friction of the contacts pulls the rider towards the velocity of the
+    /// platform surface, so the rider is carried as far as the friction allows
+    /// and keeps its own motion relative to the platform. Does nothing if the
+    /// pre-solve callback already replaced the friction.
+    pub fn carry_rider(&mut self, body1: &Body, body2: &Body, up: Vec2) {
+        let (platform, rider, sign) = if body1.carries_riders && !body1.is_dynamic() {
+            (body1, body2, 1.0)
+        } else if body2.carries_riders && !body2.is_dynamic() {
+            (body2, body1, -1.0)
+        } else {
+            return;
+        };
+        if !rider.is_dynamic() || self.modification.friction.is_some() {
+            return;
+        }
+
+        let normal = self
+            .manifold
+            .points
+            .iter()
+            .fold(Vec2::default(), |normal, contact| {
+                normal + contact.normal * sign
+            });
+        let length = normal.length();
+        // Normals cancelling out, e.g. a rider wedged into the platform
+        if length < f32::EPSILON || normal.dot(up) < 0.7 * length {
+            // not standing on top of the platform
+            return;
+        }
+        self.modification.friction = Some(platform.friction);
+    }
+}
+
+// Mass of the pair along `direction` at the points `r1` and `r2` relative to
+// the bodies.
+fn effective_mass(body1: &Body, body2: &Body, r1: Vec2, r2: Vec2, direction: Vec2) -> f32 {
+    let rn1 = r1.dot(direction);
+    let rn2 = r2.dot(direction);
+    let mut k = body1.inv_mass + body2.inv_mass;
+    k += body1.inv_moi * (r1.dot(r1) - rn1 * rn1) + body2.inv_moi * (r2.dot(r2) - rn2 * rn2);
+    1.0 / k
+}
+
+// Friction of the pair, with the rule of the pair if it has one, else the rule
+// of the bodies, else the one of the world. See `FrictionCombine`.
+fn friction(
+    world_context: &WorldContext,
+    pair_rule: Option<FrictionCombine>,
+    body1: &Body,
+    body2: &Body,
+) -> f32 {
+    let body_rule = match (body1.friction_combine, body2.friction_combine) {
+        (Some(rule1), Some(rule2)) if rule2.priority() > rule1.priority() => Some(rule2),
+        (Some(rule1), _) => Some(rule1),
+        (None, rule2) => rule2,
+    };
+    pair_rule
+        .or(body_rule)
+        .unwrap_or(world_context.friction_combine)
+        .combine(body1.friction, body2.friction)
+}
+
+// Restitution of the pair, see `RestitutionCombine`.
+fn restitution(world_context: &WorldContext, body1: &Body, body2: &Body) -> f32 {
+    world_context
+        .restitution_combine
+        .combine(body1.restitution, body2.restitution)
+}
+
+// Separating velocity the restitution aims for, given the relative velocity of
+// the bodies before solving.
+fn restitution_bias(
+    world_context: &WorldContext,
+    restitution: f32,
+    body1: &Body,
+    body2: &Body,
+    r1: Vec2,
+    r2: Vec2,
+    normal: Vec2,
+) -> f32 {
+    let dv = body2.velocity + body2.angular_velocity.cross(r2)
+        - body1.velocity
+        - body1.angular_velocity.cross(r1);
+    let vn = dv.dot(normal);
+    if restitution == 0.0 || vn > -world_context.restitution_threshold {
+        return 0.0;
+    }
+    -restitution * vn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    fn point(x: f32, separation: f32) -> ContactInfo {
+        ContactInfo {
+            position: Vec2::new(x, 0.0),
+            separation,
+            ..ContactInfo::default()
         }
     }
+
+    #[test]
+    fn test_contacts_keep_deepest_and_farthest() {
+        let points = [
+            point(0.0, -0.01),
+            point(1.0, -0.05),
+            point(3.0, -0.02),
+            point(2.0, -0.03),
+        ];
+        let contacts = Contacts::from_points(&points);
+        assert_eq!(contacts.len(), MAX_CONTACTS);
+        assert_eq!(contacts[0].position, Vec2::new(1.0, 0.0));
+        assert_eq!(contacts[1].position, Vec2::new(3.0, 0.0));
+
+        let contacts = Contacts::from_points(&points[..1]);
+        assert_eq!(contacts.len(), 1);
+
+        let manifold = Manifold::from_points(&points[..2]);
+        assert_eq!(manifold.point_count(), 2);
+    }
+
+    #[test]
+    fn test_friction_combine() {
+        let mut context = World::new(Vec2::default(), 1).world_context;
+        let mut ice = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        ice.friction = 0.04;
+        let mut rubber = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        rubber.friction = 1.0;
+        assert!((friction(&context, None, &ice, &rubber) - 0.2).abs() < 1e-6);
+        context.friction_combine = FrictionCombine::Max;
+        assert_eq!(friction(&context, None, &ice, &rubber), 1.0);
+
+        // The body overrides the world
+        ice.friction_combine = Some(FrictionCombine::Min);
+        assert_eq!(friction(&context, None, &rubber, &ice), 0.04);
+        rubber.friction_combine = Some(FrictionCombine::Average);
+        assert_eq!(friction(&context, None, &rubber, &ice), 0.04);
+        assert_eq!(friction(&context, None, &ice, &rubber), 0.04);
+        rubber.friction_combine = Some(FrictionCombine::Max);
+        assert_eq!(friction(&context, None, &ice, &rubber), 1.0);
+
+        // The pair overrides the bodies
+        let rule = Some(FrictionCombine::Multiply);
+        assert!((friction(&context, rule, &ice, &rubber) - 0.04).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_collide_into_keeps_impulses() {
+        let context = WorldContext {
+            warm_starting: true,
+            ..Default::default()
+        };
+        let ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        block.position = Vec2::new(0.0, 0.95);
+        let mut arbiter = Arbiter::new(&block, &ground);
+        assert_eq!(arbiter.manifold.point_count(), 2);
+        arbiter.manifold.points[0].pn = 1.5;
+
+        let narrowphase = NarrowphaseTable::builtin();
+        let mut contacts = Vec::new();
+        let mut clip = ClipScratch::default();
+        block.position = Vec2::new(0.01, 0.96);
+        arbiter.collide_into(
+            &mut contacts,
+            &mut clip,
+            &ground,
+            &block,
+            narrowphase,
+            &context,
+        );
+        assert_eq!(contacts.len(), 2);
+        assert_eq!(arbiter.manifold.points[0].pn, 1.5);
+        assert!((arbiter.manifold.points[0].separation + 0.04).abs() < 1e-5);
+
+        block.position = Vec2::new(0.0, 2.0);
+        arbiter.collide_into(
+            &mut contacts,
+            &mut clip,
+            &ground,
+            &block,
+            narrowphase,
+            &context,
+        );
+        assert!(arbiter.manifold.is_empty());
+    }
 }
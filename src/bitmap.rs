@@ -0,0 +1,485 @@
+use crate::body::{polygon_centroid, Body};
+use crate::errors::Sylt2DErrors;
+use crate::math_utils::{Cross, Vec2};
+use crate::tilemap::Grid;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum BitmapErrors {
+    /// An outline ends at the point, in pixels, instead of closing.
+    OpenContour(Vec2),
+}
+
+impl fmt::Display for BitmapErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitmapErrors::OpenContour(point) => {
+                write!(f, "The outline ending at {:?} is not closed.", point)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BitmapErrors {}
+
+/// Binary mask of solid pixels, for example the opaque pixels of a sprite, with
+/// the bottom left corner of pixel `(0, 0)` at the origin.
+///
+/// Turns the outlines of the solid regions into convex polygons, see
+/// `Bitmap::convex_polygons`.
+pub type Bitmap = Grid;
+
+impl Bitmap {
+    /// Returns the mask of the pixels with an alpha above `threshold`, the rows of
+    /// `alpha` going from the top of the image to the bottom.
+    pub fn from_alpha(width: usize, height: usize, alpha: &[u8], threshold: u8) -> Self {
+        let mut bitmap = Self::new(width, height);
+        for (i, &value) in alpha.iter().take(width * height).enumerate() {
+            bitmap.set(i % width, height - 1 - i / width, value > threshold);
+        }
+        bitmap
+    }
+
+    // Treats the samples outside the mask as empty.
+    fn sample(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && self.is_solid(x as usize, y as usize)
+    }
+
+    /// Returns the outlines of the solid regions found by marching squares,
+    /// counterclockwise and in pixels. The outlines of holes are clockwise.
+    pub fn contours(&self) -> Result<Vec<Vec<Vec2>>, Sylt2DErrors> {
+        // Directed segments with the solid side on the left, between the edge
+        // midpoints of the cells in doubled sample coordinates
+        let mut next = HashMap::new();
+        for y in -1..self.height() as i32 {
+            for x in -1..self.width() as i32 {
+                for (from, to) in self.cell_segments(x, y) {
+                    next.insert(from, to);
+                }
+            }
+        }
+
+        let mut starts: Vec<(i32, i32)> = next.keys().copied().collect();
+        starts.sort_unstable();
+        let mut contours = Vec::new();
+        for start in starts {
+            let Some(mut point) = next.remove(&start) else {
+                continue;
+            };
+            let mut contour = vec![start];
+            while point != start {
+                contour.push(point);
+                point = next
+                    .remove(&point)
+                    .ok_or(BitmapErrors::OpenContour(to_pixels(point)))?;
+            }
+            contours.push(contour.into_iter().map(to_pixels).collect());
+        }
+        Ok(contours)
+    }
+
+    // Segments of the cell between the samples (x, y) and (x + 1, y + 1).
+    fn cell_segments(&self, x: i32, y: i32) -> Vec<((i32, i32), (i32, i32))> {
+        let corners =
+            [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(dx, dy)| (dx, dy, self.sample(x + dx, y + dy)));
+        // Midpoints of the edges with one solid corner
+        let crossings: Vec<usize> = (0..4)
+            .filter(|&i| corners[i].2 != corners[(i + 1) % 4].2)
+            .collect();
+        let midpoint = |edge: usize| {
+            let (ax, ay, _) = corners[edge];
+            let (bx, by, _) = corners[(edge + 1) % 4];
+            (2 * x + ax + bx, 2 * y + ay + by)
+        };
+        let pairs = match crossings.len() {
+            2 => vec![(crossings[0], crossings[1])],
+            // Saddle, the solid corners are kept apart
+            4 if corners[0].2 => vec![(3, 0), (1, 2)],
+            4 => vec![(0, 1), (2, 3)],
+            _ => Vec::new(),
+        };
+        pairs
+            .into_iter()
+            .map(|(a, b)| {
+                let (from, to) = (midpoint(a), midpoint(b));
+                // Orient with a solid corner cut off by the segment on the left
+                let solid = [a, (a + 1) % 4, b, (b + 1) % 4]
+                    .into_iter()
+                    .find(|&i| corners[i].2)
+                    .expect("a crossing has a solid corner");
+                let corner = (2 * (x + corners[solid].0), 2 * (y + corners[solid].1));
+                let side =
+                    (to.0 - from.0) * (corner.1 - from.1) - (to.1 - from.1) * (corner.0 - from.0);
+                if side > 0 {
+                    (from, to)
+                } else {
+                    (to, from)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns convex pieces covering the solid regions and leaving out their
+    /// holes, in world units with pixels of `pixel_size`.
+    ///
+    /// The outlines are simplified first, dropping the points closer than
+    /// `tolerance` pixels to the simplified outline. Holes that shrink away
+    /// are filled.
+    pub fn convex_polygons(
+        &self,
+        pixel_size: f32,
+        tolerance: f32,
+    ) -> Result<Vec<Vec<Vec2>>, Sylt2DErrors> {
+        let mut outlines = Vec::new();
+        let mut holes = Vec::new();
+        for contour in self.contours()? {
+            let outline = simplify(&contour, tolerance);
+            let area = signed_area(&outline);
+            if outline.len() < 3 || area == 0.0 || signed_area(&contour) * area < 0.0 {
+                continue;
+            }
+            if area > 0.0 {
+                outlines.push(outline);
+            } else {
+                holes.push(outline);
+            }
+        }
+        // Holes from right to left, so every hole can be bridged to the ones
+        // already cut into the outline
+        holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+        for hole in &holes {
+            // The smallest outline around the hole
+            let outline = outlines
+                .iter_mut()
+                .filter(|outline| contains(outline, hole[0]))
+                .min_by(|a, b| signed_area(a).total_cmp(&signed_area(b)));
+            if let Some(outline) = outline {
+                bridge(outline, hole);
+            }
+        }
+
+        let mut polygons = Vec::new();
+        for outline in &outlines {
+            for piece in convex_decomposition(outline) {
+                polygons.push(piece.into_iter().map(|p| p * pixel_size).collect());
+            }
+        }
+        Ok(polygons)
+    }
+
+    /// Returns one static polygon per piece of `Bitmap::convex_polygons`, placed
+    /// relative to `origin`.
+    pub fn bodies(
+        &self,
+        pixel_size: f32,
+        tolerance: f32,
+        origin: Vec2,
+    ) -> Result<Vec<Body>, Sylt2DErrors> {
+        Ok(self
+            .convex_polygons(pixel_size, tolerance)?
+            .into_iter()
+            .filter_map(|piece| {
+                let centroid = polygon_centroid(&piece);
+                let mut body = Body::new_polygon(piece, f32::MAX).ok()?;
+                body.position = origin + centroid;
+                Some(body)
+            })
+            .collect())
+    }
+}
+
+// Samples sit at the pixel centers, points are in doubled sample coordinates.
+fn to_pixels((x, y): (i32, i32)) -> Vec2 {
+    Vec2::new(x as f32 + 1.0, y as f32 + 1.0) * 0.5
+}
+
+fn signed_area(points: &[Vec2]) -> f32 {
+    let n = points.len();
+    (0..n)
+        .map(|i| points[i].cross(points[(i + 1) % n]))
+        .sum::<f32>()
+        * 0.5
+}
+
+fn max_x(points: &[Vec2]) -> f32 {
+    points.iter().map(|point| point.x).fold(f32::MIN, f32::max)
+}
+
+// Even-odd test of a point against an outline.
+fn contains(outline: &[Vec2], point: Vec2) -> bool {
+    let n = outline.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (outline[i], outline[(i + 1) % n]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// Cuts a clockwise hole into a counterclockwise outline along a bridge from
+// the rightmost point of the hole to a point of the outline it sees, which
+// leaves one outline to triangulate. The points of the bridge appear twice.
+fn bridge(outline: &mut Vec<Vec2>, hole: &[Vec2]) {
+    let Some((m, &start)) = hole
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.x.total_cmp(&b.1.x))
+    else {
+        return;
+    };
+    // Closest edge hit by a ray from the start to the right
+    let n = outline.len();
+    let mut hit: Option<(f32, usize)> = None;
+    for i in 0..n {
+        let (a, b) = (outline[i], outline[(i + 1) % n]);
+        if (a.y > start.y) == (b.y > start.y) {
+            continue;
+        }
+        let x = a.x + (start.y - a.y) * (b.x - a.x) / (b.y - a.y);
+        if x >= start.x && hit.is_none_or(|(closest, _)| x < closest) {
+            // The end of the edge farther right
+            let end = if a.x > b.x { i } else { (i + 1) % n };
+            hit = Some((x, end));
+        }
+    }
+    let Some((x, mut end)) = hit else {
+        return;
+    };
+    // A point of the outline inside the triangle between the ray and the end
+    // would block the view, take the one closest in angle to the ray instead
+    let (hit, corner) = (Vec2::new(x, start.y), outline[end]);
+    let mut best = f32::MIN;
+    for (i, &point) in outline.iter().enumerate() {
+        if point != corner && point != start && in_triangle_either(point, start, hit, corner) {
+            let offset = point - start;
+            let alignment = offset.x / offset.length();
+            if alignment > best {
+                best = alignment;
+                end = i;
+            }
+        }
+    }
+    let cut: Vec<Vec2> = (0..=hole.len())
+        .map(|k| hole[(m + k) % hole.len()])
+        .chain(std::iter::once(outline[end]))
+        .collect();
+    outline.splice(end + 1..end + 1, cut);
+}
+
+fn in_triangle_either(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    in_triangle(point, a, b, c) || in_triangle(point, a, c, b)
+}
+
+fn segment_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.dot(ab).max(f32::EPSILON)).clamp(0.0, 1.0);
+    (point - (a + ab * t)).length()
+}
+
+// Ramer-Douglas-Peucker on a closed outline.
+fn simplify(contour: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    // Split the loop at the point farthest from the first one
+    let far = (1..contour.len())
+        .max_by(|&i, &j| {
+            let di = (contour[i] - contour[0]).length();
+            let dj = (contour[j] - contour[0]).length();
+            di.total_cmp(&dj)
+        })
+        .unwrap_or(0);
+    let mut keep = vec![false; contour.len()];
+    keep[0] = true;
+    keep[far] = true;
+    let mut ranges = vec![(0, far), (far, contour.len())];
+    while let Some((start, end)) = ranges.pop() {
+        let (a, b) = (contour[start], contour[end % contour.len()]);
+        let farthest = (start + 1..end)
+            .map(|i| (i, segment_distance(contour[i], a, b)))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                ranges.push((start, i));
+                ranges.push((i, end));
+            }
+        }
+    }
+    contour
+        .iter()
+        .zip(keep)
+        .filter_map(|(&point, keep)| keep.then_some(point))
+        .collect()
+}
+
+fn is_convex(polygon: &[Vec2]) -> bool {
+    let n = polygon.len();
+    (0..n).all(|i| {
+        let e1 = polygon[(i + 1) % n] - polygon[i];
+        let e2 = polygon[(i + 2) % n] - polygon[(i + 1) % n];
+        e1.cross(e2) > 1e-6
+    })
+}
+
+fn in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).cross(point - a) >= 0.0
+        && (c - b).cross(point - b) >= 0.0
+        && (a - c).cross(point - c) >= 0.0
+}
+
+// Ear clipping of a counterclockwise outline, returning vertex indices.
+fn triangulate(outline: &[Vec2]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..outline.len()).collect();
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            let (pa, pb, pc) = (outline[a], outline[b], outline[c]);
+            (pb - pa).cross(pc - pb) > 0.0
+                && remaining
+                    .iter()
+                    .map(|&j| outline[j])
+                    .filter(|point| ![pa, pb, pc].contains(point))
+                    .all(|point| !in_triangle(point, pa, pb, pc))
+        });
+        // A degenerate outline has no ear left, clip anything
+        let i = ear.unwrap_or(0);
+        triangles.push([
+            remaining[(i + n - 1) % n],
+            remaining[i],
+            remaining[(i + 1) % n],
+        ]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// Joins two pieces sharing an edge, returning None if they do not.
+fn join(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+    for i in 0..a.len() {
+        let (p, q) = (a[i], a[(i + 1) % a.len()]);
+        if let Some(j) = (0..b.len()).find(|&j| b[j] == q && b[(j + 1) % b.len()] == p) {
+            // Walk a up to p, then b from p around to q
+            let mut joined: Vec<usize> = (0..a.len()).map(|k| a[(i + 1 + k) % a.len()]).collect();
+            joined.extend((2..b.len()).map(|k| b[(j + k) % b.len()]));
+            return Some(joined);
+        }
+    }
+    None
+}
+
+// Hertel-Mehlhorn: merges the triangles while the pieces stay convex.
+fn convex_decomposition(outline: &[Vec2]) -> Vec<Vec<Vec2>> {
+    let mut pieces: Vec<Vec<usize>> = triangulate(outline)
+        .into_iter()
+        .filter(|triangle| is_convex(&triangle.map(|i| outline[i])))
+        .map(|triangle| triangle.to_vec())
+        .collect();
+    let mut merged = true;
+    while merged {
+        merged = false;
+        'pieces: for i in 0..pieces.len() {
+            for j in i + 1..pieces.len() {
+                let Some(joined) = join(&pieces[i], &pieces[j]) else {
+                    continue;
+                };
+                let points: Vec<Vec2> = joined.iter().map(|&k| outline[k]).collect();
+                if is_convex(&points) {
+                    pieces[i] = joined;
+                    pieces.swap_remove(j);
+                    merged = true;
+                    break 'pieces;
+                }
+            }
+        }
+    }
+    pieces
+        .into_iter()
+        .map(|piece| piece.into_iter().map(|i| outline[i]).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::ConvexPolygon;
+
+    fn area(polygons: &[Vec<Vec2>]) -> f32 {
+        polygons.iter().map(|polygon| signed_area(polygon)).sum()
+    }
+
+    #[test]
+    fn test_contours() {
+        let mut bitmap = Bitmap::new(4, 3);
+        for x in 0..3 {
+            bitmap.set(x, 0, true);
+            bitmap.set(x, 1, true);
+        }
+        let contours = bitmap.contours().unwrap();
+        assert_eq!(contours.len(), 1);
+        // Halfway to the empty pixels, cutting the corners
+        assert!((signed_area(&contours[0]) - 5.5).abs() < 1e-5);
+
+        // Diagonal pixels stay apart
+        let bitmap = Bitmap::from_alpha(2, 2, &[255, 0, 0, 255], 128);
+        assert!(bitmap.is_solid(0, 1) && bitmap.is_solid(1, 0));
+        assert_eq!(bitmap.contours().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_convex_polygons() {
+        // An L shape
+        let mut bitmap = Bitmap::new(10, 10);
+        for i in 0..10 {
+            for j in 0..3 {
+                bitmap.set(i, j, true);
+                bitmap.set(j, i, true);
+            }
+        }
+        let polygons = bitmap.convex_polygons(0.5, 0.1).unwrap();
+        assert!(polygons.len() >= 2 && polygons.len() <= 4);
+        for polygon in &polygons {
+            assert!(is_convex(polygon));
+        }
+        let outline = &bitmap.contours().unwrap()[0];
+        let expected = signed_area(outline) * 0.25;
+        assert!((area(&polygons) - expected).abs() < 1e-3);
+
+        let bodies = bitmap.bodies(0.5, 0.1, Vec2::new(1.0, 0.0)).unwrap();
+        assert_eq!(bodies.len(), polygons.len());
+        let vertices = bodies[0].world_vertices();
+        let offset = vertices[0] - polygons[0][0];
+        assert!((offset - Vec2::new(1.0, 0.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn test_holes() {
+        // A frame around an empty square
+        let mut bitmap = Bitmap::new(10, 10);
+        for i in 0..10 {
+            for j in 0..10 {
+                let hole = (3..=6).contains(&i) && (3..=6).contains(&j);
+                bitmap.set(i, j, !hole);
+            }
+        }
+        let contours = bitmap.contours().unwrap();
+        assert_eq!(contours.len(), 2);
+        let expected: f32 = contours.iter().map(|contour| signed_area(contour)).sum();
+
+        let polygons = bitmap.convex_polygons(1.0, 0.1).unwrap();
+        for polygon in &polygons {
+            assert!(is_convex(polygon));
+            assert!(!ConvexPolygon::new(polygon.clone()).contains_point(Vec2::new(5.0, 5.0)));
+        }
+        assert!((area(&polygons) - expected).abs() < 1e-3);
+    }
+}
@@ -1,4 +1,11 @@
-use crate::math_utils::{Mat2x2, Vec2};
+use crate::aerodynamics::Aerodynamics;
+use crate::arbiter::FrictionCombine;
+use crate::broad_phase::Aabb;
+use crate::errors::Sylt2DErrors;
+use crate::math_utils::{Cross, Mat2x2, Vec2};
+use crate::transforms::Transform;
+use std::f32::consts::PI;
+use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct ConvexPolygon {
@@ -10,6 +17,11 @@ impl ConvexPolygon {
     pub fn new(vertices: Vec<Vec2>) -> Self {
         Self { vertices }
     }
+
+    /// Returns the vertices, to reuse their memory.
+    pub fn into_vertices(self) -> Vec<Vec2> {
+        self.vertices
+    }
     /// Returns the number of vertices in the polygon.
     pub fn get_num_vertices(&self) -> usize {
         self.vertices.len()
@@ -40,8 +52,12 @@ impl ConvexPolygon {
         }
     }
 
-    /// Calculates the signed area of the polygon.
+    /// Calculates the area of the polygon, for either winding.
     pub fn area(&self) -> f32 {
+        self.signed_area().abs()
+    }
+    // Area that is negative for clockwise vertices.
+    fn signed_area(&self) -> f32 {
         let n = self.get_num_vertices();
         let mut area = 0.0;
         for i in 0..n {
@@ -49,35 +65,17 @@ impl ConvexPolygon {
             let p2 = self.get_vertex((i + 1) as isize);
             area += p1.x * p2.y - p1.y * p2.x;
         }
-        area.abs() / 2.0
+        area / 2.0
     }
     // Orient the vertices counterclockwise
     fn orient_counterclockwise(&mut self) {
-        if self.area() < 0.0 {
+        if self.signed_area() < 0.0 {
             self.vertices.reverse(); // Reverse the vertex order if the area is negative (clockwise)
         }
     }
     /// Calculates the centroid of the polygon.
     pub fn centroid(&self) -> Vec2 {
-        let n = self.get_num_vertices();
-        let mut cx = 0.0;
-        let mut cy = 0.0;
-        let mut area = 0.0;
-
-        for i in 0..n {
-            let p1 = self.get_vertex(i as isize);
-            let p2 = self.get_vertex((i + 1) as isize);
-            let cross = p1.x * p2.y - p1.y * p2.x;
-            area += cross;
-            cx += (p1.x + p2.x) * cross;
-            cy += (p1.y + p2.y) * cross;
-        }
-
-        area /= 2.0;
-        cx /= 6.0 * area;
-        cy /= 6.0 * area;
-
-        Vec2 { x: cx, y: cy }
+        polygon_centroid(&self.vertices)
     }
 
     // Scale the polygon with a factor
@@ -91,7 +89,8 @@ impl ConvexPolygon {
             .map(|v| ((*v - centroid) * factor) + centroid)
             .collect();
     }
-    /// Calculates the moment of inertia about the centroid.
+    /// Calculates the second moment of area about the centroid, the moment of
+    /// inertia of the polygon at a density of one.
     pub fn moi(&self) -> f32 {
         let n = self.get_num_vertices();
         let centroid = self.centroid();
@@ -170,18 +169,345 @@ impl ConvexPolygon {
     pub fn get_vertices(&self) -> Vec<Vec2> {
         self.vertices.clone()
     }
+
+    /// Returns true if the point is inside or on the boundary, for either
+    /// winding.
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        let n = self.vertices.len();
+        let (mut left, mut right) = (false, false);
+        for i in 0..n {
+            let (a, b) = (self.vertices[i], self.vertices[(i + 1) % n]);
+            let side = (b - a).cross(point - a);
+            left |= side > 0.0;
+            right |= side < 0.0;
+        }
+        n >= 3 && !(left && right)
+    }
+}
+
+pub(crate) fn polygon_centroid(vertices: &[Vec2]) -> Vec2 {
+    let n = vertices.len();
+    let mut cx = 0.0;
+    let mut cy = 0.0;
+    let mut area = 0.0;
+
+    for i in 0..n {
+        let p1 = vertices[i];
+        let p2 = vertices[(i + 1) % n];
+        let cross = p1.x * p2.y - p1.y * p2.x;
+        area += cross;
+        cx += (p1.x + p2.x) * cross;
+        cy += (p1.y + p2.y) * cross;
+    }
+
+    area /= 2.0;
+    cx /= 6.0 * area;
+    cy /= 6.0 * area;
+
+    Vec2 { x: cx, y: cy }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PolygonErrors {
+    TooFewVertices(usize),
+    NotConvex,
+    ZeroArea,
+}
+
+impl fmt::Display for PolygonErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolygonErrors::TooFewVertices(n) => {
+                write!(f, "A polygon needs at least 3 vertices, got {}.", n)
+            }
+            PolygonErrors::NotConvex => write!(f, "The polygon is not convex."),
+            PolygonErrors::ZeroArea => write!(f, "The polygon has no area."),
+        }
+    }
+}
+
+impl std::error::Error for PolygonErrors {}
+
+#[derive(Debug, PartialEq)]
+pub enum MassErrors {
+    /// The density is zero, negative or not a number.
+    InvalidDensity(f32),
+    /// The shape has no area to give a mass to.
+    ZeroArea,
+}
+
+impl fmt::Display for MassErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MassErrors::InvalidDensity(density) => {
+                write!(f, "The density must be positive, got {}.", density)
+            }
+            MassErrors::ZeroArea => write!(f, "The shape has no area."),
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+impl std::error::Error for MassErrors {}
+
+/// Checks that the vertices form a convex polygon with an area, in either
+/// winding order. Self-intersecting outlines and repeated vertices are
+/// reported as not convex. The area and the edges are compared to the size of
+/// the polygon, so small polygons are as valid as large ones.
+pub fn validate_polygon(vertices: &[Vec2]) -> Result<(), PolygonErrors> {
+    let n = vertices.len();
+    if n < 3 {
+        return Err(PolygonErrors::TooFewVertices(n));
+    }
+    let bounds = Aabb::from_points(vertices);
+    let size = bounds.max - bounds.min;
+    let tolerance = f32::EPSILON * size.dot(size);
+    let mut area = 0.0;
+    for i in 0..n {
+        area += vertices[i].cross(vertices[(i + 1) % n]);
+    }
+    if area.abs() * 0.5 <= tolerance {
+        return Err(PolygonErrors::ZeroArea);
+    }
+
+    let sign = area.signum();
+    let mut turning = 0.0;
+    for i in 0..n {
+        let e1 = vertices[(i + 1) % n] - vertices[i];
+        let e2 = vertices[(i + 2) % n] - vertices[(i + 1) % n];
+        let lengths = e1.length() * e2.length();
+        if lengths <= tolerance {
+            return Err(PolygonErrors::NotConvex);
+        }
+        let cross = e1.cross(e2) * sign;
+        if cross < -1e-6 * lengths {
+            return Err(PolygonErrors::NotConvex);
+        }
+        turning += f32::atan2(cross, e1.dot(e2));
+    }
+    // A convex outline turns around exactly once
+    if turning > 2.0 * PI + 1e-3 {
+        return Err(PolygonErrors::NotConvex);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Shape {
     #[default]
     Box,
     ConvexPolygon,
+    /// Segment along the local x axis, rounded by `Body::radius`.
+    Capsule,
+    /// Disc of `Body::radius` around the position.
+    Circle,
+    /// Static polyline, see `Body::new_chain`.
+    Chain,
+    /// Static terrain from evenly spaced height samples, see `Body::new_heightfield`.
+    Heightfield,
+}
+
+fn box_vertices(width: Vec2) -> Vec<Vec2> {
+    let hw = width.x / 2.0;
+    let hh = width.y / 2.0;
+    vec![
+        Vec2 { x: hw, y: hh },
+        Vec2 { x: -hw, y: hh },
+        Vec2 { x: -hw, y: -hh },
+        Vec2 { x: hw, y: -hh },
+    ]
+}
+
+fn capsule_vertices(length: f32) -> Vec<Vec2> {
+    let half_length = 0.5 * length;
+    vec![
+        Vec2 {
+            x: -half_length,
+            y: 0.0,
+        },
+        Vec2 {
+            x: half_length,
+            y: 0.0,
+        },
+    ]
+}
+
+fn heightfield_points(heights: &[f32], spacing: f32) -> Vec<Vec2> {
+    heights
+        .iter()
+        .enumerate()
+        .map(|(i, &height)| Vec2::new(i as f32 * spacing, height))
+        .collect()
+}
+
+/// Shape of a body together with its dimensions, without mass or motion.
+///
+/// Lets tools test shapes for intersection (see `collide_shapes`) without
+/// creating bodies. The constructors match the ones of `Body`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ShapeGeometry {
+    pub shape: Shape,
+    pub width: Vec2,
+    /// Local vertices, see `Body::world_vertices` for how they are placed.
+    pub vertices: Vec<Vec2>,
+    pub radius: f32,
+    /// Distance between the samples of a heightfield, zero for the other
+    /// shapes.
+    pub spacing: f32,
+}
+
+impl ShapeGeometry {
+    pub fn new_box(width: Vec2) -> Self {
+        Self {
+            shape: Shape::Box,
+            width,
+            vertices: box_vertices(width),
+            radius: 0.0,
+            spacing: 0.0,
+        }
+    }
+
+    /// Returns a convex polygon, its vertices in counterclockwise order.
+    pub fn new_polygon(vertices: Vec<Vec2>) -> Result<Self, Sylt2DErrors> {
+        validate_polygon(&vertices)?;
+        let mut convex_polygon = ConvexPolygon { vertices };
+        convex_polygon.orient_counterclockwise();
+        Ok(Self {
+            shape: Shape::ConvexPolygon,
+            width: convex_polygon.bounding_box(),
+            vertices: convex_polygon.vertices,
+            radius: 0.0,
+            spacing: 0.0,
+        })
+    }
+
+    pub fn new_capsule(length: f32, radius: f32) -> Self {
+        Self {
+            shape: Shape::Capsule,
+            width: Vec2::new(length + 2.0 * radius, 2.0 * radius),
+            vertices: capsule_vertices(length),
+            radius,
+            spacing: 0.0,
+        }
+    }
+
+    pub fn new_circle(radius: f32) -> Self {
+        Self {
+            shape: Shape::Circle,
+            width: Vec2::new(2.0 * radius, 2.0 * radius),
+            vertices: Vec::new(),
+            radius,
+            spacing: 0.0,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if there are fewer than 2 points.
+    pub fn new_chain(points: Vec<Vec2>) -> Self {
+        assert_chain(&points);
+        let bounds = Aabb::from_points(&points);
+        Self {
+            shape: Shape::Chain,
+            width: bounds.max - bounds.min,
+            vertices: points,
+            radius: 0.0,
+            spacing: 0.0,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if there are fewer than 2 heights or `spacing` is not positive.
+    pub fn new_heightfield(heights: Vec<f32>, spacing: f32) -> Self {
+        assert_heightfield(&heights, spacing);
+        Self {
+            shape: Shape::Heightfield,
+            spacing,
+            ..Self::new_chain(heightfield_points(&heights, spacing))
+        }
+    }
+
+    /// Returns the area of the shape, zero for chains and heightfields.
+    pub fn area(&self) -> f32 {
+        match self.shape {
+            Shape::Box => self.width.x * self.width.y,
+            Shape::ConvexPolygon => ConvexPolygon::new(self.vertices.clone()).area(),
+            Shape::Capsule => {
+                let length = self.width.x - 2.0 * self.radius;
+                2.0 * self.radius * length + PI * self.radius * self.radius
+            }
+            Shape::Circle => PI * self.radius * self.radius,
+            Shape::Chain | Shape::Heightfield => 0.0,
+        }
+    }
+
+    /// Returns the center of mass of the shape in local coordinates, which the
+    /// position of a body with this shape is at.
+    pub fn centroid(&self) -> Vec2 {
+        match self.shape {
+            Shape::ConvexPolygon => polygon_centroid(&self.vertices),
+            _ => Vec2::default(),
+        }
+    }
+}
+
+/// How a body takes part in the simulation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BodyType {
+    /// Never moves, e.g. the ground.
+    #[default]
+    Static,
+    /// Moves by its velocity, but neither forces nor contacts act on it, e.g. a
+    /// moving platform.
+    Kinematic,
+    /// Moved by forces, contacts and joints.
+    Dynamic,
+}
+
+/// Which bodies a body collides with.
+///
+/// Two bodies of the same nonzero group always collide if the group is
+/// positive and never if it is negative. Otherwise each category has to be in
+/// the mask of the other body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CollisionFilter {
+    /// Categories the body belongs to, one bit each.
+    pub collision_category: u32,
+    /// Categories the body collides with.
+    pub collision_mask: u32,
+    pub group_index: i32,
+}
+
+impl Default for CollisionFilter {
+    fn default() -> Self {
+        Self {
+            collision_category: 1,
+            collision_mask: u32::MAX,
+            group_index: 0,
+        }
+    }
+}
+
+impl CollisionFilter {
+    pub fn should_collide(&self, other: &CollisionFilter) -> bool {
+        if self.group_index == other.group_index && self.group_index != 0 {
+            return self.group_index > 0;
+        }
+        self.collision_category & other.collision_mask != 0
+            && other.collision_category & self.collision_mask != 0
+    }
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Body {
     pub id: usize,
+    /// Set from the mass by the constructors, an infinite mass (`f32::MAX`)
+    /// making a static body. Change it with `Body::set_body_type`.
+    body_type: BodyType,
     pub position: Vec2,
     pub rotation: f32,
     pub velocity: Vec2,
@@ -190,103 +516,596 @@ pub struct Body {
     pub torque: f32,
     pub width: Vec2,
     pub friction: f32,
+    /// Overrides `WorldContext::friction_combine` for the contacts of the body.
+    pub friction_combine: Option<FrictionCombine>,
+    /// Coefficient of restitution, 0 for bodies that do not bounce and 1 for
+    /// ones that bounce back at the speed they hit with.
+    pub restitution: f32,
     pub mass: f32,
     pub inv_mass: f32,
     pub moi: f32,
     pub inv_moi: f32,
     vertices: Vec<Vec2>,
     pub shape: Shape,
+    /// Radius of a capsule or circle, zero for the other shapes.
+    pub radius: f32,
+    /// Distance between the samples of a heightfield along the local x axis,
+    /// zero for the other shapes.
+    pub spacing: f32,
+    /// Marks a fast moving body (e.g. a projectile) for continuous collision handling.
+    pub bullet: bool,
+    /// Id of a body this body never collides with, e.g. the shooter of a projectile.
+    pub ignore_body: Option<usize>,
+    /// Remaining lifetime in seconds, the world removes the body once it runs out.
+    pub lifetime: Option<f32>,
+    /// Optional lift and drag model applied while integrating forces.
+    pub aerodynamics: Option<Aerodynamics>,
+    /// Moving platform (an infinite mass body driven by its velocity) that carries
+    /// the bodies standing on it with its friction, see `Arbiter::carry_rider`.
+    pub carries_riders: bool,
+    /// Position and rotation at the start of the last step, `None` before the
+    /// first step. See `Body::interpolated_pose`.
+    pub previous_pose: Option<Transform>,
+    /// Pose of the shape relative to the body origin, e.g. a sprite pivot that
+    /// is not the center of the collider. `position` and `rotation` stay those
+    /// of the shape, see `Body::origin`.
+    pub shape_offset: Transform,
+    pub collision_filter: CollisionFilter,
+    /// Used instead of the gravity of the world, e.g. zero for a balloon.
+    pub gravity: Option<Vec2>,
+    /// Used instead of `Wind::drag`, e.g. large for a leaf.
+    pub wind_drag: Option<f32>,
+}
+
+/// Handle of a body in a `World`, see `World::body`.
+///
+/// Made from the body id. New bodies take their ids from a counter that only
+/// grows, and `World::add_body` gives a body whose id is already in the world a
+/// new one, so two bodies never share a handle. The handle of a removed body
+/// refers to no body, unless the same body is added again, e.g. when a
+/// snapshot is restored, in which case it refers to that body again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(frozen, eq, hash))]
+pub struct BodyHandle(usize);
+
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pymethods)]
+impl BodyHandle {
+    /// Returns the id of the body.
+    pub fn id(&self) -> usize {
+        self.0
+    }
 }
 
 static BODY_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
 
+// Keeps new ids clear of the id of a body made elsewhere, e.g. loaded from a
+// save.
+pub(crate) fn reserve_body_id(id: usize) {
+    BODY_ID_COUNTER.fetch_max(id + 1, Ordering::Relaxed);
+}
+
+// Panics for a chain without a segment.
+fn assert_chain(points: &[Vec2]) {
+    assert!(
+        points.len() >= 2,
+        "a chain needs at least 2 points, got {}",
+        points.len()
+    );
+}
+
+// Panics for a heightfield without a cell or with samples that are not
+// ordered along the x axis.
+fn assert_heightfield(heights: &[f32], spacing: f32) {
+    assert!(
+        heights.len() >= 2,
+        "a heightfield needs at least 2 heights, got {}",
+        heights.len()
+    );
+    assert!(spacing > 0.0, "spacing must be positive, got {spacing}");
+}
+
+// Static for the infinite mass `f32::MAX`, dynamic otherwise.
+fn body_type_for(mass: f32) -> BodyType {
+    if mass < f32::MAX {
+        BodyType::Dynamic
+    } else {
+        BodyType::Static
+    }
+}
+
+// The mass given to the JavaScript and Python bindings, where zero (or less)
+// stands for the infinite mass of a static body.
+#[cfg(any(feature = "wasm", feature = "python"))]
+pub(crate) fn static_mass(mass: f32) -> f32 {
+    if mass > 0.0 {
+        mass
+    } else {
+        f32::MAX
+    }
+}
+
 impl Body {
     pub fn new(width: Vec2, mass: f32) -> Self {
-        let inv_mass;
-        let inv_moi;
-        let moi;
-        if mass < f32::MAX {
-            inv_mass = 1.0 / mass;
-            moi = mass * (width.x * width.x + width.y * width.y) / 12.0;
-            inv_moi = 1.0 / moi;
-        } else {
-            inv_mass = 0.0;
-            moi = f32::MAX;
-            inv_moi = 0.0;
-        }
-        let hw = width.x / 2.0;
-        let hh = width.y / 2.0;
-        let vertices = vec![
-            Vec2 { x: hw, y: hh },
-            Vec2 { x: -hw, y: hh },
-            Vec2 { x: -hw, y: -hh },
-            Vec2 { x: hw, y: -hh },
-        ];
+        Self::with_shape(Shape::Box, width, box_vertices(width), 0.0, mass, |mass| {
+            mass * (width.x * width.x + width.y * width.y) / 12.0
+        })
+    }
 
-        let id = BODY_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    /// Returns a polygon body, or an error if the vertices do not form a convex
+    /// polygon (see `validate_polygon`). The vertices are stored in
+    /// counterclockwise order, whichever winding they are given in.
+    pub fn new_polygon(vertices: Vec<Vec2>, mass: f32) -> Result<Self, Sylt2DErrors> {
+        validate_polygon(&vertices)?;
+        let mut convex_polygon = ConvexPolygon { vertices };
+        convex_polygon.orient_counterclockwise();
+        // `ConvexPolygon::moi` is the second moment of area
+        let moi = convex_polygon.moi() / convex_polygon.area();
+        let width = convex_polygon.bounding_box();
+        Ok(Self::with_shape(
+            Shape::ConvexPolygon,
+            width,
+            convex_polygon.vertices,
+            0.0,
+            mass,
+            |mass| mass * moi,
+        ))
+    }
 
-        Self {
-            id,
-            position: Vec2::new(0.0, 0.0),
-            rotation: 0.0,
-            velocity: Vec2::new(0.0, 0.0),
-            angular_velocity: 0.0,
-            force: Vec2::new(0.0, 0.0),
-            torque: 0.0,
-            friction: 0.0,
+    /// Returns a capsule whose segment of `length` lies along the local x axis.
+    pub fn new_capsule(length: f32, radius: f32, mass: f32) -> Self {
+        let half_length = 0.5 * length;
+        let width = Vec2::new(length + 2.0 * radius, 2.0 * radius);
+        Self::with_shape(
+            Shape::Capsule,
             width,
+            capsule_vertices(length),
+            radius,
             mass,
-            inv_mass,
-            inv_moi,
-            moi,
-            vertices,
-            shape: Shape::Box,
-        }
+            |mass| {
+                // Rectangle between the segment ends plus two half discs
+                let rect_area = 4.0 * half_length * radius;
+                let circle_area = PI * radius * radius;
+                let rect_mass = mass * rect_area / (rect_area + circle_area);
+                let circle_mass = mass - rect_mass;
+                let rect_moi = rect_mass * (length * length + 4.0 * radius * radius) / 12.0;
+                let circle_moi = circle_mass
+                    * (0.5 * radius * radius
+                        + half_length * half_length
+                        + 8.0 * half_length * radius / (3.0 * PI));
+                rect_moi + circle_moi
+            },
+        )
     }
-    pub fn new_polygon(vertices: Vec<Vec2>, mass: f32) -> Self {
-        let mut convex_polygon = ConvexPolygon {
-            vertices: vertices.clone(),
-        };
-        convex_polygon.orient_counterclockwise();
-        let inv_mass;
-        let inv_moi;
-        let moi;
-        if mass < f32::MAX {
-            inv_mass = 1.0 / mass;
-            moi = mass * convex_polygon.moi();
-            inv_moi = 1.0 / moi;
-        } else {
-            inv_mass = 0.0;
-            moi = f32::MAX;
-            inv_moi = 0.0;
-        }
-        let width = convex_polygon.bounding_box();
 
-        let id = BODY_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    pub fn new_circle(radius: f32, mass: f32) -> Self {
+        let width = Vec2::new(2.0 * radius, 2.0 * radius);
+        Self::with_shape(Shape::Circle, width, Vec::new(), radius, mass, |mass| {
+            0.5 * mass * radius * radius
+        })
+    }
 
-        Self {
-            id,
+    /// Returns a static polyline through the `points`, e.g. terrain from a level
+    /// editor. Chains are one-sided: bodies collide with the left side of the
+    /// direction of the points, which is the top of a chain running from left
+    /// to right.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than 2 points.
+    pub fn new_chain(points: Vec<Vec2>) -> Self {
+        assert_chain(&points);
+        let bounds = Aabb::from_points(&points);
+        let width = bounds.max - bounds.min;
+        Self::with_shape(Shape::Chain, width, points, 0.0, f32::MAX, |_| f32::MAX)
+    }
+
+    // Body of the shape at rest at the world origin with a fresh id. A finite
+    // `mass` makes it dynamic with the moment of inertia `moi(mass)`, an
+    // infinite one static.
+    fn with_shape(
+        shape: Shape,
+        width: Vec2,
+        vertices: Vec<Vec2>,
+        radius: f32,
+        mass: f32,
+        moi: impl FnOnce(f32) -> f32,
+    ) -> Self {
+        let body_type = body_type_for(mass);
+        let moi = match body_type {
+            BodyType::Dynamic => moi(mass),
+            _ => f32::MAX,
+        };
+        let mut body = Self {
+            id: BODY_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            body_type,
             position: Vec2::new(0.0, 0.0),
             rotation: 0.0,
             velocity: Vec2::new(0.0, 0.0),
             angular_velocity: 0.0,
             force: Vec2::new(0.0, 0.0),
             torque: 0.0,
-            friction: 0.0,
             width,
+            friction: 0.0,
+            friction_combine: None,
+            restitution: 0.0,
             mass,
-            inv_mass,
-            inv_moi,
+            inv_mass: 0.0,
             moi,
+            inv_moi: 0.0,
             vertices,
-            shape: Shape::ConvexPolygon,
+            shape,
+            radius,
+            spacing: 0.0,
+            bullet: false,
+            ignore_body: None,
+            lifetime: None,
+            aerodynamics: None,
+            carries_riders: false,
+            previous_pose: None,
+            shape_offset: Transform::default(),
+            collision_filter: CollisionFilter::default(),
+            gravity: None,
+            wind_drag: None,
+        };
+        // Derives the inverses from the mass and the moment of inertia
+        body.set_body_type(body_type);
+        body
+    }
+
+    /// Returns static terrain with a sample every `spacing` along the local x
+    /// axis, the first one at the position. Like a chain, bodies collide with the
+    /// top side only.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than 2 heights or `spacing` is not positive.
+    pub fn new_heightfield(heights: Vec<f32>, spacing: f32) -> Self {
+        assert_heightfield(&heights, spacing);
+        let mut body = Self::new_chain(heightfield_points(&heights, spacing));
+        body.shape = Shape::Heightfield;
+        body.spacing = spacing;
+        body
+    }
+
+    /// Returns a body of the given shape whose mass is its area times `density`,
+    /// with the matching moment of inertia about the center of mass. Chains and
+    /// heightfields are always static.
+    ///
+    /// Fails for polygon geometries that are not convex, see `validate_polygon`,
+    /// for a density that is not positive and for solid shapes without area.
+    pub fn from_density(geometry: &ShapeGeometry, density: f32) -> Result<Self, Sylt2DErrors> {
+        if density <= 0.0 || density.is_nan() {
+            return Err(MassErrors::InvalidDensity(density).into());
+        }
+        let area = geometry.area();
+        let solid = !matches!(geometry.shape, Shape::Chain | Shape::Heightfield);
+        if solid && (area <= 0.0 || area.is_nan()) {
+            return Err(MassErrors::ZeroArea.into());
+        }
+        let mass = density * area;
+        let body = match geometry.shape {
+            Shape::Box => Self::new(geometry.width, mass),
+            Shape::ConvexPolygon => Self::new_polygon(geometry.vertices.clone(), mass)?,
+            Shape::Capsule => {
+                let length = geometry.width.x - 2.0 * geometry.radius;
+                Self::new_capsule(length, geometry.radius, mass)
+            }
+            Shape::Circle => Self::new_circle(geometry.radius, mass),
+            Shape::Chain | Shape::Heightfield => {
+                let mut body = Self::new_chain(geometry.vertices.clone());
+                body.shape = geometry.shape;
+                body.spacing = geometry.spacing;
+                body
+            }
+        };
+        Ok(body)
+    }
+
+    pub fn handle(&self) -> BodyHandle {
+        BodyHandle(self.id)
+    }
+
+    // Gives the body an id no other body has, e.g. for a clone added next to
+    // the original.
+    pub(crate) fn renew_id(&mut self) {
+        self.id = BODY_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn body_type(&self) -> BodyType {
+        self.body_type
+    }
+
+    /// Changes how the body takes part in the simulation. Static and kinematic
+    /// bodies get an infinite mass, a static one also stops. A body turning
+    /// dynamic takes its mass back, so a body made with an infinite mass stays
+    /// immovable.
+    pub fn set_body_type(&mut self, body_type: BodyType) {
+        self.body_type = body_type;
+        if body_type == BodyType::Dynamic && self.mass < f32::MAX {
+            self.inv_mass = 1.0 / self.mass;
+            // An infinite moment of inertia keeps the body from turning
+            self.inv_moi = if self.moi < f32::MAX {
+                1.0 / self.moi
+            } else {
+                0.0
+            };
+        } else {
+            self.inv_mass = 0.0;
+            self.inv_moi = 0.0;
+        }
+        if body_type == BodyType::Static {
+            self.velocity = Vec2::default();
+            self.angular_velocity = 0.0;
         }
     }
 
+    /// Changes the mass and scales the moment of inertia to match, as if the
+    /// body was made with this mass. Keeps the body type, see
+    /// `Body::set_body_type`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mass` is not positive.
+    pub fn set_mass(&mut self, mass: f32) {
+        let moi = match Self::from_density(&self.geometry(), 1.0) {
+            Ok(unit) if unit.mass > 0.0 && unit.mass < f32::MAX => unit.moi * mass / unit.mass,
+            _ => f32::MAX,
+        };
+        self.set_mass_data(mass, moi, self.shape_offset.position);
+    }
+
+    /// Changes the mass, the moment of inertia about the center of mass and
+    /// the center of mass relative to the body origin, and updates the
+    /// inverses. An infinite moment of inertia (`f32::MAX`) keeps the body from
+    /// turning.
+    ///
+    /// The position of the body is its center of mass, with the shape centered
+    /// on it. A new center moves both relative to the origin, which stays in
+    /// place, see `Body::shape_offset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mass` or `moi` is not positive.
+    pub fn set_mass_data(&mut self, mass: f32, moi: f32, center: Vec2) {
+        assert!(mass > 0.0, "mass must be positive, got {mass}");
+        assert!(moi > 0.0, "moment of inertia must be positive, got {moi}");
+        let origin = self.origin();
+        self.shape_offset.position = center;
+        self.set_origin(origin);
+        self.mass = mass;
+        self.moi = moi;
+        self.set_body_type(self.body_type);
+    }
+
+    /// Returns true for bodies moved by forces and contacts.
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    /// Returns the pose between the start (`alpha` 0) and the end (`alpha` 1) of
+    /// the last step, to render smoothly at a higher rate than the physics runs.
+    /// See `FixedStepper::alpha`.
+    pub fn interpolated_pose(&self, alpha: f32) -> Transform {
+        let previous = self
+            .previous_pose
+            .unwrap_or(Transform::new(self.position, self.rotation));
+        Transform::new(
+            previous.position + (self.position - previous.position) * alpha,
+            previous.rotation + (self.rotation - previous.rotation) * alpha,
+        )
+    }
+
     pub fn add_force(&mut self, force: Vec2) {
         self.force = self.force + force;
     }
 
+    /// Returns the velocity of a point in world space moving with the body.
+    pub fn velocity_at_world_point(&self, point: Vec2) -> Vec2 {
+        self.velocity + self.angular_velocity.cross(point - self.position)
+    }
+
+    /// Returns the pose of the body origin, see `Body::shape_offset`.
+    pub fn origin(&self) -> Transform {
+        let rotation = self.rotation - self.shape_offset.rotation;
+        let offset = Mat2x2::new_from_angle(rotation) * self.shape_offset.position;
+        Transform::new(self.position - offset, rotation)
+    }
+
+    /// Moves the body so that its origin is at `origin`, see
+    /// `Body::shape_offset`.
+    pub fn set_origin(&mut self, origin: Transform) {
+        let offset = Mat2x2::new_from_angle(origin.rotation) * self.shape_offset.position;
+        self.position = origin.position + offset;
+        self.rotation = origin.rotation + self.shape_offset.rotation;
+    }
+
+    /// Returns a point given relative to the body, e.g. a joint anchor, in world
+    /// space.
+    pub fn local_to_world(&self, point: Vec2) -> Vec2 {
+        self.position + self.local_vector_to_world(point)
+    }
+
+    /// Returns a point in world space relative to the body.
+    pub fn world_to_local(&self, point: Vec2) -> Vec2 {
+        self.world_vector_to_local(point - self.position)
+    }
+
+    /// Rotates a direction given relative to the body into world space.
+    pub fn local_vector_to_world(&self, vector: Vec2) -> Vec2 {
+        Mat2x2::new_from_angle(self.rotation) * vector
+    }
+
+    /// Rotates a direction in world space into the frame of the body.
+    pub fn world_vector_to_local(&self, vector: Vec2) -> Vec2 {
+        Mat2x2::new_from_angle(self.rotation).transpose() * vector
+    }
+
+    /// Adds a force acting at a point in world space, which also turns the body
+    /// unless the force points through its center of mass.
+    pub fn apply_force_at_point(&mut self, force: Vec2, point: Vec2) {
+        self.force = self.force + force;
+        self.torque += (point - self.position).cross(force);
+    }
+
+    /// Adds a torque, applied with the forces during the next step.
+    pub fn apply_torque(&mut self, torque: f32) {
+        self.torque += torque;
+    }
+
+    /// Changes the velocity at once by an impulse through the center of mass.
+    /// Static and kinematic bodies are not affected.
+    pub fn apply_impulse(&mut self, impulse: Vec2) {
+        self.velocity = self.velocity + impulse * self.inv_mass;
+    }
+
+    /// Changes the velocity and the angular velocity at once by an impulse at a
+    /// point in world space, e.g. a hit.
+    pub fn apply_impulse_at_point(&mut self, impulse: Vec2, point: Vec2) {
+        self.apply_impulse(impulse);
+        self.apply_angular_impulse((point - self.position).cross(impulse));
+    }
+
+    /// Changes the angular velocity at once.
+    pub fn apply_angular_impulse(&mut self, impulse: f32) {
+        self.angular_velocity += impulse * self.inv_moi;
+    }
+
+    /// Returns true if the pair should never generate contacts.
+    pub fn ignores(&self, other: &Body) -> bool {
+        self.ignore_body == Some(other.id)
+            || other.ignore_body == Some(self.id)
+            || !self
+                .collision_filter
+                .should_collide(&other.collision_filter)
+    }
+
+    /// Returns true if the world point is inside the shape. Chains and
+    /// heightfields have no inside.
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        match self.shape {
+            Shape::Box => {
+                let local =
+                    Mat2x2::new_from_angle(self.rotation).transpose() * (point - self.position);
+                local.x.abs() <= 0.5 * self.width.x && local.y.abs() <= 0.5 * self.width.y
+            }
+            Shape::ConvexPolygon => ConvexPolygon::new(self.world_vertices()).contains_point(point),
+            Shape::Circle => (point - self.position).length() <= self.radius,
+            Shape::Capsule => {
+                let vertices = self.world_vertices();
+                let axis = vertices[1] - vertices[0];
+                let s = ((point - vertices[0]).dot(axis) / axis.dot(axis)).clamp(0.0, 1.0);
+                (point - (vertices[0] + axis * s)).length() <= self.radius
+            }
+            Shape::Chain | Shape::Heightfield => false,
+        }
+    }
+
+    /// Returns the world space bounding box of the body.
+    pub fn aabb(&self) -> Aabb {
+        match self.shape {
+            Shape::Box => {
+                let h = Mat2x2::new_from_angle(self.rotation).abs() * (self.width * 0.5);
+                Aabb::new(self.position - h, self.position + h)
+            }
+            Shape::ConvexPolygon => {
+                // Same transform as `ConvexPolygon::rotate` and `translate`
+                let center = polygon_centroid(&self.vertices);
+                let rotation = Mat2x2::new_from_angle(self.rotation);
+                let mut aabb =
+                    Aabb::new(Vec2::new(f32::MAX, f32::MAX), Vec2::new(f32::MIN, f32::MIN));
+                for &vertex in self.vertices.iter() {
+                    let point = rotation * (vertex - center) + self.position;
+                    aabb.min = Vec2::new(aabb.min.x.min(point.x), aabb.min.y.min(point.y));
+                    aabb.max = Vec2::new(aabb.max.x.max(point.x), aabb.max.y.max(point.y));
+                }
+                aabb
+            }
+            Shape::Capsule => {
+                let rotation = Mat2x2::new_from_angle(self.rotation);
+                let start = rotation * self.vertices[0] + self.position;
+                let end = rotation * self.vertices[1] + self.position;
+                let r = Vec2::new(self.radius, self.radius);
+                Aabb::new(
+                    Vec2::new(start.x.min(end.x), start.y.min(end.y)) - r,
+                    Vec2::new(start.x.max(end.x), start.y.max(end.y)) + r,
+                )
+            }
+            Shape::Circle => {
+                let r = Vec2::new(self.radius, self.radius);
+                Aabb::new(self.position - r, self.position + r)
+            }
+            Shape::Chain | Shape::Heightfield => {
+                let rotation = Mat2x2::new_from_angle(self.rotation);
+                let mut aabb =
+                    Aabb::new(Vec2::new(f32::MAX, f32::MAX), Vec2::new(f32::MIN, f32::MIN));
+                for &vertex in self.vertices.iter() {
+                    let point = rotation * vertex + self.position;
+                    aabb.min = Vec2::new(aabb.min.x.min(point.x), aabb.min.y.min(point.y));
+                    aabb.max = Vec2::new(aabb.max.x.max(point.x), aabb.max.y.max(point.y));
+                }
+                aabb
+            }
+        }
+    }
+
+    /// Returns the vertices of the shape in world space. A capsule has the two
+    /// ends of its segment as vertices, a chain its points and a circle none.
+    pub fn world_vertices(&self) -> Vec<Vec2> {
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        self.world_vertices_into(&mut vertices);
+        vertices
+    }
+
+    /// Like `world_vertices`, but appends the vertices to `vertices`.
+    pub fn world_vertices_into(&self, vertices: &mut Vec<Vec2>) {
+        let center = match self.shape {
+            Shape::ConvexPolygon => polygon_centroid(&self.vertices),
+            Shape::Box | Shape::Capsule | Shape::Circle | Shape::Chain | Shape::Heightfield => {
+                Vec2::default()
+            }
+        };
+        let rotation = Mat2x2::new_from_angle(self.rotation);
+        vertices.extend(
+            self.vertices
+                .iter()
+                .map(|&vertex| rotation * (vertex - center) + self.position),
+        );
+    }
+
+    /// Returns the vertices of the shape in body space.
+    pub fn local_vertices(&self) -> &[Vec2] {
+        &self.vertices
+    }
+
+    /// Returns the shape of the body with its dimensions.
+    pub fn geometry(&self) -> ShapeGeometry {
+        ShapeGeometry {
+            shape: self.shape,
+            width: self.width,
+            vertices: self.vertices.clone(),
+            radius: self.radius,
+            spacing: self.spacing,
+        }
+    }
+
+    /// Returns a static body of the given shape without mass, e.g. for collision
+    /// tests.
+    pub(crate) fn from_geometry(geometry: &ShapeGeometry, transform: Transform) -> Self {
+        Self {
+            position: transform.position,
+            rotation: transform.rotation,
+            width: geometry.width,
+            vertices: geometry.vertices.clone(),
+            shape: geometry.shape,
+            radius: geometry.radius,
+            spacing: geometry.spacing,
+            ..Default::default()
+        }
+    }
+
     pub fn get_polygon(&self) -> ConvexPolygon {
         ConvexPolygon {
             vertices: self.vertices.clone(),
@@ -305,10 +1124,264 @@ mod tests {
         let body = Body::new(Vec2::new(1.0, 5.0), 50.0);
         println!("{:?}", body);
     }
+
     #[test]
     fn test_add_force() {
         let mut body = Body::default();
         body.add_force(Vec2::new(2.0, 5.3));
         assert_eq!(body.force, Vec2::new(2.0, 5.3));
     }
+
+    #[test]
+    fn test_apply_at_point() {
+        let mut body = Body::new(Vec2::new(1.0, 1.0), 2.0);
+        body.position = Vec2::new(1.0, 0.0);
+        // Pushing up at the right edge turns counterclockwise
+        body.apply_force_at_point(Vec2::new(0.0, 3.0), Vec2::new(1.5, 0.0));
+        body.apply_torque(0.5);
+        assert_eq!(body.force, Vec2::new(0.0, 3.0));
+        assert_eq!(body.torque, 2.0);
+
+        body.apply_impulse_at_point(Vec2::new(0.0, 1.0), Vec2::new(1.5, 0.0));
+        assert_eq!(body.velocity, Vec2::new(0.0, 0.5));
+        assert!((body.angular_velocity - 0.5 * body.inv_moi).abs() < 1e-6);
+
+        // A point above the center also moves sideways as the body turns
+        let velocity = body.velocity_at_world_point(Vec2::new(1.0, 2.0));
+        assert!((velocity.x + 2.0 * body.angular_velocity).abs() < 1e-6);
+        assert_eq!(velocity.y, 0.5);
+
+        let mut ground = Body::new(Vec2::new(1.0, 1.0), f32::MAX);
+        ground.apply_impulse_at_point(Vec2::new(0.0, 1.0), Vec2::new(0.5, 0.0));
+        assert_eq!(
+            (ground.velocity, ground.angular_velocity),
+            (Vec2::default(), 0.0)
+        );
+    }
+
+    #[test]
+    fn test_local_to_world() {
+        let mut body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        body.position = Vec2::new(2.0, 1.0);
+        body.rotation = std::f32::consts::FRAC_PI_2;
+        let world = body.local_to_world(Vec2::new(1.0, 0.0));
+        assert!((world - Vec2::new(2.0, 2.0)).length() < 1e-6);
+        assert!((body.world_to_local(world) - Vec2::new(1.0, 0.0)).length() < 1e-6);
+        // Directions only rotate
+        let up = body.local_vector_to_world(Vec2::new(1.0, 0.0));
+        assert!((up - Vec2::new(0.0, 1.0)).length() < 1e-6);
+        assert!((body.world_vector_to_local(up) - Vec2::new(1.0, 0.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_body_type() {
+        assert_eq!(
+            Body::new(Vec2::new(1.0, 1.0), f32::MAX).body_type(),
+            BodyType::Static
+        );
+        let chain = Body::new_chain(vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0)]);
+        assert_eq!(chain.body_type(), BodyType::Static);
+
+        let mut body = Body::new_circle(1.0, 2.0);
+        assert!(body.is_dynamic());
+        body.velocity = Vec2::new(1.0, 0.0);
+        body.set_body_type(BodyType::Kinematic);
+        assert_eq!((body.inv_mass, body.inv_moi), (0.0, 0.0));
+        assert_eq!(body.velocity, Vec2::new(1.0, 0.0));
+        body.set_body_type(BodyType::Static);
+        assert_eq!(body.velocity, Vec2::default());
+        body.set_body_type(BodyType::Dynamic);
+        assert_eq!(body.inv_mass, 0.5);
+    }
+
+    #[test]
+    fn test_shape_offset() {
+        let mut body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        body.shape_offset = Transform::new(Vec2::new(1.0, 0.0), 0.5);
+        body.set_origin(Transform::new(
+            Vec2::new(2.0, 3.0),
+            std::f32::consts::FRAC_PI_2,
+        ));
+        // The shape is a quarter turn around the origin
+        assert!((body.position - Vec2::new(2.0, 4.0)).length() < 1e-6);
+        assert!((body.rotation - (std::f32::consts::FRAC_PI_2 + 0.5)).abs() < 1e-6);
+
+        // Turning the shape half around swings the origin to the other side
+        body.rotation += PI;
+        let origin = body.origin();
+        assert!((origin.position - Vec2::new(2.0, 5.0)).length() < 1e-5);
+        assert!((origin.rotation - 1.5 * PI).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_set_mass() {
+        let mut body = Body::new(Vec2::new(1.0, 2.0), 1.0);
+        body.set_mass(3.0);
+        let same = Body::new(Vec2::new(1.0, 2.0), 3.0);
+        assert_eq!((body.mass, body.inv_mass), (same.mass, same.inv_mass));
+        assert!((body.moi - same.moi).abs() < 1e-5);
+        assert!((body.inv_moi - same.inv_moi).abs() < 1e-5);
+
+        body.set_mass_data(2.0, f32::MAX, Vec2::default());
+        assert_eq!(body.inv_mass, 0.5);
+        assert_eq!(body.inv_moi, 0.0);
+
+        // The center of mass moves away from the origin
+        body.rotation = std::f32::consts::FRAC_PI_2;
+        body.set_mass_data(2.0, 1.0, Vec2::new(0.5, 0.0));
+        assert!((body.position - Vec2::new(0.0, 0.5)).length() < 1e-6);
+        assert!(body.origin().position.length() < 1e-6);
+        assert_eq!(body.inv_moi, 1.0);
+        body.set_mass(2.0);
+        assert_eq!(body.shape_offset.position, Vec2::new(0.5, 0.0));
+
+        // A static body keeps its infinite mass until it turns dynamic
+        let mut ground = Body::new(Vec2::new(1.0, 1.0), f32::MAX);
+        ground.set_mass(4.0);
+        assert_eq!(ground.inv_mass, 0.0);
+        ground.set_body_type(BodyType::Dynamic);
+        assert_eq!(ground.inv_mass, 0.25);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_set_zero_mass() {
+        Body::new(Vec2::new(1.0, 1.0), 1.0).set_mass(0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chain_without_segment() {
+        Body::new_chain(vec![Vec2::new(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_polygon_moi() {
+        let rectangle = vec![
+            Vec2::new(-1.0, -0.5),
+            Vec2::new(1.0, -0.5),
+            Vec2::new(1.0, 0.5),
+            Vec2::new(-1.0, 0.5),
+        ];
+        assert!((ConvexPolygon::new(rectangle.clone()).moi() - 2.0 * 5.0 / 12.0).abs() < 1e-6);
+        // Matches the box of the same size and mass, whatever the size
+        for scale in [0.5, 1.0, 4.0] {
+            let vertices = rectangle.iter().map(|&vertex| vertex * scale).collect();
+            let polygon = Body::new_polygon(vertices, 3.0).unwrap();
+            let rectangle = Body::new(Vec2::new(2.0, 1.0) * scale, 3.0);
+            assert!((polygon.moi - rectangle.moi).abs() < 1e-4 * rectangle.moi);
+        }
+    }
+
+    #[test]
+    fn test_polygon_validation() {
+        let square = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 1.0),
+        ];
+        assert!(Body::new_polygon(square.clone(), 1.0).is_ok());
+        let clockwise: Vec<Vec2> = square.iter().rev().copied().collect();
+        assert_eq!(validate_polygon(&clockwise), Ok(()));
+        let body = Body::new_polygon(clockwise.clone(), 1.0).unwrap();
+        assert!(ConvexPolygon::new(body.vertices).signed_area() > 0.0);
+        let geometry = ShapeGeometry::new_polygon(clockwise).unwrap();
+        assert!(ConvexPolygon::new(geometry.vertices).signed_area() > 0.0);
+        // A tenth of a millimeter is as good as a meter
+        let small: Vec<Vec2> = square.iter().map(|&vertex| vertex * 1e-4).collect();
+        assert_eq!(validate_polygon(&small), Ok(()));
+
+        assert_eq!(
+            validate_polygon(&square[..2]),
+            Err(PolygonErrors::TooFewVertices(2))
+        );
+        let line = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(2.0, 2.0),
+        ];
+        assert_eq!(validate_polygon(&line), Err(PolygonErrors::ZeroArea));
+        let dent = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(1.0, 0.5),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(0.0, 2.0),
+        ];
+        assert!(matches!(
+            Body::new_polygon(dent.to_vec(), 1.0),
+            Err(Sylt2DErrors::Polygon(PolygonErrors::NotConvex))
+        ));
+        let pentagram: Vec<Vec2> = (0..5)
+            .map(|i| {
+                let angle = i as f32 * 4.0 * PI / 5.0;
+                Vec2::new(angle.cos(), angle.sin())
+            })
+            .collect();
+        assert_eq!(validate_polygon(&pentagram), Err(PolygonErrors::NotConvex));
+    }
+
+    #[test]
+    fn test_from_density() {
+        let body = Body::from_density(&ShapeGeometry::new_box(Vec2::new(2.0, 3.0)), 0.5).unwrap();
+        assert_eq!(body.mass, 3.0);
+        assert!((body.moi - 3.0 * 13.0 / 12.0).abs() < 1e-5);
+
+        // A right triangle, the position is at its centroid
+        let triangle = ShapeGeometry::new_polygon(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(0.0, 3.0),
+        ])
+        .unwrap();
+        assert_eq!(triangle.centroid(), Vec2::new(1.0, 1.0));
+        let body = Body::from_density(&triangle, 2.0).unwrap();
+        assert!((body.mass - 9.0).abs() < 1e-5);
+        assert!((body.moi - 9.0 * (9.0 + 9.0) / 18.0).abs() < 1e-4);
+
+        let circle = Body::from_density(&ShapeGeometry::new_circle(1.0), 1.0).unwrap();
+        assert!((circle.mass - PI).abs() < 1e-5);
+        let capsule = Body::from_density(&ShapeGeometry::new_capsule(2.0, 1.0), 1.0).unwrap();
+        assert!((capsule.mass - (4.0 + PI)).abs() < 1e-5);
+        assert_eq!(capsule.width, Vec2::new(4.0, 2.0));
+
+        let chain = ShapeGeometry::new_chain(vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0)]);
+        assert_eq!(Body::from_density(&chain, 1.0).unwrap().inv_mass, 0.0);
+
+        let square = ShapeGeometry::new_box(Vec2::new(1.0, 1.0));
+        for density in [0.0, -1.0, f32::NAN] {
+            assert!(matches!(
+                Body::from_density(&square, density),
+                Err(Sylt2DErrors::Mass(MassErrors::InvalidDensity(_)))
+            ));
+        }
+        assert!(matches!(
+            Body::from_density(&ShapeGeometry::new_circle(0.0), 1.0),
+            Err(Sylt2DErrors::Mass(MassErrors::ZeroArea))
+        ));
+    }
+
+    #[test]
+    fn test_contains_point() {
+        let triangle = ConvexPolygon::new(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(0.0, 2.0),
+        ]);
+        assert!(triangle.contains_point(Vec2::new(0.5, 0.5)));
+        assert!(triangle.contains_point(Vec2::new(1.0, 0.0)));
+        assert!(!triangle.contains_point(Vec2::new(1.5, 1.5)));
+
+        let mut box_ = Body::new(Vec2::new(2.0, 1.0), 1.0);
+        box_.position = Vec2::new(5.0, 0.0);
+        box_.rotation = std::f32::consts::FRAC_PI_2;
+        assert!(box_.contains_point(Vec2::new(5.0, 0.9)));
+        assert!(!box_.contains_point(Vec2::new(5.9, 0.0)));
+
+        let mut capsule = Body::new_capsule(2.0, 0.5, 1.0);
+        capsule.position = Vec2::new(5.0, 0.0);
+        assert!(capsule.contains_point(Vec2::new(6.4, 0.0)));
+        assert!(!capsule.contains_point(Vec2::new(5.0, 0.6)));
+    }
 }
@@ -0,0 +1,1003 @@
+use crate::body::{Body, Shape};
+use crate::math_utils::Vec2;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Axis aligned bounding box.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the smallest box containing all the points.
+    pub fn from_points(points: &[Vec2]) -> Self {
+        let mut min = Vec2::new(f32::MAX, f32::MAX);
+        let mut max = Vec2::new(f32::MIN, f32::MIN);
+        for point in points {
+            min = Vec2::new(min.x.min(point.x), min.y.min(point.y));
+            max = Vec2::new(max.x.max(point.x), max.y.max(point.y));
+        }
+        Self { min, max }
+    }
+
+    /// Returns the smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: Vec2::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Vec2::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    /// Returns whether all bounds are finite numbers.
+    pub fn is_finite(&self) -> bool {
+        [self.min.x, self.min.y, self.max.x, self.max.y]
+            .iter()
+            .all(|bound| bound.is_finite())
+    }
+
+    /// Returns whether `other` lies completely inside this box.
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && other.max.x <= self.max.x
+            && other.max.y <= self.max.y
+    }
+
+    fn perimeter(&self) -> f32 {
+        2.0 * (self.max.x - self.min.x + self.max.y - self.min.y)
+    }
+}
+
+/// Finds the pairs of bodies whose bounding boxes overlap.
+///
+/// Bodies are identified by their id. The pipeline keeps the boxes up to date
+/// through `add`, `remove` and `move_proxy`, and asks for the overlapping pairs
+/// once per step.
+pub trait Broadphase: fmt::Debug + Send + Sync {
+    /// Adds the box of a body that is not tracked yet.
+    fn add(&mut self, id: usize, aabb: Aabb);
+    /// Forgets the box of a body.
+    fn remove(&mut self, id: usize);
+    /// Replaces the box of a tracked body.
+    fn move_proxy(&mut self, id: usize, aabb: Aabb);
+    /// Replaces `pairs` with the id pairs of the overlapping boxes, the lower id
+    /// first, in ascending order.
+    ///
+    /// Only ids of boxes added and not removed since should be reported. The
+    /// pipeline skips pairs with other ids and pairs of a body with itself.
+    fn query_pairs(&mut self, pairs: &mut Vec<(usize, usize)>);
+}
+
+impl Default for Box<dyn Broadphase> {
+    fn default() -> Self {
+        Box::new(BruteForce::default())
+    }
+}
+
+/// Algorithm finding the pairs of overlapping bounding boxes, see
+/// `WorldContext::broad_phase`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BroadPhaseMode {
+    /// Tests every pair of bodies, see `BruteForce`.
+    #[default]
+    BruteForce,
+    /// Buckets the bodies into square cells of `cell_size` and only tests the
+    /// bodies sharing a cell, see `SpatialHash`. The step panics if
+    /// `cell_size` is not a positive number.
+    SpatialHash { cell_size: f32 },
+    /// See `SweepAndPrune`.
+    SweepAndPrune,
+    /// See `DynamicTree`.
+    Tree,
+    /// The implementation given to `PhysicsPipeline::set_broadphase`.
+    Custom,
+}
+
+impl BroadPhaseMode {
+    /// Returns a new broad phase of this kind, None for `Custom`.
+    pub fn build(self) -> Option<Box<dyn Broadphase>> {
+        match self {
+            Self::BruteForce => Some(Box::new(BruteForce::new())),
+            Self::SpatialHash { cell_size } => Some(Box::new(SpatialHash::new(cell_size))),
+            Self::SweepAndPrune => Some(Box::new(SweepAndPrune::new())),
+            Self::Tree => Some(Box::new(DynamicTree::new())),
+            Self::Custom => None,
+        }
+    }
+}
+
+/// Tests every pair of boxes, fast enough for up to a few hundred bodies.
+///
+/// Each box is tested against `LANES` others at once, with SSE on x86_64 and
+/// NEON on aarch64 when the `simd` feature is on.
+#[derive(Debug, Default)]
+pub struct BruteForce {
+    aabbs: BTreeMap<usize, Aabb>,
+    lanes: AabbLanes,
+}
+
+impl BruteForce {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Broadphase for BruteForce {
+    fn add(&mut self, id: usize, aabb: Aabb) {
+        self.aabbs.insert(id, aabb);
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.aabbs.remove(&id);
+    }
+
+    fn move_proxy(&mut self, id: usize, aabb: Aabb) {
+        self.aabbs.insert(id, aabb);
+    }
+
+    fn query_pairs(&mut self, pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+        let lanes = &mut self.lanes;
+        lanes.fill(&self.aabbs);
+        for (a, (&id_a, aabb_a)) in self.aabbs.iter().enumerate() {
+            // From the group holding the next box on, ignoring the boxes
+            // before it
+            let mut start = (a + 1) / LANES * LANES;
+            let mut ignored = (1 << (a + 1 - start)) - 1;
+            while start < lanes.ids.len() {
+                let mut overlaps = lanes.overlaps(aabb_a, start) & !ignored;
+                while overlaps != 0 {
+                    let b = start + overlaps.trailing_zeros() as usize;
+                    pairs.push((id_a, lanes.ids[b]));
+                    overlaps &= overlaps - 1;
+                }
+                start += LANES;
+                ignored = 0;
+            }
+        }
+    }
+}
+
+// Boxes compared at once by `AabbLanes::overlaps`.
+const LANES: usize = 4;
+
+// Bounds of the boxes of `BruteForce`, each bound in its own array so that the
+// bounds of `LANES` boxes can be loaded together. Padded with empty boxes to a
+// multiple of `LANES`.
+#[derive(Debug, Default)]
+struct AabbLanes {
+    ids: Vec<usize>,
+    min_x: Vec<f32>,
+    min_y: Vec<f32>,
+    max_x: Vec<f32>,
+    max_y: Vec<f32>,
+}
+
+impl AabbLanes {
+    fn fill(&mut self, aabbs: &BTreeMap<usize, Aabb>) {
+        self.ids.clear();
+        self.min_x.clear();
+        self.min_y.clear();
+        self.max_x.clear();
+        self.max_y.clear();
+        // Comparisons with NaN are false, so these overlap nothing, not even
+        // infinite boxes
+        let empty = Aabb::new(Vec2::new(f32::NAN, f32::NAN), Vec2::new(f32::NAN, f32::NAN));
+        let padding = (LANES - aabbs.len() % LANES) % LANES;
+        let padding = std::iter::repeat_n((usize::MAX, &empty), padding);
+        for (id, aabb) in aabbs.iter().map(|(&id, aabb)| (id, aabb)).chain(padding) {
+            self.ids.push(id);
+            self.min_x.push(aabb.min.x);
+            self.min_y.push(aabb.min.y);
+            self.max_x.push(aabb.max.x);
+            self.max_y.push(aabb.max.y);
+        }
+    }
+
+    // Returns a mask with bit k set if `aabb` overlaps box `start + k`, see
+    // `Aabb::overlaps`.
+    #[cfg(not(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+    fn overlaps(&self, aabb: &Aabb, start: usize) -> u32 {
+        let mut mask = 0;
+        for k in 0..LANES {
+            let b = start + k;
+            if aabb.min.x <= self.max_x[b]
+                && self.min_x[b] <= aabb.max.x
+                && aabb.min.y <= self.max_y[b]
+                && self.min_y[b] <= aabb.max.y
+            {
+                mask |= 1 << k;
+            }
+        }
+        mask
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    fn overlaps(&self, aabb: &Aabb, start: usize) -> u32 {
+        use std::arch::x86_64::*;
+        let lanes = start..start + LANES;
+        let (min_x, min_y) = (&self.min_x[lanes.clone()], &self.min_y[lanes.clone()]);
+        let (max_x, max_y) = (&self.max_x[lanes.clone()], &self.max_y[lanes]);
+        // SAFETY: SSE is part of the x86_64 baseline and the slices hold
+        // `LANES` floats each
+        unsafe {
+            let overlaps_x = _mm_and_ps(
+                _mm_cmple_ps(_mm_set1_ps(aabb.min.x), _mm_loadu_ps(max_x.as_ptr())),
+                _mm_cmple_ps(_mm_loadu_ps(min_x.as_ptr()), _mm_set1_ps(aabb.max.x)),
+            );
+            let overlaps_y = _mm_and_ps(
+                _mm_cmple_ps(_mm_set1_ps(aabb.min.y), _mm_loadu_ps(max_y.as_ptr())),
+                _mm_cmple_ps(_mm_loadu_ps(min_y.as_ptr()), _mm_set1_ps(aabb.max.y)),
+            );
+            _mm_movemask_ps(_mm_and_ps(overlaps_x, overlaps_y)) as u32
+        }
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+    fn overlaps(&self, aabb: &Aabb, start: usize) -> u32 {
+        use std::arch::aarch64::*;
+        let lanes = start..start + LANES;
+        let (min_x, min_y) = (&self.min_x[lanes.clone()], &self.min_y[lanes.clone()]);
+        let (max_x, max_y) = (&self.max_x[lanes.clone()], &self.max_y[lanes]);
+        const BITS: [u32; LANES] = [1, 2, 4, 8];
+        // SAFETY: NEON is part of the aarch64 baseline and the slices hold
+        // `LANES` floats each
+        unsafe {
+            let overlaps_x = vandq_u32(
+                vcleq_f32(vdupq_n_f32(aabb.min.x), vld1q_f32(max_x.as_ptr())),
+                vcleq_f32(vld1q_f32(min_x.as_ptr()), vdupq_n_f32(aabb.max.x)),
+            );
+            let overlaps_y = vandq_u32(
+                vcleq_f32(vdupq_n_f32(aabb.min.y), vld1q_f32(max_y.as_ptr())),
+                vcleq_f32(vld1q_f32(min_y.as_ptr()), vdupq_n_f32(aabb.max.y)),
+            );
+            let overlaps = vandq_u32(overlaps_x, overlaps_y);
+            vaddvq_u32(vandq_u32(overlaps, vld1q_u32(BITS.as_ptr())))
+        }
+    }
+}
+
+// Cells covered by a box, from the lower left to the upper right one.
+type CellRange = ((i32, i32), (i32, i32));
+
+/// Uniform grid of square cells, each listing the boxes touching it.
+///
+/// Suited to many bodies of similar size, with cells about as large as the
+/// bodies. A body much larger than the cells is added to every cell it covers.
+/// Boxes with bounds that are not finite, e.g. of a body that blew up, are kept
+/// out of the cells and overlap nothing.
+#[derive(Debug)]
+pub struct SpatialHash {
+    cell_size: f32,
+    proxies: HashMap<usize, (Aabb, Option<CellRange>)>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    /// # Panics
+    ///
+    /// If `cell_size` is not a positive finite number.
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size > 0.0 && cell_size.is_finite(),
+            "cell size must be positive and finite, got {}",
+            cell_size
+        );
+        Self {
+            cell_size,
+            proxies: HashMap::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, point: Vec2) -> (i32, i32) {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn cells_of(&self, aabb: &Aabb) -> Option<CellRange> {
+        aabb.is_finite()
+            .then(|| (self.cell(aabb.min), self.cell(aabb.max)))
+    }
+
+    fn insert_cells(&mut self, id: usize, cells: Option<CellRange>) {
+        let Some((min, max)) = cells else {
+            return;
+        };
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    fn remove_cells(&mut self, id: usize, cells: Option<CellRange>) {
+        let Some((min, max)) = cells else {
+            return;
+        };
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                if let Some(bucket) = self.cells.get_mut(&(x, y)) {
+                    bucket.retain(|&other| other != id);
+                    if bucket.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Broadphase for SpatialHash {
+    fn add(&mut self, id: usize, aabb: Aabb) {
+        let cells = self.cells_of(&aabb);
+        self.insert_cells(id, cells);
+        self.proxies.insert(id, (aabb, cells));
+    }
+
+    fn remove(&mut self, id: usize) {
+        if let Some((_, cells)) = self.proxies.remove(&id) {
+            self.remove_cells(id, cells);
+        }
+    }
+
+    fn move_proxy(&mut self, id: usize, aabb: Aabb) {
+        let cells = self.cells_of(&aabb);
+        let Some(proxy) = self.proxies.get_mut(&id) else {
+            return self.add(id, aabb);
+        };
+        let old_cells = proxy.1;
+        *proxy = (aabb, cells);
+        if old_cells != cells {
+            self.remove_cells(id, old_cells);
+            self.insert_cells(id, cells);
+        }
+    }
+
+    fn query_pairs(&mut self, pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+        for (&cell, bucket) in self.cells.iter() {
+            for (k, &id_a) in bucket.iter().enumerate() {
+                for &id_b in &bucket[k + 1..] {
+                    let (a, b) = (&self.proxies[&id_a].0, &self.proxies[&id_b].0);
+                    if !a.overlaps(b) {
+                        continue;
+                    }
+                    // Only the cell of the lower corner of the overlap reports the pair
+                    let corner = Vec2::new(a.min.x.max(b.min.x), a.min.y.max(b.min.y));
+                    if self.cell(corner) == cell {
+                        pairs.push((id_a.min(id_b), id_a.max(id_b)));
+                    }
+                }
+            }
+        }
+        pairs.sort_unstable();
+    }
+}
+
+/// Sorts the boxes along the x axis and sweeps each one over the boxes starting
+/// before its end.
+///
+/// Does not depend on the size of the bodies and benefits from the order of the
+/// last step, as the boxes barely move between steps. With the `parallel`
+/// feature the boxes are swept on all cores.
+#[derive(Debug, Default)]
+pub struct SweepAndPrune {
+    aabbs: HashMap<usize, Aabb>,
+    // Boxes in the order of their lower x bound at the last query
+    sorted: Vec<(usize, Aabb)>,
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Broadphase for SweepAndPrune {
+    fn add(&mut self, id: usize, aabb: Aabb) {
+        self.aabbs.insert(id, aabb);
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.aabbs.remove(&id);
+    }
+
+    fn move_proxy(&mut self, id: usize, aabb: Aabb) {
+        self.aabbs.insert(id, aabb);
+    }
+
+    fn query_pairs(&mut self, pairs: &mut Vec<(usize, usize)>) {
+        // Refresh the boxes in the last order, which stays almost sorted
+        let aabbs = &self.aabbs;
+        self.sorted.retain_mut(|(id, aabb)| match aabbs.get(id) {
+            Some(&current) => {
+                *aabb = current;
+                true
+            }
+            None => false,
+        });
+        if self.sorted.len() < aabbs.len() {
+            self.sorted.clear();
+            self.sorted
+                .extend(aabbs.iter().map(|(&id, &aabb)| (id, aabb)));
+        }
+        self.sorted
+            .sort_by(|(_, a), (_, b)| a.min.x.total_cmp(&b.min.x));
+
+        let sorted = &self.sorted;
+        let sweep = |(k, &(id_a, aabb_a)): (usize, &(usize, Aabb))| {
+            sorted[k + 1..]
+                .iter()
+                .take_while(move |(_, aabb_b)| aabb_b.min.x <= aabb_a.max.x)
+                .filter(move |(_, aabb_b)| aabb_a.overlaps(aabb_b))
+                .map(move |&(id_b, _)| (id_a.min(id_b), id_a.max(id_b)))
+        };
+        pairs.clear();
+        #[cfg(feature = "parallel")]
+        pairs.par_extend(
+            sorted
+                .par_iter()
+                .enumerate()
+                .with_min_len(PARALLEL_MIN_LEN)
+                .flat_map_iter(sweep),
+        );
+        #[cfg(not(feature = "parallel"))]
+        pairs.extend(sorted.iter().enumerate().flat_map(sweep));
+        pairs.sort_unstable();
+    }
+}
+
+/// Margin around the boxes of `DynamicTree`, so bodies moving a little keep
+/// their place in the tree.
+pub const TREE_MARGIN: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+struct TreeNode {
+    // Box of a leaf grown by the margin, or the box of both children
+    aabb: Aabb,
+    // Box of the body of a leaf
+    tight: Aabb,
+    parent: Option<usize>,
+    children: Option<(usize, usize)>,
+    id: usize,
+}
+
+/// Binary tree of boxes, each node holding the box around its children.
+///
+/// Suited to bodies of very different sizes and to large worlds of few bodies,
+/// where a grid does not fit. A new box is placed next to the node whose box
+/// grows the least, and the boxes are grown by `TREE_MARGIN`, so bodies moving
+/// a little stay where they are.
+#[derive(Debug, Default)]
+pub struct DynamicTree {
+    nodes: Vec<TreeNode>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    // Leaf of each body
+    leaves: HashMap<usize, usize>,
+    stack: Vec<usize>,
+}
+
+impl DynamicTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn alloc(&mut self, node: TreeNode) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn fatten(aabb: Aabb) -> Aabb {
+        let margin = Vec2::new(TREE_MARGIN, TREE_MARGIN);
+        Aabb::new(aabb.min - margin, aabb.max + margin)
+    }
+
+    // Recomputes the boxes from `node` up to the root.
+    fn refit(&mut self, mut node: Option<usize>) {
+        while let Some(index) = node {
+            if let Some((left, right)) = self.nodes[index].children {
+                self.nodes[index].aabb = self.nodes[left].aabb.union(&self.nodes[right].aabb);
+            }
+            node = self.nodes[index].parent;
+        }
+    }
+
+    fn replace_child(&mut self, parent: Option<usize>, old: usize, new: usize) {
+        match parent {
+            Some(parent) => {
+                let children = self.nodes[parent].children.as_mut().unwrap();
+                if children.0 == old {
+                    children.0 = new;
+                } else {
+                    children.1 = new;
+                }
+            }
+            None => self.root = Some(new),
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let Some(mut index) = self.root else {
+            self.nodes[leaf].parent = None;
+            self.root = Some(leaf);
+            return;
+        };
+        // Walk down to the sibling whose box grows the least
+        let aabb = self.nodes[leaf].aabb;
+        while let Some((left, right)) = self.nodes[index].children {
+            let node = &self.nodes[index].aabb;
+            let combined = node.union(&aabb).perimeter();
+            let cost = 2.0 * combined;
+            let inheritance = 2.0 * (combined - node.perimeter());
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let grown = child.aabb.union(&aabb).perimeter();
+                match child.children {
+                    Some(_) => grown - child.aabb.perimeter() + inheritance,
+                    None => grown + inheritance,
+                }
+            };
+            let (cost_left, cost_right) = (child_cost(left), child_cost(right));
+            if cost < cost_left && cost < cost_right {
+                break;
+            }
+            index = if cost_left < cost_right { left } else { right };
+        }
+
+        let sibling = index;
+        let parent = self.nodes[sibling].parent;
+        let new_parent = self.alloc(TreeNode {
+            aabb: self.nodes[sibling].aabb.union(&aabb),
+            tight: Aabb::default(),
+            parent,
+            children: Some((sibling, leaf)),
+            id: 0,
+        });
+        self.nodes[sibling].parent = Some(new_parent);
+        self.nodes[leaf].parent = Some(new_parent);
+        self.replace_child(parent, sibling, new_parent);
+        self.refit(parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        let Some(parent) = self.nodes[leaf].parent else {
+            self.root = None;
+            return;
+        };
+        let (left, right) = self.nodes[parent].children.unwrap();
+        let sibling = if left == leaf { right } else { left };
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        self.replace_child(grandparent, parent, sibling);
+        self.free.push(parent);
+        self.refit(grandparent);
+    }
+}
+
+impl Broadphase for DynamicTree {
+    fn add(&mut self, id: usize, aabb: Aabb) {
+        if self.leaves.contains_key(&id) {
+            return self.move_proxy(id, aabb);
+        }
+        let leaf = self.alloc(TreeNode {
+            aabb: Self::fatten(aabb),
+            tight: aabb,
+            parent: None,
+            children: None,
+            id,
+        });
+        self.insert_leaf(leaf);
+        self.leaves.insert(id, leaf);
+    }
+
+    fn remove(&mut self, id: usize) {
+        if let Some(leaf) = self.leaves.remove(&id) {
+            self.remove_leaf(leaf);
+            self.free.push(leaf);
+        }
+    }
+
+    fn move_proxy(&mut self, id: usize, aabb: Aabb) {
+        let Some(&leaf) = self.leaves.get(&id) else {
+            return self.add(id, aabb);
+        };
+        self.nodes[leaf].tight = aabb;
+        if self.nodes[leaf].aabb.contains(&aabb) {
+            return;
+        }
+        self.remove_leaf(leaf);
+        self.nodes[leaf].aabb = Self::fatten(aabb);
+        self.insert_leaf(leaf);
+    }
+
+    fn query_pairs(&mut self, pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+        let Some(root) = self.root else {
+            return;
+        };
+        for &leaf in self.leaves.values() {
+            let TreeNode { tight, id, .. } = self.nodes[leaf];
+            self.stack.clear();
+            self.stack.push(root);
+            while let Some(index) = self.stack.pop() {
+                let node = &self.nodes[index];
+                if !node.aabb.overlaps(&tight) {
+                    continue;
+                }
+                match node.children {
+                    Some((left, right)) => self.stack.extend([left, right]),
+                    // Each pair is reported by the body with the lower id
+                    None if node.id > id && node.tight.overlaps(&tight) => {
+                        pairs.push((id, node.id))
+                    }
+                    None => {}
+                }
+            }
+        }
+        pairs.sort_unstable();
+    }
+}
+
+// Fewest bodies handled by one task of the parallel broad phase.
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_LEN: usize = 64;
+
+// Bounding box of a body together with the pose and the dimensions it was
+// computed for. The vertices are only set by the constructors of the body, and
+// `Body::shape_offset` moves the origin, not the shape.
+#[derive(Debug, Clone, Copy)]
+struct Proxy {
+    position: Vec2,
+    rotation: f32,
+    shape: Shape,
+    width: Vec2,
+    radius: f32,
+    spacing: f32,
+    aabb: Aabb,
+    stamp: u32,
+}
+
+impl Proxy {
+    fn new(body: &Body, aabb: Aabb, stamp: u32) -> Self {
+        Self {
+            position: body.position,
+            rotation: body.rotation,
+            shape: body.shape,
+            width: body.width,
+            radius: body.radius,
+            spacing: body.spacing,
+            aabb,
+            stamp,
+        }
+    }
+
+    fn is_at(&self, body: &Body) -> bool {
+        self.position == body.position
+            && self.rotation == body.rotation
+            && self.shape == body.shape
+            && self.width == body.width
+            && self.radius == body.radius
+            && self.spacing == body.spacing
+    }
+}
+
+/// Bounding boxes of the bodies, kept between steps.
+///
+/// Only bodies whose pose changed since the last update get their box
+/// recomputed, so static and resting bodies cost nothing but a comparison.
+#[derive(Debug, Default)]
+pub struct Proxies {
+    proxies: HashMap<usize, Proxy>,
+    aabbs: Vec<Aabb>,
+    stamp: u32,
+    updated: usize,
+    // Boxes of the moved bodies, computed ahead on all cores with the `parallel`
+    // feature and empty without it
+    moved: Vec<Option<Aabb>>,
+}
+
+impl Proxies {
+    /// Refreshes the boxes of the moved bodies and drops the ones of removed
+    /// bodies, passing the changes on to the `broadphase`.
+    pub fn update(&mut self, bodies: &[Body], broadphase: &mut dyn Broadphase) {
+        self.stamp = self.stamp.wrapping_add(1);
+        self.updated = 0;
+        self.aabbs.clear();
+        let stamp = self.stamp;
+        let mut seen = 0;
+        #[cfg(feature = "parallel")]
+        {
+            let proxies = &self.proxies;
+            self.moved.clear();
+            self.moved.par_extend(
+                bodies
+                    .par_iter()
+                    .with_min_len(PARALLEL_MIN_LEN)
+                    .map(|body| match proxies.get(&body.id) {
+                        Some(proxy) if proxy.is_at(body) => None,
+                        _ => Some(body.aabb()),
+                    }),
+            );
+        }
+        for (index, body) in bodies.iter().enumerate() {
+            let moved = &self.moved;
+            let aabb = || {
+                moved
+                    .get(index)
+                    .copied()
+                    .flatten()
+                    .unwrap_or_else(|| body.aabb())
+            };
+            let proxy = match self.proxies.entry(body.id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let aabb = aabb();
+                    broadphase.add(body.id, aabb);
+                    self.updated += 1;
+                    entry.insert(Proxy::new(body, aabb, stamp.wrapping_sub(1)))
+                }
+            };
+            if proxy.stamp != stamp {
+                if !proxy.is_at(body) {
+                    *proxy = Proxy::new(body, aabb(), stamp);
+                    broadphase.move_proxy(body.id, proxy.aabb);
+                    self.updated += 1;
+                }
+                proxy.stamp = stamp;
+                seen += 1;
+            }
+            self.aabbs.push(proxy.aabb);
+        }
+        if self.proxies.len() > seen {
+            self.proxies.retain(|&id, proxy| {
+                let keep = proxy.stamp == stamp;
+                if !keep {
+                    broadphase.remove(id);
+                }
+                keep
+            });
+        }
+    }
+
+    /// Returns the boxes of the last update, in the order of the bodies.
+    pub fn aabbs(&self) -> &[Aabb] {
+        &self.aabbs
+    }
+
+    /// Returns the number of boxes (re)computed by the last update.
+    pub fn updated(&self) -> usize {
+        self.updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_moved_bodies_are_updated() {
+        let ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        let mut falling = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        falling.position = Vec2::new(0.0, 3.0);
+        let mut bodies = vec![ground, falling];
+
+        let mut proxies = Proxies::default();
+        let mut broadphase = BruteForce::new();
+        proxies.update(&bodies, &mut broadphase);
+        assert_eq!(proxies.updated(), 2);
+        assert!(!proxies.aabbs()[0].overlaps(&proxies.aabbs()[1]));
+
+        bodies[1].position = Vec2::new(0.0, 0.9);
+        proxies.update(&bodies, &mut broadphase);
+        assert_eq!(proxies.updated(), 1);
+        assert!(proxies.aabbs()[0].overlaps(&proxies.aabbs()[1]));
+        let mut pairs = Vec::new();
+        broadphase.query_pairs(&mut pairs);
+        assert_eq!(pairs, vec![(bodies[0].id, bodies[1].id)]);
+
+        // Swapping a body for a new one in the same update
+        bodies[1] = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        proxies.update(&bodies, &mut broadphase);
+        assert_eq!(proxies.updated(), 1);
+        assert_eq!(broadphase.aabbs.len(), 2);
+
+        proxies.update(&bodies[..1], &mut broadphase);
+        assert_eq!(proxies.updated(), 0);
+        assert_eq!(proxies.proxies.len(), 1);
+        assert_eq!(broadphase.aabbs.len(), 1);
+    }
+
+    #[test]
+    fn test_grown_body_is_updated() {
+        let ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        let mut ball = Body::new_circle(0.1, 1.0);
+        ball.position = Vec2::new(0.0, 2.0);
+        let mut bodies = vec![ground, ball];
+
+        let mut proxies = Proxies::default();
+        let mut broadphase = BruteForce::new();
+        proxies.update(&bodies, &mut broadphase);
+        assert!(!proxies.aabbs()[0].overlaps(&proxies.aabbs()[1]));
+
+        // Grows at rest
+        bodies[1].radius = 3.0;
+        proxies.update(&bodies, &mut broadphase);
+        assert_eq!(proxies.updated(), 1);
+        let mut pairs = Vec::new();
+        broadphase.query_pairs(&mut pairs);
+        assert_eq!(pairs, vec![(bodies[0].id, bodies[1].id)]);
+    }
+
+    #[test]
+    fn test_brute_force() {
+        // Not a whole number of lanes, with boxes that blew up
+        let mut aabbs = Vec::new();
+        for i in 0..11 {
+            let min = Vec2::new((i * 7 % 5) as f32 * 0.8, (i % 3) as f32 * 1.2);
+            aabbs.push(Aabb::new(min, min + Vec2::new(1.0, 1.5)));
+        }
+        aabbs[4].max.x = f32::NAN;
+        aabbs[9].min.y = f32::NEG_INFINITY;
+        aabbs[10] = Aabb::new(
+            Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+            Vec2::new(f32::INFINITY, f32::INFINITY),
+        );
+        let mut brute_force = BruteForce::new();
+        for (id, aabb) in aabbs.iter().enumerate() {
+            brute_force.add(id * 2, *aabb);
+        }
+        let mut expected = Vec::new();
+        for a in 0..aabbs.len() {
+            for b in a + 1..aabbs.len() {
+                if aabbs[a].overlaps(&aabbs[b]) {
+                    expected.push((a * 2, b * 2));
+                }
+            }
+        }
+        let mut pairs = Vec::new();
+        brute_force.query_pairs(&mut pairs);
+        assert!(expected.iter().any(|&(a, b)| a == 18 || b == 18));
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_spatial_hash() {
+        let mut aabbs = Vec::new();
+        for i in 0..30 {
+            let min = Vec2::new((i % 6) as f32 * 0.9, (i / 6) as f32 * 1.3);
+            aabbs.push(Aabb::new(min, min + Vec2::new(1.0, 1.0)));
+        }
+        // A long floor over many cells
+        aabbs.push(Aabb::new(Vec2::new(-1.0, -0.5), Vec2::new(6.0, 0.1)));
+
+        let mut brute_force = BruteForce::new();
+        let mut hashes = [0.5, 1.0, 4.0].map(SpatialHash::new);
+        let (mut expected, mut pairs) = (Vec::new(), Vec::new());
+        for step in 0..3 {
+            // The removed boxes stay out
+            for (id, aabb) in aabbs.iter().enumerate().skip(step) {
+                let moved = Aabb::new(aabb.min, aabb.max + Vec2::new(0.3, 0.0) * step as f32);
+                brute_force.move_proxy(id, moved);
+                for hash in hashes.iter_mut() {
+                    hash.move_proxy(id, moved);
+                }
+            }
+            brute_force.remove(step);
+            brute_force.query_pairs(&mut expected);
+            assert!(!expected.is_empty());
+            for hash in hashes.iter_mut() {
+                hash.remove(step);
+                hash.query_pairs(&mut pairs);
+                assert_eq!(pairs, expected);
+            }
+        }
+        assert!(expected.iter().all(|&(a, _)| a > 2));
+
+        // A box that blew up is left out
+        let mut hash = SpatialHash::new(1.0);
+        hash.add(0, aabbs[0]);
+        hash.add(
+            1,
+            Aabb::new(Vec2::new(f32::NEG_INFINITY, 0.0), aabbs[0].max),
+        );
+        hash.move_proxy(2, Aabb::new(aabbs[0].min, Vec2::new(f32::NAN, 1.0)));
+        hash.query_pairs(&mut pairs);
+        assert!(pairs.is_empty());
+        hash.remove(1);
+        hash.move_proxy(2, aabbs[0]);
+        hash.query_pairs(&mut pairs);
+        assert_eq!(pairs, vec![(0, 2)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_spatial_hash_cell_size() {
+        SpatialHash::new(0.0);
+    }
+
+    #[test]
+    fn test_sweep_and_prune() {
+        let mut brute_force = BruteForce::new();
+        let mut sweep = SweepAndPrune::new();
+        let (mut expected, mut pairs) = (Vec::new(), Vec::new());
+        for step in 0..3 {
+            // Enough boxes to be split between threads, some of them removed
+            for id in step * 20..500 {
+                let min = Vec2::new((id * 37 % 101) as f32, (id * 53 % 97) as f32 * 0.5)
+                    + Vec2::new(step as f32, 0.0) * (id % 3) as f32;
+                let aabb = Aabb::new(min, min + Vec2::new(2.5, 1.5));
+                brute_force.move_proxy(id, aabb);
+                sweep.move_proxy(id, aabb);
+            }
+            for id in step * 20..(step + 1) * 20 {
+                brute_force.remove(id);
+                sweep.remove(id);
+            }
+            brute_force.query_pairs(&mut expected);
+            sweep.query_pairs(&mut pairs);
+            assert!(!expected.is_empty());
+            assert_eq!(pairs, expected);
+        }
+    }
+
+    #[test]
+    fn test_dynamic_tree() {
+        let mut brute_force = BruteForce::new();
+        let mut tree = DynamicTree::new();
+        let (mut expected, mut pairs) = (Vec::new(), Vec::new());
+        for step in 0..4 {
+            // Small boxes drifting over a few large ones, some of them removed
+            for id in step * 10..200 {
+                let size = if id % 25 == 0 { 20.0 } else { 1.0 };
+                let min = Vec2::new((id * 37 % 101) as f32, (id * 53 % 97) as f32 * 0.5)
+                    + Vec2::new(0.05, 0.4) * (step * (id % 3)) as f32;
+                let aabb = Aabb::new(min, min + Vec2::new(size, size * 0.5));
+                brute_force.move_proxy(id, aabb);
+                tree.move_proxy(id, aabb);
+            }
+            for id in step * 10..(step + 1) * 10 {
+                brute_force.remove(id);
+                tree.remove(id);
+            }
+            brute_force.query_pairs(&mut expected);
+            tree.query_pairs(&mut pairs);
+            assert!(!expected.is_empty());
+            assert_eq!(pairs, expected);
+        }
+        // Nodes of removed leaves are reused
+        assert_eq!(
+            tree.nodes.len() - tree.free.len(),
+            2 * tree.leaves.len() - 1
+        );
+    }
+}
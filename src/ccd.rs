@@ -0,0 +1,233 @@
+use crate::body::{Body, Shape};
+use crate::broad_phase::Aabb;
+use crate::collide_polygon::distance;
+use crate::math_utils::Vec2;
+
+/// Separation at which a sweep stops in front of the first contact.
+pub const TARGET_SEPARATION: f32 = 0.01;
+const MAX_ITERATIONS: u32 = 20;
+
+/// First contact of a bullet during a step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Impact {
+    /// Time of the contact, between zero and the step.
+    pub time: f32,
+    /// Storage index of the body that is hit.
+    pub other: usize,
+    /// Unit normal pointing from the bullet to the body that is hit.
+    pub normal: Vec2,
+    /// Point between the features that meet, at the current poses.
+    pub point: Vec2,
+    /// Distance between the features along the normal at the current poses.
+    pub separation: f32,
+}
+
+/// Returns the time within `dt` at which two bodies moving with their linear
+/// and angular velocities first come within `TARGET_SEPARATION` of each other,
+/// or `None` if they stay apart. Returns zero if they already touch.
+///
+/// Uses conservative advancement: the bodies are repeatedly moved forward by a
+/// time in which they cannot close the distance between them.
+pub fn toi(body_a: &Body, body_b: &Body, dt: f32) -> Option<f32> {
+    if distance(body_a, body_b)?.0 <= TARGET_SEPARATION {
+        return Some(0.0);
+    }
+    advance(body_a, body_b, dt).map(|(time, _)| time)
+}
+
+// Largest distance of a point of the body from its center of rotation.
+fn angular_extent(body: &Body) -> f32 {
+    match body.shape {
+        Shape::Circle => 0.0,
+        _ => {
+            body.world_vertices()
+                .iter()
+                .map(|&vertex| (vertex - body.position).length())
+                .fold(0.0, f32::max)
+                + body.radius
+        }
+    }
+}
+
+// Conservative advancement of two separated bodies. Returns the time of impact
+// together with the normal pointing from `body_a` to `body_b` at that time.
+fn advance(body_a: &Body, body_b: &Body, dt: f32) -> Option<(f32, Vec2)> {
+    let (mut a, mut b) = (body_a.clone(), body_b.clone());
+    let angular_bound = body_a.angular_velocity.abs() * angular_extent(body_a)
+        + body_b.angular_velocity.abs() * angular_extent(body_b);
+    let mut t = 0.0;
+    let mut normal = Vec2::default();
+    for _ in 0..MAX_ITERATIONS {
+        for (moved, body) in [(&mut a, body_a), (&mut b, body_b)] {
+            moved.position = body.position + body.velocity * t;
+            moved.rotation = body.rotation + body.angular_velocity * t;
+        }
+        let (separation, point_a, point_b) = distance(&a, &b)?;
+        let d = point_b - point_a;
+        if d.length() < f32::EPSILON {
+            return Some((t, normal));
+        }
+        normal = d * (1.0 / d.length());
+        let bound = (body_a.velocity - body_b.velocity).dot(normal) + angular_bound;
+        if bound <= 0.0 {
+            return None;
+        }
+        if separation <= TARGET_SEPARATION {
+            return Some((t, normal));
+        }
+        t += (separation - 0.5 * TARGET_SEPARATION) / bound;
+        if t > dt {
+            return None;
+        }
+    }
+    // Did not converge, e.g. a spinning body that never reaches the other
+    None
+}
+
+/// Returns the fraction of `translation` the body `shape` moves before it comes
+/// within `TARGET_SEPARATION` of `other`, together with the normal pointing
+/// from `other` to `shape` and the point on `other` it touches. Returns `None`
+/// if it stays apart or already touches `other`, like `sweep_bullet`.
+pub fn shape_cast(shape: &Body, translation: Vec2, other: &Body) -> Option<(f32, Vec2, Vec2)> {
+    if distance(shape, other)?.0 <= TARGET_SEPARATION {
+        return None;
+    }
+    let mut moving = shape.clone();
+    moving.velocity = translation;
+    moving.angular_velocity = 0.0;
+    let mut fixed = other.clone();
+    fixed.velocity = Vec2::default();
+    fixed.angular_velocity = 0.0;
+    let (fraction, normal) = advance(&moving, &fixed, 1.0)?;
+    moving.position = shape.position + translation * fraction;
+    let (_, _, point) = distance(&moving, &fixed)?;
+    Some((fraction, -normal, point))
+}
+
+/// Returns the first impact of the bullet at `index` with the bodies it does not
+/// already touch and `filter` lets it touch, all bodies moving with their
+/// velocities for `dt`. See `toi`.
+pub fn sweep_bullet(
+    bodies: &[Body],
+    index: usize,
+    dt: f32,
+    filter: impl Fn(&Body, &Body) -> bool,
+) -> Option<Impact> {
+    let bullet = &bodies[index];
+    let start = bullet.aabb();
+    let extent = angular_extent(bullet);
+    let start = Aabb::new(
+        bullet.position - Vec2::new(extent, extent),
+        bullet.position + Vec2::new(extent, extent),
+    )
+    .union(&start);
+    let motion = bullet.velocity * dt;
+    let swept = Aabb::new(
+        Vec2::new(
+            start.min.x + motion.x.min(0.0),
+            start.min.y + motion.y.min(0.0),
+        ),
+        Vec2::new(
+            start.max.x + motion.x.max(0.0),
+            start.max.y + motion.y.max(0.0),
+        ),
+    );
+
+    let mut first: Option<(f32, usize, Vec2)> = None;
+    for (other_index, other) in bodies.iter().enumerate() {
+        if other_index == index
+            || bullet.ignores(other)
+            || !swept.overlaps(&other.aabb())
+            || !filter(bullet, other)
+        {
+            continue;
+        }
+        if distance(bullet, other).is_none_or(|(separation, ..)| separation <= TARGET_SEPARATION) {
+            // Already touching, left to the contact solver, or without a shape
+            continue;
+        }
+        let Some((time, normal)) = advance(bullet, other, dt) else {
+            continue;
+        };
+        if first.is_none_or(|(first_time, _, _)| time < first_time) {
+            first = Some((time, other_index, normal));
+        }
+    }
+    let (time, other_index, normal) = first?;
+
+    // The features that meet, moved back to where they are now
+    let other = &bodies[other_index];
+    let (mut a, mut b) = (bullet.clone(), other.clone());
+    for moved in [&mut a, &mut b] {
+        moved.position = moved.position + moved.velocity * time;
+        moved.rotation += moved.angular_velocity * time;
+    }
+    let (_, point_a, point_b) = distance(&a, &b)?;
+    let point_a = point_a - bullet.velocity * time;
+    let point_b = point_b - other.velocity * time;
+    Some(Impact {
+        time,
+        other: other_index,
+        normal,
+        point: (point_a + point_b) * 0.5,
+        separation: (point_b - point_a).dot(normal),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_bullet() {
+        let mut wall = Body::new(Vec2::new(0.1, 4.0), f32::MAX);
+        wall.position = Vec2::new(5.0, 0.0);
+        let mut bullet = Body::new(Vec2::new(0.2, 0.2), 1.0);
+        bullet.velocity = Vec2::new(600.0, 0.0);
+        let bodies = vec![bullet, wall];
+
+        let impact = sweep_bullet(&bodies, 0, 1.0 / 60.0, |_, _| true).unwrap();
+        assert_eq!(impact.other, 1);
+        assert_eq!(impact.normal, Vec2::new(1.0, 0.0));
+        let gap = 5.0 - 0.05 - 0.1 - 600.0 * impact.time;
+        assert!(gap > 0.0 && gap <= TARGET_SEPARATION);
+        assert!((impact.separation - 4.85).abs() < 1e-4);
+        assert!((impact.point.y).abs() <= 0.1);
+
+        // Too slow to get there within the step
+        assert!(sweep_bullet(&bodies, 0, 1.0 / 600.0, |_, _| true).is_none());
+        // Not allowed to touch the wall
+        assert!(sweep_bullet(&bodies, 0, 1.0 / 60.0, |_, _| false).is_none());
+    }
+
+    #[test]
+    fn test_toi() {
+        let mut a = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        let mut b = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        a.velocity = Vec2::new(1.0, 0.0);
+        b.position = Vec2::new(3.0, 0.5);
+        b.velocity = Vec2::new(-1.0, 0.0);
+        let time = toi(&a, &b, 2.0).unwrap();
+        assert!((time - (1.0 - 0.25 * TARGET_SEPARATION)).abs() < 1e-4);
+        assert!(toi(&a, &b, 0.9).is_none());
+
+        b.velocity = Vec2::new(2.0, 0.0);
+        assert!(toi(&a, &b, 10.0).is_none());
+
+        // A spinning bar sweeps the box next to it
+        let mut bar = Body::new(Vec2::new(4.0, 0.2), 1.0);
+        bar.angular_velocity = 3.0;
+        b.velocity = Vec2::default();
+        b.position = Vec2::new(1.5, 1.5);
+        let time = toi(&bar, &b, 1.0).unwrap();
+        bar.rotation = 3.0 * time;
+        let separation = distance(&bar, &b).unwrap().0;
+        assert!(separation > 0.0 && separation <= TARGET_SEPARATION);
+
+        // A spinning bar that never reaches the box is not an impact once the
+        // iterations run out
+        bar.rotation = 0.0;
+        b.position = Vec2::new(3.0, 0.0);
+        assert!(toi(&bar, &b, 10.0).is_none());
+    }
+}
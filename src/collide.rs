@@ -1,6 +1,6 @@
-use crate::arbiter::{Contact, ContactInfo, EdgeNumbers, Edges, FeaturePair};
-use crate::body::Body;
-use crate::math_utils::{Mat2x2, Vec2};
+use crate::arbiter::{ContactInfo, EdgeNumbers, Edges, FeaturePair};
+use crate::body::{Body, BodyHandle, Shape};
+use crate::math_utils::{Cross, Mat2x2, Vec2};
 
 // Box vertex and edge numbering:
 //
@@ -137,7 +137,7 @@ fn compute_incident_edge(h: &Vec2, pos: &Vec2, rot: &Mat2x2, normal: &Vec2) -> [
     [c1, c2]
 }
 
-pub fn collide(contacts: &mut Vec<Contact>, body_a: &Body, body_b: &Body) -> i32 {
+pub fn collide(contacts: &mut Vec<ContactInfo>, body_a: &Body, body_b: &Body) -> i32 {
     let h_a = body_a.width * 0.5;
     let h_b = body_b.width * 0.5;
 
@@ -282,6 +282,7 @@ pub fn collide(contacts: &mut Vec<Contact>, body_a: &Body, body_b: &Body) -> i32
             if axis == Axis::FaceBX || axis == Axis::FaceBY {
                 flip(&mut clip_point.fp);
             }
+            clip_point.fp.value = clip_point.fp.edges.key();
             let contact = ContactInfo {
                 separation,
                 normal,
@@ -289,13 +290,245 @@ pub fn collide(contacts: &mut Vec<Contact>, body_a: &Body, body_b: &Body) -> i32
                 feature: clip_point.fp,
                 ..ContactInfo::default()
             };
-            contacts.push(Some(contact));
+            contacts.push(contact);
             num_contacts += 1;
         }
     }
     num_contacts
 }
 
+/// Appends the contact point of two circles and returns how many were found.
+pub fn collide_circles(contacts: &mut Vec<ContactInfo>, body_a: &Body, body_b: &Body) -> i32 {
+    let d = body_b.position - body_a.position;
+    let distance = d.length();
+    let radius = body_a.radius + body_b.radius;
+    if distance > radius {
+        return 0;
+    }
+    let normal = if distance > f32::EPSILON {
+        d * (1.0 / distance)
+    } else {
+        Vec2::new(0.0, 1.0)
+    };
+    contacts.push(ContactInfo {
+        separation: distance - radius,
+        normal,
+        position: body_a.position + normal * (body_a.radius + 0.5 * (distance - radius)),
+        ..ContactInfo::default()
+    });
+    1
+}
+
+/// Appends the contact point of a circle and a box, given in any order, and
+/// returns how many were found. The normal points from `body_a` to `body_b`.
+///
+/// The feature id names the part of the box that is touched: the faces +x, +y,
+/// -x, -y are 0 to 3 and the corners in the quadrants 1 to 4 are 4 to 7.
+pub fn collide_circle_box(contacts: &mut Vec<ContactInfo>, body_a: &Body, body_b: &Body) -> i32 {
+    let (circle, box_, sign) = match body_a.shape {
+        Shape::Circle => (body_a, body_b, -1.0),
+        _ => (body_b, body_a, 1.0),
+    };
+    let h = box_.width * 0.5;
+    let rot = Mat2x2::new_from_angle(box_.rotation);
+    // Center of the circle in the frame of the box
+    let center = rot.transpose() * (circle.position - box_.position);
+    let closest = Vec2::new(center.x.clamp(-h.x, h.x), center.y.clamp(-h.y, h.y));
+
+    let (normal, separation, point, feature) = if closest == center {
+        // The center is inside the box, push it out through the nearest face
+        let dx = h.x - center.x.abs();
+        let dy = h.y - center.y.abs();
+        if dx < dy {
+            let side = if center.x < 0.0 { -1.0 } else { 1.0 };
+            let face = if side > 0.0 { 0 } else { 2 };
+            (
+                Vec2::new(side, 0.0),
+                -dx,
+                Vec2::new(side * h.x, center.y),
+                face,
+            )
+        } else {
+            let side = if center.y < 0.0 { -1.0 } else { 1.0 };
+            let face = if side > 0.0 { 1 } else { 3 };
+            (
+                Vec2::new(0.0, side),
+                -dy,
+                Vec2::new(center.x, side * h.y),
+                face,
+            )
+        }
+    } else {
+        let d = center - closest;
+        let distance = d.length();
+        let on_x = closest.x.abs() == h.x;
+        let on_y = closest.y.abs() == h.y;
+        let feature = match (on_x, on_y) {
+            (true, true) => match (closest.x > 0.0, closest.y > 0.0) {
+                (true, true) => 4,
+                (false, true) => 5,
+                (false, false) => 6,
+                (true, false) => 7,
+            },
+            (true, false) if closest.x > 0.0 => 0,
+            (true, false) => 2,
+            _ if closest.y > 0.0 => 1,
+            _ => 3,
+        };
+        (d * (1.0 / distance), distance, closest, feature)
+    };
+    let separation = separation - circle.radius;
+    if separation > 0.0 {
+        return 0;
+    }
+
+    contacts.push(ContactInfo {
+        separation,
+        normal: rot * normal * sign,
+        position: box_.position + rot * point,
+        feature: FeaturePair::new(Edges::default(), feature),
+        ..ContactInfo::default()
+    });
+    1
+}
+
+/// Where a ray or a swept shape first hits a body, see `ray_cast` and
+/// `World::shape_cast`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub body: BodyHandle,
+    pub point: Vec2,
+    /// Unit normal of the surface at `point`, facing the ray.
+    pub normal: Vec2,
+    /// How far the ray or shape got, as a fraction of its length or translation.
+    pub fraction: f32,
+}
+
+/// Returns where the ray from `origin` along `direction` first enters the body
+/// within `max_distance`, or `None` if it misses. Rays starting inside a body
+/// do not hit it. Chains and heightfields are hit from both sides.
+pub fn ray_cast(body: &Body, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<RayHit> {
+    let length = direction.length();
+    if length == 0.0 || max_distance <= 0.0 {
+        return None;
+    }
+    let d = direction * (1.0 / length);
+    let vertices = body.world_vertices();
+    let (distance, normal) = match body.shape {
+        Shape::Box | Shape::ConvexPolygon => ray_cast_polygon(&vertices, origin, d, max_distance),
+        Shape::Circle => ray_cast_circle(body.position, body.radius, origin, d, max_distance),
+        // A capsule without length is a circle
+        Shape::Capsule if vertices[0] == vertices[1] => {
+            ray_cast_circle(vertices[0], body.radius, origin, d, max_distance)
+        }
+        Shape::Capsule => {
+            let (p1, p2) = (vertices[0], vertices[1]);
+            let axis = p2 - p1;
+            let side = axis.cross(1.0) * (body.radius / axis.length());
+            let inside = {
+                let s = ((origin - p1).dot(axis) / axis.dot(axis)).clamp(0.0, 1.0);
+                (origin - (p1 + axis * s)).length() < body.radius
+            };
+            if inside {
+                return None;
+            }
+            let rectangle = [p1 + side, p2 + side, p2 - side, p1 - side];
+            [
+                ray_cast_polygon(&rectangle, origin, d, max_distance),
+                ray_cast_circle(p1, body.radius, origin, d, max_distance),
+                ray_cast_circle(p2, body.radius, origin, d, max_distance),
+            ]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+        }
+        Shape::Chain | Shape::Heightfield => vertices
+            .windows(2)
+            .filter_map(|segment| ray_cast_segment(segment[0], segment[1], origin, d, max_distance))
+            .min_by(|a, b| a.0.total_cmp(&b.0)),
+    }?;
+    Some(RayHit {
+        body: body.handle(),
+        point: origin + d * distance,
+        normal,
+        fraction: distance / max_distance,
+    })
+}
+
+// Distance along the unit direction `d` to where the ray enters the convex
+// polygon, and the normal of the face it enters through.
+fn ray_cast_polygon(vertices: &[Vec2], origin: Vec2, d: Vec2, max: f32) -> Option<(f32, Vec2)> {
+    let center = vertices
+        .iter()
+        .fold(Vec2::default(), |sum, &vertex| sum + vertex)
+        * (1.0 / vertices.len() as f32);
+    let (mut lower, mut upper) = (0.0, max);
+    let mut entered = None;
+    for (i, &a) in vertices.iter().enumerate() {
+        let edge = vertices[(i + 1) % vertices.len()] - a;
+        let mut normal = edge.cross(1.0) * (1.0 / edge.length());
+        if normal.dot(a - center) < 0.0 {
+            normal = -normal;
+        }
+        let numerator = normal.dot(a - origin);
+        let denominator = normal.dot(d);
+        if denominator == 0.0 {
+            // Parallel to the face, outside of it misses
+            if numerator < 0.0 {
+                return None;
+            }
+        } else {
+            let t = numerator / denominator;
+            if denominator < 0.0 && t > lower {
+                lower = t;
+                entered = Some(normal);
+            } else if denominator > 0.0 && t < upper {
+                upper = t;
+            }
+        }
+        if upper < lower {
+            return None;
+        }
+    }
+    entered.map(|normal| (lower, normal))
+}
+
+fn ray_cast_circle(
+    center: Vec2,
+    radius: f32,
+    origin: Vec2,
+    d: Vec2,
+    max: f32,
+) -> Option<(f32, Vec2)> {
+    let m = origin - center;
+    let b = m.dot(d);
+    let c = m.dot(m) - radius * radius;
+    let discriminant = b * b - c;
+    if c < 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let t = -b - discriminant.sqrt();
+    if !(0.0..=max).contains(&t) {
+        return None;
+    }
+    Some((t, (m + d * t) * (1.0 / radius)))
+}
+
+fn ray_cast_segment(a: Vec2, b: Vec2, origin: Vec2, d: Vec2, max: f32) -> Option<(f32, Vec2)> {
+    let edge = b - a;
+    let denominator = d.cross(edge);
+    if denominator == 0.0 {
+        return None;
+    }
+    let t = (a - origin).cross(edge) / denominator;
+    let s = (a - origin).cross(d) / denominator;
+    if !(0.0..=1.0).contains(&s) || !(0.0..=max).contains(&t) {
+        return None;
+    }
+    let normal = edge.cross(1.0) * (1.0 / edge.length());
+    Some((t, if normal.dot(d) > 0.0 { -normal } else { normal }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -528,7 +761,7 @@ mod tests {
 
         let mut box_b = Body::new(Vec2::new(4.0, 4.0), 1.0);
         box_b.position = pos_b;
- 
+
         // Draw the boxes
         add_box(
             &mut grid,
@@ -591,4 +824,95 @@ mod tests {
             num_contacts
         );
     }
+
+    #[test]
+    fn test_circle_box_feature() {
+        let mut box_ = Body::new(Vec2::new(2.0, 2.0), f32::MAX);
+        box_.rotation = std::f32::consts::FRAC_PI_2;
+        let mut circle = Body::new_circle(0.5, 1.0);
+
+        // Resting on the top face, which is the +x face of the rotated box
+        circle.position = Vec2::new(0.3, 1.45);
+        let mut contacts = Vec::new();
+        assert_eq!(collide_circle_box(&mut contacts, &box_, &circle), 1);
+        let contact = contacts[0];
+        assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+        assert!((contact.separation + 0.05).abs() < 1e-5);
+        assert!((contact.position - Vec2::new(0.3, 1.0)).length() < 1e-5);
+        assert_eq!(contact.feature.value, 0);
+
+        // Sliding along the face keeps the feature, the order flips the normal
+        circle.position = Vec2::new(-0.6, 1.45);
+        contacts.clear();
+        assert_eq!(collide_circle_box(&mut contacts, &circle, &box_), 1);
+        let contact = contacts[0];
+        assert_eq!(contact.normal, Vec2::new(0.0, -1.0));
+        assert_eq!(contact.feature.value, 0);
+
+        // Corner region
+        circle.position = Vec2::new(1.3, 1.3);
+        contacts.clear();
+        assert_eq!(collide_circle_box(&mut contacts, &box_, &circle), 1);
+        let contact = contacts[0];
+        assert!((contact.position - Vec2::new(1.0, 1.0)).length() < 1e-5);
+        assert_eq!(contact.feature.value, 7);
+
+        circle.position = Vec2::new(1.4, 1.4);
+        contacts.clear();
+        assert_eq!(collide_circle_box(&mut contacts, &box_, &circle), 0);
     }
+
+    #[test]
+    fn test_circles() {
+        let a = Body::new_circle(1.0, 1.0);
+        let mut b = Body::new_circle(0.5, 1.0);
+        b.position = Vec2::new(0.0, 1.4);
+        let mut contacts = Vec::new();
+        assert_eq!(collide_circles(&mut contacts, &a, &b), 1);
+        let contact = contacts[0];
+        assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+        assert!((contact.separation + 0.1).abs() < 1e-5);
+        assert!((contact.position - Vec2::new(0.0, 0.95)).length() < 1e-5);
+    }
+
+    #[test]
+    fn test_ray_cast() {
+        let right = Vec2::new(1.0, 0.0);
+        let mut box_ = Body::new(Vec2::new(2.0, 2.0), 1.0);
+        box_.position = Vec2::new(5.0, 0.0);
+        let hit = ray_cast(&box_, Vec2::new(0.0, 0.5), right * 3.0, 10.0).unwrap();
+        assert_eq!(hit.point, Vec2::new(4.0, 0.5));
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+        assert!((hit.fraction - 0.4).abs() < 1e-6);
+        assert!(ray_cast(&box_, Vec2::new(0.0, 0.5), right, 3.0).is_none());
+        assert!(ray_cast(&box_, Vec2::new(0.0, 1.5), right, 10.0).is_none());
+        assert!(ray_cast(&box_, Vec2::new(5.0, 0.0), right, 10.0).is_none());
+
+        box_.rotation = std::f32::consts::FRAC_PI_4;
+        let hit = ray_cast(&box_, Vec2::new(0.0, 0.0), right, 10.0).unwrap();
+        assert!((hit.point.x - (5.0 - 2.0_f32.sqrt())).abs() < 1e-5);
+
+        let mut circle = Body::new_circle(1.0, 1.0);
+        circle.position = Vec2::new(5.0, 0.0);
+        let hit = ray_cast(&circle, Vec2::new(5.0, 5.0), Vec2::new(0.0, -1.0), 10.0).unwrap();
+        assert_eq!(hit.point, Vec2::new(5.0, 1.0));
+        assert_eq!(hit.normal, Vec2::new(0.0, 1.0));
+
+        let mut capsule = Body::new_capsule(2.0, 0.5, 1.0);
+        capsule.position = Vec2::new(5.0, 0.0);
+        let hit = ray_cast(&capsule, Vec2::new(0.0, 0.0), right, 10.0).unwrap();
+        assert_eq!(hit.point, Vec2::new(3.5, 0.0));
+        let hit = ray_cast(&capsule, Vec2::new(5.0, 5.0), Vec2::new(0.0, -1.0), 10.0).unwrap();
+        assert_eq!(hit.point, Vec2::new(5.0, 0.5));
+        let mut ball = Body::new_capsule(0.0, 0.5, 1.0);
+        ball.position = Vec2::new(5.0, 0.0);
+        let hit = ray_cast(&ball, Vec2::new(0.0, 0.0), right, 10.0).unwrap();
+        assert_eq!(hit.point, Vec2::new(4.5, 0.0));
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+
+        let chain = Body::new_chain(vec![Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0)]);
+        let hit = ray_cast(&chain, Vec2::new(2.0, -3.0), Vec2::new(0.0, 1.0), 10.0).unwrap();
+        assert_eq!(hit.point, Vec2::new(2.0, 0.0));
+        assert_eq!(hit.normal, Vec2::new(0.0, -1.0));
+    }
+}
@@ -1,8 +1,21 @@
 use crate::{
-    arbiter::{Contact, ContactInfo, Edges, FeaturePair},
-    body::{Body, ConvexPolygon},
-    math_utils::Vec2,
+    arbiter::{ContactInfo, Edges, FeaturePair},
+    body::{Body, ConvexPolygon, Shape},
+    broad_phase::Aabb,
+    math_utils::{Cross, Mat2x2, Vec2},
+    scratch::ClipScratch,
 };
+use std::ops::Range;
+
+/// Algorithm used for the contacts of two convex polygons.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonNarrowphase {
+    /// Separating axes with polygon clipping, see `collide_polygons`.
+    #[default]
+    Sat,
+    /// GJK and EPA, see `collide_polygons_gjk`.
+    Gjk,
+}
 
 /// Determines which side of the line the polygon is on.
 ///
@@ -72,6 +85,17 @@ fn test_intersection(c0: &ConvexPolygon, c1: &ConvexPolygon) -> bool {
     true // No separating axis found; polygons intersect
 }
 
+// Offset of the edge numbers of the clipping polygon in feature ids.
+const CLIP_EDGE_OFFSET: i32 = 128;
+
+/// Returns the feature id of a clipped point from the edges entering and leaving
+/// it. Edges of the clipped polygon keep their index, edges of the clipping
+/// polygon are offset by 128, so the id stays the same from step to step as long
+/// as the same edges touch.
+pub fn edge_feature(in_edge: i32, out_edge: i32) -> i32 {
+    in_edge << 8 | out_edge
+}
+
 /// Clips a polygon against a given edge of another polygon.
 ///
 /// # Arguments
@@ -79,25 +103,54 @@ fn test_intersection(c0: &ConvexPolygon, c1: &ConvexPolygon) -> bool {
 /// * `clip_polygon` - The polygon to use for clipping.
 ///
 /// # Returns
-/// A list of clipped points.
-pub fn clip_polygon(polygon: &ConvexPolygon, clip_polygon: &ConvexPolygon) -> Vec<(Vec2, Vec2)> {
-    let mut polygon: ConvexPolygon = ConvexPolygon::new(polygon.get_vertices());
+/// A list of clipped points with their normal and feature id. The feature id
+/// names the two edges meeting at the point, see `edge_feature`.
+pub fn clip_polygon(
+    polygon: &ConvexPolygon,
+    clip_polygon: &ConvexPolygon,
+) -> Vec<(Vec2, Vec2, i32)> {
+    let mut vertices = Vec::new();
+    let clipped = clip_vertices(polygon, clip_polygon, &mut vertices);
+    vertices[clipped]
+        .iter()
+        .map(|&(vertex, (in_edge, out_edge))| {
+            (
+                vertex,
+                closest_normal(clip_polygon, vertex),
+                edge_feature(in_edge, out_edge),
+            )
+        })
+        .collect()
+}
 
-    // This will store the final clipped vertices along with their normals
-    let mut clipped: Vec<(Vec2, Vec2)> = Vec::new();
+// Clips `polygon` by appending the vertices of every pass to `vertices` and
+// returns the range of the final vertices, with the edges entering and leaving
+// them.
+fn clip_vertices(
+    polygon: &ConvexPolygon,
+    clip_polygon: &ConvexPolygon,
+    vertices: &mut Vec<(Vec2, (i32, i32))>,
+) -> Range<usize> {
+    let n = polygon.get_num_vertices();
+    let mut start = vertices.len();
+    vertices.extend((0..n).map(|i| {
+        let edges = (((i + n - 1) % n) as i32, i as i32);
+        (polygon.get_vertex(i as isize - 1), edges)
+    }));
 
     // Iterate over all edges of the clipping polygon
     for j in 0..clip_polygon.get_num_vertices() {
         let edge_start = clip_polygon.get_vertex(j as isize);
         let edge_normal = clip_polygon.get_normal(j as isize);
 
-        // Temporary storage for the current iteration
-        let mut current_clipped: Vec<(Vec2, Vec2)> = Vec::new();
+        let clip_edge = CLIP_EDGE_OFFSET + j as i32;
 
-        let n = polygon.get_num_vertices();
+        let end = vertices.len();
+        let n = end - start;
         for i in 0..n {
-            let current = polygon.get_vertex(i as isize);
-            let next = polygon.get_vertex((i + 1) as isize);
+            // Starts with the vertex `1` like `ConvexPolygon::get_vertex`
+            let (current, edges) = vertices[start + (i + 1) % n];
+            let (next, _) = vertices[start + (i + 2) % n];
 
             // Distances from the current and next points to the clipping plane
             let dist_current = edge_normal.dot(current - edge_start) / edge_normal.length();
@@ -105,50 +158,53 @@ pub fn clip_polygon(polygon: &ConvexPolygon, clip_polygon: &ConvexPolygon) -> Ve
 
             if dist_current <= 0.0 {
                 // Current point is inside or on the plane
-                current_clipped.push((current, edge_normal));
+                vertices.push((current, edges));
             }
 
             if dist_current * dist_next < 0.0 {
                 // Edge intersects the plane; compute intersection point
                 let interp = dist_current / (dist_current - dist_next);
                 let intersection = current + (next - current) * interp;
-                current_clipped.push((intersection, edge_normal));
+                // The clipping edge replaces the part of the edge outside of it
+                let edge = edges.1;
+                vertices.push((
+                    intersection,
+                    if dist_current <= 0.0 {
+                        (edge, clip_edge)
+                    } else {
+                        (clip_edge, edge)
+                    },
+                ));
             }
         }
 
-        // Prepare for next iteration
-        let clipped_vertices: Vec<Vec2> = current_clipped.iter().map(|tuple| tuple.0).collect();
-        polygon = ConvexPolygon::new(clipped_vertices);
-
-        clipped = current_clipped;
+        // The vertices of this pass are clipped by the next edge
+        start = end;
     }
+    start..vertices.len()
+}
 
-    // Assign normals to clipped vertices based on closest edge of the clipping polygon
-    let mut final_clipped = Vec::new();
-    for (vertex, _) in clipped {
-        let mut closest_normal = Vec2::new(0.0, 0.0);
-        let mut min_distance = f32::MAX;
+// Normal of the edge of the clipping polygon closest to a clipped vertex.
+fn closest_normal(clip_polygon: &ConvexPolygon, vertex: Vec2) -> Vec2 {
+    let mut closest_normal = Vec2::new(0.0, 0.0);
+    let mut min_distance = f32::MAX;
 
-        for j in 0..clip_polygon.get_num_vertices() {
-            let edge_start = clip_polygon.get_vertex(j as isize);
-            let edge_end = clip_polygon.get_vertex((j + 1) as isize);
+    for j in 0..clip_polygon.get_num_vertices() {
+        let edge_start = clip_polygon.get_vertex(j as isize);
+        let edge_end = clip_polygon.get_vertex((j + 1) as isize);
 
-            let edge = edge_end - edge_start;
-            let mut normal = Vec2::new(-edge.y, edge.x); // Outward-facing normal
-            normal = normal * (1.0 / normal.length());
-            let to_point = vertex - edge_start;
-            let distance = (to_point.dot(normal)).abs();
+        let edge = edge_end - edge_start;
+        let mut normal = Vec2::new(-edge.y, edge.x); // Outward-facing normal
+        normal = normal * (1.0 / normal.length());
+        let to_point = vertex - edge_start;
+        let distance = (to_point.dot(normal)).abs();
 
-            if distance < min_distance {
-                min_distance = distance;
-                closest_normal = normal;
-            }
+        if distance < min_distance {
+            min_distance = distance;
+            closest_normal = normal;
         }
-
-        final_clipped.push((vertex, closest_normal));
     }
-
-    final_clipped
+    closest_normal
 }
 /// Finds contact points between two intersecting convex polygons.
 ///
@@ -160,44 +216,1375 @@ pub fn clip_polygon(polygon: &ConvexPolygon, clip_polygon: &ConvexPolygon) -> Ve
 /// A vector of contact points, where each contact point includes:
 /// - `Point`: The position of the contact point.
 /// - `Point`: The normal at the contact point.
-// Find contact points and store them in the Contact type
-fn find_contact_points(c0: &ConvexPolygon, c1: &ConvexPolygon) -> Vec<Contact> {
-    let mut result: Vec<Contact> = Vec::new();
+// Find contact points and store them in the ContactInfo type
+fn find_contact_points(
+    contacts: &mut Vec<ContactInfo>,
+    clip: &mut ClipScratch,
+    c0: &ConvexPolygon,
+    c1: &ConvexPolygon,
+) -> i32 {
     // Clip the current contact points against this edge
-    let clipped = clip_polygon(c0, c1);
-
-    // If no points remain, polygons are not intersecting
-    if clipped.is_empty() {
-        return Vec::new();
-    }
+    let clipped = clip_vertices(c0, c1, &mut clip.vertices);
 
     // Process each contact point and store the contact info
-    for (point, normal) in &clipped {
-        let relative_position = *point;
-        let separation = relative_position.dot(*normal);
+    for &(point, (in_edge, out_edge)) in &clip.vertices[clipped.clone()] {
+        let normal = closest_normal(c1, point);
+        let relative_position = point;
+        let separation = relative_position.dot(normal);
 
-        // Create FeaturePair (assuming edges is a pair of edge indices)
-        let feature = FeaturePair::new(Edges::default(), 0); // Replace 0 with appropriate value
+        let feature = FeaturePair::new(Edges::default(), edge_feature(in_edge, out_edge));
 
         let contact_info = ContactInfo {
-            position: *point,
-            normal: *normal,
+            position: point,
+            normal,
             separation: separation * 0.001,
             feature,
             ..Default::default()
         };
 
         // Add the contact info to the result vector
-        result.push(Some(contact_info));
+        contacts.push(contact_info);
+    }
+    clipped.len() as i32
+}
+/// Appends the contact points of two polygon bodies to `contacts` and returns
+/// how many were found.
+pub fn collide_polygons(contacts: &mut Vec<ContactInfo>, b1: &Body, b2: &Body) -> i32 {
+    collide_polygons_in(contacts, &mut ClipScratch::default(), b1, b2)
+}
+
+/// Like `collide_polygons`, but clips in the memory of `clip` instead of
+/// allocating.
+pub fn collide_polygons_in(
+    contacts: &mut Vec<ContactInfo>,
+    clip: &mut ClipScratch,
+    b1: &Body,
+    b2: &Body,
+) -> i32 {
+    let (mut vertices_1, mut vertices_2) = (clip.take_polygon(0), clip.take_polygon(1));
+    b1.world_vertices_into(&mut vertices_1);
+    b2.world_vertices_into(&mut vertices_2);
+    let (c0, c1) = (
+        ConvexPolygon::new(vertices_1),
+        ConvexPolygon::new(vertices_2),
+    );
+    let found = if test_intersection(&c0, &c1) {
+        find_contact_points(contacts, clip, &c0, &c1)
+    } else {
+        0
+    };
+    clip.restore(0, c0.into_vertices());
+    clip.restore(1, c1.into_vertices());
+    found
+}
+
+// Tolerance for treating the cores of two rounded shapes as touching.
+const LINEAR_SLOP: f32 = 0.005;
+
+/// Returns the outward unit normal of each edge, edge `i` going from vertex `i`
+/// to vertex `i + 1`. A segment has the two opposite normals of its sides.
+fn outward_normals(vertices: &[Vec2]) -> Vec<Vec2> {
+    let n = vertices.len();
+    let mut area = 0.0;
+    for i in 0..n {
+        area += vertices[i].cross(vertices[(i + 1) % n]);
+    }
+    let sign = if area < 0.0 { -1.0 } else { 1.0 };
+    (0..n)
+        .map(|i| {
+            let edge = vertices[(i + 1) % n] - vertices[i];
+            Vec2::new(edge.y, -edge.x) * (sign / edge.length())
+        })
+        .collect()
+}
+
+/// Returns the edge of `vertices_1` along whose normal `vertices_2` is separated
+/// the most, together with the separation.
+fn find_max_separation(
+    vertices_1: &[Vec2],
+    normals_1: &[Vec2],
+    vertices_2: &[Vec2],
+) -> (usize, f32) {
+    let mut best_index = 0;
+    let mut max_separation = f32::MIN;
+    for (i, (&vertex, &normal)) in vertices_1.iter().zip(normals_1.iter()).enumerate() {
+        let separation = vertices_2
+            .iter()
+            .map(|&other| normal.dot(other - vertex))
+            .fold(f32::MAX, f32::min);
+        if separation > max_separation {
+            max_separation = separation;
+            best_index = i;
+        }
+    }
+    (best_index, max_separation)
+}
+
+/// Returns the fractions along the segments `p1 q1` and `p2 q2` of their closest points.
+fn segment_fractions(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> (f32, f32) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let dd1 = d1.dot(d1);
+    let dd2 = d2.dot(d2);
+    let rd2 = r.dot(d2);
+    if dd1 < f32::EPSILON && dd2 < f32::EPSILON {
+        return (0.0, 0.0);
+    }
+    if dd1 < f32::EPSILON {
+        return (0.0, (rd2 / dd2).clamp(0.0, 1.0));
     }
-    result
+    let rd1 = r.dot(d1);
+    if dd2 < f32::EPSILON {
+        return ((-rd1 / dd1).clamp(0.0, 1.0), 0.0);
+    }
+    let d12 = d1.dot(d2);
+    let denominator = dd1 * dd2 - d12 * d12;
+    let mut f1 = if denominator > 0.0 {
+        ((d12 * rd2 - rd1 * dd2) / denominator).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut f2 = (d12 * f1 + rd2) / dd2;
+    if f2 < 0.0 {
+        f2 = 0.0;
+        f1 = (-rd1 / dd1).clamp(0.0, 1.0);
+    } else if f2 > 1.0 {
+        f2 = 1.0;
+        f1 = ((d12 - rd1) / dd1).clamp(0.0, 1.0);
+    }
+    (f1, f2)
+}
+
+fn rounded_contact(position: Vec2, normal: Vec2, separation: f32, value: i32) -> ContactInfo {
+    ContactInfo {
+        position,
+        normal,
+        separation,
+        feature: FeaturePair::new(Edges::default(), value),
+        ..Default::default()
+    }
+}
+
+// Convex polygon in world space, inflated by a radius.
+struct RoundedPolygon {
+    vertices: Vec<Vec2>,
+    normals: Vec<Vec2>,
+    radius: f32,
+}
+
+impl RoundedPolygon {
+    fn new(vertices: Vec<Vec2>, radius: f32) -> Self {
+        let normals = outward_normals(&vertices);
+        Self {
+            vertices,
+            normals,
+            radius,
+        }
+    }
+
+    fn from_body(body: &Body) -> Self {
+        Self::new(body.world_vertices(), body.radius)
+    }
+}
+
+/// Appends the contact points of two bodies of which at least one is a capsule
+/// and returns how many were found.
+///
+/// Both shapes are handled as convex polygons inflated by their radius, a capsule
+/// being a segment with a radius. The manifold is built by clipping the incident
+/// edge against the reference edge of the axis of least penetration. When only
+/// the rounded ends touch, a single point between the closest vertices is used.
+pub fn collide_capsule(contacts: &mut Vec<ContactInfo>, b1: &Body, b2: &Body) -> i32 {
+    rounded_manifold(
+        contacts,
+        &RoundedPolygon::from_body(b1),
+        &RoundedPolygon::from_body(b2),
+    )
 }
-pub fn collide_polygons(contacts: &mut Vec<Contact>, b1: &Body, b2: &Body) -> i32 {
-    let c0 = b1.get_polygon().rotate(b1.rotation).translate(b1.position);
-    let c1 = b2.get_polygon().rotate(b2.rotation).translate(b2.position);
-    if test_intersection(&c0, &c1) {
-        *contacts = find_contact_points(&c0, &c1);
+
+// Contact points of two rounded polygons, the normals point from `a` to `b`.
+fn rounded_manifold(
+    contacts: &mut Vec<ContactInfo>,
+    a: &RoundedPolygon,
+    b: &RoundedPolygon,
+) -> i32 {
+    let (vertices_a, normals_a) = (&a.vertices, &a.normals);
+    let (vertices_b, normals_b) = (&b.vertices, &b.normals);
+    let radius = a.radius + b.radius;
+
+    let (edge_a, separation_a) = find_max_separation(vertices_a, normals_a, vertices_b);
+    let (edge_b, separation_b) = find_max_separation(vertices_b, normals_b, vertices_a);
+    if separation_a > radius || separation_b > radius {
+        return 0;
+    }
+
+    // The reference shape owns the edge of least penetration
+    let flip = separation_b > separation_a + 0.1 * LINEAR_SLOP;
+    let (reference, incident, i11) = if flip { (b, a, edge_b) } else { (a, b, edge_a) };
+    let (poly_1, r1) = (&reference.vertices, reference.radius);
+    let (poly_2, normals_2) = (&incident.vertices, &incident.normals);
+    let normal = reference.normals[i11];
+    // Contact normals point from `a` to `b`
+    let (sign, flag) = if flip { (-1.0, 1 << 16) } else { (1.0, 0) };
+
+    // The incident edge is the most anti-parallel edge of the other shape
+    let i21 = normals_2
+        .iter()
+        .enumerate()
+        .map(|(i, other)| (i, normal.dot(*other)))
+        .fold(
+            (0, f32::MAX),
+            |best, item| if item.1 < best.1 { item } else { best },
+        )
+        .0;
+    let i12 = (i11 + 1) % poly_1.len();
+    let i22 = (i21 + 1) % poly_2.len();
+    let (v11, v12, v21, v22) = (poly_1[i11], poly_1[i12], poly_2[i21], poly_2[i22]);
+
+    if separation_a.max(separation_b) > 0.1 * LINEAR_SLOP {
+        // The cores are apart, check whether the closest features are two vertices
+        let (f1, f2) = segment_fractions(v11, v12, v21, v22);
+        let vertices = match (f1, f2) {
+            (f1, f2) if f1 == 0.0 && f2 == 0.0 => Some((v11, v21, i11, i21)),
+            (f1, f2) if f1 == 0.0 && f2 == 1.0 => Some((v11, v22, i11, i22)),
+            (f1, f2) if f1 == 1.0 && f2 == 0.0 => Some((v12, v21, i12, i21)),
+            (f1, f2) if f1 == 1.0 && f2 == 1.0 => Some((v12, v22, i12, i22)),
+            _ => None,
+        };
+        if let Some((vertex_1, vertex_2, index_1, index_2)) = vertices {
+            let d = vertex_2 - vertex_1;
+            let distance = d.length();
+            if distance > radius || distance < f32::EPSILON {
+                return 0;
+            }
+            let normal = d * (1.0 / distance);
+            let position = vertex_1 + normal * (r1 + 0.5 * (distance - radius));
+            let value = flag | 1 << 17 | (index_1 as i32) << 8 | index_2 as i32;
+            contacts.push(rounded_contact(
+                position,
+                normal * sign,
+                distance - radius,
+                value,
+            ));
+            return 1;
+        }
     }
 
-    contacts.len() as i32
+    clip_incident_edge(contacts, reference, incident, (i11, i21), flip)
+}
+
+// Clips the incident edge `edges.1` against the side planes of the reference
+// edge `edges.0` and appends the points within reach of the reference edge.
+// The normals point from the reference to the incident shape, or the other way
+// around if `flip` is set.
+fn clip_incident_edge(
+    contacts: &mut Vec<ContactInfo>,
+    reference: &RoundedPolygon,
+    incident: &RoundedPolygon,
+    edges: (usize, usize),
+    flip: bool,
+) -> i32 {
+    let (poly_1, r1) = (&reference.vertices, reference.radius);
+    let (poly_2, r2) = (&incident.vertices, incident.radius);
+    let radius = r1 + r2;
+    let (i11, i21) = edges;
+    let i12 = (i11 + 1) % poly_1.len();
+    let i22 = (i21 + 1) % poly_2.len();
+    let (v11, v12, v21, v22) = (poly_1[i11], poly_1[i12], poly_2[i21], poly_2[i22]);
+    let normal = reference.normals[i11];
+    let (sign, flag) = if flip { (-1.0, 1 << 16) } else { (1.0, 0) };
+
+    let edge = v12 - v11;
+    let tangent = edge * (1.0 / edge.length());
+    let lower_1 = 0.0;
+    let upper_1 = edge.dot(tangent);
+    let upper_2 = (v21 - v11).dot(tangent);
+    let lower_2 = (v22 - v11).dot(tangent);
+    let span = upper_2 - lower_2;
+
+    let mut v_lower = v22;
+    if lower_2 < lower_1 && span > f32::EPSILON {
+        v_lower = v22 + (v21 - v22) * ((lower_1 - lower_2) / span);
+    }
+    let mut v_upper = v21;
+    if upper_2 > upper_1 && span > f32::EPSILON {
+        v_upper = v22 + (v21 - v22) * ((upper_1 - lower_2) / span);
+    }
+    let separation_lower = (v_lower - v11).dot(normal);
+    let separation_upper = (v_upper - v11).dot(normal);
+
+    // Put the points halfway between the two surfaces
+    v_lower = v_lower + normal * (0.5 * (r1 - r2 - separation_lower));
+    v_upper = v_upper + normal * (0.5 * (r1 - r2 - separation_upper));
+
+    let mut num_contacts = 0;
+    let points = [
+        (v_lower, separation_lower, (i11 as i32) << 8 | i22 as i32),
+        (v_upper, separation_upper, (i12 as i32) << 8 | i21 as i32),
+    ];
+    for (position, separation, value) in points {
+        if separation - radius <= 0.0 {
+            contacts.push(rounded_contact(
+                position,
+                normal * sign,
+                separation - radius,
+                flag | value,
+            ));
+            num_contacts += 1;
+        }
+    }
+    num_contacts
+}
+
+// Maximum number of iterations of the GJK and EPA loops.
+const GJK_MAX_ITERATIONS: u32 = 20;
+const EPA_MAX_ITERATIONS: u32 = 32;
+const EPA_TOLERANCE: f32 = 1e-4;
+
+/// Result of a distance query between two convex polygons.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DistanceOutput {
+    /// Closest point on the first polygon.
+    pub point_a: Vec2,
+    /// Closest point on the second polygon.
+    pub point_b: Vec2,
+    /// Zero if the polygons overlap.
+    pub distance: f32,
+    pub iterations: u32,
+}
+
+// Vertex of the Minkowski difference B - A.
+#[derive(Debug, Default, Clone, Copy)]
+struct SimplexVertex {
+    w_a: Vec2,
+    w_b: Vec2,
+    w: Vec2,
+    // barycentric coordinate of the closest point
+    a: f32,
+    index_a: usize,
+    index_b: usize,
+}
+
+impl SimplexVertex {
+    fn new(vertices_a: &[Vec2], vertices_b: &[Vec2], index_a: usize, index_b: usize) -> Self {
+        let (w_a, w_b) = (vertices_a[index_a], vertices_b[index_b]);
+        Self {
+            w_a,
+            w_b,
+            w: w_b - w_a,
+            a: 1.0,
+            index_a,
+            index_b,
+        }
+    }
+}
+
+// Index of the vertex farthest along `direction`, the vertices must not be
+// empty.
+fn support(vertices: &[Vec2], direction: Vec2) -> usize {
+    let mut best = 0;
+    let mut best_value = vertices[0].dot(direction);
+    for (i, vertex) in vertices.iter().enumerate().skip(1) {
+        let value = vertex.dot(direction);
+        if value > best_value {
+            best = i;
+            best_value = value;
+        }
+    }
+    best
+}
+
+#[derive(Debug, Default)]
+struct Simplex {
+    vertices: [SimplexVertex; 3],
+    count: usize,
+}
+
+impl Simplex {
+    // Reduces the simplex to the sub-simplex closest to the origin.
+    fn solve(&mut self) {
+        match self.count {
+            2 => self.solve_2(),
+            3 => self.solve_3(),
+            _ => {}
+        }
+    }
+
+    fn solve_2(&mut self) {
+        let [v1, v2, _] = &mut self.vertices;
+        let e12 = v2.w - v1.w;
+        let d12_2 = -v1.w.dot(e12);
+        if d12_2 <= 0.0 {
+            v1.a = 1.0;
+            self.count = 1;
+            return;
+        }
+        let d12_1 = v2.w.dot(e12);
+        if d12_1 <= 0.0 {
+            v2.a = 1.0;
+            *v1 = *v2;
+            self.count = 1;
+            return;
+        }
+        let inv = 1.0 / (d12_1 + d12_2);
+        v1.a = d12_1 * inv;
+        v2.a = d12_2 * inv;
+    }
+
+    fn solve_3(&mut self) {
+        let [v1, v2, v3] = &mut self.vertices;
+        let (w1, w2, w3) = (v1.w, v2.w, v3.w);
+
+        let e12 = w2 - w1;
+        let d12_1 = w2.dot(e12);
+        let d12_2 = -w1.dot(e12);
+        let e13 = w3 - w1;
+        let d13_1 = w3.dot(e13);
+        let d13_2 = -w1.dot(e13);
+        let e23 = w3 - w2;
+        let d23_1 = w3.dot(e23);
+        let d23_2 = -w2.dot(e23);
+
+        let n123 = e12.cross(e13);
+        let d123_1 = n123 * w2.cross(w3);
+        let d123_2 = n123 * w3.cross(w1);
+        let d123_3 = n123 * w1.cross(w2);
+
+        if d12_2 <= 0.0 && d13_2 <= 0.0 {
+            v1.a = 1.0;
+            self.count = 1;
+        } else if d12_1 > 0.0 && d12_2 > 0.0 && d123_3 <= 0.0 {
+            let inv = 1.0 / (d12_1 + d12_2);
+            v1.a = d12_1 * inv;
+            v2.a = d12_2 * inv;
+            self.count = 2;
+        } else if d13_1 > 0.0 && d13_2 > 0.0 && d123_2 <= 0.0 {
+            let inv = 1.0 / (d13_1 + d13_2);
+            v1.a = d13_1 * inv;
+            v3.a = d13_2 * inv;
+            *v2 = *v3;
+            self.count = 2;
+        } else if d12_1 <= 0.0 && d23_2 <= 0.0 {
+            v2.a = 1.0;
+            *v1 = *v2;
+            self.count = 1;
+        } else if d13_1 <= 0.0 && d23_1 <= 0.0 {
+            v3.a = 1.0;
+            *v1 = *v3;
+            self.count = 1;
+        } else if d23_1 > 0.0 && d23_2 > 0.0 && d123_1 <= 0.0 {
+            let inv = 1.0 / (d23_1 + d23_2);
+            v2.a = d23_1 * inv;
+            v3.a = d23_2 * inv;
+            *v1 = *v3;
+            self.count = 2;
+        } else {
+            // The origin is inside the triangle
+            let inv = 1.0 / (d123_1 + d123_2 + d123_3);
+            v1.a = d123_1 * inv;
+            v2.a = d123_2 * inv;
+            v3.a = d123_3 * inv;
+        }
+    }
+
+    fn search_direction(&self) -> Vec2 {
+        let [v1, v2, _] = &self.vertices;
+        match self.count {
+            1 => -v1.w,
+            _ => {
+                let e12 = v2.w - v1.w;
+                if e12.cross(-v1.w) > 0.0 {
+                    Vec2::new(-e12.y, e12.x)
+                } else {
+                    Vec2::new(e12.y, -e12.x)
+                }
+            }
+        }
+    }
+
+    fn witness_points(&self) -> (Vec2, Vec2) {
+        let mut point_a = Vec2::default();
+        let mut point_b = Vec2::default();
+        for vertex in &self.vertices[..self.count] {
+            point_a = point_a + vertex.w_a * vertex.a;
+            point_b = point_b + vertex.w_b * vertex.a;
+        }
+        (point_a, point_b)
+    }
+}
+
+// Runs GJK on the Minkowski difference and returns the final simplex. Both
+// polygons need at least one vertex.
+fn gjk(vertices_a: &[Vec2], vertices_b: &[Vec2]) -> (Simplex, u32) {
+    let mut simplex = Simplex::default();
+    simplex.vertices[0] = SimplexVertex::new(vertices_a, vertices_b, 0, 0);
+    simplex.count = 1;
+
+    let mut iterations = 0;
+    while iterations < GJK_MAX_ITERATIONS {
+        // Vertices before the reduction, to detect cycling
+        let saved = simplex.vertices;
+        let saved_count = simplex.count;
+        simplex.solve();
+        if simplex.count == 3 {
+            break;
+        }
+        let direction = simplex.search_direction();
+        if direction.dot(direction) < f32::EPSILON * f32::EPSILON {
+            // The origin lies on the simplex
+            break;
+        }
+        let index_a = support(vertices_a, -direction);
+        let index_b = support(vertices_b, direction);
+        iterations += 1;
+        if saved[..saved_count]
+            .iter()
+            .any(|vertex| vertex.index_a == index_a && vertex.index_b == index_b)
+        {
+            // No progress, the closest features are found
+            break;
+        }
+        simplex.vertices[simplex.count] =
+            SimplexVertex::new(vertices_a, vertices_b, index_a, index_b);
+        simplex.count += 1;
+    }
+    if iterations == GJK_MAX_ITERATIONS {
+        simplex.solve();
+    }
+    (simplex, iterations)
+}
+
+/// Returns the closest points of two convex polygons given by their vertices,
+/// computed with GJK. Returns `None` if a polygon has no vertices.
+pub fn gjk_distance(vertices_a: &[Vec2], vertices_b: &[Vec2]) -> Option<DistanceOutput> {
+    if vertices_a.is_empty() || vertices_b.is_empty() {
+        return None;
+    }
+    let (simplex, iterations) = gjk(vertices_a, vertices_b);
+    if simplex.count == 3 {
+        let (point_a, _) = simplex.witness_points();
+        return Some(DistanceOutput {
+            point_a,
+            point_b: point_a,
+            distance: 0.0,
+            iterations,
+        });
+    }
+    let (point_a, point_b) = simplex.witness_points();
+    Some(DistanceOutput {
+        point_a,
+        point_b,
+        distance: (point_b - point_a).length(),
+        iterations,
+    })
+}
+
+/// Returns the distance between two bodies together with the closest points on
+/// the first and on the second body. The distance is zero if they overlap.
+///
+/// Works for every shape, a chain being as far as its closest segment. Returns
+/// `None` if a body has no vertices or a chain no segment.
+pub fn distance(body_a: &Body, body_b: &Body) -> Option<(f32, Vec2, Vec2)> {
+    match (body_a.shape, body_b.shape) {
+        (Shape::Chain | Shape::Heightfield, _) => chain_distance(body_a, body_b),
+        (_, Shape::Chain | Shape::Heightfield) => {
+            let (distance, point_b, point_a) = chain_distance(body_b, body_a)?;
+            Some((distance, point_a, point_b))
+        }
+        _ => rounded_distance(
+            &core_vertices(body_a),
+            body_a.radius,
+            &core_vertices(body_b),
+            body_b.radius,
+        ),
+    }
+}
+
+// Vertices of the body without its radius.
+fn core_vertices(body: &Body) -> Vec<Vec2> {
+    match body.shape {
+        Shape::Circle => vec![body.position],
+        _ => body.world_vertices(),
+    }
+}
+
+// Distance between two convex polygons inflated by a radius.
+fn rounded_distance(
+    vertices_a: &[Vec2],
+    radius_a: f32,
+    vertices_b: &[Vec2],
+    radius_b: f32,
+) -> Option<(f32, Vec2, Vec2)> {
+    let output = gjk_distance(vertices_a, vertices_b)?;
+    let (mut point_a, mut point_b) = (output.point_a, output.point_b);
+    if output.distance > f32::EPSILON {
+        let normal = (point_b - point_a) * (1.0 / output.distance);
+        point_a = point_a + normal * radius_a.min(output.distance);
+        point_b = point_b - normal * radius_b.min(output.distance);
+    }
+    let distance = (output.distance - radius_a - radius_b).max(0.0);
+    Some((distance, point_a, point_b))
+}
+
+// Distance from the closest segment of a chain to another body, the first
+// point being on the chain.
+fn chain_distance(chain: &Body, other: &Body) -> Option<(f32, Vec2, Vec2)> {
+    let points = chain.world_vertices();
+    let core = match other.shape {
+        Shape::Chain | Shape::Heightfield => other.world_vertices(),
+        _ => core_vertices(other),
+    };
+    points
+        .windows(2)
+        .map(|segment| match other.shape {
+            Shape::Chain | Shape::Heightfield => core
+                .windows(2)
+                .map(|other_segment| rounded_distance(segment, 0.0, other_segment, 0.0))
+                .fold(None, closer),
+            _ => rounded_distance(segment, 0.0, &core, other.radius),
+        })
+        .fold(None, closer)
+}
+
+// The closer of two optional distances.
+fn closer(
+    best: Option<(f32, Vec2, Vec2)>,
+    item: Option<(f32, Vec2, Vec2)>,
+) -> Option<(f32, Vec2, Vec2)> {
+    match (best, item) {
+        (Some(best), Some(item)) if best.0 <= item.0 => Some(best),
+        (best, None) => best,
+        (_, item) => item,
+    }
+}
+
+/// Returns the unit normal, pointing from the first polygon to the second, and
+/// the depth of the smallest translation that separates two overlapping convex
+/// polygons, computed with GJK and EPA. Returns `None` if they do not overlap.
+pub fn epa_penetration(vertices_a: &[Vec2], vertices_b: &[Vec2]) -> Option<(Vec2, f32)> {
+    if vertices_a.is_empty() || vertices_b.is_empty() {
+        return None;
+    }
+    let (simplex, _) = gjk(vertices_a, vertices_b);
+    let mut polytope: Vec<Vec2> = simplex.vertices[..simplex.count]
+        .iter()
+        .map(|vertex| vertex.w)
+        .collect();
+    if polytope.len() < 3 {
+        let (point_a, point_b) = simplex.witness_points();
+        if (point_b - point_a).length() > f32::EPSILON {
+            return None;
+        }
+        // Touching, grow the simplex into a triangle around the origin
+        let axis = match polytope.len() {
+            2 => polytope[1] - polytope[0],
+            _ => Vec2::new(1.0, 0.0),
+        };
+        let perpendicular = Vec2::new(-axis.y, axis.x);
+        polytope.clear();
+        for direction in [axis, -axis, perpendicular, -perpendicular] {
+            let w = vertices_b[support(vertices_b, direction)]
+                - vertices_a[support(vertices_a, -direction)];
+            if !polytope.contains(&w) {
+                polytope.push(w);
+            }
+        }
+        if polytope.len() < 3 {
+            return Some((Vec2::new(0.0, 1.0), 0.0));
+        }
+        polytope = convex_hull(polytope);
+    }
+    // Counter-clockwise winding, so the right normals point outward
+    if (polytope[1] - polytope[0]).cross(polytope[2] - polytope[0]) < 0.0 {
+        polytope.swap(1, 2);
+    }
+
+    let mut closest = (Vec2::new(0.0, 1.0), 0.0);
+    for _ in 0..EPA_MAX_ITERATIONS {
+        // Edge of the polytope closest to the origin
+        let mut edge = 0;
+        let mut min_distance = f32::MAX;
+        let mut edge_normal = Vec2::default();
+        for i in 0..polytope.len() {
+            let e = polytope[(i + 1) % polytope.len()] - polytope[i];
+            let length = e.length();
+            if length < f32::EPSILON {
+                continue;
+            }
+            let normal = Vec2::new(e.y, -e.x) * (1.0 / length);
+            let distance = normal.dot(polytope[i]);
+            if distance < min_distance {
+                min_distance = distance;
+                edge = i;
+                edge_normal = normal;
+            }
+        }
+        closest = (edge_normal, min_distance);
+
+        let w = vertices_b[support(vertices_b, edge_normal)]
+            - vertices_a[support(vertices_a, -edge_normal)];
+        if w.dot(edge_normal) - min_distance < EPA_TOLERANCE {
+            break;
+        }
+        polytope.insert(edge + 1, w);
+    }
+    // Moving B against the outward normal of B - A separates the two
+    let (normal, depth) = closest;
+    Some((-normal, depth.max(0.0)))
+}
+
+// Counter-clockwise convex hull of a handful of points, gift wrapping.
+fn convex_hull(points: Vec<Vec2>) -> Vec<Vec2> {
+    let start = (0..points.len())
+        .min_by(|&i, &j| points[i].x.total_cmp(&points[j].x))
+        .unwrap_or(0);
+    let mut hull = Vec::with_capacity(points.len());
+    let mut current = start;
+    loop {
+        hull.push(points[current]);
+        let mut next = (current + 1) % points.len();
+        for (i, &point) in points.iter().enumerate() {
+            if (points[next] - points[current]).cross(point - points[current]) < 0.0 {
+                next = i;
+            }
+        }
+        current = next;
+        if current == start || hull.len() == points.len() {
+            break;
+        }
+    }
+    hull
+}
+
+/// Appends the contact points of two polygon bodies to `contacts` and returns
+/// how many were found, using GJK and EPA instead of separating axes.
+///
+/// The penetration normal from EPA selects the reference and incident edges,
+/// which are then clipped like in `collide_capsule`.
+pub fn collide_polygons_gjk(contacts: &mut Vec<ContactInfo>, b1: &Body, b2: &Body) -> i32 {
+    let a = RoundedPolygon::from_body(b1);
+    let b = RoundedPolygon::from_body(b2);
+    let Some((normal, _)) = epa_penetration(&a.vertices, &b.vertices) else {
+        return 0;
+    };
+
+    let (edge_a, alignment_a) = a
+        .normals
+        .iter()
+        .map(|other| normal.dot(*other))
+        .enumerate()
+        .fold(
+            (0, f32::MIN),
+            |best, item| {
+                if item.1 > best.1 {
+                    item
+                } else {
+                    best
+                }
+            },
+        );
+    let (edge_b, alignment_b) = b
+        .normals
+        .iter()
+        .map(|other| -normal.dot(*other))
+        .enumerate()
+        .fold(
+            (0, f32::MIN),
+            |best, item| {
+                if item.1 > best.1 {
+                    item
+                } else {
+                    best
+                }
+            },
+        );
+    // The reference edge is the one most parallel to the normal
+    let incident_edge = |polygon: &RoundedPolygon, reference_normal: Vec2| {
+        polygon
+            .normals
+            .iter()
+            .enumerate()
+            .map(|(i, other)| (i, reference_normal.dot(*other)))
+            .fold(
+                (0, f32::MAX),
+                |best, item| if item.1 < best.1 { item } else { best },
+            )
+            .0
+    };
+    if alignment_b > alignment_a + 1e-3 {
+        let edge = incident_edge(&a, b.normals[edge_b]);
+        clip_incident_edge(contacts, &b, &a, (edge_b, edge), true)
+    } else {
+        let edge = incident_edge(&b, a.normals[edge_a]);
+        clip_incident_edge(contacts, &a, &b, (edge_a, edge), false)
+    }
+}
+
+/// Appends the contact point of a circle and a polygon or capsule, given in any
+/// order, and returns how many were found. The normal points from `b1` to `b2`.
+///
+/// The feature id is the index of the touched edge, or 256 plus the index of the
+/// touched vertex.
+pub fn collide_circle_polygon(contacts: &mut Vec<ContactInfo>, b1: &Body, b2: &Body) -> i32 {
+    let (circle, polygon, sign) = match b1.shape {
+        Shape::Circle => (b1, b2, -1.0),
+        _ => (b2, b1, 1.0),
+    };
+    let polygon = RoundedPolygon::from_body(polygon);
+    match circle_manifold(circle.position, circle.radius, &polygon) {
+        Some(mut contact) => {
+            contact.normal = contact.normal * sign;
+            contacts.push(contact);
+            1
+        }
+        None => 0,
+    }
+}
+
+// Contact point of a circle and a rounded polygon, the normal points from the
+// polygon to the circle.
+fn circle_manifold(
+    center: Vec2,
+    circle_radius: f32,
+    polygon: &RoundedPolygon,
+) -> Option<ContactInfo> {
+    let (vertices, normals) = (&polygon.vertices, &polygon.normals);
+    let radius = circle_radius + polygon.radius;
+
+    let (edge, separation) = find_max_separation(vertices, normals, &[center]);
+    if separation > radius {
+        return None;
+    }
+    let v1 = vertices[edge];
+    let v2 = vertices[(edge + 1) % vertices.len()];
+
+    // A center inside the polygon always projects onto its nearest edge
+    let u1 = (center - v1).dot(v2 - v1);
+    let u2 = (center - v2).dot(v1 - v2);
+    let vertex = if u1 < 0.0 {
+        Some((v1, edge))
+    } else if u2 < 0.0 {
+        Some((v2, (edge + 1) % vertices.len()))
+    } else {
+        None
+    };
+    let (normal, separation, feature) = match vertex {
+        Some((vertex, index)) => {
+            let d = center - vertex;
+            let distance = d.length();
+            if distance > radius {
+                return None;
+            }
+            (d * (1.0 / distance), distance - radius, 256 + index as i32)
+        }
+        None => (normals[edge], separation - radius, edge as i32),
+    };
+
+    Some(rounded_contact(
+        center - normal * (circle_radius + 0.5 * separation),
+        normal,
+        separation,
+        feature,
+    ))
+}
+
+// What to do with a contact of a chain segment.
+enum ChainContact {
+    Keep,
+    // Use the normal of the segment instead
+    Project,
+    // The neighbouring segment reports it
+    Discard,
+}
+
+// Left normal of the edge from `start` to `end`.
+fn left_normal(start: Vec2, end: Vec2) -> Vec2 {
+    let edge = end - start;
+    Vec2::new(-edge.y, edge.x) * (1.0 / edge.length())
+}
+
+/// Appends the contact points of a chain and another body, given in any order,
+/// and returns how many were found. The normal points from `b1` to `b2`.
+///
+/// Each segment is collided on its own, then contacts are smoothed over the
+/// joints between segments: a normal is only kept if it points into the region
+/// owned by the segment, which for a convex joint includes the rounded corner
+/// at the end of the segment. Other normals are replaced by the normal of the
+/// segment, so bodies slide over collinear or concave joints without snagging.
+pub fn collide_chain(contacts: &mut Vec<ContactInfo>, b1: &Body, b2: &Body) -> i32 {
+    let (chain, other, sign) = match b1.shape {
+        Shape::Chain => (b1, b2, 1.0),
+        _ => (b2, b1, -1.0),
+    };
+    if let Shape::Chain | Shape::Heightfield = other.shape {
+        return 0;
+    }
+    let points = chain.local_vertices();
+    let segments = 0..points.len().saturating_sub(1);
+    collide_segments(contacts, chain, points, segments, 0, other, sign)
+}
+
+/// Appends the contact points of a heightfield and another body, given in any
+/// order, and returns how many were found. The normal points from `b1` to `b2`.
+///
+/// Only the cells under the bounding box of the other body are collided, like
+/// the segments of a chain.
+pub fn collide_heightfield(contacts: &mut Vec<ContactInfo>, b1: &Body, b2: &Body) -> i32 {
+    let (heightfield, other, sign) = match b1.shape {
+        Shape::Heightfield => (b1, b2, 1.0),
+        _ => (b2, b1, -1.0),
+    };
+    if let Shape::Chain | Shape::Heightfield = other.shape {
+        return 0;
+    }
+    let samples = heightfield.local_vertices();
+    let spacing = heightfield.spacing;
+    if samples.len() < 2 || spacing <= 0.0 {
+        return 0;
+    }
+
+    // Range of the other body along the heightfield
+    let aabb = other.aabb();
+    let rotation = Mat2x2::new_from_angle(heightfield.rotation).transpose();
+    let (mut min_x, mut max_x) = (f32::MAX, f32::MIN);
+    for corner in [
+        aabb.min,
+        aabb.max,
+        Vec2::new(aabb.min.x, aabb.max.y),
+        Vec2::new(aabb.max.x, aabb.min.y),
+    ] {
+        let x = (rotation * (corner - heightfield.position)).x;
+        min_x = min_x.min(x);
+        max_x = max_x.max(x);
+    }
+    let cells = samples.len() - 1;
+    let first = ((min_x / spacing).floor().max(0.0) as usize).min(cells);
+    let last = ((max_x / spacing).ceil().max(0.0) as usize).min(cells);
+    if first >= last {
+        return 0;
+    }
+
+    // The cells plus their neighbours, which smooth the joints at the ends
+    let start = first.saturating_sub(1);
+    let end = (last + 2).min(samples.len());
+    let segments = first - start..last - start;
+    collide_segments(
+        contacts,
+        heightfield,
+        &samples[start..end],
+        segments,
+        start,
+        other,
+        sign,
+    )
+}
+
+// Collides the `segments` of the polyline through `points`, local to `body`,
+// with `other`, see `collide_chain`. Segment `i` is numbered
+// `first_segment + i` in feature ids.
+fn collide_segments(
+    contacts: &mut Vec<ContactInfo>,
+    body: &Body,
+    points: &[Vec2],
+    segments: Range<usize>,
+    first_segment: usize,
+    other: &Body,
+    sign: f32,
+) -> i32 {
+    let other_aabb = other.aabb();
+    let other_polygon = RoundedPolygon::from_body(other);
+    let mut found = Vec::new();
+    let mut num_contacts = 0;
+    let rotation = Mat2x2::new_from_angle(body.rotation);
+    let point = |i: usize| rotation * points[i] + body.position;
+
+    for i in segments {
+        let (v1, v2) = (point(i), point(i + 1));
+        let edge = v2 - v1;
+        if edge.length() < f32::EPSILON || !Aabb::from_points(&[v1, v2]).overlaps(&other_aabb) {
+            continue;
+        }
+        let normal = left_normal(v1, v2);
+        let previous = (i > 0).then(|| left_normal(point(i - 1), v1));
+        let next = (i + 2 < points.len()).then(|| left_normal(v2, point(i + 2)));
+
+        let segment = RoundedPolygon::new(vec![v1, v2], 0.0);
+        found.clear();
+        match other.shape {
+            Shape::Circle => found.extend(circle_manifold(other.position, other.radius, &segment)),
+            _ => {
+                rounded_manifold(&mut found, &segment, &other_polygon);
+            }
+        }
+
+        for mut contact in found.iter().copied() {
+            if contact.normal.dot(normal) < 0.0 {
+                // Behind the chain
+                continue;
+            }
+            let at_start = (contact.position - v1).dot(edge) < 0.5 * edge.dot(edge);
+            let action = if contact.normal.dot(normal) > 1.0 - 1e-4 {
+                ChainContact::Keep
+            } else {
+                match (at_start, previous, next) {
+                    (true, None, _) | (false, _, None) => ChainContact::Keep,
+                    (true, Some(n0), _) => {
+                        // Convex joints belong to the previous segment
+                        let between =
+                            n0.cross(contact.normal) <= 0.0 && contact.normal.cross(normal) <= 0.0;
+                        if n0.cross(normal) < 0.0 && between {
+                            ChainContact::Discard
+                        } else {
+                            ChainContact::Project
+                        }
+                    }
+                    (false, _, Some(n2)) => {
+                        let between =
+                            normal.cross(contact.normal) <= 0.0 && contact.normal.cross(n2) <= 0.0;
+                        if normal.cross(n2) < 0.0 && between {
+                            ChainContact::Keep
+                        } else {
+                            ChainContact::Project
+                        }
+                    }
+                }
+            };
+            match action {
+                ChainContact::Keep => {}
+                ChainContact::Discard => continue,
+                ChainContact::Project => {
+                    // Deepest point of the body along the segment normal, within the segment
+                    let core_points: &[Vec2] = match other.shape {
+                        Shape::Circle => &[other.position],
+                        _ => &other_polygon.vertices,
+                    };
+                    let deepest = core_points
+                        .iter()
+                        .filter(|&&point| {
+                            (0.0..=1.0).contains(&((point - v1).dot(edge) / edge.dot(edge)))
+                        })
+                        .map(|&point| (point, normal.dot(point - v1) - other.radius))
+                        .min_by(|a, b| a.1.total_cmp(&b.1));
+                    let Some((point, separation)) = deepest else {
+                        continue;
+                    };
+                    if separation > 0.0 {
+                        continue;
+                    }
+                    contact.normal = normal;
+                    contact.separation = separation;
+                    contact.position = point - normal * (other.radius + 0.5 * separation);
+                }
+            }
+            contact.normal = contact.normal * sign;
+            contact.feature.value |= ((first_segment + i) as i32) << 18;
+            contacts.push(contact);
+            num_contacts += 1;
+        }
+    }
+    num_contacts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capsule_lying_on_box() {
+        let mut ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        let mut capsule = Body::new_capsule(2.0, 0.5, 1.0);
+        capsule.position = Vec2::new(0.0, 0.45);
+
+        let mut contacts = Vec::new();
+        assert_eq!(collide_capsule(&mut contacts, &ground, &capsule), 2);
+        for contact in contacts.iter() {
+            assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+            assert!((contact.separation + 0.05).abs() < 1e-5);
+            assert!((contact.position.x.abs() - 1.0).abs() < 1e-5);
+        }
+
+        // Same pair in the other order flips the normal
+        contacts.clear();
+        assert_eq!(collide_capsule(&mut contacts, &capsule, &ground), 2);
+        assert_eq!(contacts[0].normal, Vec2::new(0.0, -1.0));
+
+        capsule.position.y = 0.55;
+        contacts.clear();
+        assert_eq!(collide_capsule(&mut contacts, &ground, &capsule), 0);
+    }
+
+    #[test]
+    fn test_capsule_end_touches_corner() {
+        let ground = Body::new(Vec2::new(2.0, 2.0), f32::MAX);
+        let mut capsule = Body::new_capsule(2.0, 0.5, 1.0);
+        // Rounded end diagonally next to the corner (1, 1)
+        capsule.position = Vec2::new(1.3 + 1.0, 1.3);
+
+        let mut contacts = Vec::new();
+        assert_eq!(collide_capsule(&mut contacts, &ground, &capsule), 1);
+        let contact = contacts[0];
+        let diagonal = Vec2::new(1.0, 1.0) * (1.0 / 2.0_f32.sqrt());
+        assert_eq!(contact.normal, diagonal);
+        assert!((contact.separation - (0.3 * 2.0_f32.sqrt() - 0.5)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_circle_polygon() {
+        let mut triangle = Body::new_polygon(
+            vec![
+                Vec2::new(-1.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(0.0, 1.0),
+            ],
+            f32::MAX,
+        )
+        .unwrap();
+        // Polygons rotate about their centroid
+        triangle.position = Vec2::new(0.0, 1.0 / 3.0);
+        let mut circle = Body::new_circle(0.5, 1.0);
+
+        // Below the bottom edge
+        circle.position = Vec2::new(0.2, -0.3);
+        let mut contacts = Vec::new();
+        assert_eq!(collide_circle_polygon(&mut contacts, &circle, &triangle), 1);
+        let contact = contacts[0];
+        assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+        assert!((contact.separation + 0.2).abs() < 1e-5);
+
+        // Next to a corner, the normal points from the corner to the center
+        let corner = triangle.world_vertices()[0];
+        circle.position = corner + Vec2::new(-0.3, -0.3);
+        contacts.clear();
+        assert_eq!(collide_circle_polygon(&mut contacts, &triangle, &circle), 1);
+        let contact = contacts[0];
+        let diagonal = Vec2::new(-1.0, -1.0) * (1.0 / 2.0_f32.sqrt());
+        assert_eq!(contact.normal, diagonal);
+        assert!(contact.feature.value >= 256);
+
+        circle.position = corner + Vec2::new(-0.4, -0.4);
+        contacts.clear();
+        assert_eq!(collide_circle_polygon(&mut contacts, &triangle, &circle), 0);
+    }
+
+    #[test]
+    fn test_circle_capsule() {
+        let capsule = Body::new_capsule(2.0, 0.5, f32::MAX);
+        let mut circle = Body::new_circle(0.5, 1.0);
+        circle.position = Vec2::new(0.3, 0.9);
+        let mut contacts = Vec::new();
+        assert_eq!(collide_circle_polygon(&mut contacts, &capsule, &circle), 1);
+        let contact = contacts[0];
+        assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+        assert!((contact.separation + 0.1).abs() < 1e-5);
+        assert!((contact.position - Vec2::new(0.3, 0.45)).length() < 1e-5);
+
+        // Around the rounded end
+        circle.position = Vec2::new(1.8, 0.0);
+        contacts.clear();
+        assert_eq!(collide_circle_polygon(&mut contacts, &capsule, &circle), 1);
+        assert_eq!(contacts[0].normal, Vec2::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn test_chain_smooths_internal_joint() {
+        let chain = Body::new_chain(vec![
+            Vec2::new(-1.0, 0.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+        ]);
+        // The corner just passed the joint, a single segment would push it back
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(-0.49, 0.45);
+        let segment = Body::new_chain(vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0)]);
+        let mut contacts = Vec::new();
+        collide_chain(&mut contacts, &segment, &box_);
+        assert_eq!(contacts[0].normal, Vec2::new(-1.0, 0.0));
+
+        contacts.clear();
+        assert!(collide_chain(&mut contacts, &chain, &box_) > 0);
+        for contact in contacts.iter() {
+            assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+            assert!((contact.separation + 0.05).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_chain_is_one_sided() {
+        let chain = Body::new_chain(vec![Vec2::new(-5.0, 0.0), Vec2::new(5.0, 0.0)]);
+        let mut circle = Body::new_circle(0.5, 1.0);
+        circle.position = Vec2::new(0.0, 0.4);
+        let mut contacts = Vec::new();
+        assert_eq!(collide_chain(&mut contacts, &circle, &chain), 1);
+        assert_eq!(contacts[0].normal, Vec2::new(0.0, -1.0));
+
+        circle.position = Vec2::new(0.0, -0.4);
+        contacts.clear();
+        assert_eq!(collide_chain(&mut contacts, &circle, &chain), 0);
+    }
+
+    fn square(center: Vec2, half: f32) -> Vec<Vec2> {
+        vec![
+            center + Vec2::new(-half, -half),
+            center + Vec2::new(half, -half),
+            center + Vec2::new(half, half),
+            center + Vec2::new(-half, half),
+        ]
+    }
+
+    #[test]
+    fn test_gjk_distance() {
+        let a = square(Vec2::new(0.0, 0.0), 1.0);
+        let b = square(Vec2::new(3.0, 0.5), 1.0);
+        let output = gjk_distance(&a, &b).unwrap();
+        assert!((output.distance - 1.0).abs() < 1e-5);
+        assert!((output.point_a.x - 1.0).abs() < 1e-5);
+        assert!((output.point_b.x - 2.0).abs() < 1e-5);
+
+        // Corner to corner
+        let b = square(Vec2::new(3.0, 3.0), 1.0);
+        let output = gjk_distance(&a, &b).unwrap();
+        assert!((output.distance - 2.0_f32.sqrt()).abs() < 1e-5);
+        assert_eq!(output.point_a, Vec2::new(1.0, 1.0));
+        assert!(epa_penetration(&a, &b).is_none());
+
+        let b = square(Vec2::new(1.5, 0.2), 1.0);
+        assert_eq!(gjk_distance(&a, &b).unwrap().distance, 0.0);
+
+        // Empty shapes have no closest points
+        assert!(gjk_distance(&a, &[]).is_none());
+        assert!(epa_penetration(&[], &b).is_none());
+    }
+
+    #[test]
+    fn test_epa_penetration() {
+        let a = square(Vec2::new(0.0, 0.0), 1.0);
+        let b = square(Vec2::new(1.5, 0.2), 1.0);
+        let (normal, depth) = epa_penetration(&a, &b).unwrap();
+        assert_eq!(normal, Vec2::new(1.0, 0.0));
+        assert!((depth - 0.5).abs() < 1e-4);
+
+        let b = square(Vec2::new(-0.1, -1.8), 1.0);
+        let (normal, depth) = epa_penetration(&a, &b).unwrap();
+        assert_eq!(normal, Vec2::new(0.0, -1.0));
+        assert!((depth - 0.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_collide_polygons_gjk() {
+        let mut ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.3, 0.45);
+
+        let mut contacts = Vec::new();
+        assert_eq!(collide_polygons_gjk(&mut contacts, &ground, &box_), 2);
+        for contact in contacts.iter() {
+            assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+            assert!((contact.separation + 0.05).abs() < 1e-5);
+        }
+
+        contacts.clear();
+        assert_eq!(collide_polygons_gjk(&mut contacts, &box_, &ground), 2);
+        assert_eq!(contacts[0].normal, Vec2::new(0.0, -1.0));
+
+        box_.position.y = 0.6;
+        contacts.clear();
+        assert_eq!(collide_polygons_gjk(&mut contacts, &ground, &box_), 0);
+    }
+
+    #[test]
+    fn test_polygon_features_are_stable() {
+        let ground = Body::new_polygon(square(Vec2::new(0.0, -1.0), 1.0), f32::MAX).unwrap();
+        let mut box_ = Body::new_polygon(square(Vec2::default(), 0.5), 1.0).unwrap();
+        box_.position = Vec2::new(0.2, 0.45);
+
+        let mut before = Vec::new();
+        assert_eq!(collide_polygons(&mut before, &ground, &box_), 4);
+        let mut features: Vec<i32> = before.iter().map(|c| c.feature.value).collect();
+        features.sort();
+        features.dedup();
+        assert_eq!(features.len(), 4);
+
+        // Sliding and sinking a bit keeps the features of the points
+        box_.position = Vec2::new(0.25, 0.44);
+        let mut after = Vec::new();
+        assert_eq!(collide_polygons(&mut after, &ground, &box_), 4);
+        for (old, new) in before.iter().zip(after.iter()) {
+            assert_eq!(old.feature.value, new.feature.value);
+        }
+    }
+
+    #[test]
+    fn test_distance() {
+        let ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        let mut circle = Body::new_circle(0.5, 1.0);
+        circle.position = Vec2::new(1.0, 2.5);
+        let (d, point_a, point_b) = distance(&ground, &circle).unwrap();
+        assert!((d - 1.5).abs() < 1e-5);
+        assert!((point_a - Vec2::new(1.0, 0.5)).length() < 1e-5);
+        assert!((point_b - Vec2::new(1.0, 2.0)).length() < 1e-5);
+
+        let (d, point_a, _) = distance(&circle, &ground).unwrap();
+        assert!((d - 1.5).abs() < 1e-5);
+        assert!((point_a - Vec2::new(1.0, 2.0)).length() < 1e-5);
+
+        circle.position = Vec2::new(1.0, 0.9);
+        assert_eq!(distance(&ground, &circle).unwrap().0, 0.0);
+
+        let chain = Body::new_chain(vec![
+            Vec2::new(-5.0, 0.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(5.0, 1.0),
+        ]);
+        let mut capsule = Body::new_capsule(2.0, 0.5, 1.0);
+        capsule.position = Vec2::new(-2.0, 3.0);
+        let (d, point_a, point_b) = distance(&capsule, &chain).unwrap();
+        assert!((d - 2.5).abs() < 1e-5);
+        assert!((point_a.y - 2.5).abs() < 1e-5);
+        assert!(point_b.y.abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_heightfield() {
+        let heights: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut terrain = Body::new_heightfield(heights, 0.5);
+        terrain.position = Vec2::new(-250.0, -1.0);
+        let flat = Body::new_heightfield(vec![0.0; 4], 1.0);
+
+        let mut circle = Body::new_circle(0.5, 1.0);
+        circle.position = Vec2::new(1.5, 0.4);
+        let mut contacts = Vec::new();
+        assert_eq!(collide_heightfield(&mut contacts, &flat, &circle), 1);
+        assert_eq!(contacts[0].normal, Vec2::new(0.0, 1.0));
+        assert!((contacts[0].separation + 0.1).abs() < 1e-5);
+        // Cell 1 of the flat field
+        assert_eq!(contacts[0].feature.value >> 18, 1);
+
+        // Resting on the terrain, only the cells below are tested
+        let x = 100.0_f32;
+        let height = ((x + 250.0) / 0.5 * 0.1).sin() - 1.0;
+        circle.position = Vec2::new(x, height + 0.45);
+        contacts.clear();
+        assert!(collide_heightfield(&mut contacts, &circle, &terrain) > 0);
+        assert!(contacts[0].normal.y < 0.0);
+
+        circle.position = Vec2::new(x, height + 1.5);
+        contacts.clear();
+        assert_eq!(collide_heightfield(&mut contacts, &terrain, &circle), 0);
+        // Beyond the ends
+        circle.position = Vec2::new(-300.0, 0.0);
+        assert_eq!(collide_heightfield(&mut contacts, &terrain, &circle), 0);
+
+        // The spacing is kept when the shape is copied
+        let copy = Body::from_density(&terrain.geometry(), 1.0).unwrap();
+        assert_eq!(copy.spacing, 0.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_heightfield_without_spacing() {
+        Body::new_heightfield(vec![0.0; 4], 0.0);
+    }
 }
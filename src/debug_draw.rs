@@ -0,0 +1,250 @@
+use crate::body::{Body, BodyType, Shape};
+use crate::joint::JointKind;
+use crate::math_utils::Vec2;
+use crate::world::World;
+
+/// A color with components between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+/// Drawing primitives in world space, implemented for a renderer to draw a
+/// world with `draw_world`, see `macroquad_draw` and `ggez_draw`.
+pub trait DebugDraw {
+    /// Fills a convex polygon.
+    fn polygon(&mut self, vertices: &[Vec2], color: Color);
+    /// Fills a circle.
+    fn circle(&mut self, center: Vec2, radius: f32, color: Color);
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color);
+}
+
+/// What `draw_world` draws and in which colors. The colors follow the nannou
+/// samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugDrawOptions {
+    pub bodies: bool,
+    /// Contact points with their normals.
+    pub contacts: bool,
+    /// Lines from the bodies to the anchors, the ropes of distance and pulley
+    /// joints and the rings of area joints.
+    pub joints: bool,
+    pub aabbs: bool,
+    pub static_color: Color,
+    pub dynamic_color: Color,
+    pub contact_color: Color,
+    pub joint_color: Color,
+    pub aabb_color: Color,
+    /// Thickness of the lines, in world units.
+    pub thickness: f32,
+}
+
+impl Default for DebugDrawOptions {
+    fn default() -> Self {
+        Self {
+            bodies: true,
+            contacts: true,
+            joints: true,
+            aabbs: false,
+            static_color: Color::new(0.56, 0.74, 0.56, 1.0),
+            dynamic_color: Color::new(0.85, 0.44, 0.84, 1.0),
+            contact_color: Color::new(1.0, 0.63, 0.48, 1.0),
+            joint_color: Color::new(0.42, 0.35, 0.8, 1.0),
+            aabb_color: Color::new(1.0, 1.0, 1.0, 0.5),
+            thickness: 0.05,
+        }
+    }
+}
+
+/// Draws the bodies, contacts, joints and bounding boxes of the world, as
+/// chosen by `options`.
+pub fn draw_world(world: &World, draw: &mut impl DebugDraw, options: &DebugDrawOptions) {
+    if options.bodies {
+        for body in world.iter_bodies() {
+            let color = match body.body_type() {
+                BodyType::Static => options.static_color,
+                _ => options.dynamic_color,
+            };
+            draw_body(body, draw, color, options.thickness);
+        }
+    }
+    if options.aabbs {
+        for body in world.iter_bodies() {
+            let aabb = body.aabb();
+            let corners = [
+                aabb.min,
+                Vec2::new(aabb.max.x, aabb.min.y),
+                aabb.max,
+                Vec2::new(aabb.min.x, aabb.max.y),
+            ];
+            draw_outline(&corners, true, draw, options.aabb_color, options.thickness);
+        }
+    }
+    if options.contacts {
+        for (_, arbiter) in world.iter_arbiters() {
+            for contact in arbiter.manifold.points.iter() {
+                draw.circle(
+                    contact.position,
+                    2.0 * options.thickness,
+                    options.contact_color,
+                );
+                draw.line(
+                    contact.position,
+                    contact.position + contact.normal,
+                    options.thickness,
+                    options.contact_color,
+                );
+            }
+        }
+    }
+    if options.joints {
+        let mut ring = Vec::new();
+        for joint in world.iter_joints() {
+            if let JointKind::Area(area) = joint.kind() {
+                ring.clear();
+                ring.extend(
+                    area.bodies
+                        .iter()
+                        .filter_map(|&handle| world.body(handle))
+                        .map(|body| body.position),
+                );
+                draw_outline(&ring, true, draw, options.joint_color, options.thickness);
+                continue;
+            }
+            let (Some(body_1), Some(body_2)) =
+                (world.get_body(joint.body_1), world.get_body(joint.body_2))
+            else {
+                continue;
+            };
+            let p1 = body_1.local_to_world(joint.local_anchor_1);
+            let p2 = body_2.local_to_world(joint.local_anchor_2);
+            let mut lines = vec![(body_1.position, p1), (body_2.position, p2)];
+            match joint.kind() {
+                JointKind::Distance(_) => lines.push((p1, p2)),
+                JointKind::Pulley(pulley) => {
+                    lines.push((pulley.ground_anchors.0, p1));
+                    lines.push((pulley.ground_anchors.1, p2));
+                }
+                _ => {}
+            }
+            for (start, end) in lines {
+                draw.line(start, end, options.thickness, options.joint_color);
+            }
+        }
+    }
+}
+
+fn draw_body(body: &Body, draw: &mut impl DebugDraw, color: Color, thickness: f32) {
+    match body.shape {
+        Shape::Box | Shape::ConvexPolygon => draw.polygon(&body.world_vertices(), color),
+        Shape::Capsule => {
+            let vertices = body.world_vertices();
+            draw.line(vertices[0], vertices[1], 2.0 * body.radius, color);
+            draw.circle(vertices[0], body.radius, color);
+            draw.circle(vertices[1], body.radius, color);
+        }
+        Shape::Circle => {
+            draw.circle(body.position, body.radius, color);
+            // Shows the rotation
+            let rim = body.local_to_world(Vec2::new(body.radius, 0.0));
+            draw.line(
+                body.position,
+                rim,
+                thickness,
+                Color::new(0.0, 0.0, 0.0, 0.5),
+            );
+        }
+        Shape::Chain | Shape::Heightfield => {
+            draw_outline(&body.world_vertices(), false, draw, color, 2.0 * thickness)
+        }
+    }
+}
+
+fn draw_outline(
+    points: &[Vec2],
+    closed: bool,
+    draw: &mut impl DebugDraw,
+    color: Color,
+    thickness: f32,
+) {
+    for pair in points.windows(2) {
+        draw.line(pair[0], pair[1], thickness, color);
+    }
+    if let (true, Some(&first), Some(&last)) = (closed, points.first(), points.last()) {
+        draw.line(last, first, thickness, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joint::JointBuilder;
+
+    #[derive(Default)]
+    struct Counts {
+        polygons: usize,
+        circles: usize,
+        lines: usize,
+    }
+
+    impl DebugDraw for Counts {
+        fn polygon(&mut self, _vertices: &[Vec2], _color: Color) {
+            self.polygons += 1;
+        }
+
+        fn circle(&mut self, _center: Vec2, _radius: f32, _color: Color) {
+            self.circles += 1;
+        }
+
+        fn line(&mut self, _start: Vec2, _end: Vec2, _thickness: f32, _color: Color) {
+            self.lines += 1;
+        }
+    }
+
+    #[test]
+    fn test_draw_world() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        let ground = world.add_body(ground);
+        let mut ball = Body::new_circle(0.5, 1.0);
+        ball.position = Vec2::new(0.0, 0.45);
+        let ball = world.add_body(ball);
+        JointBuilder::distance(ground, ball)
+            .build(&mut world)
+            .unwrap();
+        world.step(1.0 / 60.0).unwrap();
+        let contacts: usize = world
+            .iter_arbiters()
+            .map(|(_, arbiter)| arbiter.manifold.points.len())
+            .sum();
+        assert!(contacts > 0);
+
+        let mut counts = Counts::default();
+        draw_world(&world, &mut counts, &DebugDrawOptions::default());
+        assert_eq!(counts.polygons, 1);
+        assert_eq!(counts.circles, 1 + contacts);
+        // Rotation of the ball, contact normals and three joint lines
+        assert_eq!(counts.lines, 1 + contacts + 3);
+
+        let mut counts = Counts::default();
+        let options = DebugDrawOptions {
+            bodies: false,
+            contacts: false,
+            joints: false,
+            aabbs: true,
+            ..Default::default()
+        };
+        draw_world(&world, &mut counts, &options);
+        assert_eq!(counts.lines, 8);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::arbiter::Contact;
+use crate::arbiter::ContactInfo;
 use crate::math_utils::{Mat2x2, Vec2};
 // Define an enum for text styles
 #[derive(Clone, Copy)]
@@ -185,6 +185,7 @@ impl Default for StyledSymbol {
 fn create_styled_symbol(symbol: char, style: ColorStyle) -> StyledSymbol {
     StyledSymbol { symbol, style }
 }
+#[allow(clippy::needless_range_loop)]
 pub fn make_grid(grid_size: usize) -> Vec<Vec<StyledSymbol>> {
     let mut grid = vec![vec![StyledSymbol::default(); grid_size]; grid_size];
 
@@ -225,8 +226,8 @@ pub fn make_grid(grid_size: usize) -> Vec<Vec<StyledSymbol>> {
     let center_y = grid_size / 2;
 
     grid[center_y][center_x] = create_styled_symbol('┬', arrow_style); // Arrow head at origin
-    for i in center_x + 1..grid_size {
-        grid[center_y][i] = create_styled_symbol('─', arrow_style); // Horizontal arrow line
+    for cell in grid[center_y].iter_mut().skip(center_x + 1) {
+        *cell = create_styled_symbol('─', arrow_style); // Horizontal arrow line
     }
     for grid_y in grid.iter_mut().take(center_y) {
         grid_y[center_x] = create_styled_symbol('│', arrow_style); // Vertical arrow line
@@ -389,15 +390,10 @@ pub fn draw_rectangle(
     grid[y2][x1] = create_styled_symbol('┘', style);
 }
 
-pub fn draw_collision_result(grid: &mut Vec<Vec<StyledSymbol>>, contacts: &Vec<Contact>) {
+pub fn draw_collision_result(grid: &mut Vec<Vec<StyledSymbol>>, contacts: &[ContactInfo]) {
     // Draw collision contacts
-    for contact in contacts {
-        match contact {
-            Some(contact) => {
-                add_point(grid, contact.position, 'C', COLLISION_STYLE);
-            }
-            None => (),
-        }
+    for contact in contacts.iter() {
+        add_point(grid, contact.position, 'C', COLLISION_STYLE);
     }
 }
 
@@ -0,0 +1,288 @@
+use crate::body::BodyType;
+use crate::joint::JointKind;
+use crate::math_utils::Vec2;
+use crate::solver::{PositionSolver, Solver};
+use crate::world::World;
+use egui::{CollapsingHeader, ComboBox, DragValue, Grid, Ui};
+
+/// An egui panel listing the bodies and joints of a world with their
+/// properties, editable while the world runs, and the settings of the solver.
+///
+/// Show it in a window of its own with `show`, or inside any `Ui` with `ui`.
+#[derive(Debug, Clone, Default)]
+pub struct WorldInspector {
+    /// Only the bodies and joints whose id contains the filter are listed.
+    pub filter: String,
+}
+
+impl WorldInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shows the inspector in a window titled "World". Returns true if
+    /// anything was edited.
+    pub fn show(&mut self, ctx: &egui::Context, world: &mut World) -> bool {
+        let mut changed = false;
+        egui::Window::new("World").show(ctx, |ui| changed = self.ui(ui, world));
+        changed
+    }
+
+    /// Adds the inspector to `ui`. Returns true if anything was edited.
+    pub fn ui(&mut self, ui: &mut Ui, world: &mut World) -> bool {
+        let mut changed = false;
+        CollapsingHeader::new("Solver")
+            .default_open(true)
+            .show(ui, |ui| changed |= solver_ui(ui, world));
+        ui.horizontal(|ui| {
+            ui.label("Filter by id:");
+            ui.text_edit_singleline(&mut self.filter);
+        });
+        CollapsingHeader::new(format!("Bodies ({})", world.body_count()))
+            .show(ui, |ui| changed |= self.bodies_ui(ui, world));
+        CollapsingHeader::new(format!("Joints ({})", world.joint_count()))
+            .show(ui, |ui| changed |= self.joints_ui(ui, world));
+        changed
+    }
+
+    fn shows(&self, id: usize) -> bool {
+        id.to_string().contains(self.filter.trim())
+    }
+
+    fn bodies_ui(&self, ui: &mut Ui, world: &mut World) -> bool {
+        let mut changed = false;
+        // Moved bodies go through `World::set_body_transform` afterwards
+        let mut moved = Vec::new();
+        for body in world.iter_bodies_mut() {
+            if !self.shows(body.id) {
+                continue;
+            }
+            let static_ = body.body_type() == BodyType::Static;
+            let title = format!("Body {} ({:?})", body.id, body.shape);
+            CollapsingHeader::new(title)
+                .id_salt(("body", body.id))
+                .show(ui, |ui| {
+                    Grid::new(("body grid", body.id))
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            let (mut position, mut rotation) = (body.position, body.rotation);
+                            let mut edited = vec2_row(ui, "Position", &mut position);
+                            edited |= row(ui, "Rotation", &mut rotation, 0.01);
+                            if edited {
+                                moved.push((body.handle(), position, rotation));
+                            }
+                            changed |= edited;
+                            changed |= vec2_row(ui, "Velocity", &mut body.velocity);
+                            changed |=
+                                row(ui, "Angular velocity", &mut body.angular_velocity, 0.01);
+                            changed |= row(ui, "Friction", &mut body.friction, 0.01);
+                            changed |= row(ui, "Restitution", &mut body.restitution, 0.01);
+                            ui.label("Mass");
+                            if static_ {
+                                ui.label("static");
+                            } else {
+                                let mut mass = body.mass;
+                                let response = ui.add(
+                                    DragValue::new(&mut mass).speed(0.1).range(0.001..=f32::MAX),
+                                );
+                                if response.changed() {
+                                    body.set_mass(mass);
+                                    changed = true;
+                                }
+                            }
+                            ui.end_row();
+                        });
+                });
+        }
+        for (handle, position, rotation) in moved {
+            world.set_body_transform(handle, position, rotation);
+        }
+        changed
+    }
+
+    fn joints_ui(&self, ui: &mut Ui, world: &mut World) -> bool {
+        let mut changed = false;
+        let handles: Vec<_> = world.iter_joints().map(|joint| joint.handle()).collect();
+        for handle in handles {
+            let Some(joint) = world.joint_mut(handle) else {
+                continue;
+            };
+            if !self.shows(handle.id()) {
+                continue;
+            }
+            let title = format!(
+                "Joint {} ({}, bodies {} and {})",
+                handle.id(),
+                kind_name(joint.kind()),
+                joint.body_1,
+                joint.body_2
+            );
+            CollapsingHeader::new(title)
+                .id_salt(("joint", handle.id()))
+                .show(ui, |ui| {
+                    Grid::new(("joint grid", handle.id()))
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            changed |= row(ui, "Softness", &mut joint.softness, 0.001);
+                            changed |= row(ui, "Bias factor", &mut joint.bias_factor, 0.01);
+                            if let JointKind::Revolute(revolute) = joint.kind_mut() {
+                                changed |= row(ui, "Motor speed", &mut revolute.motor_speed, 0.1);
+                                changed |= row(
+                                    ui,
+                                    "Max motor torque",
+                                    &mut revolute.max_motor_torque,
+                                    0.1,
+                                );
+                            }
+                            let force = joint.reaction_force();
+                            ui.label("Reaction force");
+                            ui.label(format!("({:.2}, {:.2})", force.x, force.y));
+                            ui.end_row();
+                        });
+                });
+        }
+        changed
+    }
+}
+
+fn solver_ui(ui: &mut Ui, world: &mut World) -> bool {
+    let mut changed = false;
+    let mut gravity = world.gravity();
+    Grid::new("solver grid").num_columns(2).show(ui, |ui| {
+        if vec2_row(ui, "Gravity", &mut gravity) {
+            world.set_gravity(gravity);
+            changed = true;
+        }
+        let context = &mut world.world_context;
+        changed |= row(ui, "Bias factor", &mut context.bias_factor, 0.01);
+        changed |= row(
+            ui,
+            "Allowed penetration",
+            &mut context.allowed_penetration,
+            0.001,
+        );
+        changed |= row(ui, "Max correction", &mut context.max_correction, 0.01);
+
+        ui.label("Solver");
+        ComboBox::from_id_salt("solver")
+            .selected_text(match context.solver {
+                Solver::Impulse => "Impulse",
+                Solver::Soft { .. } => "Soft",
+            })
+            .show_ui(ui, |ui| {
+                changed |= ui
+                    .selectable_value(&mut context.solver, Solver::Impulse, "Impulse")
+                    .changed();
+                changed |= ui
+                    .selectable_value(&mut context.solver, Solver::Soft { substeps: 4 }, "Soft")
+                    .changed();
+            });
+        ui.end_row();
+        if let Solver::Soft { substeps } = &mut context.solver {
+            ui.label("Substeps");
+            changed |= ui.add(DragValue::new(substeps).range(1..=64)).changed();
+            ui.end_row();
+        }
+
+        ui.label("Position solver");
+        ComboBox::from_id_salt("position solver")
+            .selected_text(match context.position_solver {
+                PositionSolver::Baumgarte => "Baumgarte",
+                PositionSolver::NonlinearGaussSeidel { .. } => "Nonlinear Gauss-Seidel",
+            })
+            .show_ui(ui, |ui| {
+                changed |= ui
+                    .selectable_value(
+                        &mut context.position_solver,
+                        PositionSolver::Baumgarte,
+                        "Baumgarte",
+                    )
+                    .changed();
+                changed |= ui
+                    .selectable_value(
+                        &mut context.position_solver,
+                        PositionSolver::NonlinearGaussSeidel { iterations: 4 },
+                        "Nonlinear Gauss-Seidel",
+                    )
+                    .changed();
+            });
+        ui.end_row();
+    });
+
+    let context = &mut world.world_context;
+    changed |= ui
+        .checkbox(&mut context.warm_starting, "Warm starting")
+        .changed();
+    changed |= ui
+        .checkbox(&mut context.position_correction, "Position correction")
+        .changed();
+    changed |= ui
+        .checkbox(&mut context.accumulate_impulse, "Accumulate impulses")
+        .changed();
+    changed
+}
+
+fn row(ui: &mut Ui, label: &str, value: &mut f32, speed: f32) -> bool {
+    ui.label(label);
+    let changed = ui.add(DragValue::new(value).speed(speed)).changed();
+    ui.end_row();
+    changed
+}
+
+fn vec2_row(ui: &mut Ui, label: &str, value: &mut Vec2) -> bool {
+    ui.label(label);
+    let changed = ui
+        .horizontal(|ui| {
+            let x = ui.add(DragValue::new(&mut value.x).speed(0.1)).changed();
+            let y = ui.add(DragValue::new(&mut value.y).speed(0.1)).changed();
+            x || y
+        })
+        .inner;
+    ui.end_row();
+    changed
+}
+
+fn kind_name(kind: &JointKind) -> &'static str {
+    match kind {
+        JointKind::Revolute(_) => "revolute",
+        JointKind::Weld(_) => "weld",
+        JointKind::Distance(_) => "distance",
+        JointKind::Pulley(_) => "pulley",
+        JointKind::Motor(_) => "motor",
+        JointKind::Friction(_) => "friction",
+        JointKind::Area(_) => "area",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::joint::JointBuilder;
+
+    #[test]
+    fn test_inspector() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let ground = world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 2.0);
+        let box_ = world.add_body(box_);
+        JointBuilder::revolute(ground, box_)
+            .build(&mut world)
+            .unwrap();
+
+        let mut inspector = WorldInspector::new();
+        let ctx = egui::Context::default();
+        for _ in 0..2 {
+            let _ = ctx.run(egui::RawInput::default(), |ctx| {
+                assert!(!inspector.show(ctx, &mut world));
+            });
+        }
+        assert_eq!(world.body(box_).unwrap().position, Vec2::new(0.0, 2.0));
+
+        inspector.filter = "nothing".into();
+        assert!(!inspector.shows(ground.id()));
+        inspector.filter = ground.id().to_string();
+        assert!(inspector.shows(ground.id()));
+    }
+}
@@ -1,11 +1,34 @@
-use crate::arbiter::ArbiterErrors;
+#[cfg(feature = "bitmap")]
+use crate::bitmap::BitmapErrors;
+use crate::body::{MassErrors, PolygonErrors};
+use crate::joint::JointErrors;
 use crate::math_utils::MathErrors;
+use crate::replay::ReplayErrors;
+#[cfg(feature = "scene")]
+use crate::scene::SceneErrors;
+#[cfg(feature = "snapshot")]
+use crate::world::SnapshotErrors;
 use std::fmt;
 
+/// Errors of the crate. Some variants only exist with the feature of their
+/// module, so matches need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Sylt2DErrors {
     MathOperations(MathErrors),
-    Arbiter(ArbiterErrors),
+    BodyNotFound(usize),
+    Polygon(PolygonErrors),
+    Mass(MassErrors),
+    Joint(JointErrors),
+    /// A change to a world a `Recorder` cannot record, see `Recorder::step`.
+    Replay(ReplayErrors),
+    /// A snapshot that could not be decoded, see `World::restore`.
+    #[cfg(feature = "snapshot")]
+    Snapshot(SnapshotErrors),
+    #[cfg(feature = "scene")]
+    Scene(SceneErrors),
+    #[cfg(feature = "bitmap")]
+    Bitmap(BitmapErrors),
 }
 
 impl fmt::Display for Sylt2DErrors {
@@ -16,7 +39,17 @@ impl fmt::Display for Sylt2DErrors {
                 "In performing math operations the following error occured: {}",
                 err
             ),
-            Sylt2DErrors::Arbiter(err)=> write!(f, "In updating and finding the contacts between objects the following error occured: {}", err),
+            Sylt2DErrors::BodyNotFound(id) => write!(f, "No body with id {} could be found.", id),
+            Sylt2DErrors::Polygon(err) => write!(f, "Invalid polygon: {}", err),
+            Sylt2DErrors::Mass(err) => write!(f, "Invalid mass: {}", err),
+            Sylt2DErrors::Joint(err) => write!(f, "Invalid joint: {}", err),
+            Sylt2DErrors::Replay(err) => write!(f, "Cannot record: {}", err),
+            #[cfg(feature = "snapshot")]
+            Sylt2DErrors::Snapshot(err) => write!(f, "Invalid snapshot: {}", err),
+            #[cfg(feature = "scene")]
+            Sylt2DErrors::Scene(err) => write!(f, "Invalid scene: {}", err),
+            #[cfg(feature = "bitmap")]
+            Sylt2DErrors::Bitmap(err) => write!(f, "Invalid bitmap: {}", err),
         }
     }
 }
@@ -29,9 +62,48 @@ impl From<MathErrors> for Sylt2DErrors {
     }
 }
 
-impl From<ArbiterErrors> for Sylt2DErrors {
-    fn from(value: ArbiterErrors) -> Self {
-        Sylt2DErrors::Arbiter(value)
+impl From<PolygonErrors> for Sylt2DErrors {
+    fn from(value: PolygonErrors) -> Self {
+        Sylt2DErrors::Polygon(value)
+    }
+}
+
+impl From<MassErrors> for Sylt2DErrors {
+    fn from(value: MassErrors) -> Self {
+        Sylt2DErrors::Mass(value)
+    }
+}
+
+impl From<JointErrors> for Sylt2DErrors {
+    fn from(value: JointErrors) -> Self {
+        Sylt2DErrors::Joint(value)
+    }
+}
+
+impl From<ReplayErrors> for Sylt2DErrors {
+    fn from(value: ReplayErrors) -> Self {
+        Sylt2DErrors::Replay(value)
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl From<SnapshotErrors> for Sylt2DErrors {
+    fn from(value: SnapshotErrors) -> Self {
+        Sylt2DErrors::Snapshot(value)
+    }
+}
+
+#[cfg(feature = "scene")]
+impl From<SceneErrors> for Sylt2DErrors {
+    fn from(value: SceneErrors) -> Self {
+        Sylt2DErrors::Scene(value)
+    }
+}
+
+#[cfg(feature = "bitmap")]
+impl From<BitmapErrors> for Sylt2DErrors {
+    fn from(value: BitmapErrors) -> Self {
+        Sylt2DErrors::Bitmap(value)
     }
 }
 
@@ -0,0 +1,106 @@
+use crate::world::World;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Header of the rows written by `CsvRecorder`.
+pub const CSV_HEADER: &str = "step,time,body,x,y,rotation,vx,vy,angular_velocity,contacts";
+
+/// Writes the state of a world after every step as CSV, one row per body, for
+/// plotting or analysis elsewhere, e.g. while tuning the solver.
+///
+/// The columns are `CSV_HEADER`. `contacts` is the number of contact points of
+/// the body.
+#[derive(Debug)]
+pub struct CsvRecorder<W: Write> {
+    writer: W,
+    step: usize,
+}
+
+impl<W: Write> CsvRecorder<W> {
+    /// Starts recording into `writer`, e.g. a `BufWriter` around a file, by
+    /// writing the header.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        Ok(Self { writer, step: 0 })
+    }
+
+    /// Writes the rows of the current state of the world. Call it after each
+    /// step.
+    pub fn record(&mut self, world: &World) -> io::Result<()> {
+        let mut contacts: HashMap<usize, usize> = HashMap::new();
+        for (key, arbiter) in world.iter_arbiters() {
+            let (id_1, id_2) = key.ids();
+            let points = arbiter.manifold.points.len();
+            *contacts.entry(id_1).or_default() += points;
+            *contacts.entry(id_2).or_default() += points;
+        }
+        let time = world.world_context.time;
+        for body in world.iter_bodies() {
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{},{}",
+                self.step,
+                time,
+                body.id,
+                body.position.x,
+                body.position.y,
+                body.rotation,
+                body.velocity.x,
+                body.velocity.y,
+                body.angular_velocity,
+                contacts.get(&body.id).copied().unwrap_or(0)
+            )?;
+        }
+        self.step += 1;
+        Ok(())
+    }
+
+    /// Returns the number of recorded states.
+    pub fn step_count(&self) -> usize {
+        self.step
+    }
+
+    /// Flushes and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::math_utils::Vec2;
+
+    #[test]
+    fn test_csv_recorder() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        world.add_body(ground);
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 0.5);
+        let box_ = world.add_body(box_);
+
+        let mut recorder = CsvRecorder::new(Vec::new()).unwrap();
+        for _ in 0..10 {
+            world.step(1.0 / 60.0).unwrap();
+            recorder.record(&world).unwrap();
+        }
+        assert_eq!(recorder.step_count(), 10);
+        let csv = String::from_utf8(recorder.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines.len(), 1 + 2 * 10);
+
+        let last: Vec<&str> = lines[20].split(',').collect();
+        assert_eq!(last.len(), CSV_HEADER.split(',').count());
+        assert_eq!(last[0], "9");
+        assert_eq!(last[2], box_.id().to_string());
+        let y: f32 = last[4].parse().unwrap();
+        assert_eq!(y, world.body(box_).unwrap().position.y);
+        // Resting on the ground on two corners
+        assert_eq!(last[9], "2");
+    }
+}
@@ -0,0 +1,173 @@
+use crate::debug_draw::{self, Color, DebugDraw, DebugDrawOptions};
+use crate::math_utils::Vec2;
+use crate::world::World;
+use ggez::context::Has;
+use ggez::graphics::{self, Canvas, DrawMode, DrawParam, GraphicsContext, Mesh, MeshBuilder};
+use ggez::{Context, GameError, GameResult};
+
+/// Maps world space to the pixels of the screen, y pointing up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// World point at the center of the screen.
+    pub center: Vec2,
+    /// Pixels per world unit.
+    pub scale: f32,
+}
+
+impl Camera {
+    pub fn new(center: Vec2, scale: f32) -> Self {
+        Self { center, scale }
+    }
+
+    /// Returns the pixel of a screen of `screen` pixels the world point is at.
+    pub fn to_screen(&self, point: Vec2, screen: (f32, f32)) -> [f32; 2] {
+        [
+            0.5 * screen.0 + (point.x - self.center.x) * self.scale,
+            0.5 * screen.1 - (point.y - self.center.y) * self.scale,
+        ]
+    }
+}
+
+/// Collects what `debug_draw::draw_world` draws into one ggez mesh.
+#[derive(Debug)]
+pub struct GgezDraw {
+    builder: MeshBuilder,
+    camera: Camera,
+    screen: (f32, f32),
+    empty: bool,
+    // The first error of the mesh builder, the drawing goes on without the
+    // shape
+    error: Option<GameError>,
+}
+
+impl GgezDraw {
+    pub fn new(camera: Camera, screen: (f32, f32)) -> Self {
+        Self {
+            builder: MeshBuilder::new(),
+            camera,
+            screen,
+            empty: true,
+            error: None,
+        }
+    }
+
+    /// Returns the mesh of everything drawn, None if nothing was drawn.
+    pub fn mesh(self, gfx: &impl Has<GraphicsContext>) -> GameResult<Option<Mesh>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        Ok((!self.empty).then(|| Mesh::from_data(gfx, self.builder.build())))
+    }
+
+    fn point(&self, point: Vec2) -> [f32; 2] {
+        self.camera.to_screen(point, self.screen)
+    }
+
+    fn record(&mut self, result: GameResult<()>) {
+        match result {
+            Ok(()) => self.empty = false,
+            Err(error) => {
+                self.error.get_or_insert(error);
+            }
+        }
+    }
+}
+
+impl DebugDraw for GgezDraw {
+    fn polygon(&mut self, vertices: &[Vec2], color: Color) {
+        let points: Vec<[f32; 2]> = vertices.iter().map(|&vertex| self.point(vertex)).collect();
+        let result = self
+            .builder
+            .polygon(DrawMode::fill(), &points, color.into())
+            .map(|_| ());
+        self.record(result);
+    }
+
+    fn circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        let center = self.point(center);
+        let radius = radius * self.camera.scale;
+        let result = self
+            .builder
+            .circle(DrawMode::fill(), center, radius, 0.1, color.into())
+            .map(|_| ());
+        self.record(result);
+    }
+
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        // Degenerate lines make the tessellation fail
+        if start == end {
+            return;
+        }
+        let points = [self.point(start), self.point(end)];
+        let width = thickness * self.camera.scale;
+        let result = self.builder.line(&points, width, color.into()).map(|_| ());
+        self.record(result);
+    }
+}
+
+/// Draws the world on top of the current frame with the default options, in
+/// a render pass of its own. Call it after finishing the canvas of the game.
+pub fn draw_world(ctx: &mut Context, world: &World, camera: Camera) -> GameResult {
+    let mut canvas = Canvas::from_frame(ctx, None);
+    draw_world_on(
+        ctx,
+        &mut canvas,
+        world,
+        camera,
+        &DebugDrawOptions::default(),
+    )?;
+    canvas.finish(ctx)
+}
+
+/// Draws the world on `canvas`, see `debug_draw::draw_world`.
+pub fn draw_world_on(
+    ctx: &Context,
+    canvas: &mut Canvas,
+    world: &World,
+    camera: Camera,
+    options: &DebugDrawOptions,
+) -> GameResult {
+    let screen = canvas
+        .screen_coordinates()
+        .map_or_else(|| ctx.gfx.drawable_size(), |rect| (rect.w, rect.h));
+    let mut draw = GgezDraw::new(camera, screen);
+    debug_draw::draw_world(world, &mut draw, options);
+    if let Some(mesh) = draw.mesh(ctx)? {
+        canvas.draw(&mesh, DrawParam::default());
+    }
+    Ok(())
+}
+
+impl From<Color> for graphics::Color {
+    fn from(color: Color) -> Self {
+        graphics::Color::new(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+
+    #[test]
+    fn test_ggez_draw() {
+        let camera = Camera::new(Vec2::new(1.0, 2.0), 10.0);
+        assert_eq!(
+            camera.to_screen(Vec2::new(1.0, 2.0), (800.0, 600.0)),
+            [400.0, 300.0]
+        );
+        assert_eq!(
+            camera.to_screen(Vec2::new(2.0, 3.0), (800.0, 600.0)),
+            [410.0, 290.0]
+        );
+
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        world.add_body(Body::new_circle(0.5, 1.0));
+        let mut draw = GgezDraw::new(camera, (800.0, 600.0));
+        debug_draw::draw_world(&world, &mut draw, &DebugDrawOptions::default());
+        assert!(draw.error.is_none());
+        assert!(!draw.empty);
+        assert!(!draw.builder.build().vertices.is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::body::Body;
+use crate::scratch::Scratch;
+use std::ops::Range;
+
+const NO_ISLAND: usize = usize::MAX;
+
+// Ranges of one island in the grouped lists of `Islands`.
+#[derive(Debug, Default, Clone)]
+struct Island {
+    bodies: Range<usize>,
+    contacts: Range<usize>,
+    joints: Range<usize>,
+}
+
+/// Groups of bodies connected by contacts or joints, which the solver handles
+/// independently of each other.
+///
+/// Static bodies do not connect islands, so two stacks on the same ground are
+/// separate islands. Bodies, contacts and joints are referred to by their
+/// position in the lists passed to `build`, and keep that order within an island.
+#[derive(Debug, Default)]
+pub struct Islands {
+    parents: Vec<usize>,
+    island_of: Vec<usize>,
+    bodies: Vec<usize>,
+    contacts: Vec<usize>,
+    joints: Vec<usize>,
+    islands: Vec<Island>,
+}
+
+impl Islands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            // Path halving
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        // The lower index becomes the root, keeping the islands in body order
+        self.parents[root_a.max(root_b)] = root_a.min(root_b);
+    }
+
+    // Island of a pair of bodies, given the island of each body.
+    fn pair_island(&self, a: usize, b: usize) -> usize {
+        match self.island_of[a] {
+            NO_ISLAND => self.island_of[b],
+            island => island,
+        }
+    }
+
+    /// Partitions the bodies given the storage indices of the body pairs of the
+    /// contacts and joints, taking the temporaries from `scratch`. `links` are
+    /// further pairs kept in one island without a constraint of their own, e.g.
+    /// the ring of an area joint.
+    pub fn build(
+        &mut self,
+        bodies: &[Body],
+        contacts: &[(usize, usize)],
+        joints: &[(usize, usize)],
+        links: &[(usize, usize)],
+        scratch: &mut Scratch,
+    ) {
+        self.parents.clear();
+        self.parents.extend(0..bodies.len());
+        for &(a, b) in contacts.iter().chain(joints).chain(links) {
+            if bodies[a].is_dynamic() && bodies[b].is_dynamic() {
+                self.union(a, b);
+            }
+        }
+
+        self.islands.clear();
+        self.island_of.clear();
+        self.island_of.resize(bodies.len(), NO_ISLAND);
+        for (index, body) in bodies.iter().enumerate() {
+            if !body.is_dynamic() {
+                continue;
+            }
+            let root = self.find(index);
+            if root == index {
+                self.island_of[index] = self.islands.len();
+                self.islands.push(Island::default());
+            } else {
+                self.island_of[index] = self.island_of[root];
+            }
+        }
+
+        // Every joint adds at most one island
+        let [contact_islands, joint_islands, ends] = scratch.alloc_indices([
+            contacts.len(),
+            joints.len(),
+            self.islands.len() + joints.len(),
+        ]);
+        for (island, &(a, b)) in contact_islands.iter_mut().zip(contacts) {
+            *island = self.pair_island(a, b);
+        }
+        for (island, &(a, b)) in joint_islands.iter_mut().zip(joints) {
+            *island = self.pair_island(a, b);
+            if *island == NO_ISLAND {
+                // Joints between static bodies are still solved, on their own
+                *island = self.islands.len();
+                self.islands.push(Island::default());
+            }
+        }
+
+        let ends = &mut ends[..self.islands.len()];
+        group(&self.island_of, &mut self.bodies, ends);
+        for (island, range) in self.islands.iter_mut().zip(ranges(ends)) {
+            island.bodies = range;
+        }
+        group(contact_islands, &mut self.contacts, ends);
+        for (island, range) in self.islands.iter_mut().zip(ranges(ends)) {
+            island.contacts = range;
+        }
+        group(joint_islands, &mut self.joints, ends);
+        for (island, range) in self.islands.iter_mut().zip(ranges(ends)) {
+            island.joints = range;
+        }
+    }
+
+    /// Returns the number of islands.
+    pub fn len(&self) -> usize {
+        self.islands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.islands.is_empty()
+    }
+
+    /// Returns the dynamic bodies of the island.
+    pub fn bodies(&self, island: usize) -> &[usize] {
+        &self.bodies[self.islands[island].bodies.clone()]
+    }
+
+    /// Returns the contacts of the island.
+    pub fn contacts(&self, island: usize) -> &[usize] {
+        &self.contacts[self.islands[island].contacts.clone()]
+    }
+
+    /// Returns the joints of the island.
+    pub fn joints(&self, island: usize) -> &[usize] {
+        &self.joints[self.islands[island].joints.clone()]
+    }
+}
+
+// Counting sort of the positions by island, leaving the end of each island in
+// `grouped` in `ends`, which holds one entry per island.
+fn group(islands: &[usize], grouped: &mut Vec<usize>, ends: &mut [usize]) {
+    ends.fill(0);
+    for &island in islands.iter().filter(|&&island| island != NO_ISLAND) {
+        ends[island] += 1;
+    }
+    // Starts of the islands, which become their ends while filling them
+    let mut start = 0;
+    for end in ends.iter_mut() {
+        let size = *end;
+        *end = start;
+        start += size;
+    }
+    grouped.clear();
+    grouped.resize(start, 0);
+    for (position, &island) in islands.iter().enumerate() {
+        if island != NO_ISLAND {
+            grouped[ends[island]] = position;
+            ends[island] += 1;
+        }
+    }
+}
+
+fn ranges(ends: &[usize]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let starts = std::iter::once(0).chain(ends.iter().copied());
+    starts
+        .zip(ends.iter().copied())
+        .map(|(start, end)| start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math_utils::Vec2;
+
+    #[test]
+    fn test_islands() {
+        let ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        let block = || Body::new(Vec2::new(1.0, 1.0), 1.0);
+        let bodies = vec![block(), ground, block(), block(), block(), block()];
+
+        // Two stacks on the ground, a pendulum and a lone block
+        let contacts = [(1, 0), (0, 2), (1, 3)];
+        let joints = [(4, 1)];
+        let mut islands = Islands::new();
+        islands.build(&bodies, &contacts, &joints, &[], &mut Scratch::new());
+        assert_eq!(islands.len(), 4);
+        assert_eq!(islands.bodies(0), &[0, 2]);
+        assert_eq!(islands.contacts(0), &[0, 1]);
+        assert_eq!(islands.bodies(1), &[3]);
+        assert_eq!(islands.contacts(1), &[2]);
+        assert_eq!(islands.joints(2), &[0]);
+        assert_eq!(islands.bodies(3), &[5]);
+        assert!(islands.contacts(3).is_empty());
+
+        // Joining the stacks
+        islands.build(
+            &bodies,
+            &[(2, 3), (0, 2)],
+            &joints,
+            &[],
+            &mut Scratch::new(),
+        );
+        assert_eq!(islands.len(), 3);
+        assert_eq!(islands.bodies(0), &[0, 2, 3]);
+        assert_eq!(islands.contacts(0), &[0, 1]);
+
+        // Linking the pendulum to the lone block
+        islands.build(&bodies, &contacts, &joints, &[(4, 5)], &mut Scratch::new());
+        assert_eq!(islands.len(), 3);
+        assert_eq!(islands.bodies(2), &[4, 5]);
+        assert_eq!(islands.joints(2), &[0]);
+    }
+}
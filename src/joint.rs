@@ -1,47 +1,530 @@
 use crate::errors::Sylt2DErrors;
+use crate::pipeline::{offset, pair_mut};
+use crate::solver::{PositionSolver, Softness};
 use crate::{
-    body::Body,
+    body::{Body, BodyHandle},
     math_utils::{Cross, Mat2x2, Vec2},
     world::{World, WorldContext},
 };
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Default)]
+#[derive(Debug, PartialEq)]
+pub enum JointErrors {
+    /// Limits were set on a joint other than a revolute joint.
+    LimitsNotSupported,
+    /// A motor was set on a joint other than a revolute joint.
+    MotorNotSupported,
+}
+
+impl fmt::Display for JointErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JointErrors::LimitsNotSupported => write!(f, "Only revolute joints have limits."),
+            JointErrors::MotorNotSupported => write!(f, "Only revolute joints have a motor."),
+        }
+    }
+}
+
+impl std::error::Error for JointErrors {}
+
+/// Constraint a joint keeps between the anchors of its bodies, with the
+/// parameters and the solver state of each kind, see `Joint::kind`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum JointKind {
+    /// Pins the anchors together, the bodies turn freely about them.
+    Revolute(RevoluteJoint),
+    /// Keeps the anchors apart, like a massless rod between them.
+    Distance(DistanceJoint),
+    /// Pins the anchors together and keeps the angle between the bodies, gluing
+    /// them into one. `Joint::softness` makes the glue give way a little.
+    Weld(WeldJoint),
+    /// Hangs the anchors from ropes over two fixed points in the world.
+    Pulley(PulleyJoint),
+    /// Drives the second body towards an offset from the first. Collisions
+    /// still push the body around.
+    Motor(MotorJoint),
+    /// Resists the motion of the second body relative to the first, like
+    /// friction against the ground in a top down game.
+    Friction(FrictionJoint),
+    /// Keeps the area of the polygon through the centers of a ring of bodies,
+    /// like the air in a balloon. Join the neighbours with distance joints as
+    /// well to get a squishy blob out of rigid nodes.
+    Area(AreaJoint),
+}
+
+impl Default for JointKind {
+    fn default() -> Self {
+        JointKind::Revolute(RevoluteJoint::default())
+    }
+}
+
+impl JointKind {
+    // Accumulated impulse turning the second body relative to the first, other
+    // than through the anchors.
+    fn angular_impulse(&self) -> f32 {
+        match self {
+            JointKind::Revolute(revolute) => {
+                let (lower, upper) = revolute.limit_impulses;
+                revolute.motor_impulse + lower - upper
+            }
+            JointKind::Weld(weld) => weld.angular_impulse,
+            JointKind::Motor(motor) => motor.angular_impulse,
+            JointKind::Friction(friction) => friction.angular_impulse,
+            JointKind::Distance(_) | JointKind::Pulley(_) | JointKind::Area(_) => 0.0,
+        }
+    }
+
+    fn reset_impulses(&mut self) {
+        match self {
+            JointKind::Revolute(revolute) => {
+                revolute.limit_impulses = (0.0, 0.0);
+                revolute.motor_impulse = 0.0;
+            }
+            JointKind::Weld(weld) => weld.angular_impulse = 0.0,
+            JointKind::Motor(motor) => motor.angular_impulse = 0.0,
+            JointKind::Friction(friction) => friction.angular_impulse = 0.0,
+            JointKind::Pulley(pulley) => pulley.impulse = 0.0,
+            JointKind::Area(area) => area.impulse = 0.0,
+            JointKind::Distance(_) => {}
+        }
+    }
+}
+
+/// Impulses accumulated by the solver for a joint, see
+/// `Joint::accumulated_impulse`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JointImpulse {
+    /// Impulse on the anchors.
+    pub linear: Vec2,
+    /// Impulses of the lower and upper limit of a revolute joint.
+    pub limits: (f32, f32),
+    /// Impulse of the motor of a revolute joint, or the one keeping the angle
+    /// of a weld, motor or friction joint.
+    pub angular: f32,
+    /// Impulse along the ropes of a pulley joint, or the one keeping the area
+    /// of an area joint.
+    pub axial: f32,
+}
+
+/// Parameters and solver state of a revolute joint, see `JointKind::Revolute`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RevoluteJoint {
+    /// Lowest and highest angle of the second body relative to the first, see
+    /// `Joint::angle`.
+    pub limits: Option<(f32, f32)>,
+    /// Angular velocity of the second body relative to the first the motor
+    /// drives towards.
+    pub motor_speed: f32,
+    /// Largest torque of the motor, zero turns the motor off.
+    pub max_motor_torque: f32,
+    // Accumulated impulses and biases of the lower and upper limit
+    limit_impulses: (f32, f32),
+    limit_biases: (f32, f32),
+    motor_impulse: f32,
+    max_motor_impulse: f32,
+}
+
+impl RevoluteJoint {
+    fn has_motor(&self) -> bool {
+        self.max_motor_torque > 0.0
+    }
+
+    // Drives the relative angular velocity towards the motor speed with at most
+    // `max_impulse`.
+    fn solve_motor(
+        &mut self,
+        angular_mass: f32,
+        body_1: &mut Body,
+        body_2: &mut Body,
+        max_impulse: f32,
+    ) {
+        let w = body_2.angular_velocity - body_1.angular_velocity - self.motor_speed;
+        let accumulated = self.motor_impulse;
+        self.motor_impulse = f32::clamp(accumulated - angular_mass * w, -max_impulse, max_impulse);
+        apply_angular_impulse(body_1, body_2, self.motor_impulse - accumulated);
+    }
+
+    // Applies the impulses of the lower and upper limit, given the bias and the
+    // softness of each.
+    fn solve_limits(
+        &mut self,
+        angular_mass: f32,
+        body_1: &mut Body,
+        body_2: &mut Body,
+        biases: (f32, f32),
+        softness: (Softness, Softness),
+    ) {
+        let (lower, upper) = self.limit_impulses;
+        let lower = solve_limit(
+            angular_mass,
+            body_1,
+            body_2,
+            lower,
+            1.0,
+            biases.0,
+            softness.0,
+        );
+        let upper = solve_limit(
+            angular_mass,
+            body_1,
+            body_2,
+            upper,
+            -1.0,
+            biases.1,
+            softness.1,
+        );
+        self.limit_impulses = (lower, upper);
+    }
+}
+
+/// Parameters and solver state of a distance joint, see `JointKind::Distance`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DistanceJoint {
+    /// Distance kept between the anchors.
+    pub length: f32,
+    // Direction from the first to the second anchor and the mass along it
+    axis: Vec2,
+    axial_mass: f32,
+}
+
+impl DistanceJoint {
+    // Updates the axis between the anchors and the mass along it.
+    fn update_axis(&mut self, body_1: &Body, body_2: &Body, arms: (Vec2, Vec2), softness: f32) {
+        let (r1, r2) = arms;
+        let d = body_2.position + r2 - body_1.position - r1;
+        let length = d.length();
+        self.axis = if length > f32::EPSILON {
+            d * (1.0 / length)
+        } else {
+            Vec2::new(0.0, 0.0)
+        };
+        let rn1 = r1.cross(self.axis);
+        let rn2 = r2.cross(self.axis);
+        let k = body_1.inv_mass
+            + body_2.inv_mass
+            + body_1.inv_moi * rn1 * rn1
+            + body_2.inv_moi * rn2 * rn2
+            + softness;
+        self.axial_mass = if k > 0.0 { 1.0 / k } else { 0.0 };
+    }
+}
+
+/// Solver state of a weld joint, see `JointKind::Weld`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeldJoint {
+    // Accumulated impulse and bias keeping the angle
+    angular_impulse: f32,
+    angular_bias: f32,
+}
+
+impl WeldJoint {
+    // Keeps the angle, see `solve_limit` for the arguments.
+    fn solve_angle(
+        &mut self,
+        angular_mass: f32,
+        body_1: &mut Body,
+        body_2: &mut Body,
+        bias: f32,
+        softness: Softness,
+    ) {
+        let w = body_2.angular_velocity - body_1.angular_velocity;
+        let impulse = -angular_mass * softness.mass_scale * (w + bias)
+            - softness.impulse_scale * self.angular_impulse;
+        self.angular_impulse += impulse;
+        apply_angular_impulse(body_1, body_2, impulse);
+    }
+}
+
+/// Parameters and solver state of a pulley joint, see `JointKind::Pulley`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PulleyJoint {
+    /// Fixed points in the world the ropes run over.
+    pub ground_anchors: (Vec2, Vec2),
+    /// The length of the first rope plus `ratio` times the second stays
+    /// `length`.
+    pub ratio: f32,
+    pub length: f32,
+    // Directions from the ground anchors to the anchors and the mass along them
+    axes: (Vec2, Vec2),
+    axial_mass: f32,
+    impulse: f32,
+    bias: f32,
+    // Length the ropes are too short by when the soft solver was prepared
+    error: f32,
+}
+
+impl PulleyJoint {
+    // Updates the directions of the ropes and the mass along them.
+    fn update_axes(&mut self, body_1: &Body, body_2: &Body, arms: (Vec2, Vec2)) {
+        let (r1, r2) = arms;
+        let direction = |rope: Vec2| {
+            let length = rope.length();
+            if length > f32::EPSILON {
+                rope * (1.0 / length)
+            } else {
+                Vec2::new(0.0, 0.0)
+            }
+        };
+        let u1 = direction(body_1.position + r1 - self.ground_anchors.0);
+        let u2 = direction(body_2.position + r2 - self.ground_anchors.1);
+        self.axes = (u1, u2);
+        let rn1 = r1.cross(u1);
+        let rn2 = r2.cross(u2);
+        let k = body_1.inv_mass
+            + body_1.inv_moi * rn1 * rn1
+            + self.ratio * self.ratio * (body_2.inv_mass + body_2.inv_moi * rn2 * rn2);
+        self.axial_mass = if k > 0.0 { 1.0 / k } else { 0.0 };
+    }
+
+    // How much shorter the ropes are than `length`.
+    fn length_error(&self, body_1: &Body, body_2: &Body, arms: (Vec2, Vec2)) -> f32 {
+        let length_1 = (body_1.position + arms.0 - self.ground_anchors.0).length();
+        let length_2 = (body_2.position + arms.1 - self.ground_anchors.1).length();
+        self.length - length_1 - self.ratio * length_2
+    }
+
+    // Applies `impulse` along the ropes, pulling the anchors towards the ground
+    // anchors.
+    fn apply_impulse(
+        &self,
+        body_1: &mut Body,
+        body_2: &mut Body,
+        arms: (Vec2, Vec2),
+        impulse: f32,
+    ) {
+        let p1 = self.axes.0 * -impulse;
+        let p2 = self.axes.1 * (-self.ratio * impulse);
+        body_1.velocity = body_1.velocity + p1 * body_1.inv_mass;
+        body_1.angular_velocity += body_1.inv_moi * arms.0.cross(p1);
+        body_2.velocity = body_2.velocity + p2 * body_2.inv_mass;
+        body_2.angular_velocity += body_2.inv_moi * arms.1.cross(p2);
+    }
+
+    // Solves the ropes, see `solve_limit` for the arguments.
+    fn solve(
+        &mut self,
+        body_1: &mut Body,
+        body_2: &mut Body,
+        arms: (Vec2, Vec2),
+        bias: f32,
+        softness: Softness,
+    ) {
+        let v1 = body_1.velocity + body_1.angular_velocity.cross(arms.0);
+        let v2 = body_2.velocity + body_2.angular_velocity.cross(arms.1);
+        let c_dot = -self.axes.0.dot(v1) - self.ratio * self.axes.1.dot(v2);
+        let impulse = -self.axial_mass * softness.mass_scale * (c_dot + bias)
+            - softness.impulse_scale * self.impulse;
+        self.impulse += impulse;
+        self.apply_impulse(body_1, body_2, arms, impulse);
+    }
+}
+
+/// Parameters and solver state of a motor joint, see `JointKind::Motor`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MotorJoint {
+    /// Place of the second body in the frame of the first to drive towards.
+    pub linear_offset: Vec2,
+    /// Angle of the second body to drive towards, see `Joint::angle`.
+    pub angular_offset: f32,
+    pub max_force: f32,
+    pub max_torque: f32,
+    /// Fraction of the offset to close per step.
+    pub correction_factor: f32,
+    // Accumulated impulse and bias turning the second body
+    angular_impulse: f32,
+    angular_bias: f32,
+}
+
+/// Parameters and solver state of a friction joint, see `JointKind::Friction`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrictionJoint {
+    pub max_force: f32,
+    pub max_torque: f32,
+    angular_impulse: f32,
+}
+
+/// Parameters and solver state of an area joint, see `JointKind::Area`.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AreaJoint {
+    /// The ring, in order around the polygon.
+    pub bodies: Vec<BodyHandle>,
+    /// Signed area to keep, positive when the ring goes counterclockwise.
+    pub target_area: f32,
+    impulse: f32,
+    bias: f32,
+    // Inverse of the mass of the area
+    mass: f32,
+    // Area the ring is too large by when the soft solver was prepared
+    error: f32,
+    // Storage indices of the ring and the gradient of the area by the position
+    // of each body, updated on every step into the same buffers
+    #[cfg_attr(feature = "serde", serde(skip))]
+    indices: Vec<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    gradients: Vec<Vec2>,
+}
+
+impl AreaJoint {
+    /// Returns the signed area of the polygon through `points`.
+    pub fn area(points: &[Vec2]) -> f32 {
+        let next = points.iter().cycle().skip(1);
+        0.5 * points
+            .iter()
+            .zip(next)
+            .map(|(point, next)| point.cross(*next))
+            .sum::<f32>()
+    }
+
+    // Signed area of the polygon through the bodies of the ring.
+    fn ring_area(&self, bodies: &[Body]) -> f32 {
+        let count = self.indices.len();
+        0.5 * (0..count)
+            .map(|i| {
+                let next = self.indices[(i + 1) % count];
+                bodies[self.indices[i]]
+                    .position
+                    .cross(bodies[next].position)
+            })
+            .sum::<f32>()
+    }
+
+    // Updates the gradients and the mass of the area for the current positions.
+    // Rings of fewer than three bodies have no area to keep.
+    fn update_mass(&mut self, bodies: &[Body], softness: f32) {
+        let count = self.indices.len();
+        let indices = &self.indices;
+        self.gradients.clear();
+        if count >= 3 {
+            self.gradients.extend((0..count).map(|i| {
+                let next = bodies[indices[(i + 1) % count]].position;
+                let previous = bodies[indices[(i + count - 1) % count]].position;
+                (next - previous).cross(0.5)
+            }));
+        }
+        let k = indices
+            .iter()
+            .zip(self.gradients.iter())
+            .map(|(&index, gradient)| bodies[index].inv_mass * gradient.dot(*gradient))
+            .sum::<f32>()
+            + softness;
+        self.mass = if k > 0.0 && count >= 3 { 1.0 / k } else { 0.0 };
+    }
+
+    // Rate the area changes at with the current velocities.
+    fn area_velocity(&self, bodies: &[Body]) -> f32 {
+        self.indices
+            .iter()
+            .zip(self.gradients.iter())
+            .map(|(&index, gradient)| gradient.dot(bodies[index].velocity))
+            .sum()
+    }
+
+    // Pushes the bodies along the gradients, growing the area for a positive
+    // `impulse`.
+    fn apply_impulse(&self, bodies: &mut [Body], impulse: f32) {
+        for (&index, &gradient) in self.indices.iter().zip(self.gradients.iter()) {
+            let body = &mut bodies[index];
+            body.velocity = body.velocity + gradient * (impulse * body.inv_mass);
+        }
+    }
+}
+
+/// Refers to a joint in a world, see `World::add_joint`.
+///
+/// Made from the joint id, which is never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "python", pyo3::pyclass(frozen, eq, hash))]
+pub struct JointHandle(usize);
+
+#[cfg_attr(feature = "python", pyo3::pymethods)]
+impl JointHandle {
+    /// Returns the id of the joint.
+    pub fn id(&self) -> usize {
+        self.0
+    }
+}
+
+static JOINT_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+// See `body::reserve_body_id`.
+pub(crate) fn reserve_joint_id(id: usize) {
+    JOINT_ID_COUNTER.fetch_max(id + 1, Ordering::Relaxed);
+}
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Joint {
+    id: usize,
+    kind: JointKind,
     p: Vec2, // accumulated impuls
     bias: Vec2,
     r1: Vec2,
     r2: Vec2,
     m: Mat2x2,
+    // Distance between the anchors when the soft solver was prepared
+    separation: Vec2,
+    // Relative angle of the bodies when the joint was made, and the mass of
+    // their relative rotation
+    reference_angle: f32,
+    angular_mass: f32,
+    // Angle when the soft solver was prepared
+    angle: f32,
+    // Inverse of the time the accumulated impulses were applied over, the
+    // substep for the soft solver
+    inv_dt: f32,
+    // Storage indices of the two bodies for the current step
+    #[cfg_attr(feature = "serde", serde(skip))]
+    indices: (usize, usize),
     pub bias_factor: f32,
     pub softness: f32,
     pub local_anchor_1: Vec2,
     pub local_anchor_2: Vec2,
-    pub body_1: Rc<RefCell<Body>>,
-    pub body_2: Rc<RefCell<Body>>,
+    /// Id of the first body.
+    pub body_1: usize,
+    /// Id of the second body.
+    pub body_2: usize,
 }
 
 impl Joint {
-    pub fn new(body_1: Body, body_2: Body, anchor: Vec2, world: &World) -> Self {
-        let body_1_rc = world
-            .bodies
-            .iter()
-            .find(|body| body.borrow().id == body_1.id)
-            .expect("couldn't find body 1 in world bodies.");
-        let body_2_rc = world
-            .bodies
-            .iter()
-            .find(|body| body.borrow().id == body_2.id)
-            .expect("couldn't find body 2 in world bodies.");
-        let rot_trans_1 = Mat2x2::new_from_angle(body_1_rc.borrow().rotation).transpose();
-        let rot_trans_2 = Mat2x2::new_from_angle(body_2_rc.borrow().rotation).transpose();
-        let local_anchor_1 = rot_trans_1 * (anchor - body_1_rc.borrow().position);
-        let local_anchor_2 = rot_trans_2 * (anchor - body_2_rc.borrow().position);
+    /// Returns a joint connecting the bodies of the world with the handles
+    /// `body_1` and `body_2` at the world point `anchor`, or an error if one of
+    /// them is not in the world.
+    pub fn new(
+        body_1: BodyHandle,
+        body_2: BodyHandle,
+        anchor: Vec2,
+        world: &World,
+    ) -> Result<Self, Sylt2DErrors> {
+        let find = |handle: BodyHandle| {
+            world
+                .body(handle)
+                .ok_or(Sylt2DErrors::BodyNotFound(handle.id()))
+        };
+        Ok(Self::from_bodies(find(body_1)?, find(body_2)?, anchor))
+    }
+
+    /// Returns a joint connecting the two bodies at the world point `anchor`.
+    pub fn from_bodies(body_1: &Body, body_2: &Body, anchor: Vec2) -> Self {
+        let local_anchor_1 = body_1.world_to_local(anchor);
+        let local_anchor_2 = body_2.world_to_local(anchor);
 
         Self {
-            body_1: body_1_rc.clone(),
-            body_2: body_2_rc.clone(),
+            id: JOINT_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            kind: JointKind::default(),
+            body_1: body_1.id,
+            body_2: body_2.id,
             local_anchor_1,
             local_anchor_2,
             softness: 0.0,
@@ -51,21 +534,352 @@ impl Joint {
             r1: Vec2::new(0.0, 0.0),
             r2: Vec2::new(0.0, 0.0),
             m: Mat2x2::new(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0)),
+            separation: Vec2::new(0.0, 0.0),
+            reference_angle: body_2.rotation - body_1.rotation,
+            angular_mass: 0.0,
+            angle: 0.0,
+            inv_dt: 0.0,
+            indices: (0, 0),
+        }
+    }
+
+    /// Returns a joint keeping the world points `anchor_1` on the first body and
+    /// `anchor_2` on the second at their current distance.
+    pub fn distance(body_1: &Body, body_2: &Body, anchor_1: Vec2, anchor_2: Vec2) -> Self {
+        Self {
+            kind: JointKind::Distance(DistanceJoint {
+                length: (anchor_2 - anchor_1).length(),
+                ..Default::default()
+            }),
+            local_anchor_1: body_1.world_to_local(anchor_1),
+            local_anchor_2: body_2.world_to_local(anchor_2),
+            ..Self::from_bodies(body_1, body_2, anchor_1)
+        }
+    }
+
+    /// Returns a joint gluing the two bodies together at the world point
+    /// `anchor`, see `JointKind::Weld`.
+    pub fn weld(body_1: &Body, body_2: &Body, anchor: Vec2) -> Self {
+        Self {
+            kind: JointKind::Weld(WeldJoint::default()),
+            ..Self::from_bodies(body_1, body_2, anchor)
+        }
+    }
+
+    /// Returns a pulley joint hanging `anchor_1` and `anchor_2` from the ground
+    /// anchors, see `PulleyJoint`. All points are in world space.
+    pub fn pulley(
+        body_1: &Body,
+        body_2: &Body,
+        ground_anchors: (Vec2, Vec2),
+        anchor_1: Vec2,
+        anchor_2: Vec2,
+        ratio: f32,
+    ) -> Self {
+        let length =
+            (anchor_1 - ground_anchors.0).length() + ratio * (anchor_2 - ground_anchors.1).length();
+        Self {
+            kind: JointKind::Pulley(PulleyJoint {
+                ground_anchors,
+                ratio,
+                length,
+                ..Default::default()
+            }),
+            local_anchor_1: body_1.world_to_local(anchor_1),
+            local_anchor_2: body_2.world_to_local(anchor_2),
+            ..Self::from_bodies(body_1, body_2, anchor_1)
+        }
+    }
+
+    /// Returns a motor joint holding the second body at its current place
+    /// relative to the first with at most `max_force` and `max_torque`, see
+    /// `MotorJoint`.
+    pub fn motor(body_1: &Body, body_2: &Body, max_force: f32, max_torque: f32) -> Self {
+        Self {
+            kind: JointKind::Motor(MotorJoint {
+                linear_offset: body_1.world_to_local(body_2.position),
+                max_force,
+                max_torque,
+                correction_factor: 0.3,
+                ..Default::default()
+            }),
+            local_anchor_1: Vec2::new(0.0, 0.0),
+            local_anchor_2: Vec2::new(0.0, 0.0),
+            ..Self::from_bodies(body_1, body_2, body_2.position)
+        }
+    }
+
+    /// Returns a friction joint between the second body and the first, usually
+    /// a static ground body, resisting with at most `max_force` and
+    /// `max_torque`.
+    pub fn friction(body_1: &Body, body_2: &Body, max_force: f32, max_torque: f32) -> Self {
+        Self {
+            kind: JointKind::Friction(FrictionJoint {
+                max_force,
+                max_torque,
+                ..Default::default()
+            }),
+            ..Self::from_bodies(body_1, body_2, body_2.position)
+        }
+    }
+
+    /// Returns a joint keeping the current area of the ring, see
+    /// `JointKind::Area`. The first two bodies of the ring are `body_1` and
+    /// `body_2`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ring has fewer than three bodies.
+    pub fn area(ring: &[&Body]) -> Self {
+        assert!(ring.len() >= 3, "an area joint needs at least three bodies");
+        let positions: Vec<Vec2> = ring.iter().map(|body| body.position).collect();
+        Self {
+            kind: JointKind::Area(AreaJoint {
+                bodies: ring.iter().map(|body| body.handle()).collect(),
+                target_area: AreaJoint::area(&positions),
+                ..Default::default()
+            }),
+            ..Self::from_bodies(ring[0], ring[1], ring[0].position)
         }
     }
 
-    pub fn pre_step(
+    pub fn handle(&self) -> JointHandle {
+        JointHandle(self.id)
+    }
+
+    // See `Body::renew_id`.
+    pub(crate) fn renew_id(&mut self) {
+        self.id = JOINT_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn kind(&self) -> &JointKind {
+        &self.kind
+    }
+
+    /// Returns the kind of the joint to change its parameters, e.g. the limits
+    /// of a revolute joint.
+    pub fn kind_mut(&mut self) -> &mut JointKind {
+        &mut self.kind
+    }
+
+    /// Returns true if the joint is attached to the body with the given id.
+    pub fn involves(&self, id: usize) -> bool {
+        match &self.kind {
+            JointKind::Area(area) => area.bodies.iter().any(|handle| handle.id() == id),
+            _ => self.body_1 == id || self.body_2 == id,
+        }
+    }
+
+    // Looks up the storage indices of the bodies for the current step. An area
+    // joint is solved in the island of the first dynamic body of its ring.
+    pub(crate) fn resolve(
+        &mut self,
+        bodies: &[Body],
+        body_indices: &HashMap<usize, usize>,
+    ) -> Result<(), Sylt2DErrors> {
+        let index_of = |id: usize| {
+            body_indices
+                .get(&id)
+                .copied()
+                .ok_or(Sylt2DErrors::BodyNotFound(id))
+        };
+        if let JointKind::Area(area) = &mut self.kind {
+            area.indices.clear();
+            for handle in area.bodies.iter() {
+                area.indices.push(index_of(handle.id())?);
+            }
+            let first = area
+                .indices
+                .iter()
+                .copied()
+                .find(|&index| bodies[index].is_dynamic());
+            let first = match first {
+                Some(index) => index,
+                None => index_of(self.body_1)?,
+            };
+            self.indices = (first, first);
+            return Ok(());
+        }
+        self.indices = (index_of(self.body_1)?, index_of(self.body_2)?);
+        Ok(())
+    }
+
+    // Storage indices of the bodies found by `resolve`.
+    pub(crate) fn indices(&self) -> (usize, usize) {
+        self.indices
+    }
+
+    // Storage indices of the bodies of the ring of an area joint, empty for the
+    // other joints.
+    pub(crate) fn ring(&self) -> &[usize] {
+        match &self.kind {
+            JointKind::Area(area) => &area.indices,
+            _ => &[],
+        }
+    }
+
+    /// Returns the impulses accumulated by the solver, used for warm starting.
+    pub fn accumulated_impulse(&self) -> JointImpulse {
+        let mut impulse = JointImpulse {
+            linear: self.p,
+            ..Default::default()
+        };
+        match &self.kind {
+            JointKind::Revolute(revolute) => {
+                impulse.limits = revolute.limit_impulses;
+                impulse.angular = revolute.motor_impulse;
+            }
+            JointKind::Weld(weld) => impulse.angular = weld.angular_impulse,
+            JointKind::Motor(motor) => impulse.angular = motor.angular_impulse,
+            JointKind::Friction(friction) => impulse.angular = friction.angular_impulse,
+            JointKind::Pulley(pulley) => impulse.axial = pulley.impulse,
+            JointKind::Area(area) => impulse.axial = area.impulse,
+            JointKind::Distance(_) => {}
+        }
+        impulse
+    }
+
+    /// Sets the impulses the solver starts the next step from. The impulses of
+    /// other kinds of joints are ignored.
+    pub fn set_accumulated_impulse(&mut self, impulse: JointImpulse) {
+        self.p = impulse.linear;
+        match &mut self.kind {
+            JointKind::Revolute(revolute) => {
+                revolute.limit_impulses = impulse.limits;
+                revolute.motor_impulse = impulse.angular;
+            }
+            JointKind::Weld(weld) => weld.angular_impulse = impulse.angular,
+            JointKind::Motor(motor) => motor.angular_impulse = impulse.angular,
+            JointKind::Friction(friction) => friction.angular_impulse = impulse.angular,
+            JointKind::Pulley(pulley) => pulley.impulse = impulse.axial,
+            JointKind::Area(area) => area.impulse = impulse.axial,
+            JointKind::Distance(_) => {}
+        }
+    }
+
+    /// Returns the force the joint applied to the second body during the last
+    /// step, e.g. to break it apart once the force gets too large.
+    pub fn reaction_force(&self) -> Vec2 {
+        match &self.kind {
+            JointKind::Pulley(pulley) => {
+                pulley.axes.1 * (-pulley.ratio * pulley.impulse * self.inv_dt)
+            }
+            _ => self.p * self.inv_dt,
+        }
+    }
+
+    /// Returns the torque the joint applied to the second body during the last
+    /// step, other than through its anchors.
+    pub fn reaction_torque(&self) -> f32 {
+        self.kind.angular_impulse() * self.inv_dt
+    }
+
+    /// Returns the rotation of the second body relative to the first, zero when
+    /// the joint was made.
+    pub fn angle(&self, body_1: &Body, body_2: &Body) -> f32 {
+        body_2.rotation - body_1.rotation - self.reference_angle
+    }
+
+    /// Prepares the joint for `apply_impulse`, given the bodies in storage
+    /// order.
+    pub(crate) fn pre_step(
         &mut self,
+        bodies: &mut [Body],
         world_context: &WorldContext,
         inv_dt: f32,
     ) -> Result<(), Sylt2DErrors> {
-        let mut body_1 = self.body_1.borrow_mut();
-        let mut body_2 = self.body_2.borrow_mut();
+        self.inv_dt = inv_dt;
+        // Fraction of the position errors to correct per second
+        let correction = if world_context.position_correction
+            && world_context.position_solver == PositionSolver::Baumgarte
+        {
+            inv_dt * self.bias_factor
+        } else {
+            0.0
+        };
+        if let JointKind::Area(area) = &mut self.kind {
+            area.update_mass(bodies, self.softness);
+            area.bias = (area.ring_area(bodies) - area.target_area) * correction;
+            if world_context.warm_starting {
+                area.apply_impulse(bodies, area.impulse);
+            } else {
+                area.impulse = 0.0;
+            }
+            return Ok(());
+        }
+
+        let (body_1, body_2) = pair_mut(bodies, self.indices.0, self.indices.1);
+        self.update_mass(body_1, body_2)?;
+        let error = self.position_error(body_1, body_2);
+        let angle = self.angle(body_1, body_2);
+        let arms = (self.r1, self.r2);
+
+        self.bias = error * -correction;
+        match &mut self.kind {
+            JointKind::Revolute(revolute) => {
+                if let Some((lower, upper)) = revolute.limits {
+                    let bias = |error: f32| {
+                        if error > 0.0 {
+                            // Only stops the bodies from passing the limit
+                            error * inv_dt
+                        } else {
+                            error * correction
+                        }
+                    };
+                    revolute.limit_biases = (bias(angle - lower), bias(upper - angle));
+                }
+                revolute.max_motor_impulse = if inv_dt > 0.0 {
+                    revolute.max_motor_torque / inv_dt
+                } else {
+                    0.0
+                };
+            }
+            JointKind::Weld(weld) => weld.angular_bias = -angle * correction,
+            JointKind::Motor(motor) => {
+                self.bias = error * inv_dt * motor.correction_factor * -1.0;
+                motor.angular_bias =
+                    (motor.angular_offset - angle) * inv_dt * motor.correction_factor;
+            }
+            JointKind::Pulley(pulley) => {
+                pulley.bias = pulley.length_error(body_1, body_2, arms) * correction;
+            }
+            JointKind::Distance(_) | JointKind::Friction(_) | JointKind::Area(_) => {}
+        }
+
+        if world_context.warm_starting {
+            self.project_impulse();
+            self.warm_start_pair(body_1, body_2);
+        } else {
+            self.p = Vec2::new(0.0, 0.0);
+            self.kind.reset_impulses();
+        }
+        Ok(())
+    }
+
+    // Updates the anchors and the mass matrix for the current rotations.
+    fn update_mass(&mut self, body_1: &Body, body_2: &Body) -> Result<(), Sylt2DErrors> {
         let rot_1 = Mat2x2::new_from_angle(body_1.rotation);
         let rot_2 = Mat2x2::new_from_angle(body_2.rotation);
 
         self.r1 = rot_1 * self.local_anchor_1;
         self.r2 = rot_2 * self.local_anchor_2;
+        let mut k = body_1.inv_moi + body_2.inv_moi;
+        if let JointKind::Weld(_) = self.kind {
+            k += self.softness;
+        }
+        self.angular_mass = if k > 0.0 { 1.0 / k } else { 0.0 };
+        let arms = (self.r1, self.r2);
+        match &mut self.kind {
+            JointKind::Distance(distance) => {
+                distance.update_axis(body_1, body_2, arms, self.softness);
+                return Ok(());
+            }
+            JointKind::Pulley(pulley) => {
+                pulley.update_axes(body_1, body_2, arms);
+                return Ok(());
+            }
+            _ => {}
+        }
 
         // deltaV = deltaV0 + K * impulse
         // invM = [(1/m1 + 1/m2) * eye(2) - skew(r1) * invI1 * skew(r1) - skew(r2) * invI2 * skew(r2)]
@@ -93,39 +907,876 @@ impl Joint {
         k.col1.x += self.softness;
         k.col2.y += self.softness;
         self.m = k.invert()?;
-        let p1 = body_1.position + self.r1;
-        let p2 = body_2.position + self.r2;
-        let dp = p2 - p1;
+        Ok(())
+    }
 
-        if world_context.position_correction {
-            self.bias = dp * inv_dt * self.bias_factor * -1.0;
+    // How far the anchors are from where the joint wants them, along the axis
+    // for the joints constraining only the distance.
+    fn position_error(&self, body_1: &Body, body_2: &Body) -> Vec2 {
+        let d = body_2.position + self.r2 - body_1.position - self.r1;
+        match &self.kind {
+            JointKind::Revolute(_) | JointKind::Weld(_) => d,
+            JointKind::Motor(motor) => {
+                d - Mat2x2::new_from_angle(body_1.rotation) * motor.linear_offset
+            }
+            // Solved along the ropes or the ring instead
+            JointKind::Pulley(_) | JointKind::Friction(_) | JointKind::Area(_) => {
+                Vec2::new(0.0, 0.0)
+            }
+            JointKind::Distance(distance) => {
+                distance.axis * (d.dot(distance.axis) - distance.length)
+            }
+        }
+    }
+
+    // Impulse that removes the velocity `dv` between the anchors less
+    // `target`, counting the accumulated impulse `p` as given for the softness.
+    fn solve_velocity(&self, dv: Vec2, target: Vec2, p: Vec2, softness: f32) -> Vec2 {
+        match &self.kind {
+            JointKind::Revolute(_)
+            | JointKind::Weld(_)
+            | JointKind::Motor(_)
+            | JointKind::Friction(_) => self.m * (target - dv - p * softness),
+            JointKind::Pulley(_) | JointKind::Area(_) => Vec2::new(0.0, 0.0),
+            JointKind::Distance(distance) => {
+                let axis = distance.axis;
+                let lambda = distance.axial_mass
+                    * (target.dot(axis) - dv.dot(axis) - p.dot(axis) * softness);
+                axis * lambda
+            }
+        }
+    }
+
+    // Drives a motor or friction joint towards the velocity `linear_target` and
+    // `angular_target`, within the largest force and torque.
+    fn solve_drive(
+        &mut self,
+        body_1: &mut Body,
+        body_2: &mut Body,
+        linear_target: Vec2,
+        angular_target: f32,
+    ) {
+        let (max_force, max_torque, angular_impulse) = match &mut self.kind {
+            JointKind::Motor(motor) => (
+                motor.max_force,
+                motor.max_torque,
+                &mut motor.angular_impulse,
+            ),
+            JointKind::Friction(friction) => (
+                friction.max_force,
+                friction.max_torque,
+                &mut friction.angular_impulse,
+            ),
+            _ => return,
+        };
+        let dt = if self.inv_dt > 0.0 {
+            1.0 / self.inv_dt
         } else {
-            self.bias = Vec2::new(0.0, 0.0);
+            0.0
+        };
+
+        let w = body_2.angular_velocity - body_1.angular_velocity;
+        let accumulated = *angular_impulse;
+        let max_impulse = max_torque * dt;
+        *angular_impulse = f32::clamp(
+            accumulated + self.angular_mass * (angular_target - w),
+            -max_impulse,
+            max_impulse,
+        );
+        apply_angular_impulse(body_1, body_2, *angular_impulse - accumulated);
+
+        let dv = body_2.velocity + body_2.angular_velocity.cross(self.r2)
+            - body_1.velocity
+            - body_1.angular_velocity.cross(self.r1);
+        let accumulated = self.p;
+        self.p = self.p + self.solve_velocity(dv, linear_target, self.p, 0.0);
+        let max_impulse = max_force * dt;
+        if self.p.length() > max_impulse {
+            self.p = self.p * (max_impulse / self.p.length());
+        }
+        self.apply_linear_impulse(body_1, body_2, self.p - accumulated);
+    }
+
+    // Applies `impulse` at the anchors, pushing the second body and pulling the
+    // first.
+    fn apply_linear_impulse(&self, body_1: &mut Body, body_2: &mut Body, impulse: Vec2) {
+        body_1.velocity = body_1.velocity - impulse * body_1.inv_mass;
+        body_1.angular_velocity -= body_1.inv_moi * self.r1.cross(impulse);
+        body_2.velocity = body_2.velocity + impulse * body_2.inv_mass;
+        body_2.angular_velocity += body_2.inv_moi * self.r2.cross(impulse);
+    }
+
+    /// Moves the bodies to join the anchors again, see
+    /// `PositionSolver::NonlinearGaussSeidel`. Returns the distance between the
+    /// anchors, or the angle past the limits if larger, before the correction.
+    ///
+    /// An area joint moves the bodies of the ring to keep the area and returns
+    /// the farthest it moved one.
+    pub(crate) fn solve_position(&mut self, bodies: &mut [Body]) -> Result<f32, Sylt2DErrors> {
+        if let JointKind::Area(area) = &mut self.kind {
+            area.update_mass(bodies, self.softness);
+            let impulse = -area.mass * (area.ring_area(bodies) - area.target_area);
+            let mut largest = 0.0_f32;
+            for (&index, &gradient) in area.indices.iter().zip(area.gradients.iter()) {
+                let body = &mut bodies[index];
+                let correction = gradient * (impulse * body.inv_mass);
+                body.position = body.position + correction;
+                largest = largest.max(correction.length());
+            }
+            return Ok(largest);
+        }
+
+        let (body_1, body_2) = pair_mut(bodies, self.indices.0, self.indices.1);
+        self.update_mass(body_1, body_2)?;
+        let error = self.position_error(body_1, body_2);
+        let impulse = self.solve_velocity(error, Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0), 0.0);
+        body_1.position = body_1.position - impulse * body_1.inv_mass;
+        body_1.rotation -= body_1.inv_moi * self.r1.cross(impulse);
+        body_2.position = body_2.position + impulse * body_2.inv_mass;
+        body_2.rotation += body_2.inv_moi * self.r2.cross(impulse);
+        let angle = self.angle(body_1, body_2);
+        let angle_error = match &self.kind {
+            // Only drives the velocities
+            JointKind::Motor(_) | JointKind::Friction(_) | JointKind::Area(_) => return Ok(0.0),
+            JointKind::Pulley(pulley) => {
+                let error = pulley.length_error(body_1, body_2, (self.r1, self.r2));
+                let impulse = -pulley.axial_mass * error;
+                let p1 = pulley.axes.0 * -impulse;
+                let p2 = pulley.axes.1 * (-pulley.ratio * impulse);
+                body_1.position = body_1.position + p1 * body_1.inv_mass;
+                body_1.rotation += body_1.inv_moi * self.r1.cross(p1);
+                body_2.position = body_2.position + p2 * body_2.inv_mass;
+                body_2.rotation += body_2.inv_moi * self.r2.cross(p2);
+                return Ok(error.abs());
+            }
+            JointKind::Weld(_) => angle,
+            JointKind::Revolute(RevoluteJoint {
+                limits: Some((lower, upper)),
+                ..
+            }) => angle - angle.clamp(*lower, *upper),
+            JointKind::Revolute(_) | JointKind::Distance(_) => 0.0,
+        };
+        let impulse = -self.angular_mass * angle_error;
+        body_1.rotation -= body_1.inv_moi * impulse;
+        body_2.rotation += body_2.inv_moi * impulse;
+        Ok(error.length().max(angle_error.abs()))
+    }
+
+    /// Applies the accumulated impulse to the bodies.
+    pub(crate) fn warm_start(&self, bodies: &mut [Body]) {
+        if let JointKind::Area(area) = &self.kind {
+            area.apply_impulse(bodies, area.impulse);
+            return;
         }
+        let (body_1, body_2) = pair_mut(bodies, self.indices.0, self.indices.1);
+        self.warm_start_pair(body_1, body_2);
+    }
 
+    fn warm_start_pair(&self, body_1: &mut Body, body_2: &mut Body) {
+        self.apply_linear_impulse(body_1, body_2, self.p);
+        apply_angular_impulse(body_1, body_2, self.kind.angular_impulse());
+        if let JointKind::Pulley(pulley) = &self.kind {
+            pulley.apply_impulse(body_1, body_2, (self.r1, self.r2), pulley.impulse);
+        }
+    }
+
+    /// Prepares the joint for the soft solver, see `Solver::Soft`. The
+    /// accumulated impulse is applied by `warm_start` in every substep.
+    pub(crate) fn prepare_soft(
+        &mut self,
+        bodies: &[Body],
+        world_context: &WorldContext,
+    ) -> Result<(), Sylt2DErrors> {
+        if let JointKind::Area(area) = &mut self.kind {
+            area.update_mass(bodies, self.softness);
+            area.error = area.ring_area(bodies) - area.target_area;
+            if !world_context.warm_starting {
+                area.impulse = 0.0;
+            }
+            return Ok(());
+        }
+
+        let (body_1, body_2) = (&bodies[self.indices.0], &bodies[self.indices.1]);
+        self.update_mass(body_1, body_2)?;
+        self.separation = body_2.position + self.r2 - body_1.position - self.r1;
+        self.angle = self.angle(body_1, body_2);
+        let arms = (self.r1, self.r2);
+        if let JointKind::Pulley(pulley) = &mut self.kind {
+            pulley.error = pulley.length_error(body_1, body_2, arms);
+        }
         if world_context.warm_starting {
-            body_1.velocity = body_1.velocity - self.p * body_1.inv_mass;
-            body_1.angular_velocity -= body_1.inv_moi * self.r1.cross(self.p);
-            body_2.velocity = body_2.velocity + self.p * body_2.inv_mass;
-            body_2.angular_velocity += body_2.inv_moi * self.r2.cross(self.p);
+            self.project_impulse();
         } else {
             self.p = Vec2::new(0.0, 0.0);
+            self.kind.reset_impulses();
         }
         Ok(())
     }
-    pub fn apply_impulse(&mut self) {
-        let mut body_1 = self.body_1.borrow_mut();
-        let mut body_2 = self.body_2.borrow_mut();
+
+    // Keeps the part of the accumulated impulse the joint can still apply after
+    // the axis turned or the limits and the motor were turned off.
+    fn project_impulse(&mut self) {
+        match &mut self.kind {
+            JointKind::Distance(distance) => {
+                self.p = distance.axis * self.p.dot(distance.axis);
+            }
+            JointKind::Revolute(revolute) => {
+                if revolute.limits.is_none() {
+                    revolute.limit_impulses = (0.0, 0.0);
+                }
+                if !revolute.has_motor() {
+                    revolute.motor_impulse = 0.0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Solves the joint as a soft constraint, given how far each body moved and
+    /// turned since `prepare_soft` after `elapsed` seconds of the step, see
+    /// `PhysicsPipeline::solve`. `inv_h` is the inverse of the substep.
+    pub(crate) fn solve_soft(
+        &mut self,
+        bodies: &mut [Body],
+        offsets: &[(Vec2, f32)],
+        elapsed: f32,
+        softness: Softness,
+        inv_h: f32,
+    ) {
+        self.inv_dt = inv_h;
+        if let JointKind::Area(area) = &mut self.kind {
+            // Current area, assuming small motions
+            let moved: f32 = area
+                .indices
+                .iter()
+                .zip(area.gradients.iter())
+                .map(|(&index, gradient)| gradient.dot(offset(bodies, offsets, index, elapsed).0))
+                .sum();
+            let bias = (area.error + moved) * softness.bias_rate;
+            let impulse = -area.mass * softness.mass_scale * (area.area_velocity(bodies) + bias)
+                - softness.impulse_scale * area.impulse;
+            area.impulse += impulse;
+            area.apply_impulse(bodies, impulse);
+            return;
+        }
+
+        let (i, j) = self.indices;
+        let (offset_1, offset_2) = (
+            offset(bodies, offsets, i, elapsed),
+            offset(bodies, offsets, j, elapsed),
+        );
+        let (body_1, body_2) = pair_mut(bodies, i, j);
+        let angular_mass = self.angular_mass;
+        let arms = (self.r1, self.r2);
+        let angle = self.angle + offset_2.1 - offset_1.1;
+        match &mut self.kind {
+            JointKind::Revolute(revolute) => {
+                if revolute.has_motor() && inv_h > 0.0 {
+                    let max_impulse = revolute.max_motor_torque / inv_h;
+                    revolute.solve_motor(angular_mass, body_1, body_2, max_impulse);
+                }
+                if let Some((lower, upper)) = revolute.limits {
+                    let bias = |error: f32| {
+                        if error > 0.0 {
+                            // Only stops the bodies from passing the limit
+                            (error * inv_h, Softness::rigid())
+                        } else {
+                            (error * softness.bias_rate, softness)
+                        }
+                    };
+                    let (lower_bias, lower_softness) = bias(angle - lower);
+                    let (upper_bias, upper_softness) = bias(upper - angle);
+                    revolute.solve_limits(
+                        angular_mass,
+                        body_1,
+                        body_2,
+                        (lower_bias, upper_bias),
+                        (lower_softness, upper_softness),
+                    );
+                }
+            }
+            JointKind::Weld(weld) => {
+                let bias = angle * softness.bias_rate;
+                weld.solve_angle(angular_mass, body_1, body_2, bias, softness);
+            }
+            JointKind::Motor(motor) => {
+                let rotation_1 = Mat2x2::new_from_angle(body_1.rotation + offset_1.1);
+                let error =
+                    self.separation + offset_2.0 - offset_1.0 - rotation_1 * motor.linear_offset;
+                let bias = motor.correction_factor * inv_h;
+                let angular_target = (motor.angular_offset - angle) * bias;
+                self.solve_drive(body_1, body_2, error * -bias, angular_target);
+                return;
+            }
+            JointKind::Friction(_) => {
+                self.solve_drive(body_1, body_2, Vec2::new(0.0, 0.0), 0.0);
+                return;
+            }
+            JointKind::Pulley(pulley) => {
+                let (u1, u2) = pulley.axes;
+                let error = pulley.error
+                    - u1.dot(offset_1.0 + offset_1.1.cross(arms.0))
+                    - pulley.ratio * u2.dot(offset_2.0 + offset_2.1.cross(arms.1));
+                pulley.solve(body_1, body_2, arms, error * softness.bias_rate, softness);
+                return;
+            }
+            JointKind::Distance(_) | JointKind::Area(_) => {}
+        }
+
+        // Current separation, assuming small rotations
+        let mut separation = self.separation + offset_2.0 + offset_2.1.cross(self.r2)
+            - offset_1.0
+            - offset_1.1.cross(self.r1);
+        if let JointKind::Distance(distance) = &self.kind {
+            separation = distance.axis * (separation.dot(distance.axis) - distance.length);
+        }
         let dv = body_2.velocity + body_2.angular_velocity.cross(self.r2)
             - body_1.velocity
             - body_1.angular_velocity.cross(self.r1);
-        let impulse = self.m * (self.bias - dv - self.p * self.softness);
-        body_1.velocity = body_1.velocity - impulse * body_1.inv_mass;
-        body_1.angular_velocity -= body_1.inv_moi * self.r1.cross(impulse);
+        let impulse = self.solve_velocity(
+            dv + separation * softness.bias_rate,
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 0.0),
+            0.0,
+        ) * softness.mass_scale
+            - self.p * softness.impulse_scale;
+        self.apply_linear_impulse(body_1, body_2, impulse);
+        self.p = self.p + impulse;
+    }
 
-        body_2.velocity = body_2.velocity + impulse * body_2.inv_mass;
-        body_2.angular_velocity += body_2.inv_moi * self.r2.cross(impulse);
+    pub(crate) fn apply_impulse(&mut self, bodies: &mut [Body]) {
+        if let JointKind::Area(area) = &mut self.kind {
+            let c_dot = area.area_velocity(bodies);
+            let impulse = -area.mass * (c_dot + area.bias + self.softness * area.impulse);
+            area.impulse += impulse;
+            area.apply_impulse(bodies, impulse);
+            return;
+        }
 
+        let (body_1, body_2) = pair_mut(bodies, self.indices.0, self.indices.1);
+        let angular_mass = self.angular_mass;
+        match &mut self.kind {
+            JointKind::Revolute(revolute) => {
+                if revolute.has_motor() {
+                    let max_impulse = revolute.max_motor_impulse;
+                    revolute.solve_motor(angular_mass, body_1, body_2, max_impulse);
+                }
+                if revolute.limits.is_some() {
+                    let rigid = Softness::rigid();
+                    let biases = revolute.limit_biases;
+                    revolute.solve_limits(angular_mass, body_1, body_2, biases, (rigid, rigid));
+                }
+            }
+            JointKind::Weld(weld) => {
+                let softness = Softness {
+                    impulse_scale: self.softness * angular_mass,
+                    ..Softness::rigid()
+                };
+                let bias = -weld.angular_bias;
+                weld.solve_angle(angular_mass, body_1, body_2, bias, softness);
+            }
+            JointKind::Pulley(pulley) => {
+                let (arms, bias) = ((self.r1, self.r2), pulley.bias);
+                pulley.solve(body_1, body_2, arms, bias, Softness::rigid());
+                return;
+            }
+            JointKind::Motor(motor) => {
+                let angular_bias = motor.angular_bias;
+                self.solve_drive(body_1, body_2, self.bias, angular_bias);
+                return;
+            }
+            JointKind::Friction(_) => {
+                self.solve_drive(body_1, body_2, self.bias, 0.0);
+                return;
+            }
+            JointKind::Distance(_) | JointKind::Area(_) => {}
+        }
+        let dv = body_2.velocity + body_2.angular_velocity.cross(self.r2)
+            - body_1.velocity
+            - body_1.angular_velocity.cross(self.r1);
+        let impulse = self.solve_velocity(dv, self.bias, self.p, self.softness);
+        self.apply_linear_impulse(body_1, body_2, impulse);
         self.p = self.p + impulse;
     }
 }
+
+// Turns the second body by `impulse` and the first by the opposite.
+fn apply_angular_impulse(body_1: &mut Body, body_2: &mut Body, impulse: f32) {
+    body_1.angular_velocity -= body_1.inv_moi * impulse;
+    body_2.angular_velocity += body_2.inv_moi * impulse;
+}
+
+// Applies the impulse of one limit, `sign` 1 for the lower and -1 for the upper
+// one, and returns its new accumulated impulse. `bias` is the velocity the
+// limit pushes the bodies apart with and `softness` the one of the solver.
+fn solve_limit(
+    angular_mass: f32,
+    body_1: &mut Body,
+    body_2: &mut Body,
+    accumulated: f32,
+    sign: f32,
+    bias: f32,
+    softness: Softness,
+) -> f32 {
+    let w = (body_2.angular_velocity - body_1.angular_velocity) * sign;
+    let impulse =
+        -angular_mass * softness.mass_scale * (w + bias) - softness.impulse_scale * accumulated;
+    let new_accumulated = f32::max(accumulated + impulse, 0.0);
+    apply_angular_impulse(body_1, body_2, (new_accumulated - accumulated) * sign);
+    new_accumulated
+}
+
+/// Builds a joint between bodies already in a world by their handles, e.g.
+/// `JointBuilder::revolute(a, b).anchor(p).build(&mut world)?`.
+///
+/// The anchors default to the position of the second body, or of each body for
+/// a distance joint.
+#[derive(Debug, Clone)]
+pub struct JointBuilder {
+    kind: JointKind,
+    body_1: BodyHandle,
+    body_2: BodyHandle,
+    anchors: (Option<Vec2>, Option<Vec2>),
+    softness: Option<f32>,
+    bias_factor: Option<f32>,
+    limits: Option<(f32, f32)>,
+    motor: Option<(f32, f32)>,
+}
+
+impl JointBuilder {
+    fn new(kind: JointKind, body_1: BodyHandle, body_2: BodyHandle) -> Self {
+        Self {
+            kind,
+            body_1,
+            body_2,
+            anchors: (None, None),
+            softness: None,
+            bias_factor: None,
+            limits: None,
+            motor: None,
+        }
+    }
+
+    /// See `JointKind::Revolute`.
+    pub fn revolute(body_1: BodyHandle, body_2: BodyHandle) -> Self {
+        Self::new(JointKind::default(), body_1, body_2)
+    }
+
+    /// See `JointKind::Weld`.
+    pub fn weld(body_1: BodyHandle, body_2: BodyHandle) -> Self {
+        Self::new(JointKind::Weld(WeldJoint::default()), body_1, body_2)
+    }
+
+    /// See `JointKind::Distance`, the length is the distance between the
+    /// anchors when built.
+    pub fn distance(body_1: BodyHandle, body_2: BodyHandle) -> Self {
+        Self::new(
+            JointKind::Distance(DistanceJoint::default()),
+            body_1,
+            body_2,
+        )
+    }
+
+    /// Sets the world point both bodies are joined at.
+    pub fn anchor(mut self, anchor: Vec2) -> Self {
+        self.anchors = (Some(anchor), Some(anchor));
+        self
+    }
+
+    /// Sets a world point on each body, for distance joints.
+    pub fn anchors(mut self, anchor_1: Vec2, anchor_2: Vec2) -> Self {
+        self.anchors = (Some(anchor_1), Some(anchor_2));
+        self
+    }
+
+    pub fn softness(mut self, softness: f32) -> Self {
+        self.softness = Some(softness);
+        self
+    }
+
+    pub fn bias_factor(mut self, bias_factor: f32) -> Self {
+        self.bias_factor = Some(bias_factor);
+        self
+    }
+
+    /// See `RevoluteJoint::limits`, revolute joints only.
+    pub fn limits(mut self, lower: f32, upper: f32) -> Self {
+        self.limits = Some((lower, upper));
+        self
+    }
+
+    /// See `RevoluteJoint::motor_speed` and `RevoluteJoint::max_motor_torque`,
+    /// revolute joints only.
+    pub fn motor(mut self, speed: f32, max_torque: f32) -> Self {
+        self.motor = Some((speed, max_torque));
+        self
+    }
+
+    /// Returns the joint without adding it to the world, or an error if one of
+    /// the bodies is not in the world or limits or a motor were set on a weld
+    /// or distance joint.
+    pub fn joint(&self, world: &World) -> Result<Joint, Sylt2DErrors> {
+        if !matches!(self.kind, JointKind::Revolute(_)) {
+            if self.limits.is_some() {
+                return Err(JointErrors::LimitsNotSupported.into());
+            }
+            if self.motor.is_some() {
+                return Err(JointErrors::MotorNotSupported.into());
+            }
+        }
+        let find = |handle: BodyHandle| {
+            world
+                .body(handle)
+                .ok_or(Sylt2DErrors::BodyNotFound(handle.id()))
+        };
+        let (body_1, body_2) = (find(self.body_1)?, find(self.body_2)?);
+        let mut joint = match &self.kind {
+            JointKind::Distance(_) => Joint::distance(
+                body_1,
+                body_2,
+                self.anchors.0.unwrap_or(body_1.position),
+                self.anchors.1.unwrap_or(body_2.position),
+            ),
+            kind => Joint {
+                kind: kind.clone(),
+                ..Joint::from_bodies(body_1, body_2, self.anchors.0.unwrap_or(body_2.position))
+            },
+        };
+        if let Some(softness) = self.softness {
+            joint.softness = softness;
+        }
+        if let Some(bias_factor) = self.bias_factor {
+            joint.bias_factor = bias_factor;
+        }
+        if let JointKind::Revolute(revolute) = &mut joint.kind {
+            revolute.limits = self.limits;
+            if let Some((speed, max_torque)) = self.motor {
+                revolute.motor_speed = speed;
+                revolute.max_motor_torque = max_torque;
+            }
+        }
+        Ok(joint)
+    }
+
+    /// Adds the joint to the world, or returns an error as `JointBuilder::joint`
+    /// does.
+    pub fn build(self, world: &mut World) -> Result<JointHandle, Sylt2DErrors> {
+        let joint = self.joint(world)?;
+        Ok(world.add_joint(joint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+
+    #[test]
+    fn test_new() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        body.position = Vec2::new(2.0, 0.0);
+        let pivot = world.add_body(Body::new(Vec2::new(0.2, 0.2), f32::MAX));
+        let body = world.add_body(body);
+        let joint = Joint::new(pivot, body, Vec2::new(1.0, 0.0), &world).unwrap();
+        assert_eq!((joint.body_1, joint.body_2), (pivot.id(), body.id()));
+        assert_eq!(joint.local_anchor_2, Vec2::new(-1.0, 0.0));
+
+        world.remove_body(body.id());
+        assert!(matches!(
+            Joint::new(pivot, body, Vec2::new(1.0, 0.0), &world),
+            Err(Sylt2DErrors::BodyNotFound(id)) if id == body.id()
+        ));
+    }
+
+    #[test]
+    fn test_distance_joint() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context.solver = solver;
+            let mut pivot = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+            pivot.position = Vec2::new(0.0, 10.0);
+            let mut bob = Body::new(Vec2::new(0.5, 0.5), 1.0);
+            bob.position = Vec2::new(3.0, 10.0);
+            // The rod ends at the left side of the bob
+            let joint = Joint::distance(&pivot, &bob, pivot.position, Vec2::new(2.75, 10.0));
+            assert!(
+                matches!(joint.kind(), JointKind::Distance(distance) if distance.length == 2.75)
+            );
+            world.add_joint(joint);
+            world.add_body(pivot);
+            let bob = world.add_body(bob);
+
+            let mut lowest = f32::MAX;
+            for _ in 0..120 {
+                world.step(1.0 / 60.0).unwrap();
+                let bob = world.body(bob).unwrap();
+                lowest = lowest.min(bob.position.y);
+                let end = bob.local_to_world(Vec2::new(-0.25, 0.0));
+                assert!(((end - Vec2::new(0.0, 10.0)).length() - 2.75).abs() < 0.05);
+            }
+            // The bob swung down
+            assert!(lowest < 7.5);
+        }
+    }
+
+    #[test]
+    fn test_revolute_limits() {
+        let baumgarte = World::new(Vec2::new(0.0, -10.0), 10).world_context;
+        let ngs = WorldContext {
+            position_solver: PositionSolver::NonlinearGaussSeidel { iterations: 4 },
+            ..baumgarte
+        };
+        let soft = WorldContext {
+            solver: Solver::Soft { substeps: 4 },
+            ..baumgarte
+        };
+        for world_context in [baumgarte, ngs, soft] {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context = WorldContext {
+                position_correction: true,
+                warm_starting: true,
+                ..world_context
+            };
+            let mut pivot = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+            pivot.position = Vec2::new(0.0, 10.0);
+            // An arm hinged just left of its end
+            let mut arm = Body::new(Vec2::new(2.0, 0.2), 1.0);
+            arm.position = Vec2::new(1.2, 10.0);
+            let mut joint = Joint::from_bodies(&pivot, &arm, pivot.position);
+            if let JointKind::Revolute(revolute) = joint.kind_mut() {
+                revolute.limits = Some((-0.5, 0.5));
+            }
+            world.add_joint(joint);
+            world.add_body(pivot);
+            world.add_body(arm);
+
+            // The arm swings down until it reaches the lower limit
+            for _ in 0..120 {
+                world.step(1.0 / 60.0).unwrap();
+                let angle = world.joints[0].angle(&world.bodies[0], &world.bodies[1]);
+                assert!(angle > -0.55);
+            }
+            let angle = world.joints[0].angle(&world.bodies[0], &world.bodies[1]);
+            assert!((angle + 0.5).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn test_revolute_motor() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+            world.world_context.solver = solver;
+            world.world_context.warm_starting = true;
+            let mut frame = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+            frame.position = Vec2::new(0.0, -5.0);
+            let strong = Body::new_circle(1.0, 2.0);
+            let mut weak = Body::new_circle(1.0, 2.0);
+            weak.position = Vec2::new(5.0, 0.0);
+            for (wheel, max_motor_torque) in [(&strong, 100.0), (&weak, 0.5)] {
+                let mut joint = Joint::from_bodies(&frame, wheel, wheel.position);
+                if let JointKind::Revolute(revolute) = joint.kind_mut() {
+                    revolute.motor_speed = 2.0;
+                    revolute.max_motor_torque = max_motor_torque;
+                }
+                world.add_joint(joint);
+            }
+            world.add_body(frame);
+            let strong = world.add_body(strong);
+            let weak = world.add_body(weak);
+
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            // The weak motor is still speeding up at its largest torque
+            assert!((world.body(strong).unwrap().angular_velocity - 2.0).abs() < 1e-3);
+            let moi = world.body(weak).unwrap().moi;
+            let angular_velocity = world.body(weak).unwrap().angular_velocity;
+            assert!((angular_velocity - 0.5 / moi).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_weld_joint() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context.solver = solver;
+            world.world_context.warm_starting = true;
+            let wall = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+            // A beam sticking out of the wall
+            let mut beam = Body::new(Vec2::new(2.0, 0.2), 1.0);
+            beam.position = Vec2::new(1.2, 0.0);
+            world.add_joint(Joint::weld(&wall, &beam, Vec2::new(0.2, 0.0)));
+            world.add_body(wall);
+            let beam = world.add_body(beam);
+
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            let beam = world.body(beam).unwrap();
+            assert!((beam.position - Vec2::new(1.2, 0.0)).length() < 0.02);
+            assert!(beam.rotation.abs() < 0.01);
+            // The wall holds the weight of the beam a meter away
+            let joint = &world.joints[0];
+            assert!((joint.reaction_force() - Vec2::new(0.0, 10.0)).length() < 0.1);
+            assert!((joint.reaction_torque() - 10.0).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn test_pulley_joint() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context.solver = solver;
+            world.world_context.warm_starting = true;
+            let mut heavy = Body::new(Vec2::new(0.5, 0.5), 2.0);
+            heavy.position = Vec2::new(-2.0, 5.0);
+            let mut light = Body::new(Vec2::new(0.5, 0.5), 1.0);
+            light.position = Vec2::new(2.0, 5.0);
+            let ground_anchors = (Vec2::new(-2.0, 10.0), Vec2::new(2.0, 10.0));
+            let joint = Joint::pulley(
+                &heavy,
+                &light,
+                ground_anchors,
+                heavy.position,
+                light.position,
+                1.0,
+            );
+            world.add_joint(joint);
+            let heavy = world.add_body(heavy);
+            let light = world.add_body(light);
+
+            for _ in 0..30 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            // The heavy side falls with a third of the gravity
+            let (heavy, light) = (world.body(heavy).unwrap(), world.body(light).unwrap());
+            let fallen = 5.0 - heavy.position.y;
+            assert!((fallen - 0.5 * 10.0 / 3.0 * 0.25).abs() < 0.02);
+            assert!((light.position.y - 5.0 - fallen).abs() < 0.01);
+            assert!((heavy.position.x + 2.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_motor_joint() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context.solver = solver;
+            world.world_context.warm_starting = true;
+            let ground = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+            let mut strong = Body::new(Vec2::new(0.5, 0.5), 1.0);
+            strong.position = Vec2::new(2.0, 0.0);
+            let mut weak = Body::new(Vec2::new(0.5, 0.5), 1.0);
+            weak.position = Vec2::new(-2.0, 0.0);
+            let mut joint = Joint::motor(&ground, &strong, 50.0, 10.0);
+            if let JointKind::Motor(motor) = joint.kind_mut() {
+                assert_eq!(motor.linear_offset, Vec2::new(2.0, 0.0));
+                motor.linear_offset = Vec2::new(3.0, 1.0);
+                motor.angular_offset = 0.5;
+            }
+            world.add_joint(joint);
+            // Too weak to hold the weight of the body
+            world.add_joint(Joint::motor(&ground, &weak, 5.0, 10.0));
+            world.add_body(ground);
+            let strong = world.add_body(strong);
+            let weak = world.add_body(weak);
+
+            for _ in 0..120 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            let strong = world.body(strong).unwrap();
+            assert!((strong.position - Vec2::new(3.0, 1.0)).length() < 0.02);
+            assert!((strong.rotation - 0.5).abs() < 0.02);
+            let weak = world.body(weak).unwrap();
+            assert!(weak.position.y < -1.0);
+            assert!((world.joints[1].reaction_force().length() - 5.0).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn test_friction_joint() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+            world.world_context.solver = solver;
+            world.world_context.warm_starting = true;
+            let ground = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+            let mut puck = Body::new(Vec2::new(0.5, 0.5), 2.0);
+            puck.position = Vec2::new(2.0, 0.0);
+            puck.velocity = Vec2::new(3.0, 0.0);
+            puck.angular_velocity = 2.0;
+            world.add_joint(Joint::friction(&ground, &puck, 4.0, 1.0));
+            world.add_body(ground);
+            let puck = world.add_body(puck);
+
+            // Slows down by 2 m/s every second
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            let body = world.body(puck).unwrap();
+            assert!((body.velocity - Vec2::new(1.0, 0.0)).length() < 0.05);
+            assert!(body.angular_velocity.abs() < 2.0);
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            // Comes to a stop instead of going back
+            let body = world.body(puck).unwrap();
+            assert!(body.velocity.length() < 1e-3);
+            assert!(body.angular_velocity.abs() < 1e-3);
+            assert!((body.position.x - 4.25).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn test_joint_builder() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let pivot = world.add_body(Body::new(Vec2::new(0.2, 0.2), f32::MAX));
+        let mut bob = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        bob.position = Vec2::new(2.0, 0.0);
+        let bob = world.add_body(bob);
+
+        let revolute = JointBuilder::revolute(pivot, bob)
+            .anchor(Vec2::new(1.0, 0.0))
+            .softness(0.01)
+            .limits(-0.5, 0.5)
+            .build(&mut world)
+            .unwrap();
+        JointBuilder::distance(pivot, bob)
+            .build(&mut world)
+            .unwrap();
+        let joint = world.joint(revolute).unwrap();
+        assert!(matches!(
+            joint.kind(),
+            JointKind::Revolute(revolute) if revolute.limits == Some((-0.5, 0.5))
+        ));
+        assert_eq!(joint.local_anchor_2, Vec2::new(-1.0, 0.0));
+        assert_eq!(joint.softness, 0.01);
+        assert!(matches!(
+            world.joints[1].kind(),
+            JointKind::Distance(distance) if distance.length == 2.0
+        ));
+
+        world.remove_body(bob.id());
+        let result = JointBuilder::weld(pivot, bob).build(&mut world);
+        assert!(matches!(result, Err(Sylt2DErrors::BodyNotFound(id)) if id == bob.id()));
+        // Both joints went with the body
+        assert!(world.joints.is_empty());
+
+        let bob = world.add_body(Body::new(Vec2::new(0.5, 0.5), 1.0));
+        let result = JointBuilder::weld(pivot, bob)
+            .limits(-0.5, 0.5)
+            .build(&mut world);
+        assert!(matches!(
+            result,
+            Err(Sylt2DErrors::Joint(JointErrors::LimitsNotSupported))
+        ));
+        let result = JointBuilder::distance(pivot, bob)
+            .motor(1.0, 10.0)
+            .build(&mut world);
+        assert!(matches!(
+            result,
+            Err(Sylt2DErrors::Joint(JointErrors::MotorNotSupported))
+        ));
+        assert!(world.joints.is_empty());
+    }
+}
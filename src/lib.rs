@@ -1,9 +1,46 @@
+// Enters a `tracing` span until the end of the enclosing block with the `trace`
+// feature, does nothing without it.
+macro_rules! span {
+    ($name:literal) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::info_span!($name).entered();
+    };
+}
+
+pub mod aerodynamics;
 pub mod arbiter;
+#[cfg(feature = "bitmap")]
+pub mod bitmap;
 pub mod body;
+pub mod broad_phase;
+pub mod ccd;
 pub mod collide;
 pub mod collide_polygon;
+pub mod debug_draw;
 pub mod draw;
+#[cfg(feature = "egui")]
+pub mod egui_inspector;
 pub mod errors;
+pub mod export;
+#[cfg(feature = "ggez")]
+pub mod ggez_draw;
+pub mod island;
 pub mod joint;
+#[cfg(feature = "macroquad")]
+pub mod macroquad_draw;
 pub mod math_utils;
+pub mod narrowphase;
+pub mod pipeline;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod replay;
+#[cfg(feature = "scene")]
+pub mod scene;
+pub mod scratch;
+pub mod solver;
+pub mod stepper;
+pub mod tilemap;
+pub mod transforms;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod world;
@@ -0,0 +1,75 @@
+use crate::debug_draw::{self, Color, DebugDraw, DebugDrawOptions};
+use crate::math_utils::Vec2;
+use crate::world::World;
+use macroquad::camera::Camera2D;
+use macroquad::shapes::{draw_circle, draw_line, draw_triangle};
+
+/// Draws with macroquad in world units, under the camera set with
+/// `macroquad::camera::set_camera`, e.g. `camera`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MacroquadDraw;
+
+impl DebugDraw for MacroquadDraw {
+    fn polygon(&mut self, vertices: &[Vec2], color: Color) {
+        let Some((&first, rest)) = vertices.split_first() else {
+            return;
+        };
+        for pair in rest.windows(2) {
+            draw_triangle(first.into(), pair[0].into(), pair[1].into(), color.into());
+        }
+    }
+
+    fn circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        draw_circle(center.x, center.y, radius, color.into());
+    }
+
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        draw_line(start.x, start.y, end.x, end.y, thickness, color.into());
+    }
+}
+
+/// Draws the world with macroquad, see `debug_draw::draw_world`.
+pub fn draw_world(world: &World, options: &DebugDrawOptions) {
+    debug_draw::draw_world(world, &mut MacroquadDraw, options);
+}
+
+/// Returns a camera looking at `center` with `height` world units from the
+/// bottom to the top of the screen, y pointing up. `aspect` is the width of
+/// the screen over its height.
+pub fn camera(center: Vec2, height: f32, aspect: f32) -> Camera2D {
+    Camera2D {
+        target: center.into(),
+        // macroquad flips y on the screen, so y points down for a positive zoom
+        zoom: macroquad::math::vec2(2.0 / (height * aspect), -2.0 / height),
+        ..Default::default()
+    }
+}
+
+impl From<Color> for macroquad::color::Color {
+    fn from(color: Color) -> Self {
+        macroquad::color::Color::new(color.r, color.g, color.b, color.a)
+    }
+}
+
+impl From<Vec2> for macroquad::math::Vec2 {
+    fn from(v: Vec2) -> Self {
+        macroquad::math::vec2(v.x, v.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera() {
+        let camera = camera(Vec2::new(1.0, 2.0), 10.0, 2.0);
+        let center = macroquad::camera::Camera::matrix(&camera)
+            .project_point3(macroquad::math::vec3(1.0, 2.0, 0.0));
+        assert!(center.truncate().length() < 1e-6);
+        // The top of the view is the top of the screen
+        let top = macroquad::camera::Camera::matrix(&camera)
+            .project_point3(macroquad::math::vec3(1.0, 7.0, 0.0));
+        assert!((top.y - 1.0).abs() < 1e-6);
+    }
+}
@@ -21,6 +21,7 @@ impl Display for MathErrors {
 impl std::error::Error for MathErrors {}
 
 #[derive(Debug, Default, PartialOrd, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -154,7 +155,14 @@ impl From<Vec2> for (f32, f32) {
     }
 }
 
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Vec2::new(x, y)
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mat2x2 {
     pub col1: Vec2,
     pub col2: Vec2,
@@ -191,6 +199,7 @@ impl Mat2x2 {
         if det == 0.0 {
             Err(MathErrors::NoInverse { matrix: *self })
         } else {
+            let det = 1.0 / det;
             Ok(Self {
                 col1: Vec2::new(det * d, -det * c),
                 col2: Vec2::new(-det * b, det * a),
@@ -247,6 +256,126 @@ impl Mul for Mat2x2 {
     }
 }
 
+// Conversions for glam users, e.g. with Bevy or macroquad
+#[cfg(feature = "glam")]
+impl From<glam::Vec2> for Vec2 {
+    fn from(v: glam::Vec2) -> Self {
+        Vec2::new(v.x, v.y)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Vec2> for glam::Vec2 {
+    fn from(v: Vec2) -> Self {
+        glam::Vec2::new(v.x, v.y)
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<glam::Mat2> for Mat2x2 {
+    fn from(m: glam::Mat2) -> Self {
+        Mat2x2::new(m.x_axis.into(), m.y_axis.into())
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Mat2x2> for glam::Mat2 {
+    fn from(m: Mat2x2) -> Self {
+        glam::Mat2::from_cols(m.col1.into(), m.col2.into())
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Vector2<f32>> for Vec2 {
+    fn from(v: nalgebra::Vector2<f32>) -> Self {
+        Vec2::new(v.x, v.y)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Vec2> for nalgebra::Vector2<f32> {
+    fn from(v: Vec2) -> Self {
+        nalgebra::Vector2::new(v.x, v.y)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Point2<f32>> for Vec2 {
+    fn from(p: nalgebra::Point2<f32>) -> Self {
+        Vec2::new(p.x, p.y)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Vec2> for nalgebra::Point2<f32> {
+    fn from(v: Vec2) -> Self {
+        nalgebra::Point2::new(v.x, v.y)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Matrix2<f32>> for Mat2x2 {
+    fn from(m: nalgebra::Matrix2<f32>) -> Self {
+        Mat2x2::new(
+            Vec2::new(m[(0, 0)], m[(1, 0)]),
+            Vec2::new(m[(0, 1)], m[(1, 1)]),
+        )
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Mat2x2> for nalgebra::Matrix2<f32> {
+    fn from(m: Mat2x2) -> Self {
+        nalgebra::Matrix2::from_columns(&[m.col1.into(), m.col2.into()])
+    }
+}
+
+// Conversions for renderers that take mint types, without depending on them
+#[cfg(feature = "mint")]
+impl From<mint::Vector2<f32>> for Vec2 {
+    fn from(v: mint::Vector2<f32>) -> Self {
+        Vec2::new(v.x, v.y)
+    }
+}
+
+#[cfg(feature = "mint")]
+impl From<Vec2> for mint::Vector2<f32> {
+    fn from(v: Vec2) -> Self {
+        mint::Vector2 { x: v.x, y: v.y }
+    }
+}
+
+#[cfg(feature = "mint")]
+impl From<mint::Point2<f32>> for Vec2 {
+    fn from(p: mint::Point2<f32>) -> Self {
+        Vec2::new(p.x, p.y)
+    }
+}
+
+#[cfg(feature = "mint")]
+impl From<Vec2> for mint::Point2<f32> {
+    fn from(v: Vec2) -> Self {
+        mint::Point2 { x: v.x, y: v.y }
+    }
+}
+
+#[cfg(feature = "mint")]
+impl From<mint::ColumnMatrix2<f32>> for Mat2x2 {
+    fn from(m: mint::ColumnMatrix2<f32>) -> Self {
+        Mat2x2::new(m.x.into(), m.y.into())
+    }
+}
+
+#[cfg(feature = "mint")]
+impl From<Mat2x2> for mint::ColumnMatrix2<f32> {
+    fn from(m: Mat2x2) -> Self {
+        mint::ColumnMatrix2 {
+            x: m.col1.into(),
+            y: m.col2.into(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use core::f32;
@@ -292,6 +421,14 @@ mod tests {
         assert_eq!(mat1.transpose().col1.y, -1.0);
     }
 
+    #[test]
+    fn test_invert_scaled() {
+        let mat = Mat2x2::new(Vec2::new(2.0, 0.0), Vec2::new(1.0, 4.0));
+        let res = mat * mat.invert().unwrap();
+        assert_eq!(res.col1, Vec2::new(1.0, 0.0));
+        assert_eq!(res.col2, Vec2::new(0.0, 1.0));
+    }
+
     #[test]
     fn test_mat_ops() {
         let mat1 = Mat2x2::new_from_angle(PI / 4.0);
@@ -309,4 +446,70 @@ mod tests {
         assert!(res.x - f32::consts::FRAC_1_SQRT_2 < f32::EPSILON);
         //println!("{} * {} = {}", mat1, pos, res);
     }
+
+    #[test]
+    fn test_mat_ops_match_scalar() {
+        let a = Mat2x2::new(Vec2::new(0.3, -1.7), Vec2::new(2.9, 0.11));
+        let b = Mat2x2::new_from_angle(0.7);
+        let v = Vec2::new(-4.1, 0.37);
+        let product = a * v;
+        assert_eq!(
+            product.x.to_bits(),
+            (a.col1.x * v.x + a.col2.x * v.y).to_bits()
+        );
+        assert_eq!(
+            product.y.to_bits(),
+            (a.col1.y * v.x + a.col2.y * v.y).to_bits()
+        );
+        let product = a * b;
+        assert_eq!(product.col2.x.to_bits(), (a * b.col2).x.to_bits());
+        assert_eq!(product.col1.y.to_bits(), (a * b.col1).y.to_bits());
+        assert_eq!((a + b).col2.y.to_bits(), (a.col2.y + b.col2.y).to_bits());
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn test_glam() {
+        let v = Vec2::new(1.5, -2.0);
+        let g: glam::Vec2 = v.into();
+        assert_eq!(g, glam::Vec2::new(1.5, -2.0));
+        assert_eq!(Vec2::from(g), v);
+
+        let m = Mat2x2::new_from_angle(0.3);
+        let g: glam::Mat2 = m.into();
+        assert_eq!(g, glam::Mat2::from_angle(0.3));
+        assert_eq!(Vec2::from(g * glam::Vec2::from(v)), m * v);
+        assert_eq!(Mat2x2::from(g), m);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_nalgebra() {
+        let v = Vec2::new(1.5, -2.0);
+        let n: nalgebra::Vector2<f32> = v.into();
+        assert_eq!(n, nalgebra::Vector2::new(1.5, -2.0));
+        assert_eq!(Vec2::from(n), v);
+        assert_eq!(Vec2::from(nalgebra::Point2::from(v)), v);
+
+        let m = Mat2x2::new(Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0));
+        let n: nalgebra::Matrix2<f32> = m.into();
+        assert_eq!(n, nalgebra::Matrix2::new(1.0, 3.0, 2.0, 4.0));
+        assert_eq!(Vec2::from(n * nalgebra::Vector2::from(v)), m * v);
+        assert_eq!(Mat2x2::from(n), m);
+    }
+
+    #[cfg(feature = "mint")]
+    #[test]
+    fn test_mint() {
+        let v = Vec2::new(1.5, -2.0);
+        let m: mint::Vector2<f32> = v.into();
+        assert_eq!(m, mint::Vector2 { x: 1.5, y: -2.0 });
+        assert_eq!(Vec2::from(m), v);
+        assert_eq!(Vec2::from(mint::Point2::from(v)), v);
+
+        let mat = Mat2x2::new(Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0));
+        let m: mint::ColumnMatrix2<f32> = mat.into();
+        assert_eq!(m.y, mint::Vector2 { x: 3.0, y: 4.0 });
+        assert_eq!(Mat2x2::from(m), mat);
+    }
 }
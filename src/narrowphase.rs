@@ -0,0 +1,270 @@
+use crate::arbiter::{ContactInfo, Manifold};
+use crate::body::{Body, Shape, ShapeGeometry};
+use crate::collide::{collide, collide_circle_box, collide_circles};
+use crate::collide_polygon::{
+    collide_capsule, collide_chain, collide_circle_polygon, collide_heightfield, collide_polygons,
+    collide_polygons_gjk, collide_polygons_in, PolygonNarrowphase,
+};
+use crate::scratch::ClipScratch;
+use crate::transforms::Transform;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+const SHAPES: [Shape; 6] = [
+    Shape::Box,
+    Shape::ConvexPolygon,
+    Shape::Capsule,
+    Shape::Circle,
+    Shape::Chain,
+    Shape::Heightfield,
+];
+
+/// Computes the contact points of a pair of bodies.
+///
+/// Implemented for every function with the signature of the built-in ones, like
+/// `collide_circles`.
+pub trait Narrowphase: Send + Sync {
+    /// Appends the contact points of the two bodies to `contacts` and returns how
+    /// many were found. The normals point from `body_1` to `body_2`.
+    fn collide(&self, contacts: &mut Vec<ContactInfo>, body_1: &Body, body_2: &Body) -> i32;
+
+    /// Like `collide`, but may take temporaries from `clip` instead of the heap.
+    fn collide_in(
+        &self,
+        contacts: &mut Vec<ContactInfo>,
+        _clip: &mut ClipScratch,
+        body_1: &Body,
+        body_2: &Body,
+    ) -> i32 {
+        self.collide(contacts, body_1, body_2)
+    }
+}
+
+impl<F> Narrowphase for F
+where
+    F: Fn(&mut Vec<ContactInfo>, &Body, &Body) -> i32 + Send + Sync,
+{
+    fn collide(&self, contacts: &mut Vec<ContactInfo>, body_1: &Body, body_2: &Body) -> i32 {
+        self(contacts, body_1, body_2)
+    }
+}
+
+// The separating axes narrow phase of polygons, clipping in the scratch memory
+// of the step.
+struct SatPolygons;
+
+impl Narrowphase for SatPolygons {
+    fn collide(&self, contacts: &mut Vec<ContactInfo>, body_1: &Body, body_2: &Body) -> i32 {
+        collide_polygons(contacts, body_1, body_2)
+    }
+
+    fn collide_in(
+        &self,
+        contacts: &mut Vec<ContactInfo>,
+        clip: &mut ClipScratch,
+        body_1: &Body,
+        body_2: &Body,
+    ) -> i32 {
+        collide_polygons_in(contacts, clip, body_1, body_2)
+    }
+}
+
+/// Narrow phase to use for each pair of shapes.
+///
+/// A narrow phase registered for `(a, b)` also handles `(b, a)`: it is called
+/// with the bodies swapped and the normals of its contacts are flipped.
+pub struct NarrowphaseTable {
+    colliders: HashMap<(Shape, Shape), Box<dyn Narrowphase>>,
+}
+
+impl NarrowphaseTable {
+    /// Returns a table without any narrow phase, pairs of shapes that are not
+    /// registered never touch.
+    pub fn empty() -> Self {
+        Self {
+            colliders: HashMap::new(),
+        }
+    }
+
+    /// Returns the shared table with the built-in narrow phases.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<NarrowphaseTable> = OnceLock::new();
+        BUILTIN.get_or_init(Self::default)
+    }
+
+    /// Registers the narrow phase of a pair of shapes, replacing the previous one
+    /// of the pair in either order.
+    pub fn register(
+        &mut self,
+        shape_a: Shape,
+        shape_b: Shape,
+        narrowphase: impl Narrowphase + 'static,
+    ) {
+        self.colliders.remove(&(shape_b, shape_a));
+        self.colliders
+            .insert((shape_a, shape_b), Box::new(narrowphase));
+    }
+
+    /// Selects the algorithm used for the pairs of polygons and boxes other than
+    /// box-box.
+    pub fn set_polygon_narrowphase(&mut self, narrowphase: PolygonNarrowphase) {
+        for pair in [
+            (Shape::ConvexPolygon, Shape::ConvexPolygon),
+            (Shape::Box, Shape::ConvexPolygon),
+        ] {
+            match narrowphase {
+                PolygonNarrowphase::Sat => self.register(pair.0, pair.1, SatPolygons),
+                PolygonNarrowphase::Gjk => self.register(pair.0, pair.1, collide_polygons_gjk),
+            }
+        }
+    }
+
+    /// Returns true if a narrow phase is registered for the pair, in either order.
+    pub fn contains(&self, shape_a: Shape, shape_b: Shape) -> bool {
+        self.colliders.contains_key(&(shape_a, shape_b))
+            || self.colliders.contains_key(&(shape_b, shape_a))
+    }
+
+    /// Appends the contact points of the two bodies to `contacts` with the
+    /// narrow phase registered for their shapes and returns how many were found.
+    pub fn collide(&self, contacts: &mut Vec<ContactInfo>, body_1: &Body, body_2: &Body) -> i32 {
+        self.collide_in(contacts, &mut ClipScratch::default(), body_1, body_2)
+    }
+
+    /// Like `collide`, but lets the narrow phase take temporaries from `clip`.
+    pub fn collide_in(
+        &self,
+        contacts: &mut Vec<ContactInfo>,
+        clip: &mut ClipScratch,
+        body_1: &Body,
+        body_2: &Body,
+    ) -> i32 {
+        if let Some(narrowphase) = self.colliders.get(&(body_1.shape, body_2.shape)) {
+            return narrowphase.collide_in(contacts, clip, body_1, body_2);
+        }
+        let Some(narrowphase) = self.colliders.get(&(body_2.shape, body_1.shape)) else {
+            return 0;
+        };
+        let start = contacts.len();
+        let found = narrowphase.collide_in(contacts, clip, body_2, body_1);
+        for contact in contacts[start..].iter_mut() {
+            contact.normal = -contact.normal;
+        }
+        found
+    }
+}
+
+/// Returns the contact points of two shapes placed at the given transforms, with
+/// the normals pointing from `shape_a` to `shape_b`. Uses the built-in narrow
+/// phases.
+pub fn collide_shapes(
+    shape_a: &ShapeGeometry,
+    transform_a: Transform,
+    shape_b: &ShapeGeometry,
+    transform_b: Transform,
+) -> Manifold {
+    let body_a = Body::from_geometry(shape_a, transform_a);
+    let body_b = Body::from_geometry(shape_b, transform_b);
+    let mut points = Vec::new();
+    NarrowphaseTable::builtin().collide(&mut points, &body_a, &body_b);
+    Manifold::from_points(&points)
+}
+
+impl Default for NarrowphaseTable {
+    fn default() -> Self {
+        // Later registrations replace the more general earlier ones
+        let mut table = Self::empty();
+        table.set_polygon_narrowphase(PolygonNarrowphase::Sat);
+        for shape in SHAPES {
+            table.register(Shape::Capsule, shape, collide_capsule);
+        }
+        for shape in SHAPES {
+            table.register(Shape::Circle, shape, collide_circle_polygon);
+        }
+        table.register(Shape::Circle, Shape::Circle, collide_circles);
+        table.register(Shape::Circle, Shape::Box, collide_circle_box);
+        for shape in SHAPES {
+            table.register(Shape::Chain, shape, collide_chain);
+        }
+        for shape in SHAPES {
+            table.register(Shape::Heightfield, shape, collide_heightfield);
+        }
+        table.register(Shape::Box, Shape::Box, collide);
+        table
+    }
+}
+
+impl fmt::Debug for NarrowphaseTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.colliders.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbiter::Arbiter;
+    use crate::math_utils::Vec2;
+
+    #[test]
+    fn test_registered_pair_in_either_order() {
+        let mut table = NarrowphaseTable::empty();
+        let ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        let mut circle = Body::new_circle(0.5, 1.0);
+        circle.position = Vec2::new(0.0, 0.9);
+        let mut contacts = Vec::new();
+        assert_eq!(table.collide(&mut contacts, &ground, &circle), 0);
+
+        // Always pushes the circle up, whatever the order of the bodies
+        table.register(
+            Shape::Circle,
+            Shape::Box,
+            |contacts: &mut Vec<ContactInfo>, _: &Body, _: &Body| {
+                contacts.push(ContactInfo {
+                    normal: Vec2::new(0.0, -1.0),
+                    ..Default::default()
+                });
+                1
+            },
+        );
+        assert!(table.contains(Shape::Box, Shape::Circle));
+        assert_eq!(table.collide(&mut contacts, &circle, &ground), 1);
+        assert_eq!(table.collide(&mut contacts, &ground, &circle), 1);
+        assert_eq!(contacts[0].normal, Vec2::new(0.0, -1.0));
+        assert_eq!(contacts[1].normal, Vec2::new(0.0, 1.0));
+
+        let builtin = NarrowphaseTable::builtin();
+        for a in SHAPES {
+            for b in SHAPES {
+                assert!(builtin.contains(a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn test_collide_shapes() {
+        let ground = ShapeGeometry::new_box(Vec2::new(10.0, 1.0));
+        let circle = ShapeGeometry::new_circle(0.5);
+        let at = |x: f32, y: f32| Transform::new(Vec2::new(x, y), 0.0);
+
+        let contacts = collide_shapes(&circle, at(1.0, 0.9), &ground, at(0.0, 0.0));
+        assert_eq!(contacts.point_count(), 1);
+        assert_eq!(contacts.normal, Vec2::new(0.0, -1.0));
+        assert!((contacts.points[0].separation + 0.1).abs() < 1e-5);
+        assert!(collide_shapes(&circle, at(1.0, 1.1), &ground, at(0.0, 0.0)).is_empty());
+
+        // Same result as the bodies
+        let mut body = Body::new_capsule(2.0, 0.5, 1.0);
+        body.position = Vec2::new(0.0, 0.95);
+        let ground_body = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        let contacts = collide_shapes(
+            &ground_body.geometry(),
+            at(0.0, 0.0),
+            &body.geometry(),
+            at(0.0, 0.95),
+        );
+        assert_eq!(contacts.point_count(), 2);
+        assert_eq!(Arbiter::new(&ground_body, &body).manifold.point_count(), 2);
+    }
+}
@@ -0,0 +1,1249 @@
+#[cfg(feature = "parallel")]
+use crate::arbiter::ContactInfo;
+use crate::arbiter::{Arbiter, ArbiterKey, ContactModification, FrictionCombine, Manifold};
+use crate::body::{Body, BodyType};
+use crate::broad_phase::{BroadPhaseMode, Broadphase, Proxies};
+use crate::ccd::sweep_bullet;
+use crate::errors::Sylt2DErrors;
+use crate::island::Islands;
+use crate::joint::{Joint, JointImpulse};
+use crate::math_utils::Vec2;
+use crate::narrowphase::NarrowphaseTable;
+#[cfg(feature = "parallel")]
+use crate::scratch::ClipScratch;
+use crate::scratch::Scratch;
+use crate::solver::{
+    PositionSolver, Softness, Solver, CONTACT_DAMPING_RATIO, CONTACT_HERTZ, JOINT_DAMPING_RATIO,
+};
+use crate::transforms::Transform;
+use crate::world::WorldContext;
+use std::collections::{btree_map, BTreeMap, HashMap};
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+use std::vec::Drain;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Measures the time spent in a stage of the step.
+///
+/// `Instant::now` panics on wasm32 without a clock, so there the stages are not
+/// timed and their statistics stay zero.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the milliseconds since `start`.
+    pub(crate) fn elapsed_ms(&self) -> f32 {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed().as_secs_f32() * 1000.0;
+        #[cfg(target_arch = "wasm32")]
+        0.0
+    }
+}
+
+/// Returns mutable references to two different bodies of the storage.
+pub(crate) fn pair_mut(bodies: &mut [Body], i: usize, j: usize) -> (&mut Body, &mut Body) {
+    if i < j {
+        let (left, right) = bodies.split_at_mut(j);
+        (&mut left[i], &mut right[0])
+    } else {
+        let (left, right) = bodies.split_at_mut(i);
+        (&mut right[0], &mut left[j])
+    }
+}
+
+/// Statistics of the last step.
+///
+/// Each stage records its own numbers, so they come from the last run of the
+/// stage, e.g. the last substep of `World::step_substeps`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct StepStats {
+    /// Bytes of scratch memory used by the step.
+    pub scratch_bytes: usize,
+    /// Largest number of scratch bytes used by any step so far.
+    pub scratch_high_water_mark: usize,
+    /// Milliseconds spent finding the pairs and their contact points.
+    pub broadphase_ms: f32,
+    /// Milliseconds spent solving the contacts and joints.
+    pub solve_ms: f32,
+    /// Milliseconds spent integrating the forces and velocities.
+    pub integrate_ms: f32,
+    /// Number of pairs the narrow phase checked for contacts.
+    pub pairs_tested: usize,
+    /// Number of contact points found.
+    pub contacts: usize,
+    /// Number of islands solved.
+    pub islands: usize,
+}
+
+/// Accumulated impulses of the contacts and joints.
+///
+/// The solver starts every step from these impulses. A saved world has to carry
+/// them to continue exactly where it left off, otherwise the restored bodies
+/// briefly sink into each other and pop back out.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WarmStart {
+    /// Contact points of each touching pair, ordered by the body ids.
+    pub contacts: Vec<(ArbiterKey, Manifold)>,
+    /// Impulses of each joint, in the order of the joints.
+    pub joints: Vec<JointImpulse>,
+}
+
+/// A pair of bodies starting or stopping to touch, see
+/// `PhysicsPipeline::drain_events`.
+#[derive(Debug, Clone, Copy)]
+pub enum ContactEvent {
+    /// The pair touches for the first time, with its first contact points.
+    Begin { key: ArbiterKey, manifold: Manifold },
+    /// The pair stopped touching or one of the bodies was moved or removed,
+    /// with the contact points left at that time.
+    End { key: ArbiterKey, manifold: Manifold },
+}
+
+/// Impulse the solver applied at one contact point over the last step, see
+/// `PhysicsPipeline::contact_impulses`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactImpulse {
+    pub key: ArbiterKey,
+    pub point: Vec2,
+    /// Points from the first body of `key` to the second.
+    pub normal: Vec2,
+    pub normal_impulse: f32,
+    pub tangent_impulse: f32,
+}
+
+/// The simulation step, decoupled from body storage.
+///
+/// The pipeline only keeps the contact cache between steps. Bodies and joints are
+/// passed in on every step, so they can live in user storage (e.g. ECS
+/// components) instead of being copied into a `World`. Bodies are matched to
+/// their contacts and joints by id, so the storage may be reordered between steps.
+#[derive(Debug, Default)]
+pub struct PhysicsPipeline {
+    /// Contacts of the touching pairs. Ordered by the body ids, so the solver
+    /// visits them in the same order in every run.
+    pub(crate) arbiters: BTreeMap<ArbiterKey, Arbiter>,
+    pub proxies: Proxies,
+    /// Narrow phase of each pair of shapes.
+    pub narrowphase: NarrowphaseTable,
+    pairs: Vec<ArbiterKey>,
+    broadphase: Box<dyn Broadphase>,
+    // Kind of `broadphase`, replaced when the world context asks for another
+    broadphase_mode: BroadPhaseMode,
+    contact_filter: Option<ContactFilter>,
+    pre_solve: Option<PreSolve>,
+    // Friction rules of pairs of bodies, see `set_pair_friction_combine`
+    pair_friction: BTreeMap<ArbiterKey, FrictionCombine>,
+    candidates: Vec<(usize, usize)>,
+    scratch: Scratch,
+    stats: StepStats,
+    body_indices: HashMap<usize, usize>,
+    joint_indices: Vec<(usize, usize)>,
+    // Bodies of the rings of area joints tied to the body they are solved with
+    joint_links: Vec<(usize, usize)>,
+    contact_indices: Vec<(usize, usize)>,
+    islands: Islands,
+    // Copies of the enabled arbiters the solver works on, written back to
+    // `arbiters` after solving
+    solver_arbiters: Vec<Arbiter>,
+    // Distance and angle each body moved during the substeps of the soft solver
+    offsets: Vec<(Vec2, f32)>,
+    // Positions and rotations of the bodies before the position solver
+    start_poses: Vec<(Vec2, f32)>,
+    events: Vec<ContactEvent>,
+    // Events produced by the last step, older ones are dropped by the next
+    step_events: usize,
+    impulses: Vec<ContactImpulse>,
+    // Pairs of a bullet and the body it hits, solved for one step only
+    bullet_keys: Vec<ArbiterKey>,
+    // Pairs passed on to the narrow phase with the storage indices of their
+    // bodies, and the contacts found for them
+    narrow_pairs: Vec<(ArbiterKey, (usize, usize))>,
+    manifolds: Vec<Manifold>,
+}
+
+// Fewest pairs collided by one task of the parallel narrow phase.
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_PAIRS: usize = 32;
+
+#[cfg(feature = "parallel")]
+thread_local! {
+    // Temporaries of the narrow phase on each thread, kept between steps
+    static NARROW_SCRATCH: std::cell::RefCell<(Vec<ContactInfo>, ClipScratch)> =
+        std::cell::RefCell::default();
+}
+
+// Game rules deciding which pairs may touch, see `set_contact_filter`
+struct ContactFilter(Box<FilterFn>);
+
+type FilterFn = dyn Fn(&Body, &Body) -> bool + Send + Sync;
+
+impl fmt::Debug for ContactFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ContactFilter")
+    }
+}
+
+// Changes to the contacts before solving, see `set_pre_solve`
+struct PreSolve(Box<PreSolveFn>);
+
+type PreSolveFn = dyn Fn(&Body, &Body, &mut Arbiter) + Send + Sync;
+
+impl fmt::Debug for PreSolve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PreSolve")
+    }
+}
+
+// Distance and angle the body moved during the substeps so far. Bodies outside
+// the islands keep their velocity.
+pub(crate) fn offset(
+    bodies: &[Body],
+    offsets: &[(Vec2, f32)],
+    index: usize,
+    elapsed: f32,
+) -> (Vec2, f32) {
+    let body = &bodies[index];
+    if !body.is_dynamic() {
+        (body.velocity * elapsed, body.angular_velocity * elapsed)
+    } else {
+        offsets[index]
+    }
+}
+
+impl PhysicsPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets all cached contacts.
+    pub fn clear(&mut self) {
+        self.arbiters.clear();
+        self.pairs.clear();
+    }
+
+    /// Determines overlapping bodies and updates the cached contacts.
+    pub fn broad_phase(
+        &mut self,
+        bodies: &[Body],
+        world_context: &WorldContext,
+    ) -> Result<(), Sylt2DErrors> {
+        let start = Stopwatch::start();
+        if world_context.broad_phase != self.broadphase_mode {
+            self.broadphase_mode = world_context.broad_phase;
+            if let Some(broadphase) = self.broadphase_mode.build() {
+                self.broadphase = broadphase;
+                self.proxies = Proxies::default();
+            }
+        }
+        self.pairs.clear();
+        self.scratch.reset();
+        // Events nobody drained during the last step are dropped, the ones
+        // pushed since by `remove_contacts_of` are kept
+        self.events.drain(..self.step_events);
+        {
+            span!("broad_phase");
+            self.proxies.update(bodies, self.broadphase.as_mut());
+            self.broadphase.query_pairs(&mut self.candidates);
+        }
+        self.body_indices.clear();
+        self.body_indices.extend(
+            bodies
+                .iter()
+                .enumerate()
+                .map(|(index, body)| (body.id, index)),
+        );
+
+        span!("narrow_phase");
+        self.narrow_pairs.clear();
+        for (id_i, id_j) in self.candidates.iter() {
+            // A custom broad phase may report bodies that are gone
+            let (Some(&i), Some(&j)) = (self.body_indices.get(id_i), self.body_indices.get(id_j))
+            else {
+                continue;
+            };
+            if i == j {
+                continue;
+            }
+            let (body_i, body_j) = (&bodies[i], &bodies[j]);
+            if !body_i.is_dynamic() && !body_j.is_dynamic() {
+                continue;
+            };
+            if body_i.ignores(body_j) {
+                continue;
+            }
+            if let Some(ContactFilter(filter)) = &self.contact_filter {
+                if !filter(body_i, body_j) {
+                    continue;
+                }
+            }
+            let key = ArbiterKey::new(body_i, body_j);
+            self.pairs.push(key);
+            let indices = if body_i.id < body_j.id {
+                (i, j)
+            } else {
+                (j, i)
+            };
+            self.narrow_pairs.push((key, indices));
+        }
+        self.collide_pairs(bodies);
+
+        for (&(key, indices), manifold) in self.narrow_pairs.iter().zip(self.manifolds.iter()) {
+            // Touching pairs keep their arbiter and only refresh the contacts
+            if let Some(arbiter) = self.arbiters.get_mut(&key) {
+                arbiter.update(manifold, world_context);
+                arbiter.indices = indices;
+                continue;
+            }
+            if !manifold.is_empty() {
+                let mut arbiter = Arbiter::from_manifold(*manifold);
+                arbiter.indices = indices;
+                self.events.push(ContactEvent::Begin {
+                    key,
+                    manifold: arbiter.manifold,
+                });
+                self.arbiters.insert(key, arbiter);
+            }
+        }
+        // Drop the pairs that separated or left the broad phase
+        self.pairs.sort_unstable();
+        let (pairs, events) = (&self.pairs, &mut self.events);
+        self.arbiters.retain(|key, arbiter| {
+            let touching = !arbiter.manifold.is_empty() && pairs.binary_search(key).is_ok();
+            if !touching {
+                events.push(ContactEvent::End {
+                    key: *key,
+                    manifold: arbiter.manifold,
+                });
+            }
+            touching
+        });
+        self.step_events = self.events.len();
+        self.stats.scratch_bytes = self.scratch.used();
+        self.stats.scratch_high_water_mark = self.scratch.high_water_mark();
+        self.stats.pairs_tested = self.pairs.len();
+        self.stats.contacts = self
+            .arbiters
+            .values()
+            .map(|arbiter| arbiter.manifold.point_count())
+            .sum();
+        self.stats.broadphase_ms = start.elapsed_ms();
+        Ok(())
+    }
+
+    // Finds the contact points of `narrow_pairs` into `manifolds`, on all cores
+    // with the `parallel` feature.
+    #[cfg(not(feature = "parallel"))]
+    fn collide_pairs(&mut self, bodies: &[Body]) {
+        self.manifolds.clear();
+        for &(_, (i, j)) in self.narrow_pairs.iter() {
+            let points = self.scratch.alloc_contacts(|contacts, clip| {
+                self.narrowphase
+                    .collide_in(contacts, clip, &bodies[i], &bodies[j]);
+            });
+            self.manifolds.push(Manifold::from_points(points));
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    fn collide_pairs(&mut self, bodies: &[Body]) {
+        let narrowphase = &self.narrowphase;
+        self.manifolds.clear();
+        self.manifolds.par_extend(
+            self.narrow_pairs
+                .par_iter()
+                .with_min_len(PARALLEL_MIN_PAIRS)
+                .map(|&(_, (i, j))| {
+                    NARROW_SCRATCH.with_borrow_mut(|(contacts, clip)| {
+                        contacts.clear();
+                        narrowphase.collide_in(contacts, clip, &bodies[i], &bodies[j]);
+                        Manifold::from_points(contacts)
+                    })
+                }),
+        );
+    }
+
+    /// Sets a function called with each touching pair before it is solved, the
+    /// bodies in the order of the arbiter. It can turn the contacts off or
+    /// change them for this step through `Arbiter::modification` and the
+    /// contact points, e.g. for one-way platforms or ladders.
+    pub fn set_pre_solve(
+        &mut self,
+        pre_solve: impl Fn(&Body, &Body, &mut Arbiter) + Send + Sync + 'static,
+    ) {
+        self.pre_solve = Some(PreSolve(Box::new(pre_solve)));
+    }
+
+    /// Sets how the frictions of the bodies with the given ids combine when
+    /// they touch, over `Body::friction_combine` and
+    /// `WorldContext::friction_combine`. None goes back to the rules of the
+    /// bodies. Like the callbacks, the rules are not part of snapshots or
+    /// replays.
+    pub fn set_pair_friction_combine(
+        &mut self,
+        id_1: usize,
+        id_2: usize,
+        rule: Option<FrictionCombine>,
+    ) {
+        let key = ArbiterKey::from_ids(id_1, id_2);
+        match rule {
+            Some(rule) => self.pair_friction.insert(key, rule),
+            None => self.pair_friction.remove(&key),
+        };
+    }
+
+    /// Forgets the friction rules of the pairs of the body with the given id,
+    /// e.g. after it was removed.
+    pub fn remove_pair_rules_of(&mut self, id: usize) {
+        self.pair_friction.retain(|key, _| !key.involves(id));
+    }
+
+    /// Forgets the contacts of the body with the given id, e.g. after it was
+    /// moved or removed, ending them as in `drain_events`.
+    pub fn remove_contacts_of(&mut self, id: usize) {
+        let events = &mut self.events;
+        self.arbiters.retain(|key, arbiter| {
+            if key.involves(id) {
+                events.push(ContactEvent::End {
+                    key: *key,
+                    manifold: arbiter.manifold,
+                });
+            }
+            !key.involves(id)
+        });
+    }
+
+    /// Iterates over the contacts of the touching pairs, ordered by the body
+    /// ids.
+    pub fn iter_arbiters(&self) -> btree_map::Iter<'_, ArbiterKey, Arbiter> {
+        self.arbiters.iter()
+    }
+
+    /// Returns the impulses applied at the contact points by the last `solve`,
+    /// e.g. to play a sound as loud as an impact.
+    pub fn contact_impulses(&self) -> &[ContactImpulse] {
+        &self.impulses
+    }
+
+    /// Removes and returns the pairs that started or stopped touching since the
+    /// last call, oldest first. Events are kept until the step after the one
+    /// that produced them, so drain them after every step.
+    pub fn drain_events(&mut self) -> Drain<'_, ContactEvent> {
+        self.step_events = 0;
+        self.events.drain(..)
+    }
+
+    /// Replaces the algorithm finding the pairs of overlapping bounding boxes
+    /// with an implementation of its own. It is used while
+    /// `WorldContext::broad_phase` is `BroadPhaseMode::Custom`.
+    pub fn set_broadphase(&mut self, broadphase: impl Broadphase + 'static) {
+        self.broadphase = Box::new(broadphase);
+        self.broadphase_mode = BroadPhaseMode::Custom;
+        // Lets the new broad phase learn all boxes
+        self.proxies = Proxies::default();
+    }
+
+    /// Sets a function called with each pair of overlapping bodies before the
+    /// narrow phase. Pairs it returns false for do not touch, on top of
+    /// `Body::collision_filter`.
+    pub fn set_contact_filter(
+        &mut self,
+        filter: impl Fn(&Body, &Body) -> bool + Send + Sync + 'static,
+    ) {
+        self.contact_filter = Some(ContactFilter(Box::new(filter)));
+    }
+
+    /// Returns the islands of bodies solved by the last step.
+    pub fn islands(&self) -> &Islands {
+        &self.islands
+    }
+
+    /// Returns the statistics of the last step.
+    pub fn last_step_stats(&self) -> StepStats {
+        self.stats
+    }
+
+    /// Records the time spent in `integrate_forces` and `integrate_velocities`,
+    /// which do not have access to the statistics.
+    pub fn record_integrate_time(&mut self, milliseconds: f32) {
+        self.stats.integrate_ms = milliseconds;
+    }
+
+    /// Returns the accumulated impulses of the cached contacts and the joints.
+    pub fn warm_start(&self, joints: &[Joint]) -> WarmStart {
+        WarmStart {
+            contacts: self
+                .arbiters
+                .iter()
+                .map(|(key, arbiter)| (*key, arbiter.manifold))
+                .collect(),
+            joints: joints.iter().map(Joint::accumulated_impulse).collect(),
+        }
+    }
+
+    /// Replaces the cached contacts and the joint impulses, so the next step
+    /// continues from the given warm start data. Fails without changing
+    /// anything if a contact refers to a body that is not in `bodies`.
+    pub fn set_warm_start(
+        &mut self,
+        warm_start: &WarmStart,
+        bodies: &[Body],
+        joints: &mut [Joint],
+    ) -> Result<(), Sylt2DErrors> {
+        self.body_indices.clear();
+        self.body_indices.extend(
+            bodies
+                .iter()
+                .enumerate()
+                .map(|(index, body)| (body.id, index)),
+        );
+        for (key, _) in warm_start.contacts.iter() {
+            let (id_1, id_2) = key.ids();
+            for id in [id_1, id_2] {
+                if !self.body_indices.contains_key(&id) {
+                    return Err(Sylt2DErrors::BodyNotFound(id));
+                }
+            }
+        }
+        self.arbiters.clear();
+        self.arbiters.extend(
+            warm_start
+                .contacts
+                .iter()
+                .map(|(key, manifold)| (*key, Arbiter::from_manifold(*manifold))),
+        );
+        for (joint, impulse) in joints.iter_mut().zip(warm_start.joints.iter()) {
+            joint.set_accumulated_impulse(*impulse);
+        }
+        Ok(())
+    }
+
+    /// Returns the candidate pairs that passed the broad phase of the last
+    /// update, whether or not the narrow phase found contacts for them.
+    pub fn broad_phase_pairs(&self) -> &[ArbiterKey] {
+        &self.pairs
+    }
+
+    /// Advances the bodies and joints by `dt` seconds.
+    ///
+    /// Convenience wrapper running `integrate_forces`, `update_contacts`, `solve`
+    /// and `integrate_velocities` in that order.
+    pub fn step(
+        &mut self,
+        bodies: &mut [Body],
+        joints: &mut [Joint],
+        gravity: Vec2,
+        iterations: u32,
+        world_context: &WorldContext,
+        dt: f32,
+    ) -> Result<(), Sylt2DErrors> {
+        let start = Stopwatch::start();
+        Self::integrate_forces(bodies, gravity, world_context, dt);
+        let integrate_forces = start.elapsed_ms();
+        self.update_contacts(bodies, world_context)?;
+        self.solve(bodies, joints, gravity, iterations, world_context, dt)?;
+        let start = Stopwatch::start();
+        Self::integrate_velocities(bodies, dt);
+        self.record_integrate_time(integrate_forces + start.elapsed_ms());
+        Ok(())
+    }
+
+    /// Applies gravity, wind, aerodynamic and the accumulated forces to the
+    /// velocities, then the floor friction of top-down worlds.
+    ///
+    /// Starts the step, so it also remembers the pose of every body as
+    /// `Body::previous_pose`.
+    pub fn integrate_forces(
+        bodies: &mut [Body],
+        gravity: Vec2,
+        world_context: &WorldContext,
+        dt: f32,
+    ) {
+        span!("integrate_forces");
+        for body in bodies.iter_mut() {
+            body.previous_pose = Some(Transform::new(body.position, body.rotation));
+            if !body.is_dynamic() {
+                continue;
+            };
+            let wind = world_context.wind;
+            if let Some(aerodynamics) = body.aerodynamics {
+                let air = wind.map_or(Vec2::default(), |wind| wind.velocity_at(world_context.time));
+                let (force, torque) =
+                    aerodynamics.force_and_torque_in_wind(body, world_context.air_density, air);
+                body.add_force(force);
+                body.torque += torque;
+            } else if let Some(wind) = wind {
+                body.add_force(wind.force(body, world_context.time));
+            }
+            let gravity = body.gravity.unwrap_or(gravity);
+            body.velocity = body.velocity + (gravity + body.force * body.inv_mass) * dt;
+            body.angular_velocity += body.inv_moi * body.torque * dt;
+            if let Some(floor_friction) = world_context.floor_friction {
+                floor_friction.apply(body, dt);
+            }
+        }
+    }
+
+    /// Determines overlapping bodies and updates the contact points.
+    pub fn update_contacts(
+        &mut self,
+        bodies: &[Body],
+        world_context: &WorldContext,
+    ) -> Result<(), Sylt2DErrors> {
+        self.broad_phase(bodies, world_context)
+    }
+
+    /// Solves the contacts found by the last `update_contacts` and the joints,
+    /// changing the velocities of the bodies.
+    ///
+    /// The soft solver moves the bodies through its substeps. It applies the
+    /// difference to moving them by their final velocity to the positions, so
+    /// `integrate_velocities` completes the motion. The non-linear Gauss-Seidel
+    /// position solver also moves the bodies, see `PositionSolver`.
+    pub fn solve(
+        &mut self,
+        bodies: &mut [Body],
+        joints: &mut [Joint],
+        gravity: Vec2,
+        iterations: u32,
+        world_context: &WorldContext,
+        dt: f32,
+    ) -> Result<(), Sylt2DErrors> {
+        let start = Stopwatch::start();
+        span!("solve");
+        let inv_dt = if dt > 0.0 { 1.0 / dt } else { 0.0 };
+
+        // Resolve the bodies of the contacts and joints
+        self.body_indices.clear();
+        self.body_indices.extend(
+            bodies
+                .iter()
+                .enumerate()
+                .map(|(index, body)| (body.id, index)),
+        );
+        self.add_bullet_contacts(bodies, dt);
+        for (key, arbiter) in self.arbiters.iter_mut() {
+            let (id_1, id_2) = key.ids();
+            let index_1 = *self
+                .body_indices
+                .get(&id_1)
+                .ok_or(Sylt2DErrors::BodyNotFound(id_1))?;
+            let index_2 = *self
+                .body_indices
+                .get(&id_2)
+                .ok_or(Sylt2DErrors::BodyNotFound(id_2))?;
+            arbiter.indices = (index_1, index_2);
+            arbiter.modification = ContactModification {
+                friction_combine: self.pair_friction.get(key).copied(),
+                ..Default::default()
+            };
+            if let Some(PreSolve(pre_solve)) = &self.pre_solve {
+                pre_solve(&bodies[index_1], &bodies[index_2], arbiter);
+            }
+        }
+        self.joint_indices.clear();
+        self.joint_links.clear();
+        for joint in joints.iter_mut() {
+            joint.resolve(bodies, &self.body_indices)?;
+            self.joint_indices.push(joint.indices());
+            let (hub, _) = joint.indices();
+            self.joint_links
+                .extend(joint.ring().iter().map(|&index| (hub, index)));
+        }
+
+        // Let moving platforms carry their riders
+        let gravity_length = gravity.length();
+        if gravity_length > 0.0 {
+            let up = gravity * (-1.0 / gravity_length);
+            for arbiter in self.enabled_arbiters_mut() {
+                let (index_1, index_2) = arbiter.indices;
+                arbiter.carry_rider(&bodies[index_1], &bodies[index_2], up);
+            }
+        }
+
+        self.contact_indices.clear();
+        self.contact_indices.extend(
+            self.arbiters
+                .values()
+                .filter(|arbiter| arbiter.modification.enabled)
+                .map(|arbiter| arbiter.indices),
+        );
+        self.islands.build(
+            bodies,
+            &self.contact_indices,
+            &self.joint_indices,
+            &self.joint_links,
+            &mut self.scratch,
+        );
+        self.stats.scratch_bytes = self.scratch.used();
+        self.stats.scratch_high_water_mark = self.scratch.high_water_mark();
+        // The solver works on copies of the enabled arbiters, the islands refer
+        // to them by their position among these
+        let mut arbiters = std::mem::take(&mut self.solver_arbiters);
+        arbiters.clear();
+        arbiters.extend(
+            self.arbiters
+                .values()
+                .filter(|arbiter| arbiter.modification.enabled),
+        );
+        let result = if let Solver::Soft { .. } = world_context.solver {
+            self.solve_soft(&mut arbiters, bodies, joints, gravity, world_context, dt)
+        } else {
+            self.solve_impulse(
+                &mut arbiters,
+                bodies,
+                joints,
+                iterations,
+                world_context,
+                inv_dt,
+            )
+        };
+        for (arbiter, solved) in self.enabled_arbiters_mut().zip(arbiters.iter()) {
+            *arbiter = *solved;
+        }
+        self.solver_arbiters = arbiters;
+        result?;
+        // The soft solver accumulates the impulse of one substep
+        let scale = match world_context.solver {
+            Solver::Soft { substeps } => substeps.max(1) as f32,
+            _ => 1.0,
+        };
+        self.impulses.clear();
+        for (key, arbiter) in self.arbiters.iter() {
+            if !arbiter.modification.enabled {
+                continue;
+            }
+            self.impulses.extend(
+                arbiter
+                    .manifold
+                    .points
+                    .iter()
+                    .map(|contact| ContactImpulse {
+                        key: *key,
+                        point: contact.position,
+                        normal: contact.normal,
+                        normal_impulse: contact.pn * scale,
+                        tangent_impulse: contact.pt * scale,
+                    }),
+            );
+        }
+        for key in self.bullet_keys.drain(..) {
+            self.arbiters.remove(&key);
+        }
+        self.stats.islands = self.islands.len();
+        self.stats.solve_ms = start.elapsed_ms();
+        Ok(())
+    }
+
+    // Adds a speculative contact between each bullet and the first body it
+    // would pass through during the step, so the solver stops it there with
+    // the friction and restitution of the pair. These contacts start and end
+    // no events.
+    fn add_bullet_contacts(&mut self, bodies: &[Body], dt: f32) {
+        let contact_filter = &self.contact_filter;
+        let filter = |body_1: &Body, body_2: &Body| match contact_filter {
+            Some(ContactFilter(filter)) => filter(body_1, body_2),
+            None => true,
+        };
+        for (index, bullet) in bodies.iter().enumerate() {
+            if !bullet.bullet || !bullet.is_dynamic() {
+                continue;
+            }
+            let Some(impact) = sweep_bullet(bodies, index, dt, filter) else {
+                continue;
+            };
+            let other = &bodies[impact.other];
+            let key = ArbiterKey::new(bullet, other);
+            let normal = if bullet.id < other.id {
+                impact.normal
+            } else {
+                -impact.normal
+            };
+            if let btree_map::Entry::Vacant(entry) = self.arbiters.entry(key) {
+                let mut arbiter = Arbiter::from_impact(impact.point, normal, impact.separation);
+                arbiter.indices = if bullet.id < other.id {
+                    (index, impact.other)
+                } else {
+                    (impact.other, index)
+                };
+                entry.insert(arbiter);
+                self.bullet_keys.push(key);
+            }
+        }
+    }
+
+    fn enabled_arbiters_mut(&mut self) -> impl Iterator<Item = &mut Arbiter> {
+        self.arbiters
+            .values_mut()
+            .filter(|arbiter| arbiter.modification.enabled)
+    }
+
+    // Sequential impulses with a Baumgarte bias, see `Solver::Impulse`.
+    fn solve_impulse(
+        &mut self,
+        arbiters: &mut [Arbiter],
+        bodies: &mut [Body],
+        joints: &mut [Joint],
+        iterations: u32,
+        world_context: &WorldContext,
+        inv_dt: f32,
+    ) -> Result<(), Sylt2DErrors> {
+        for island in 0..self.islands.len() {
+            let (contacts, island_joints) =
+                (self.islands.contacts(island), self.islands.joints(island));
+
+            // Pefrom pre-steps
+            {
+                span!("pre_step");
+                for &contact in contacts {
+                    let arbiter = &mut arbiters[contact];
+                    let (body1, body2) = pair_mut(bodies, arbiter.indices.0, arbiter.indices.1);
+                    arbiter.pre_step(body1, body2, inv_dt, world_context);
+                }
+
+                for &joint in island_joints {
+                    joints[joint].pre_step(bodies, world_context, inv_dt)?;
+                }
+            }
+
+            // Perfrom iterations
+            span!("solve_iterations");
+            for _ in 0..iterations {
+                for &contact in contacts {
+                    let arbiter = &mut arbiters[contact];
+                    let (body1, body2) = pair_mut(bodies, arbiter.indices.0, arbiter.indices.1);
+                    arbiter.apply_impulse(body1, body2, world_context);
+                }
+
+                for &joint in island_joints {
+                    joints[joint].apply_impulse(bodies);
+                }
+            }
+        }
+
+        if let (true, PositionSolver::NonlinearGaussSeidel { iterations }) = (
+            world_context.position_correction,
+            world_context.position_solver,
+        ) {
+            span!("position_iterations");
+            self.start_poses.clear();
+            self.start_poses
+                .extend(bodies.iter().map(|body| (body.position, body.rotation)));
+            for island in 0..self.islands.len() {
+                let (contacts, island_joints) =
+                    (self.islands.contacts(island), self.islands.joints(island));
+                for _ in 0..iterations {
+                    let mut min_separation = 0.0_f32;
+                    for &contact in contacts {
+                        let arbiter = &arbiters[contact];
+                        let (i, j) = arbiter.indices;
+                        let (start1, start2) = (self.start_poses[i], self.start_poses[j]);
+                        let (body1, body2) = pair_mut(bodies, i, j);
+                        let separation =
+                            arbiter.solve_position(body1, body2, start1, start2, world_context);
+                        min_separation = min_separation.min(separation);
+                    }
+                    let mut max_error = 0.0_f32;
+                    for &joint in island_joints {
+                        max_error = max_error.max(joints[joint].solve_position(bodies)?);
+                    }
+                    let allowed_penetration = world_context.allowed_penetration;
+                    if min_separation >= -3.0 * allowed_penetration
+                        && max_error <= allowed_penetration
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Soft constraints with substeps, see `Solver::Soft`.
+    fn solve_soft(
+        &mut self,
+        arbiters: &mut [Arbiter],
+        bodies: &mut [Body],
+        joints: &mut [Joint],
+        gravity: Vec2,
+        world_context: &WorldContext,
+        dt: f32,
+    ) -> Result<(), Sylt2DErrors> {
+        let substeps = match world_context.solver {
+            Solver::Soft { substeps } => substeps.max(1),
+            _ => 1,
+        };
+        let h = dt / substeps as f32;
+        let inv_h = if h > 0.0 { 1.0 / h } else { 0.0 };
+        let contact_hertz = f32::min(CONTACT_HERTZ, 0.25 * inv_h);
+        let contact_softness = Softness::new(contact_hertz, CONTACT_DAMPING_RATIO, h);
+        let joint_softness = Softness::new(2.0 * contact_hertz, JOINT_DAMPING_RATIO, h);
+        let offsets = &mut self.offsets;
+        offsets.clear();
+        offsets.resize(bodies.len(), (Vec2::default(), 0.0));
+
+        for island in 0..self.islands.len() {
+            let (island_bodies, contacts, island_joints) = (
+                self.islands.bodies(island),
+                self.islands.contacts(island),
+                self.islands.joints(island),
+            );
+
+            // Spread the forces applied by `integrate_forces` over the substeps
+            for &index in island_bodies {
+                let body = &mut bodies[index];
+                let gravity = body.gravity.unwrap_or(gravity);
+                body.velocity = body.velocity - (gravity + body.force * body.inv_mass) * dt;
+                body.angular_velocity -= body.inv_moi * body.torque * dt;
+            }
+            {
+                span!("pre_step");
+                for &contact in contacts {
+                    let arbiter = &mut arbiters[contact];
+                    let (body1, body2) = pair_mut(bodies, arbiter.indices.0, arbiter.indices.1);
+                    arbiter.prepare_soft(body1, body2, world_context);
+                }
+                for &joint in island_joints {
+                    joints[joint].prepare_soft(bodies, world_context)?;
+                }
+            }
+
+            for substep in 0..substeps {
+                span!("substep");
+                for &index in island_bodies {
+                    let body = &mut bodies[index];
+                    let gravity = body.gravity.unwrap_or(gravity);
+                    body.velocity = body.velocity + (gravity + body.force * body.inv_mass) * h;
+                    body.angular_velocity += body.inv_moi * body.torque * h;
+                }
+                for &contact in contacts {
+                    let arbiter = &arbiters[contact];
+                    let (body1, body2) = pair_mut(bodies, arbiter.indices.0, arbiter.indices.1);
+                    arbiter.warm_start(body1, body2);
+                }
+                for &joint in island_joints {
+                    joints[joint].warm_start(bodies);
+                }
+
+                // Solve with the bias, move the bodies and relax without it
+                for relax in [false, true] {
+                    if relax {
+                        for &index in island_bodies {
+                            let body = &bodies[index];
+                            let offset = &mut offsets[index];
+                            offset.0 = offset.0 + body.velocity * h;
+                            offset.1 += body.angular_velocity * h;
+                        }
+                    }
+                    let elapsed = h * (substep + relax as u32) as f32;
+                    let softness = (!relax).then_some(contact_softness);
+                    for &contact in contacts {
+                        let arbiter = &mut arbiters[contact];
+                        let (i, j) = arbiter.indices;
+                        let (offset1, offset2) = (
+                            offset(bodies, offsets, i, elapsed),
+                            offset(bodies, offsets, j, elapsed),
+                        );
+                        let (body1, body2) = pair_mut(bodies, i, j);
+                        arbiter.solve_soft(body1, body2, offset1, offset2, softness, inv_h);
+                    }
+                    let softness = if relax {
+                        Softness::rigid()
+                    } else {
+                        joint_softness
+                    };
+                    for &joint in island_joints {
+                        joints[joint].solve_soft(bodies, offsets, elapsed, softness, inv_h);
+                    }
+                }
+            }
+
+            for &contact in contacts {
+                let arbiter = &mut arbiters[contact];
+                let (body1, body2) = pair_mut(bodies, arbiter.indices.0, arbiter.indices.1);
+                arbiter.apply_restitution(body1, body2);
+            }
+
+            // Leave the motion at the final velocity to `integrate_velocities`
+            for &index in island_bodies {
+                let body = &mut bodies[index];
+                let (distance, angle) = offsets[index];
+                body.position = body.position + distance - body.velocity * dt;
+                body.rotation += angle - body.angular_velocity * dt;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the bodies other than the static ones by their velocities and
+    /// clears the accumulated forces.
+    pub fn integrate_velocities(bodies: &mut [Body], dt: f32) {
+        span!("integrate_velocities");
+        for body in bodies.iter_mut() {
+            body.force = Vec2::default();
+            body.torque = 0.0;
+            if body.body_type() == BodyType::Static {
+                continue;
+            }
+            body.position = body.position + body.velocity * dt;
+            body.rotation += body.angular_velocity * dt;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbiter::RestitutionCombine;
+    use crate::broad_phase::Aabb;
+    use crate::world::World;
+
+    fn context() -> WorldContext {
+        WorldContext {
+            warm_starting: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_step_external_storage() {
+        let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        ground.friction = 0.5;
+        let mut falling = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        falling.friction = 0.5;
+        falling.position = Vec2::new(0.0, 2.0);
+        let mut anchored = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        anchored.position = Vec2::new(5.0, 5.0);
+        let mut bodies = vec![falling, ground];
+        let mut joints = vec![Joint::from_bodies(
+            &bodies[1],
+            &anchored,
+            Vec2::new(5.0, 6.0),
+        )];
+        bodies.push(anchored);
+
+        let mut pipeline = PhysicsPipeline::new();
+        for step in 0..120 {
+            // Reordering the storage between steps is allowed
+            if step == 60 {
+                bodies.swap(0, 2);
+            }
+            pipeline
+                .step(
+                    &mut bodies,
+                    &mut joints,
+                    Vec2::new(0.0, -10.0),
+                    10,
+                    &context(),
+                    1.0 / 60.0,
+                )
+                .unwrap();
+        }
+        let falling = bodies.iter().find(|body| body.width.x == 1.0).unwrap();
+        assert!((falling.position.y - 1.0).abs() < 0.05);
+        assert_eq!(pipeline.arbiters.len(), 1);
+        // The ground does not join the falling and the anchored body
+        assert_eq!(pipeline.islands().len(), 2);
+        let anchored = bodies.iter().find(|body| body.width.x == 0.5).unwrap();
+        assert!((anchored.position - Vec2::new(5.0, 5.0)).length() < 0.1);
+
+        let stats = pipeline.last_step_stats();
+        assert!(stats.scratch_bytes > 0);
+        assert_eq!(stats.scratch_high_water_mark, stats.scratch_bytes);
+        assert_eq!(stats.pairs_tested, 1);
+        assert_eq!(stats.contacts, 2);
+        assert_eq!(stats.islands, 2);
+        assert!(stats.solve_ms >= 0.0);
+    }
+
+    #[test]
+    fn test_custom_broadphase_unknown_ids() {
+        // Reports a pair of the two bodies, a body paired with itself and
+        // pairs with bodies it never saw
+        #[derive(Debug)]
+        struct Stale(usize, usize);
+        impl Broadphase for Stale {
+            fn add(&mut self, _: usize, _: Aabb) {}
+            fn remove(&mut self, _: usize) {}
+            fn move_proxy(&mut self, _: usize, _: Aabb) {}
+            fn query_pairs(&mut self, pairs: &mut Vec<(usize, usize)>) {
+                let Stale(a, b) = *self;
+                pairs.clear();
+                pairs.extend([
+                    (a, b),
+                    (b, b),
+                    (b, usize::MAX),
+                    (usize::MAX - 1, usize::MAX),
+                ]);
+            }
+        }
+        let ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        let mut falling = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        falling.position = Vec2::new(0.0, 0.95);
+        let mut pipeline = PhysicsPipeline::new();
+        pipeline.set_broadphase(Stale(ground.id, falling.id));
+        let mut bodies = vec![ground, falling];
+        let context = WorldContext {
+            broad_phase: BroadPhaseMode::Custom,
+            ..context()
+        };
+        pipeline
+            .step(
+                &mut bodies,
+                &mut [],
+                Vec2::default(),
+                1,
+                &context,
+                1.0 / 60.0,
+            )
+            .unwrap();
+        assert_eq!(pipeline.last_step_stats().pairs_tested, 1);
+        assert_eq!(pipeline.arbiters.len(), 1);
+    }
+
+    #[test]
+    fn test_missing_joint_body() {
+        let body_1 = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        let body_2 = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        let mut joints = vec![Joint::from_bodies(&body_1, &body_2, Vec2::default())];
+        let mut bodies = vec![body_1];
+        let result = PhysicsPipeline::new().step(
+            &mut bodies,
+            &mut joints,
+            Vec2::default(),
+            1,
+            &context(),
+            1.0 / 60.0,
+        );
+        assert!(matches!(result, Err(Sylt2DErrors::BodyNotFound(_))));
+    }
+
+    #[test]
+    fn test_allowed_penetration() {
+        let depth = |position_solver, solver, allowed_penetration| {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context.warm_starting = true;
+            world.world_context.position_solver = position_solver;
+            world.world_context.solver = solver;
+            world.world_context.allowed_penetration = allowed_penetration;
+            let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+            ground.position = Vec2::new(0.0, -0.5);
+            world.add_body(ground);
+            // Starts out sunk into the ground
+            let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            box_.position = Vec2::new(0.0, 0.3);
+            let box_ = world.add_body(box_);
+            for _ in 0..120 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            0.5 - world.body(box_).unwrap().position.y
+        };
+        let ngs = PositionSolver::NonlinearGaussSeidel { iterations: 4 };
+        for (position_solver, solver) in [
+            (PositionSolver::Baumgarte, Solver::Impulse),
+            (ngs, Solver::Impulse),
+            (PositionSolver::Baumgarte, Solver::Soft { substeps: 4 }),
+        ] {
+            let shallow = depth(position_solver, solver, 0.01);
+            let deep = depth(position_solver, solver, 0.1);
+            assert!(deep - shallow > 0.05, "{:?} {:?}", solver, position_solver);
+        }
+    }
+
+    #[test]
+    fn test_soft_solver() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        world.world_context.solver = Solver::Soft { substeps: 4 };
+        let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        world.add_body(ground);
+        let mut top = None;
+        for row in 0..10 {
+            let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            block.friction = 0.5;
+            block.position = Vec2::new(0.0, row as f32 * 1.05 + 0.5);
+            top = Some(world.add_body(block));
+        }
+        let mut pivot = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+        pivot.position = Vec2::new(5.0, 10.0);
+        let mut bob = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        bob.position = Vec2::new(8.0, 10.0);
+        world
+            .joints
+            .push(Joint::from_bodies(&pivot, &bob, Vec2::new(5.0, 10.0)));
+        let bob = world.add_body(bob);
+        world.add_body(pivot);
+
+        // Large steps at which the impulse solver topples the stack
+        for _ in 0..200 {
+            world.step(1.0 / 20.0).unwrap();
+            let length = (world.body(bob).unwrap().position - Vec2::new(5.0, 10.0)).length();
+            assert!((length - 3.0).abs() < 0.05);
+        }
+        let top = world.body(top.unwrap()).unwrap();
+        assert!(top.position.x.abs() < 0.05 && top.position.y > 9.2);
+        assert!(top.velocity.length() < 0.01);
+    }
+
+    #[test]
+    fn test_restitution() {
+        // Speed of a ball dropped on the ground after the bounce
+        let bounce = |solver, speed: f32, combine| {
+            let mut world = World::new(Vec2::default(), 10);
+            world.world_context.solver = solver;
+            world.world_context.restitution_combine = combine;
+            let mut ground = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+            ground.position = Vec2::new(0.0, -0.5);
+            world.add_body(ground);
+            let mut ball = Body::new_circle(0.5, 1.0);
+            ball.position = Vec2::new(0.0, 0.495);
+            ball.velocity = Vec2::new(0.0, -speed);
+            ball.restitution = 0.5;
+            let ball = world.add_body(ball);
+            world.step(1.0 / 60.0).unwrap();
+            world.body(ball).unwrap().velocity.y
+        };
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let speed = bounce(solver, 10.0, RestitutionCombine::Max);
+            assert!((speed - 5.0).abs() < 0.1, "{solver:?}: {speed}");
+            let speed = bounce(solver, 10.0, RestitutionCombine::Average);
+            assert!((speed - 2.5).abs() < 0.1, "{solver:?}: {speed}");
+            // Below the threshold
+            assert!(bounce(solver, 0.5, RestitutionCombine::Max).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_position_solver_does_not_add_velocity() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.position_solver =
+            PositionSolver::NonlinearGaussSeidel { iterations: 3 };
+        let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        world.add_body(ground);
+        let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        block.position = Vec2::new(0.0, 0.3);
+        let block = world.add_body(block);
+
+        // Pushed out of the ground without popping up
+        for _ in 0..30 {
+            world.step(1.0 / 60.0).unwrap();
+            assert!(world.body(block).unwrap().velocity.y < 0.01);
+        }
+        assert!((world.body(block).unwrap().position.y - 0.5).abs() < 0.02);
+    }
+}
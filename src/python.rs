@@ -0,0 +1,318 @@
+use crate::body::{static_mass, Body, BodyHandle};
+use crate::errors::Sylt2DErrors;
+use crate::joint::{JointBuilder, JointHandle};
+use crate::math_utils::Vec2;
+use crate::world::World;
+use numpy::{
+    IntoPyArray, PyArray1, PyArray2, PyArrayMethods, PyReadonlyArray2, PyUntypedArrayMethods,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Number of columns of `PyWorld::states`.
+pub const STATE_COLUMNS: usize = 6;
+
+/// A `World` for Python, exported as `sylt_2d.World`.
+///
+/// Bodies and joints are referred to by their handles. The state of all bodies
+/// is read and written in batches as numpy arrays, one row per body in the
+/// order of `body_ids`, e.g. for the observations and actions of a
+/// reinforcement learning environment.
+#[pyclass(name = "World", unsendable)]
+pub struct PyWorld {
+    world: World,
+}
+
+#[pymethods]
+impl PyWorld {
+    #[new]
+    #[pyo3(signature = (gravity = (0.0, -10.0), iterations = 10))]
+    pub fn new(gravity: (f32, f32), iterations: u32) -> Self {
+        World::new(gravity.into(), iterations).into()
+    }
+
+    /// Adds a box, static when `mass` is zero.
+    #[pyo3(signature = (width, height, mass, position = (0.0, 0.0), rotation = 0.0))]
+    pub fn add_box(
+        &mut self,
+        width: f32,
+        height: f32,
+        mass: f32,
+        position: (f32, f32),
+        rotation: f32,
+    ) -> BodyHandle {
+        let body = Body::new(Vec2::new(width, height), static_mass(mass));
+        self.add(body, position, rotation)
+    }
+
+    /// Adds a circle, static when `mass` is zero.
+    #[pyo3(signature = (radius, mass, position = (0.0, 0.0)))]
+    pub fn add_circle(&mut self, radius: f32, mass: f32, position: (f32, f32)) -> BodyHandle {
+        let body = Body::new_circle(radius, static_mass(mass));
+        self.add(body, position, 0.0)
+    }
+
+    /// Adds a convex polygon, static when `mass` is zero.
+    #[pyo3(signature = (vertices, mass, position = (0.0, 0.0), rotation = 0.0))]
+    pub fn add_polygon(
+        &mut self,
+        vertices: Vec<(f32, f32)>,
+        mass: f32,
+        position: (f32, f32),
+        rotation: f32,
+    ) -> PyResult<BodyHandle> {
+        let vertices = vertices.into_iter().map(Vec2::from).collect();
+        let body = Body::new_polygon(vertices, static_mass(mass))?;
+        Ok(self.add(body, position, rotation))
+    }
+
+    pub fn remove_body(&mut self, body: BodyHandle) -> bool {
+        self.world.remove_body(body.id())
+    }
+
+    /// Joins two bodies at a world point, see `JointBuilder::revolute`.
+    pub fn add_revolute_joint(
+        &mut self,
+        body_1: BodyHandle,
+        body_2: BodyHandle,
+        anchor: (f32, f32),
+    ) -> PyResult<JointHandle> {
+        Ok(JointBuilder::revolute(body_1, body_2)
+            .anchor(anchor.into())
+            .build(&mut self.world)?)
+    }
+
+    /// Keeps two world points on the bodies at their current distance, see
+    /// `JointBuilder::distance`.
+    pub fn add_distance_joint(
+        &mut self,
+        body_1: BodyHandle,
+        body_2: BodyHandle,
+        anchor_1: (f32, f32),
+        anchor_2: (f32, f32),
+    ) -> PyResult<JointHandle> {
+        Ok(JointBuilder::distance(body_1, body_2)
+            .anchors(anchor_1.into(), anchor_2.into())
+            .build(&mut self.world)?)
+    }
+
+    pub fn remove_joint(&mut self, joint: JointHandle) -> bool {
+        self.world.remove_joint(joint)
+    }
+
+    pub fn step(&mut self, dt: f32) -> PyResult<()> {
+        Ok(self.world.step(dt)?)
+    }
+
+    pub fn body_count(&self) -> usize {
+        self.world.body_count()
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.world.joint_count()
+    }
+
+    pub fn contact_count(&self) -> usize {
+        self.world.contact_count()
+    }
+
+    /// Returns `(x, y, rotation)` of the body, or None if it was removed.
+    pub fn transform(&self, body: BodyHandle) -> Option<(f32, f32, f32)> {
+        let body = self.world.body(body)?;
+        Some((body.position.x, body.position.y, body.rotation))
+    }
+
+    pub fn set_transform(&mut self, body: BodyHandle, position: (f32, f32), rotation: f32) -> bool {
+        self.world
+            .set_body_transform(body, position.into(), rotation)
+    }
+
+    /// Returns `(vx, vy, angular_velocity)` of the body, or None if it was
+    /// removed.
+    pub fn velocity(&self, body: BodyHandle) -> Option<(f32, f32, f32)> {
+        let body = self.world.body(body)?;
+        Some((body.velocity.x, body.velocity.y, body.angular_velocity))
+    }
+
+    pub fn set_velocity(
+        &mut self,
+        body: BodyHandle,
+        velocity: (f32, f32),
+        angular_velocity: f32,
+    ) -> bool {
+        self.with_body(body, |body| {
+            body.velocity = velocity.into();
+            body.angular_velocity = angular_velocity;
+        })
+    }
+
+    pub fn apply_force(&mut self, body: BodyHandle, force: (f32, f32)) -> bool {
+        self.with_body(body, |body| body.add_force(force.into()))
+    }
+
+    pub fn apply_impulse(&mut self, body: BodyHandle, impulse: (f32, f32)) -> bool {
+        self.with_body(body, |body| body.apply_impulse(impulse.into()))
+    }
+
+    /// Returns the first body hit by the ray as `(body, point, normal,
+    /// fraction)`, see `World::ray_cast`.
+    #[allow(clippy::type_complexity)]
+    pub fn ray_cast(
+        &self,
+        origin: (f32, f32),
+        direction: (f32, f32),
+        max_distance: f32,
+    ) -> Option<(BodyHandle, (f32, f32), (f32, f32), f32)> {
+        let hit = self
+            .world
+            .ray_cast(origin.into(), direction.into(), max_distance)?;
+        Some((hit.body, hit.point.into(), hit.normal.into(), hit.fraction))
+    }
+
+    /// See `World::query_point`.
+    pub fn query_point(&self, point: (f32, f32)) -> Vec<BodyHandle> {
+        self.world.query_point(point.into())
+    }
+
+    /// Returns the ids of the bodies, in the order of the rows of `states`.
+    pub fn body_ids<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<u64>> {
+        self.world
+            .iter_bodies()
+            .map(|body| body.id as u64)
+            .collect::<Vec<_>>()
+            .into_pyarray(py)
+    }
+
+    /// Returns an `(n, 6)` array with `x, y, rotation, vx, vy,
+    /// angular_velocity` of every body.
+    pub fn states<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let states = self.state_rows();
+        states
+            .into_pyarray(py)
+            .reshape([self.world.body_count(), STATE_COLUMNS])
+    }
+
+    /// Sets the velocities of all bodies from an `(n, 3)` array of `vx, vy,
+    /// angular_velocity`.
+    pub fn set_velocities(&mut self, velocities: PyReadonlyArray2<'_, f32>) -> PyResult<()> {
+        let velocities = self.rows(&velocities, 3)?;
+        for (body, row) in self.world.iter_bodies_mut().zip(velocities.chunks_exact(3)) {
+            body.velocity = Vec2::new(row[0], row[1]);
+            body.angular_velocity = row[2];
+        }
+        Ok(())
+    }
+
+    /// Adds forces through the center of mass of all bodies from an `(n, 2)`
+    /// array, e.g. the actions of an agent.
+    pub fn apply_forces(&mut self, forces: PyReadonlyArray2<'_, f32>) -> PyResult<()> {
+        let forces = self.rows(&forces, 2)?;
+        for (body, row) in self.world.iter_bodies_mut().zip(forces.chunks_exact(2)) {
+            body.add_force(Vec2::new(row[0], row[1]));
+        }
+        Ok(())
+    }
+}
+
+impl PyWorld {
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Returns the rows of `states` one after the other.
+    pub fn state_rows(&self) -> Vec<f32> {
+        self.world
+            .iter_bodies()
+            .flat_map(|body| {
+                [
+                    body.position.x,
+                    body.position.y,
+                    body.rotation,
+                    body.velocity.x,
+                    body.velocity.y,
+                    body.angular_velocity,
+                ]
+            })
+            .collect()
+    }
+
+    fn add(&mut self, mut body: Body, position: (f32, f32), rotation: f32) -> BodyHandle {
+        body.position = position.into();
+        body.rotation = rotation;
+        self.world.add_body(body)
+    }
+
+    fn with_body(&mut self, body: BodyHandle, f: impl FnOnce(&mut Body)) -> bool {
+        self.world.body_mut(body).map(f).is_some()
+    }
+
+    // Returns the rows one after the other, if there is one per body.
+    fn rows(&self, array: &PyReadonlyArray2<'_, f32>, columns: usize) -> PyResult<Vec<f32>> {
+        let shape = [self.world.body_count(), columns];
+        if array.shape() != shape {
+            return Err(PyValueError::new_err(format!(
+                "expected an array of shape {:?}, got {:?}",
+                shape,
+                array.shape()
+            )));
+        }
+        Ok(array.as_array().iter().copied().collect())
+    }
+}
+
+impl From<World> for PyWorld {
+    fn from(world: World) -> Self {
+        Self { world }
+    }
+}
+
+impl From<Sylt2DErrors> for PyErr {
+    fn from(err: Sylt2DErrors) -> Self {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The `sylt_2d` Python module.
+#[pymodule]
+fn sylt_2d(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyWorld>()?;
+    module.add_class::<BodyHandle>()?;
+    module.add_class::<JointHandle>()?;
+    module.add("STATE_COLUMNS", STATE_COLUMNS)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_world() {
+        let mut world = PyWorld::new((0.0, -10.0), 10);
+        let ground = world.add_box(20.0, 1.0, 0.0, (0.0, -0.5), 0.0);
+        let anchor = world.add_box(0.2, 0.2, 0.0, (0.0, 4.0), 0.0);
+        let bob = world.add_circle(0.25, 1.0, (2.0, 4.0));
+        world
+            .add_distance_joint(anchor, bob, (0.0, 4.0), (2.0, 4.0))
+            .unwrap();
+        assert_eq!(world.joint_count(), 1);
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        let (x, y, _) = world.transform(bob).unwrap();
+        assert!(((x * x + (y - 4.0) * (y - 4.0)).sqrt() - 2.0).abs() < 0.05);
+
+        let states = world.state_rows();
+        assert_eq!(states.len(), 3 * STATE_COLUMNS);
+        assert_eq!(&states[2 * STATE_COLUMNS..][..2], &[x, y]);
+        assert_eq!(world.query_point((0.0, -0.5)), vec![ground]);
+
+        assert!(world.remove_body(bob));
+        assert_eq!(world.joint_count(), 0);
+        assert!(world.velocity(bob).is_none());
+    }
+}
@@ -0,0 +1,474 @@
+use crate::body::{Body, BodyHandle};
+use crate::broad_phase::BroadPhaseMode;
+use crate::errors::Sylt2DErrors;
+use crate::joint::{Joint, JointHandle};
+use crate::math_utils::Vec2;
+use crate::world::{World, WorldContext};
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayErrors {
+    /// The world uses a broad phase given to `PhysicsPipeline::set_broadphase`,
+    /// which a replay cannot rebuild.
+    CustomBroadPhase,
+}
+
+impl fmt::Display for ReplayErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayErrors::CustomBroadPhase => {
+                write!(f, "A custom broad phase cannot be recorded.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayErrors {}
+
+/// Input to a world during a recording, see `Recorder`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReplayEvent {
+    AddBody(Body),
+    AddJoint(Joint),
+    RemoveBody(BodyHandle),
+    RemoveJoint(JointHandle),
+    /// Force through the center of mass, or at a point in world space.
+    ApplyForce {
+        body: BodyHandle,
+        force: Vec2,
+        point: Option<Vec2>,
+    },
+    ApplyTorque {
+        body: BodyHandle,
+        torque: f32,
+    },
+    ApplyImpulse {
+        body: BodyHandle,
+        impulse: Vec2,
+    },
+    /// See `World::set_body_transform`.
+    SetBodyTransform {
+        body: BodyHandle,
+        position: Vec2,
+        rotation: f32,
+    },
+    SetGravity(Vec2),
+    /// Replaces the world context but keeps its `WorldContext::time`.
+    SetWorldContext(WorldContext),
+    Step(f32),
+    /// See `World::step_substeps`.
+    StepSubsteps {
+        dt: f32,
+        substeps: u32,
+    },
+}
+
+/// Settings and inputs of a world recorded by a `Recorder`, played back with
+/// `Replay::play` or step by step with a `Player`.
+///
+/// With the `serde` feature a replay can be saved, e.g. to attach the recording
+/// of an exploding stack to a bug report.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Replay {
+    pub gravity: Vec2,
+    pub iterations: u32,
+    pub world_context: WorldContext,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    /// Returns the number of recorded steps.
+    pub fn step_count(&self) -> usize {
+        self.events.iter().filter(|event| event.is_step()).count()
+    }
+
+    /// Returns an empty world with the recorded settings, to play the replay in.
+    pub fn world(&self) -> World {
+        let mut world = World::new(self.gravity, self.iterations);
+        world.world_context = self.world_context;
+        world
+    }
+
+    /// Returns a player going through the replay one step at a time.
+    pub fn player(&self) -> Player<'_> {
+        Player {
+            replay: self,
+            next: 0,
+        }
+    }
+
+    /// Returns a new world after all recorded inputs and steps.
+    pub fn play(&self) -> Result<World, Sylt2DErrors> {
+        let mut world = self.world();
+        let mut player = self.player();
+        while player.step(&mut world)? {}
+        Ok(world)
+    }
+}
+
+impl ReplayEvent {
+    fn is_step(&self) -> bool {
+        matches!(
+            self,
+            ReplayEvent::Step(_) | ReplayEvent::StepSubsteps { .. }
+        )
+    }
+}
+
+/// Records what happens to a world, so the same simulation can be replayed
+/// later. Make the changes to the bodies and joints through the recorder.
+///
+/// Changes of the gravity and of `World::world_context` are picked up at the
+/// next step of the recorder.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    replay: Replay,
+    // Gravity and world context the recorded events leave the world with
+    gravity: Vec2,
+    world_context: WorldContext,
+}
+
+impl Recorder {
+    /// Starts recording, with the bodies and joints already in the world. The
+    /// cached contacts are not recorded, so a replay only matches exactly when
+    /// recording starts before the first step.
+    pub fn new(world: &World) -> Self {
+        let events = world
+            .iter_bodies()
+            .cloned()
+            .map(ReplayEvent::AddBody)
+            .chain(world.iter_joints().cloned().map(ReplayEvent::AddJoint))
+            .collect();
+        Self {
+            replay: Replay {
+                gravity: world.gravity(),
+                iterations: world.iterations(),
+                world_context: world.world_context,
+                events,
+            },
+            gravity: world.gravity(),
+            world_context: world.world_context,
+        }
+    }
+
+    pub fn add_body(&mut self, world: &mut World, body: Body) -> BodyHandle {
+        let handle = world.add_body(body);
+        // Recorded with the id the world gave it
+        if let Some(body) = world.body(handle) {
+            self.replay.events.push(ReplayEvent::AddBody(body.clone()));
+        }
+        handle
+    }
+
+    pub fn add_joint(&mut self, world: &mut World, joint: Joint) -> JointHandle {
+        let handle = world.add_joint(joint);
+        if let Some(joint) = world.joint(handle) {
+            self.replay
+                .events
+                .push(ReplayEvent::AddJoint(joint.clone()));
+        }
+        handle
+    }
+
+    pub fn remove_body(&mut self, world: &mut World, body: BodyHandle) -> bool {
+        self.replay.events.push(ReplayEvent::RemoveBody(body));
+        world.remove_body(body.id())
+    }
+
+    pub fn remove_joint(&mut self, world: &mut World, joint: JointHandle) -> bool {
+        self.replay.events.push(ReplayEvent::RemoveJoint(joint));
+        world.remove_joint(joint)
+    }
+
+    /// See `Body::add_force` and `Body::apply_force_at_point`.
+    pub fn apply_force(
+        &mut self,
+        world: &mut World,
+        body: BodyHandle,
+        force: Vec2,
+        point: Option<Vec2>,
+    ) -> Result<(), Sylt2DErrors> {
+        let event = ReplayEvent::ApplyForce { body, force, point };
+        apply(world, &event)?;
+        self.replay.events.push(event);
+        Ok(())
+    }
+
+    pub fn apply_torque(
+        &mut self,
+        world: &mut World,
+        body: BodyHandle,
+        torque: f32,
+    ) -> Result<(), Sylt2DErrors> {
+        let event = ReplayEvent::ApplyTorque { body, torque };
+        apply(world, &event)?;
+        self.replay.events.push(event);
+        Ok(())
+    }
+
+    pub fn apply_impulse(
+        &mut self,
+        world: &mut World,
+        body: BodyHandle,
+        impulse: Vec2,
+    ) -> Result<(), Sylt2DErrors> {
+        let event = ReplayEvent::ApplyImpulse { body, impulse };
+        apply(world, &event)?;
+        self.replay.events.push(event);
+        Ok(())
+    }
+
+    /// See `World::set_body_transform`.
+    pub fn set_body_transform(
+        &mut self,
+        world: &mut World,
+        body: BodyHandle,
+        position: Vec2,
+        rotation: f32,
+    ) -> Result<(), Sylt2DErrors> {
+        let event = ReplayEvent::SetBodyTransform {
+            body,
+            position,
+            rotation,
+        };
+        apply(world, &event)?;
+        self.replay.events.push(event);
+        Ok(())
+    }
+
+    /// See `World::step`.
+    ///
+    /// Fails without stepping if the world uses a broad phase given to
+    /// `PhysicsPipeline::set_broadphase`, which a replay cannot rebuild.
+    pub fn step(&mut self, world: &mut World, dt: f32) -> Result<(), Sylt2DErrors> {
+        self.record_settings(world)?;
+        self.replay.events.push(ReplayEvent::Step(dt));
+        world.step(dt)
+    }
+
+    /// See `World::step_substeps` and `Recorder::step`.
+    pub fn step_substeps(
+        &mut self,
+        world: &mut World,
+        dt: f32,
+        substeps: u32,
+    ) -> Result<(), Sylt2DErrors> {
+        self.record_settings(world)?;
+        self.replay
+            .events
+            .push(ReplayEvent::StepSubsteps { dt, substeps });
+        world.step_substeps(dt, substeps)
+    }
+
+    /// Returns the recording so far.
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+
+    // Records the changes of the gravity and the world context since the last
+    // step
+    fn record_settings(&mut self, world: &World) -> Result<(), Sylt2DErrors> {
+        let world_context = world.world_context;
+        if world_context.broad_phase == BroadPhaseMode::Custom {
+            return Err(ReplayErrors::CustomBroadPhase.into());
+        }
+        if world.gravity() != self.gravity {
+            self.gravity = world.gravity();
+            self.replay
+                .events
+                .push(ReplayEvent::SetGravity(self.gravity));
+        }
+        // The steps advance the time
+        let time = self.world_context.time;
+        if (WorldContext {
+            time,
+            ..world_context
+        }) != self.world_context
+        {
+            self.replay
+                .events
+                .push(ReplayEvent::SetWorldContext(world_context));
+        }
+        self.world_context = world_context;
+        Ok(())
+    }
+}
+
+/// Plays a replay one step at a time, see `Replay::player`.
+#[derive(Debug, Clone)]
+pub struct Player<'a> {
+    replay: &'a Replay,
+    next: usize,
+}
+
+impl Player<'_> {
+    /// Applies the inputs up to and including the next step to `world`, which
+    /// has to start out as `Replay::world`. Returns false at the end of the
+    /// replay.
+    pub fn step(&mut self, world: &mut World) -> Result<bool, Sylt2DErrors> {
+        while let Some(event) = self.replay.events.get(self.next) {
+            self.next += 1;
+            apply(world, event)?;
+            if event.is_step() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn apply(world: &mut World, event: &ReplayEvent) -> Result<(), Sylt2DErrors> {
+    match event {
+        ReplayEvent::AddBody(body) => {
+            world.add_body(body.clone());
+        }
+        ReplayEvent::AddJoint(joint) => {
+            world.add_joint(joint.clone());
+        }
+        ReplayEvent::RemoveBody(body) => {
+            world.remove_body(body.id());
+        }
+        ReplayEvent::RemoveJoint(joint) => {
+            world.remove_joint(*joint);
+        }
+        ReplayEvent::ApplyForce { body, force, point } => {
+            let body = body_mut(world, *body)?;
+            match point {
+                Some(point) => body.apply_force_at_point(*force, *point),
+                None => body.add_force(*force),
+            }
+        }
+        ReplayEvent::ApplyTorque { body, torque } => body_mut(world, *body)?.apply_torque(*torque),
+        ReplayEvent::ApplyImpulse { body, impulse } => {
+            body_mut(world, *body)?.apply_impulse(*impulse)
+        }
+        ReplayEvent::SetBodyTransform {
+            body,
+            position,
+            rotation,
+        } => {
+            if !world.set_body_transform(*body, *position, *rotation) {
+                return Err(Sylt2DErrors::BodyNotFound(body.id()));
+            }
+        }
+        ReplayEvent::SetGravity(gravity) => world.set_gravity(*gravity),
+        ReplayEvent::SetWorldContext(world_context) => {
+            world.world_context = WorldContext {
+                time: world.world_context.time,
+                ..*world_context
+            };
+        }
+        ReplayEvent::Step(dt) => world.step(*dt)?,
+        ReplayEvent::StepSubsteps { dt, substeps } => world.step_substeps(*dt, *substeps)?,
+    }
+    Ok(())
+}
+
+fn body_mut(world: &mut World, body: BodyHandle) -> Result<&mut Body, Sylt2DErrors> {
+    world
+        .body_mut(body)
+        .ok_or(Sylt2DErrors::BodyNotFound(body.id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+
+    #[test]
+    fn test_replay() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut recorder = Recorder::new(&world);
+        let mut boxes = Vec::new();
+        for i in 0..60 {
+            if i % 10 == 0 {
+                let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+                box_.position = Vec2::new(0.05 * i as f32, 6.0);
+                boxes.push(recorder.add_body(&mut world, box_));
+            }
+            if i == 25 {
+                recorder
+                    .apply_impulse(&mut world, boxes[0], Vec2::new(3.0, 0.0))
+                    .unwrap();
+            }
+            if i == 40 {
+                recorder.remove_body(&mut world, boxes[1]);
+            }
+            let first = boxes[0];
+            recorder
+                .apply_force(&mut world, first, Vec2::new(1.0, 0.0), None)
+                .unwrap();
+            recorder.step(&mut world, 1.0 / 60.0).unwrap();
+        }
+        let replay = recorder.finish();
+        assert_eq!(replay.step_count(), 60);
+
+        let played = replay.play().unwrap();
+        assert_eq!(played.body_count(), world.body_count());
+        for (played, body) in played.iter_bodies().zip(world.iter_bodies()) {
+            assert_eq!(played.id, body.id);
+            assert_eq!(played.position, body.position);
+            assert_eq!(played.velocity, body.velocity);
+        }
+
+        // Step by step up to the moment the box was removed
+        let mut world = replay.world();
+        let mut player = replay.player();
+        for _ in 0..40 {
+            assert!(player.step(&mut world).unwrap());
+        }
+        assert!(world.body(boxes[1]).is_some());
+        player.step(&mut world).unwrap();
+        assert!(world.body(boxes[1]).is_none());
+    }
+
+    #[test]
+    fn test_replay_settings() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 2.0);
+        let box_ = world.add_body(box_);
+        let mut recorder = Recorder::new(&world);
+        for i in 0..40 {
+            match i {
+                10 => world.set_gravity(Vec2::new(2.0, -5.0)),
+                20 => world.world_context.solver = Solver::Soft { substeps: 4 },
+                30 => recorder
+                    .set_body_transform(&mut world, box_, Vec2::new(-3.0, 1.0), 0.5)
+                    .unwrap(),
+                _ => {}
+            }
+            if i % 2 == 0 {
+                recorder.step(&mut world, 1.0 / 60.0).unwrap();
+            } else {
+                recorder.step_substeps(&mut world, 1.0 / 60.0, 2).unwrap();
+            }
+        }
+        let replay = recorder.finish();
+        assert_eq!(replay.step_count(), 40);
+
+        let played = replay.play().unwrap();
+        assert_eq!(played.gravity(), world.gravity());
+        assert_eq!(played.world_context, world.world_context);
+        let (played, body) = (played.body(box_).unwrap(), world.body(box_).unwrap());
+        assert_eq!(played.position, body.position);
+        assert_eq!(played.velocity, body.velocity);
+
+        let mut recorder = Recorder::new(&world);
+        world.world_context.broad_phase = BroadPhaseMode::Custom;
+        assert!(matches!(
+            recorder.step(&mut world, 1.0 / 60.0),
+            Err(Sylt2DErrors::Replay(ReplayErrors::CustomBroadPhase))
+        ));
+        assert_eq!(recorder.replay().step_count(), 0);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::body::{Body, BodyHandle};
+use crate::errors::Sylt2DErrors;
+use crate::joint::JointBuilder;
+use crate::math_utils::Vec2;
+use crate::world::{World, WorldDef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum SceneErrors {
+    /// The text is not a valid scene, with the message of the parser.
+    Parse(String),
+    /// A joint refers to a body name no body has.
+    UnknownBody(String),
+    /// A shape the constructors of `Body` reject, e.g. a chain with fewer than
+    /// 2 points.
+    InvalidShape(String),
+}
+
+impl fmt::Display for SceneErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneErrors::Parse(err) => write!(f, "Could not parse the scene: {}", err),
+            SceneErrors::UnknownBody(name) => write!(f, "No body is named {:?}.", name),
+            SceneErrors::InvalidShape(err) => write!(f, "Invalid shape: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SceneErrors {}
+
+/// Shape and dimensions of a body in a scene, see the constructors of `Body`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShapeDef {
+    Box { width: Vec2 },
+    Polygon { vertices: Vec<Vec2> },
+    Capsule { length: f32, radius: f32 },
+    Circle { radius: f32 },
+    Chain { points: Vec<Vec2> },
+    Heightfield { heights: Vec<f32>, spacing: f32 },
+}
+
+impl Default for ShapeDef {
+    fn default() -> Self {
+        ShapeDef::Box {
+            width: Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
+/// A body in a scene. Left out fields take the defaults of `Body`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BodyDef {
+    /// Name the joints of the scene refer to the body by.
+    pub name: Option<String>,
+    pub shape: ShapeDef,
+    /// Static without a mass.
+    pub mass: Option<f32>,
+    pub position: Vec2,
+    pub rotation: f32,
+    pub velocity: Vec2,
+    pub angular_velocity: f32,
+    pub friction: f32,
+    pub restitution: f32,
+    pub bullet: bool,
+}
+
+impl BodyDef {
+    /// Returns a new body as described.
+    pub fn body(&self) -> Result<Body, Sylt2DErrors> {
+        let mass = self.mass.unwrap_or(f32::MAX);
+        let mut body = match &self.shape {
+            ShapeDef::Box { width } => Body::new(*width, mass),
+            ShapeDef::Polygon { vertices } => Body::new_polygon(vertices.clone(), mass)?,
+            ShapeDef::Capsule { length, radius } => Body::new_capsule(*length, *radius, mass),
+            ShapeDef::Circle { radius } => Body::new_circle(*radius, mass),
+            ShapeDef::Chain { points } => {
+                if points.len() < 2 {
+                    return Err(SceneErrors::InvalidShape(format!(
+                        "a chain needs at least 2 points, got {}",
+                        points.len()
+                    ))
+                    .into());
+                }
+                Body::new_chain(points.clone())
+            }
+            ShapeDef::Heightfield { heights, spacing } => {
+                if heights.len() < 2 || *spacing <= 0.0 || spacing.is_nan() {
+                    return Err(SceneErrors::InvalidShape(format!(
+                        "a heightfield needs at least 2 heights and a positive spacing, got {} and {}",
+                        heights.len(),
+                        spacing
+                    ))
+                    .into());
+                }
+                Body::new_heightfield(heights.clone(), *spacing)
+            }
+        };
+        body.position = self.position;
+        body.rotation = self.rotation;
+        body.velocity = self.velocity;
+        body.angular_velocity = self.angular_velocity;
+        body.friction = self.friction;
+        body.restitution = self.restitution;
+        body.bullet = self.bullet;
+        Ok(body)
+    }
+}
+
+/// Constraint of a joint in a scene, see `JointBuilder`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JointType {
+    #[default]
+    Revolute,
+    Weld,
+    Distance,
+}
+
+/// A joint between two named bodies of a scene, see `JointBuilder` for the
+/// defaults of the left out fields.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JointDef {
+    #[serde(rename = "type")]
+    pub joint_type: JointType,
+    pub body_1: String,
+    pub body_2: String,
+    /// World point both bodies are joined at.
+    pub anchor: Option<Vec2>,
+    /// World point on each body, for distance joints.
+    pub anchors: Option<(Vec2, Vec2)>,
+    pub softness: Option<f32>,
+    pub bias_factor: Option<f32>,
+    pub limits: Option<(f32, f32)>,
+    /// Speed and largest torque of the motor.
+    pub motor: Option<(f32, f32)>,
+}
+
+impl JointDef {
+    fn builder(&self, bodies: &HashMap<String, BodyHandle>) -> Result<JointBuilder, SceneErrors> {
+        let find = |name: &String| {
+            bodies
+                .get(name)
+                .copied()
+                .ok_or_else(|| SceneErrors::UnknownBody(name.clone()))
+        };
+        let (body_1, body_2) = (find(&self.body_1)?, find(&self.body_2)?);
+        let mut builder = match self.joint_type {
+            JointType::Revolute => JointBuilder::revolute(body_1, body_2),
+            JointType::Weld => JointBuilder::weld(body_1, body_2),
+            JointType::Distance => JointBuilder::distance(body_1, body_2),
+        };
+        if let Some(anchor) = self.anchor {
+            builder = builder.anchor(anchor);
+        }
+        if let Some((anchor_1, anchor_2)) = self.anchors {
+            builder = builder.anchors(anchor_1, anchor_2);
+        }
+        if let Some(softness) = self.softness {
+            builder = builder.softness(softness);
+        }
+        if let Some(bias_factor) = self.bias_factor {
+            builder = builder.bias_factor(bias_factor);
+        }
+        if let Some((lower, upper)) = self.limits {
+            builder = builder.limits(lower, upper);
+        }
+        if let Some((speed, max_torque)) = self.motor {
+            builder = builder.motor(speed, max_torque);
+        }
+        Ok(builder)
+    }
+}
+
+/// Places copies of a body in a pattern, for stress scenes and levels in scene
+/// files. The first copy is at the position of `body`, the named copies are
+/// numbered from zero, e.g. `brick_0`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Spawner {
+    /// `columns` times `rows` copies, `spacing` apart.
+    Grid {
+        body: BodyDef,
+        columns: u32,
+        rows: u32,
+        spacing: Vec2,
+    },
+    /// `count` copies on top of each other, `spacing` apart.
+    Stack {
+        body: BodyDef,
+        count: u32,
+        spacing: f32,
+    },
+    /// Rows of copies `spacing.x` apart, starting with `base` copies and one
+    /// less in each row `spacing.y` above, centered on the row below.
+    Pyramid {
+        body: BodyDef,
+        base: u32,
+        spacing: Vec2,
+    },
+}
+
+impl Spawner {
+    /// Returns the copies of the body.
+    pub fn bodies(&self) -> Vec<BodyDef> {
+        let (body, offsets): (&BodyDef, Vec<Vec2>) = match self {
+            Spawner::Grid {
+                body,
+                columns,
+                rows,
+                spacing,
+            } => (
+                body,
+                (0..*rows)
+                    .flat_map(|row| {
+                        (0..*columns).map(move |column| {
+                            Vec2::new(column as f32 * spacing.x, row as f32 * spacing.y)
+                        })
+                    })
+                    .collect(),
+            ),
+            Spawner::Stack {
+                body,
+                count,
+                spacing,
+            } => (
+                body,
+                (0..*count)
+                    .map(|i| Vec2::new(0.0, i as f32 * spacing))
+                    .collect(),
+            ),
+            Spawner::Pyramid {
+                body,
+                base,
+                spacing,
+            } => (
+                body,
+                (0..*base)
+                    .flat_map(|row| {
+                        (row..*base).map(move |column| {
+                            Vec2::new(
+                                (column as f32 - 0.5 * row as f32) * spacing.x,
+                                row as f32 * spacing.y,
+                            )
+                        })
+                    })
+                    .collect(),
+            ),
+        };
+        offsets
+            .into_iter()
+            .enumerate()
+            .map(|(i, offset)| BodyDef {
+                name: body.name.as_ref().map(|name| format!("{}_{}", name, i)),
+                position: body.position + offset,
+                ..body.clone()
+            })
+            .collect()
+    }
+}
+
+/// Bodies, joints and settings of a world read from a JSON, RON or TOML file,
+/// so levels and test setups can be edited without recompiling.
+///
+/// ```ron
+/// (
+///     world: (gravity: (x: 0.0, y: -10.0)),
+///     bodies: [
+///         (name: Some("ground"), shape: Box(width: (x: 100.0, y: 20.0)), position: (x: 0.0, y: -10.0)),
+///         (name: Some("bob"), mass: Some(100.0), position: (x: 9.0, y: 11.0)),
+///     ],
+///     joints: [(body_1: "ground", body_2: "bob", anchor: Some((x: 0.0, y: 11.0)))],
+/// )
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    pub world: WorldDef,
+    pub bodies: Vec<BodyDef>,
+    /// Added after the bodies, so the joints can refer to the copies.
+    pub spawners: Vec<Spawner>,
+    pub joints: Vec<JointDef>,
+}
+
+impl Scene {
+    pub fn from_json(text: &str) -> Result<Self, Sylt2DErrors> {
+        serde_json::from_str(text).map_err(|err| SceneErrors::Parse(err.to_string()).into())
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, Sylt2DErrors> {
+        ron::from_str(text).map_err(|err| SceneErrors::Parse(err.to_string()).into())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, Sylt2DErrors> {
+        toml::from_str(text).map_err(|err| SceneErrors::Parse(err.to_string()).into())
+    }
+
+    /// Returns a new world with the settings, bodies and joints of the scene.
+    pub fn to_world(&self) -> Result<World, Sylt2DErrors> {
+        let mut world = World::from_def(self.world);
+        self.populate(&mut world)?;
+        Ok(world)
+    }
+
+    /// Adds the bodies and joints of the scene to the world, keeping its
+    /// settings. Returns the handles of the named bodies.
+    pub fn populate(&self, world: &mut World) -> Result<HashMap<String, BodyHandle>, Sylt2DErrors> {
+        let defs: Vec<BodyDef> = self
+            .bodies
+            .iter()
+            .cloned()
+            .chain(self.spawners.iter().flat_map(Spawner::bodies))
+            .collect();
+        let bodies = defs
+            .iter()
+            .map(BodyDef::body)
+            .collect::<Result<Vec<Body>, Sylt2DErrors>>()?;
+        let mut handles = HashMap::new();
+        for (def, body) in defs.iter().zip(bodies) {
+            let handle = world.add_body(body);
+            if let Some(name) = &def.name {
+                handles.insert(name.clone(), handle);
+            }
+        }
+        for joint in self.joints.iter() {
+            joint.builder(&handles)?.build(world)?;
+        }
+        Ok(handles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joint::JointKind;
+
+    #[test]
+    fn test_scene() {
+        let scene =
+            Scene::from_ron(include_str!("../examples/samples/scenes/pendulum.ron")).unwrap();
+        let mut world = scene.to_world().unwrap();
+        assert_eq!(world.gravity(), Vec2::new(0.0, -10.0));
+        assert_eq!(world.body_count(), 2);
+        let joint = world.iter_joints().next().unwrap();
+        assert!(matches!(joint.kind(), JointKind::Revolute(_)));
+        for _ in 0..10 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+
+        let json = r#"{
+            "bodies": [
+                {"name": "a", "shape": {"Circle": {"radius": 0.5}}, "mass": 1.0},
+                {"name": "b", "mass": 1.0, "position": {"x": 2.0, "y": 0.0}}
+            ],
+            "joints": [{"type": "Distance", "body_1": "a", "body_2": "b"}]
+        }"#;
+        let handles = Scene::from_json(json)
+            .unwrap()
+            .populate(&mut world)
+            .unwrap();
+        assert_eq!(world.body_count(), 4);
+        let a = world.body(handles["a"]).unwrap();
+        assert_eq!(a.radius, 0.5);
+        let joint = world.iter_joints().last().unwrap();
+        assert!(matches!(joint.kind(), JointKind::Distance(distance) if distance.length == 2.0));
+
+        // Settings of the context left out keep their defaults
+        let settings = r#"(world: (context: (warm_starting: true)))"#;
+        let world = Scene::from_ron(settings).unwrap().to_world().unwrap();
+        assert!(world.world_context.warm_starting);
+        assert_eq!(world.world_context.bias_factor, 0.2);
+
+        let unknown = r#"{"joints": [{"body_1": "a", "body_2": "c"}]}"#;
+        assert!(matches!(
+            Scene::from_json(unknown).unwrap().to_world(),
+            Err(Sylt2DErrors::Scene(SceneErrors::UnknownBody(_)))
+        ));
+        assert!(matches!(
+            Scene::from_ron("(bodies: 3)"),
+            Err(Sylt2DErrors::Scene(SceneErrors::Parse(_)))
+        ));
+        let chain = r#"{"bodies": [{"shape": {"Chain": {"points": []}}}]}"#;
+        assert!(matches!(
+            Scene::from_json(chain).unwrap().to_world(),
+            Err(Sylt2DErrors::Scene(SceneErrors::InvalidShape(_)))
+        ));
+    }
+
+    #[test]
+    fn test_spawners() {
+        let scene =
+            Scene::from_toml(include_str!("../examples/samples/scenes/pyramid.toml")).unwrap();
+        let world = scene.to_world().unwrap();
+        // The ground and 12 + 11 + ... + 1 boxes
+        assert_eq!(world.body_count(), 1 + 78);
+        let top = world
+            .iter_bodies()
+            .max_by(|a, b| a.position.y.total_cmp(&b.position.y))
+            .unwrap();
+        assert!((top.position.x - 0.1875).abs() < 1e-4);
+        assert!((top.position.y - 22.75).abs() < 1e-4);
+
+        let toml = r#"
+            [[spawners]]
+            type = "Grid"
+            columns = 3
+            rows = 2
+            spacing = { x = 2.0, y = 3.0 }
+            body = { name = "crate", mass = 1.0, position = { x = 1.0, y = 1.0 } }
+
+            [[spawners]]
+            type = "Stack"
+            count = 4
+            spacing = 1.1
+            body = { mass = 1.0 }
+
+            [[joints]]
+            type = "Weld"
+            body_1 = "crate_0"
+            body_2 = "crate_5"
+        "#;
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let handles = Scene::from_toml(toml)
+            .unwrap()
+            .populate(&mut world)
+            .unwrap();
+        assert_eq!(world.body_count(), 10);
+        assert_eq!(handles.len(), 6);
+        let last = world.body(handles["crate_5"]).unwrap();
+        assert_eq!(last.position, Vec2::new(5.0, 4.0));
+        assert_eq!(world.joint_count(), 1);
+    }
+}
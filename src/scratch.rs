@@ -0,0 +1,127 @@
+use crate::arbiter::ContactInfo;
+use crate::math_utils::Vec2;
+use std::mem::{size_of, take};
+
+/// Bump storage for the temporaries of a step.
+///
+/// Allocations only append to buffers which are reset at the start of every
+/// step, so once the buffers have grown to the needs of the scene stepping does
+/// not request heap memory for them anymore. Holds the contact points found by
+/// the narrow phase, the vertices of the polygon clipping and the temporaries of
+/// `Islands::build`.
+#[derive(Debug, Default)]
+pub struct Scratch {
+    contacts: Vec<ContactInfo>,
+    clip: ClipScratch,
+    indices: Vec<usize>,
+    high_water_mark: usize,
+}
+
+/// Temporaries of the polygon clipping, see `collide_polygons_in`.
+#[derive(Debug, Default)]
+pub struct ClipScratch {
+    // World vertices of the two polygons, reused by every pair
+    pub(crate) polygons: [Vec<Vec2>; 2],
+    // Clipped vertices with the edges entering and leaving them
+    pub(crate) vertices: Vec<(Vec2, (i32, i32))>,
+}
+
+impl ClipScratch {
+    /// Takes the vertex buffer of polygon `index`, to be given back with
+    /// `ClipScratch::restore`.
+    pub(crate) fn take_polygon(&mut self, index: usize) -> Vec<Vec2> {
+        let mut vertices = take(&mut self.polygons[index]);
+        vertices.clear();
+        vertices
+    }
+
+    pub(crate) fn restore(&mut self, index: usize, vertices: Vec<Vec2>) {
+        self.polygons[index] = vertices;
+    }
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Releases everything allocated since the last reset.
+    pub fn reset(&mut self) {
+        self.contacts.clear();
+        self.clip.vertices.clear();
+        self.indices.clear();
+    }
+
+    /// Lets `fill` append contact points, using the clipping temporaries, and
+    /// returns the appended points.
+    pub fn alloc_contacts(
+        &mut self,
+        fill: impl FnOnce(&mut Vec<ContactInfo>, &mut ClipScratch),
+    ) -> &[ContactInfo] {
+        let start = self.contacts.len();
+        fill(&mut self.contacts, &mut self.clip);
+        self.track();
+        &self.contacts[start.min(self.contacts.len())..]
+    }
+
+    /// Returns `N` zeroed slices of indices with the given lengths.
+    pub fn alloc_indices<const N: usize>(&mut self, lengths: [usize; N]) -> [&mut [usize]; N] {
+        let start = self.indices.len();
+        self.indices
+            .resize(start + lengths.iter().sum::<usize>(), 0);
+        self.track();
+        let mut rest = &mut self.indices[start..];
+        lengths.map(|length| {
+            let (slice, tail) = take(&mut rest).split_at_mut(length);
+            rest = tail;
+            slice
+        })
+    }
+
+    fn track(&mut self) {
+        self.high_water_mark = self.high_water_mark.max(self.used());
+    }
+
+    /// Returns the number of bytes allocated since the last reset.
+    pub fn used(&self) -> usize {
+        self.contacts.len() * size_of::<ContactInfo>()
+            + self.clip.vertices.len() * size_of::<(Vec2, (i32, i32))>()
+            + self.indices.len() * size_of::<usize>()
+    }
+
+    /// Returns the largest number of bytes that were in use at the same time.
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reset_keeps_high_water_mark() {
+        let mut scratch = Scratch::new();
+        let points = scratch.alloc_contacts(|contacts, _| {
+            contacts.push(ContactInfo::default());
+            contacts.push(ContactInfo::default());
+        });
+        assert_eq!(points.len(), 2);
+        let points = scratch.alloc_contacts(|contacts, _| contacts.push(ContactInfo::default()));
+        assert_eq!(points.len(), 1);
+        assert_eq!(scratch.used(), 3 * size_of::<ContactInfo>());
+
+        scratch.reset();
+        scratch.alloc_contacts(|contacts, _| contacts.push(ContactInfo::default()));
+        assert_eq!(scratch.used(), size_of::<ContactInfo>());
+        assert_eq!(scratch.high_water_mark(), 3 * size_of::<ContactInfo>());
+
+        let [first, second] = scratch.alloc_indices([2, 3]);
+        first[1] = 7;
+        assert_eq!((first.len(), second), (2, &mut [0, 0, 0][..]));
+        assert_eq!(
+            scratch.used(),
+            size_of::<ContactInfo>() + 5 * size_of::<usize>()
+        );
+    }
+}
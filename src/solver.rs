@@ -0,0 +1,81 @@
+use std::f32::consts::PI;
+
+/// Overlap of touching bodies that is left alone, avoiding jitter.
+pub const ALLOWED_PENETRATION: f32 = 0.01;
+/// Stiffness of the contacts of the soft solver, capped at a quarter of the
+/// substep rate.
+pub const CONTACT_HERTZ: f32 = 30.0;
+/// Damping ratio of the contacts of the soft solver.
+pub const CONTACT_DAMPING_RATIO: f32 = 10.0;
+/// Damping ratio of the joints of the soft solver, which are twice as stiff as
+/// the contacts.
+pub const JOINT_DAMPING_RATIO: f32 = 2.0;
+/// Largest speed at which the soft solver pushes overlapping bodies apart.
+pub const MAX_PUSHOUT_VELOCITY: f32 = 3.0;
+
+/// Algorithm solving the contacts and joints.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Solver {
+    /// Sequential impulses with a Baumgarte bias pushing overlapping bodies
+    /// apart, running the world's number of iterations.
+    #[default]
+    Impulse,
+    /// Soft constraints solved once per substep, followed by a relaxation pass
+    /// without the bias. Keeps tall stacks and joints stiff at large time steps
+    /// and ignores the world's number of iterations.
+    Soft { substeps: u32 },
+}
+
+/// How the impulse solver removes the overlap of bodies and the drift of joints
+/// when `WorldContext::position_correction` is set.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PositionSolver {
+    /// Adds a bias velocity pushing the bodies apart. Simple, but the bias
+    /// stays in the velocities and makes stacks creep and bounce.
+    #[default]
+    Baumgarte,
+    /// Moves the bodies apart directly after the velocity iterations, without
+    /// changing their velocities. Stops early once the overlap is within
+    /// `WorldContext::allowed_penetration`.
+    NonlinearGaussSeidel { iterations: u32 },
+}
+
+/// Spring and damper of a soft constraint for a given time step, see
+/// "Solver2D" by Erin Catto.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Softness {
+    /// Fraction of the position error to remove per second.
+    pub bias_rate: f32,
+    /// Scale of the effective mass of the impulse.
+    pub mass_scale: f32,
+    /// Fraction of the accumulated impulse to subtract from the impulse.
+    pub impulse_scale: f32,
+}
+
+impl Softness {
+    pub fn new(hertz: f32, damping_ratio: f32, h: f32) -> Self {
+        if hertz == 0.0 {
+            return Self {
+                bias_rate: 0.0,
+                mass_scale: 1.0,
+                impulse_scale: 0.0,
+            };
+        }
+        let omega = 2.0 * PI * hertz;
+        let a1 = 2.0 * damping_ratio + h * omega;
+        let a2 = h * omega * a1;
+        let a3 = 1.0 / (1.0 + a2);
+        Self {
+            bias_rate: omega / a1,
+            mass_scale: a2 * a3,
+            impulse_scale: a3,
+        }
+    }
+
+    /// Returns a rigid constraint, used by the relaxation pass.
+    pub fn rigid() -> Self {
+        Self::new(0.0, 0.0, 0.0)
+    }
+}
@@ -0,0 +1,101 @@
+use crate::errors::Sylt2DErrors;
+use crate::world::World;
+
+/// Steps a world at a fixed rate, independent of the frame rate.
+///
+/// The time of each frame is accumulated and the world is stepped as many whole
+/// time steps as fit. The time left over is returned as a fraction of a step,
+/// to interpolate the rendered bodies between their last two poses.
+#[derive(Debug, Clone)]
+pub struct FixedStepper {
+    time_step: f32,
+    /// Most steps taken per frame. A frame that would need more drops the rest
+    /// of its time, so slow frames do not make the next ones slower still.
+    pub max_steps: u32,
+    accumulator: f32,
+}
+
+impl FixedStepper {
+    /// # Panics
+    ///
+    /// Panics if `time_step` is not positive.
+    pub fn new(time_step: f32) -> Self {
+        assert!(
+            time_step > 0.0,
+            "time_step must be positive, got {time_step}"
+        );
+        Self {
+            time_step,
+            max_steps: 5,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn time_step(&self) -> f32 {
+        self.time_step
+    }
+
+    /// Returns the time not yet stepped as a fraction of a time step, between 0
+    /// and 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.time_step
+    }
+
+    /// Adds the time of a frame, in seconds, and steps the world. Returns the
+    /// interpolation factor, see `FixedStepper::alpha`.
+    pub fn advance(&mut self, world: &mut World, frame_time: f32) -> Result<f32, Sylt2DErrors> {
+        self.advance_with(frame_time, |dt| world.step(dt))
+    }
+
+    /// Like `advance`, but calls `step` with the time step instead of stepping a
+    /// `World`, e.g. to run a `PhysicsPipeline` on bodies in user storage.
+    pub fn advance_with<E>(
+        &mut self,
+        frame_time: f32,
+        mut step: impl FnMut(f32) -> Result<(), E>,
+    ) -> Result<f32, E> {
+        self.accumulator += frame_time.max(0.0);
+        let mut steps = 0;
+        while self.accumulator >= self.time_step {
+            if steps == self.max_steps {
+                // Drop the time that could not be caught up with
+                self.accumulator %= self.time_step;
+                break;
+            }
+            step(self.time_step)?;
+            self.accumulator -= self.time_step;
+            steps += 1;
+        }
+        Ok(self.alpha())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_fixed_stepper() {
+        let mut stepper = FixedStepper::new(0.25);
+        let steps = Cell::new(0);
+        let count = |_: f32| -> Result<(), ()> {
+            steps.set(steps.get() + 1);
+            Ok(())
+        };
+        assert_eq!(stepper.advance_with(0.125, count), Ok(0.5));
+        assert_eq!(stepper.advance_with(0.5, count), Ok(0.5));
+        assert_eq!(steps.get(), 2);
+
+        // A long frame is capped
+        stepper.max_steps = 2;
+        assert_eq!(stepper.advance_with(10.0, count), Ok(0.5));
+        assert_eq!(steps.get(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_time_step() {
+        FixedStepper::new(0.0);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::body::Body;
+use crate::math_utils::Vec2;
+
+/// Rectangle of tiles, in tile coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Grid of solid and empty cells with the bottom left corner of cell `(0, 0)`
+/// at the origin, the tiles of a `Tilemap` or the pixels of a
+/// `bitmap::Bitmap`.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    solid: Vec<bool>,
+}
+
+impl Grid {
+    /// Returns a grid of `width` by `height` empty cells.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            solid: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Cells outside the grid are empty.
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.solid[y * self.width + x]
+    }
+
+    /// Does nothing for cells outside the grid.
+    pub fn set(&mut self, x: usize, y: usize, solid: bool) {
+        if x < self.width && y < self.height {
+            self.solid[y * self.width + x] = solid;
+        }
+    }
+}
+
+/// Grid of solid and empty tiles with the bottom left corner of tile `(0, 0)`
+/// at the origin.
+///
+/// Turns the solid tiles into a few static boxes instead of one body per tile,
+/// see `Tilemap::bodies`.
+#[derive(Debug, Clone)]
+pub struct Tilemap {
+    grid: Grid,
+    tile_size: f32,
+}
+
+impl Tilemap {
+    /// Returns an empty map of `width` by `height` square tiles.
+    pub fn new(width: usize, height: usize, tile_size: f32) -> Self {
+        Self {
+            grid: Grid::new(width, height),
+            tile_size,
+        }
+    }
+
+    /// Returns a map from rows of text, the first row at the top. Tiles are solid
+    /// where the character is `#`.
+    pub fn from_rows(rows: &[&str], tile_size: f32) -> Self {
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut map = Self::new(width, rows.len(), tile_size);
+        for (row, line) in rows.iter().enumerate() {
+            for (x, tile) in line.chars().enumerate() {
+                map.set(x, rows.len() - 1 - row, tile == '#');
+            }
+        }
+        map
+    }
+
+    /// Returns the solid and empty tiles.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn width(&self) -> usize {
+        self.grid.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.height
+    }
+
+    /// See `Grid::is_solid`.
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        self.grid.is_solid(x, y)
+    }
+
+    /// See `Grid::set`.
+    pub fn set(&mut self, x: usize, y: usize, solid: bool) {
+        self.grid.set(x, y, solid);
+    }
+
+    /// Covers the solid tiles with rectangles that do not overlap.
+    ///
+    /// Greedy merging: starting from the bottom left, each rectangle grows as
+    /// far right as possible, then up while the whole row is solid.
+    pub fn rectangles(&self) -> Vec<TileRect> {
+        let (columns, rows) = (self.width(), self.height());
+        let mut covered = vec![false; columns * rows];
+        let free =
+            |covered: &[bool], x: usize, y: usize| self.is_solid(x, y) && !covered[y * columns + x];
+        let mut rectangles = Vec::new();
+        for y in 0..rows {
+            for x in 0..columns {
+                if !free(&covered, x, y) {
+                    continue;
+                }
+                let mut width = 1;
+                while free(&covered, x + width, y) {
+                    width += 1;
+                }
+                let mut height = 1;
+                while y + height < rows
+                    && (x..x + width).all(|column| free(&covered, column, y + height))
+                {
+                    height += 1;
+                }
+                for row in y..y + height {
+                    covered[row * columns + x..row * columns + x + width].fill(true);
+                }
+                rectangles.push(TileRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+        }
+        rectangles
+    }
+
+    /// Returns one static box per rectangle of `Tilemap::rectangles`, placed
+    /// relative to `origin`.
+    pub fn bodies(&self, origin: Vec2) -> Vec<Body> {
+        self.rectangles()
+            .into_iter()
+            .map(|rect| {
+                let size = Vec2::new(rect.width as f32, rect.height as f32) * self.tile_size;
+                let corner = Vec2::new(rect.x as f32, rect.y as f32) * self.tile_size;
+                let mut body = Body::new(size, f32::MAX);
+                body.position = origin + corner + size * 0.5;
+                body
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tilemap_rectangles() {
+        let rows = ["#......#", "#..##..#", "#..##..#", "########"];
+        let map = Tilemap::from_rows(&rows, 0.5);
+        assert_eq!(map.height(), 4);
+        assert!(map.is_solid(3, 1) && !map.is_solid(1, 1) && !map.is_solid(8, 0));
+
+        let rectangles = map.rectangles();
+        assert_eq!(rectangles.len(), 4);
+        assert_eq!(
+            rectangles[0],
+            TileRect {
+                x: 0,
+                y: 0,
+                width: 8,
+                height: 1
+            }
+        );
+        let area: usize = rectangles.iter().map(|rect| rect.width * rect.height).sum();
+        assert_eq!(area, map.grid.solid.iter().filter(|&&solid| solid).count());
+
+        let bodies = map.bodies(Vec2::new(-2.0, 0.0));
+        assert_eq!(bodies[0].position, Vec2::new(0.0, 0.25));
+        assert_eq!(bodies[0].width, Vec2::new(4.0, 0.5));
+        assert_eq!(bodies[0].inv_mass, 0.0);
+        // The walls stand on the floor
+        assert_eq!(bodies[1].position, Vec2::new(-1.75, 1.25));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::math_utils::Vec2;
+use std::sync::{Arc, Mutex};
+
+/// Position and rotation of a shape.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub position: Vec2,
+    pub rotation: f32,
+}
+
+impl Transform {
+    pub fn new(position: Vec2, rotation: f32) -> Self {
+        Self { position, rotation }
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Transform> for glam::Affine2 {
+    fn from(transform: Transform) -> Self {
+        glam::Affine2::from_angle_translation(transform.rotation, transform.position.into())
+    }
+}
+
+/// Drops any scale or shear of the affine transform.
+#[cfg(feature = "glam")]
+impl From<glam::Affine2> for Transform {
+    fn from(affine: glam::Affine2) -> Self {
+        let x_axis = affine.matrix2.x_axis;
+        Transform::new(affine.translation.into(), x_axis.y.atan2(x_axis.x))
+    }
+}
+
+/// Position and rotation of a body at the end of a step.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BodyTransform {
+    pub id: usize,
+    pub position: Vec2,
+    pub rotation: f32,
+}
+
+/// Read-only handle to the body transforms published by the last finished step.
+///
+/// The handle is cheap to clone and can be sent to other threads (rendering,
+/// audio, ...). Reading never borrows the bodies, so it does not contend with a
+/// running `World::step`.
+#[derive(Debug, Clone, Default)]
+pub struct SharedTransforms {
+    front: Arc<Mutex<Arc<Vec<BodyTransform>>>>,
+}
+
+impl SharedTransforms {
+    /// Returns the transforms of the last finished step.
+    pub fn load(&self) -> Arc<Vec<BodyTransform>> {
+        let front = self.front.lock().unwrap_or_else(|err| err.into_inner());
+        front.clone()
+    }
+
+    /// Publishes the back buffer and hands the previous front buffer back for reuse.
+    pub(crate) fn swap(&self, back: &mut Arc<Vec<BodyTransform>>) {
+        let mut front = self.front.lock().unwrap_or_else(|err| err.into_inner());
+        std::mem::swap(&mut *front, back);
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Transform> for nalgebra::Isometry2<f32> {
+    fn from(transform: Transform) -> Self {
+        nalgebra::Isometry2::new(transform.position.into(), transform.rotation)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Isometry2<f32>> for Transform {
+    fn from(isometry: nalgebra::Isometry2<f32>) -> Self {
+        Transform::new(
+            isometry.translation.vector.into(),
+            isometry.rotation.angle(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::world::World;
+    use std::thread;
+
+    #[test]
+    fn test_read_transforms_from_other_thread() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        let id = body.id;
+        world.add_body(body);
+        let shared = world.shared_transforms();
+        assert!(shared.load().is_empty());
+
+        world.step(1.0 / 60.0).unwrap();
+        let first = shared.load();
+        world.step(1.0 / 60.0).unwrap();
+
+        let reader = shared.clone();
+        let latest = thread::spawn(move || reader.load()).join().unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(latest[0].id, id);
+        assert!(latest[0].position.y < first[0].position.y);
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn test_glam_affine() {
+        let transform = Transform::new(Vec2::new(2.0, 3.0), 0.5);
+        let affine = glam::Affine2::from(transform);
+        let point = affine.transform_point2(glam::Vec2::new(1.0, 0.0));
+        assert!((point - glam::Vec2::new(2.0 + 0.5f32.cos(), 3.0 + 0.5f32.sin())).length() < 1e-6);
+        let back = Transform::from(affine);
+        assert_eq!(back.position, transform.position);
+        assert!((back.rotation - 0.5).abs() < 1e-6);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_nalgebra_isometry() {
+        let transform = Transform::new(Vec2::new(2.0, 3.0), 0.5);
+        let isometry = nalgebra::Isometry2::from(transform);
+        let point = isometry * nalgebra::Point2::new(1.0, 0.0);
+        assert!(
+            (point - nalgebra::Point2::new(2.0 + 0.5f32.cos(), 3.0 + 0.5f32.sin())).norm() < 1e-6
+        );
+        let back = Transform::from(isometry);
+        assert_eq!(back.position, transform.position);
+        assert!((back.rotation - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::body::{static_mass, Body, BodyHandle};
+use crate::collide::RayHit;
+use crate::math_utils::Vec2;
+use crate::transforms::BodyTransform;
+use crate::world::World;
+use wasm_bindgen::prelude::*;
+
+/// A `World` for JavaScript, exported as `World`.
+///
+/// Bodies are referred to by their `BodyHandle`. The transforms of all bodies
+/// are read in one go as a `Float32Array`, see `transforms`.
+#[wasm_bindgen(js_name = World)]
+pub struct WasmWorld {
+    world: World,
+}
+
+#[wasm_bindgen(js_class = World)]
+impl WasmWorld {
+    #[wasm_bindgen(constructor)]
+    pub fn new(gravity_x: f32, gravity_y: f32, iterations: u32) -> Self {
+        World::new(Vec2::new(gravity_x, gravity_y), iterations).into()
+    }
+
+    /// Adds a box, static when `mass` is zero.
+    #[wasm_bindgen(js_name = addBox)]
+    pub fn add_box(
+        &mut self,
+        width: f32,
+        height: f32,
+        mass: f32,
+        x: f32,
+        y: f32,
+        rotation: f32,
+    ) -> BodyHandle {
+        let body = Body::new(Vec2::new(width, height), static_mass(mass));
+        self.add(body, x, y, rotation)
+    }
+
+    /// Adds a circle, static when `mass` is zero.
+    #[wasm_bindgen(js_name = addCircle)]
+    pub fn add_circle(&mut self, radius: f32, mass: f32, x: f32, y: f32) -> BodyHandle {
+        let body = Body::new_circle(radius, static_mass(mass));
+        self.add(body, x, y, 0.0)
+    }
+
+    /// Adds a convex polygon from its vertices in `[x0, y0, x1, y1, ...]`
+    /// order, static when `mass` is zero.
+    #[wasm_bindgen(js_name = addPolygon)]
+    pub fn add_polygon(
+        &mut self,
+        vertices: &[f32],
+        mass: f32,
+        x: f32,
+        y: f32,
+        rotation: f32,
+    ) -> Result<BodyHandle, JsError> {
+        let vertices = vertices
+            .chunks_exact(2)
+            .map(|vertex| Vec2::new(vertex[0], vertex[1]))
+            .collect();
+        let body = Body::new_polygon(vertices, static_mass(mass))?;
+        Ok(self.add(body, x, y, rotation))
+    }
+
+    #[wasm_bindgen(js_name = removeBody)]
+    pub fn remove_body(&mut self, body: &BodyHandle) -> bool {
+        self.world.remove_body(body.id())
+    }
+
+    pub fn step(&mut self, dt: f32) -> Result<(), JsError> {
+        Ok(self.world.step(dt)?)
+    }
+
+    #[wasm_bindgen(js_name = bodyCount)]
+    pub fn body_count(&self) -> usize {
+        self.world.body_count()
+    }
+
+    #[wasm_bindgen(js_name = contactCount)]
+    pub fn contact_count(&self) -> usize {
+        self.world.contact_count()
+    }
+
+    /// Returns `[x, y, rotation]` of the body, or undefined if it was removed.
+    pub fn transform(&self, body: &BodyHandle) -> Option<Vec<f32>> {
+        let body = self.world.body(*body)?;
+        Some(vec![body.position.x, body.position.y, body.rotation])
+    }
+
+    #[wasm_bindgen(js_name = setTransform)]
+    pub fn set_transform(&mut self, body: &BodyHandle, x: f32, y: f32, rotation: f32) -> bool {
+        self.world
+            .set_body_transform(*body, Vec2::new(x, y), rotation)
+    }
+
+    /// Returns `[vx, vy, angularVelocity]` of the body, or undefined if it was
+    /// removed.
+    pub fn velocity(&self, body: &BodyHandle) -> Option<Vec<f32>> {
+        let body = self.world.body(*body)?;
+        Some(vec![
+            body.velocity.x,
+            body.velocity.y,
+            body.angular_velocity,
+        ])
+    }
+
+    #[wasm_bindgen(js_name = setVelocity)]
+    pub fn set_velocity(&mut self, body: &BodyHandle, vx: f32, vy: f32, angular: f32) -> bool {
+        self.with_body(body, |body| {
+            body.velocity = Vec2::new(vx, vy);
+            body.angular_velocity = angular;
+        })
+    }
+
+    #[wasm_bindgen(js_name = applyForce)]
+    pub fn apply_force(&mut self, body: &BodyHandle, fx: f32, fy: f32) -> bool {
+        self.with_body(body, |body| body.add_force(Vec2::new(fx, fy)))
+    }
+
+    #[wasm_bindgen(js_name = applyImpulse)]
+    pub fn apply_impulse(&mut self, body: &BodyHandle, ix: f32, iy: f32) -> bool {
+        self.with_body(body, |body| body.apply_impulse(Vec2::new(ix, iy)))
+    }
+
+    /// Returns the ids of the bodies, in the order of `transforms`.
+    #[wasm_bindgen(js_name = bodyIds)]
+    pub fn body_ids(&self) -> Vec<u32> {
+        self.world
+            .iter_bodies()
+            .map(|body| body.id as u32)
+            .collect()
+    }
+
+    /// Returns `[x, y, rotation]` of every body, in storage order.
+    pub fn transforms(&self) -> Vec<f32> {
+        let mut transforms = vec![0.0; 3 * self.world.body_count()];
+        self.write_transforms(&mut transforms);
+        transforms
+    }
+
+    /// Writes `[x, y, rotation]` of every body into `out`, e.g. a
+    /// `Float32Array` kept between frames, as far as it fits.
+    #[wasm_bindgen(js_name = writeTransforms)]
+    pub fn write_transforms(&self, out: &mut [f32]) {
+        for (chunk, transform) in out.chunks_exact_mut(3).zip(self.world.transforms()) {
+            let BodyTransform {
+                position, rotation, ..
+            } = transform;
+            chunk.copy_from_slice(&[position.x, position.y, rotation]);
+        }
+    }
+
+    /// See `World::ray_cast`.
+    #[wasm_bindgen(js_name = rayCast)]
+    pub fn ray_cast(
+        &self,
+        origin_x: f32,
+        origin_y: f32,
+        direction_x: f32,
+        direction_y: f32,
+        max_distance: f32,
+    ) -> Option<WasmRayHit> {
+        self.world
+            .ray_cast(
+                Vec2::new(origin_x, origin_y),
+                Vec2::new(direction_x, direction_y),
+                max_distance,
+            )
+            .map(WasmRayHit::from)
+    }
+
+    /// See `World::query_point`.
+    #[wasm_bindgen(js_name = queryPoint)]
+    pub fn query_point(&self, x: f32, y: f32) -> Vec<BodyHandle> {
+        self.world.query_point(Vec2::new(x, y))
+    }
+}
+
+impl WasmWorld {
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    fn add(&mut self, mut body: Body, x: f32, y: f32, rotation: f32) -> BodyHandle {
+        body.position = Vec2::new(x, y);
+        body.rotation = rotation;
+        self.world.add_body(body)
+    }
+
+    fn with_body(&mut self, body: &BodyHandle, f: impl FnOnce(&mut Body)) -> bool {
+        self.world.body_mut(*body).map(f).is_some()
+    }
+}
+
+impl From<World> for WasmWorld {
+    fn from(world: World) -> Self {
+        Self { world }
+    }
+}
+
+/// A `RayHit` for JavaScript, exported as `RayHit`.
+#[wasm_bindgen(js_name = RayHit)]
+#[derive(Debug, Clone, Copy)]
+pub struct WasmRayHit {
+    body: BodyHandle,
+    pub x: f32,
+    pub y: f32,
+    #[wasm_bindgen(js_name = normalX)]
+    pub normal_x: f32,
+    #[wasm_bindgen(js_name = normalY)]
+    pub normal_y: f32,
+    pub fraction: f32,
+}
+
+#[wasm_bindgen(js_class = RayHit)]
+impl WasmRayHit {
+    #[wasm_bindgen(getter)]
+    pub fn body(&self) -> BodyHandle {
+        self.body
+    }
+}
+
+impl From<RayHit> for WasmRayHit {
+    fn from(hit: RayHit) -> Self {
+        Self {
+            body: hit.body,
+            x: hit.point.x,
+            y: hit.point.y,
+            normal_x: hit.normal.x,
+            normal_y: hit.normal.y,
+            fraction: hit.fraction,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // Runs under wasm-bindgen-test-runner on wasm32
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[test]
+    fn test_wasm_world() {
+        let mut world = WasmWorld::new(0.0, -10.0, 10);
+        let ground = world.add_box(20.0, 1.0, 0.0, 0.0, -0.5, 0.0);
+        let ball = world.add_circle(0.5, 1.0, 0.0, 2.0);
+        for _ in 0..120 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        assert_eq!(world.body_count(), 2);
+        let transform = world.transform(&ball).unwrap();
+        assert!((transform[1] - 0.5).abs() < 0.05);
+
+        let transforms = world.transforms();
+        assert_eq!(transforms.len(), 6);
+        assert_eq!(world.body_ids(), vec![ground.id() as u32, ball.id() as u32]);
+        assert_eq!(&transforms[3..], &transform[..]);
+
+        let hit = world.ray_cast(0.0, 5.0, 0.0, -1.0, 10.0).unwrap();
+        assert_eq!(hit.body(), ball);
+        assert!((hit.y - 1.0).abs() < 0.05);
+        assert_eq!(world.query_point(0.0, -0.5), vec![ground]);
+
+        assert!(world.remove_body(&ball));
+        assert!(world.transform(&ball).is_none());
+        assert!(!world.apply_impulse(&ball, 1.0, 0.0));
+    }
+}
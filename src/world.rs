@@ -1,153 +1,2541 @@
-use crate::arbiter::{Arbiter, ArbiterKey};
-use crate::body::Body;
+use crate::aerodynamics::Wind;
+use crate::arbiter::{Arbiter, ArbiterKey, FrictionCombine, Manifold, RestitutionCombine};
+use crate::body::{reserve_body_id, Body, BodyHandle, BodyType, ShapeGeometry};
+use crate::broad_phase::{Aabb, BroadPhaseMode};
+use crate::ccd::shape_cast;
+use crate::collide::{ray_cast, RayHit};
+use crate::collide_polygon::distance;
 use crate::errors::Sylt2DErrors;
-use crate::joint::Joint;
+use crate::joint::{reserve_joint_id, Joint, JointHandle};
 use crate::math_utils::Vec2;
-use std::cell::{Ref, RefCell};
-use std::collections::HashMap;
-use std::rc::Rc;
-use std::slice::Iter;
+use crate::pipeline::{
+    ContactEvent, ContactImpulse, PhysicsPipeline, StepStats, Stopwatch, WarmStart,
+};
+use crate::solver::{PositionSolver, Solver, ALLOWED_PENETRATION};
+use crate::transforms::{BodyTransform, SharedTransforms, Transform};
+#[cfg(feature = "snapshot")]
+use bincode::Options;
+use std::collections::{btree_map, HashMap};
+use std::slice::{Iter, IterMut};
+use std::sync::Arc;
+use std::vec::Drain;
+#[cfg(feature = "snapshot")]
+use std::{fmt, io};
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct WorldContext {
     pub accumulate_impulse: bool,
     pub warm_starting: bool,
     pub position_correction: bool,
+    /// Fraction of the overlap of bodies the Baumgarte bias removes per step,
+    /// or `PositionSolver::NonlinearGaussSeidel` per iteration. 0.2 by default.
+    pub bias_factor: f32,
+    /// Overlap of touching bodies all solvers leave alone, `ALLOWED_PENETRATION`
+    /// by default.
+    pub allowed_penetration: f32,
+    /// Largest distance `PositionSolver::NonlinearGaussSeidel` moves a contact
+    /// apart per iteration, 0.2 by default.
+    pub max_correction: f32,
+    /// Density of the air used by bodies with an aerodynamics model.
+    pub air_density: f32,
+    /// Algorithm solving the contacts and joints, `Solver::Impulse` by default.
+    pub solver: Solver,
+    /// How the impulse solver corrects positions, `PositionSolver::Baumgarte`
+    /// by default. Not used by the soft solver.
+    pub position_solver: PositionSolver,
+    /// Approach speed below which contacts do not bounce, so resting bodies do
+    /// not jitter. 1 m/s by default.
+    pub restitution_threshold: f32,
+    /// How the restitutions of two bodies combine, `RestitutionCombine::Max` by
+    /// default.
+    pub restitution_combine: RestitutionCombine,
+    /// How the frictions of two bodies combine, `FrictionCombine::GeometricMean`
+    /// by default.
+    pub friction_combine: FrictionCombine,
+    /// Wind blowing on the bodies, none by default.
+    pub wind: Option<Wind>,
+    /// Seconds simulated so far, advanced by `World::step`. Drives the gusts of
+    /// the wind.
+    pub time: f32,
+    /// Friction against the ground of a top-down world, none by default.
+    pub floor_friction: Option<FloorFriction>,
+    /// Clears the forces and torques of the bodies after every step, true by
+    /// default. Without it they keep acting until `World::clear_forces`.
+    pub auto_clear_forces: bool,
+    /// Algorithm finding the pairs of overlapping bounding boxes,
+    /// `BroadPhaseMode::BruteForce` by default.
+    pub broad_phase: BroadPhaseMode,
 }
+
+impl Default for WorldContext {
+    fn default() -> Self {
+        Self {
+            accumulate_impulse: true,
+            warm_starting: false,
+            position_correction: true,
+            bias_factor: 0.2,
+            allowed_penetration: ALLOWED_PENETRATION,
+            max_correction: 0.2,
+            air_density: 1.2,
+            solver: Solver::Impulse,
+            position_solver: PositionSolver::Baumgarte,
+            restitution_threshold: 1.0,
+            restitution_combine: RestitutionCombine::Max,
+            friction_combine: FrictionCombine::GeometricMean,
+            wind: None,
+            time: 0.0,
+            floor_friction: None,
+            auto_clear_forces: true,
+            broad_phase: BroadPhaseMode::BruteForce,
+        }
+    }
+}
+
+/// Settings of a new world, see `World::from_def`.
+///
+/// The defaults match `World::new` with a gravity of 10 m/s² and 10
+/// iterations. Bodies never sleep in this engine, so there is no setting for
+/// it.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WorldDef {
+    pub gravity: Vec2,
+    /// Iterations of the impulse solver.
+    pub velocity_iterations: u32,
+    /// Iterations of `PositionSolver::NonlinearGaussSeidel`, zero uses the
+    /// Baumgarte bias instead. Replaces `WorldContext::position_solver`.
+    pub position_iterations: u32,
+    /// Solver settings, see `World::world_context`.
+    pub context: WorldContext,
+}
+
+impl Default for WorldDef {
+    fn default() -> Self {
+        Self {
+            gravity: Vec2::new(0.0, -10.0),
+            velocity_iterations: 10,
+            position_iterations: 0,
+            context: WorldContext::default(),
+        }
+    }
+}
+
+/// Friction slowing the bodies of a top-down world down as if they slid on the
+/// floor, see `World::top_down`.
+///
+/// Bodies decelerate by `Body::friction` times `normal_acceleration` until they
+/// stop, and turn slower as if their weight was spread over the body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FloorFriction {
+    /// Acceleration pressing the bodies onto the floor, 9.81 m/s² by default.
+    pub normal_acceleration: f32,
+}
+
+impl Default for FloorFriction {
+    fn default() -> Self {
+        Self {
+            normal_acceleration: 9.81,
+        }
+    }
+}
+
+impl FloorFriction {
+    /// Slows the body down over `dt` seconds, without reversing its motion.
+    pub fn apply(&self, body: &mut Body, dt: f32) {
+        if !body.is_dynamic() || body.inv_mass == 0.0 {
+            return;
+        }
+        let deceleration = body.friction * self.normal_acceleration;
+        let speed = body.velocity.length();
+        if speed > 0.0 {
+            let slowed = (speed - deceleration * dt).max(0.0);
+            body.velocity = body.velocity * (slowed / speed);
+        }
+        // Weight spread over the radius of gyration of the body
+        if body.inv_moi > 0.0 {
+            let radius = (body.inv_mass / body.inv_moi).sqrt();
+            let slowed = (body.angular_velocity.abs() - deceleration / radius * dt).max(0.0);
+            body.angular_velocity = slowed.copysign(body.angular_velocity);
+        }
+    }
+}
+/// Motion of the bodies, the joint impulses and the contacts of a world, for
+/// rollback netcode. See `World::save_state`.
+///
+/// Reused between saves, so saving every frame does not allocate once the
+/// buffers have grown.
+#[derive(Debug, Default, Clone)]
+pub struct RollbackState {
+    bodies: Vec<BodyState>,
+    joints: Vec<Joint>,
+    contacts: Vec<(ArbiterKey, Arbiter)>,
+    time: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BodyState {
+    id: usize,
+    position: Vec2,
+    rotation: f32,
+    velocity: Vec2,
+    angular_velocity: f32,
+    force: Vec2,
+    torque: f32,
+    previous_pose: Option<Transform>,
+    lifetime: Option<f32>,
+}
+
 pub struct World {
     gravity: Vec2,
     iterations: u32,
     pub world_context: WorldContext,
-    pub bodies: Vec<Rc<RefCell<Body>>>,
-    pub joints: Vec<Joint>,
-    pub arbiters: HashMap<ArbiterKey, Arbiter>,
+    pub(crate) bodies: Vec<Body>,
+    pub(crate) joints: Vec<Joint>,
+    pub pipeline: PhysicsPipeline,
+    // Storage index by body id, stale if `bodies` was changed directly
+    body_indices: HashMap<usize, usize>,
+    shared_transforms: SharedTransforms,
+    transforms_back_buffer: Arc<Vec<BodyTransform>>,
+    joint_removed: Option<Box<JointRemovedFn>>,
+    // Forces added by the caller, kept by `integrate_velocities` without
+    // `WorldContext::auto_clear_forces`
+    kept_forces: Vec<(Vec2, f32)>,
+    // Forces and poses of the bodies at the start of `step_substeps`
+    substep_forces: Vec<(Vec2, f32)>,
+    substep_poses: Vec<Transform>,
 }
 
+type JointRemovedFn = dyn FnMut(&Joint) + Send + Sync;
+
 pub struct BodiesIter<'a> {
-    inner: Iter<'a, Rc<RefCell<Body>>>,
+    inner: Iter<'a, Body>,
 }
 impl<'a> Iterator for BodiesIter<'a> {
-    type Item = Ref<'a, Body>;
+    type Item = &'a Body;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+pub struct JointsIter<'a> {
+    inner: Iter<'a, Joint>,
+}
+impl<'a> Iterator for JointsIter<'a> {
+    type Item = &'a Joint;
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(|body| body.borrow())
+        self.inner.next()
+    }
+}
+
+pub struct BodiesIterMut<'a> {
+    inner: IterMut<'a, Body>,
+}
+impl<'a> Iterator for BodiesIterMut<'a> {
+    type Item = &'a mut Body;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
     }
 }
 
 impl World {
+    /// Returns a world seen from above, without gravity and with the default
+    /// `FloorFriction` stopping sliding bodies.
+    pub fn top_down(iterations: u32) -> Self {
+        let mut world = Self::new(Vec2::default(), iterations);
+        world.world_context.floor_friction = Some(FloorFriction::default());
+        world
+    }
+
     pub fn new(gravity: Vec2, iterations: u32) -> Self {
+        Self::from_def(WorldDef {
+            gravity,
+            velocity_iterations: iterations,
+            ..Default::default()
+        })
+    }
+
+    /// Returns a world with the settings of `def`.
+    pub fn from_def(def: WorldDef) -> Self {
+        let position_solver = match def.position_iterations {
+            0 => PositionSolver::Baumgarte,
+            iterations => PositionSolver::NonlinearGaussSeidel { iterations },
+        };
         let context = WorldContext {
-            accumulate_impulse: true,
-            warm_starting: false,
-            position_correction: true,
+            position_solver,
+            ..def.context
         };
         Self {
-            gravity,
-            iterations,
+            gravity: def.gravity,
+            iterations: def.velocity_iterations,
             world_context: context,
-            bodies: Vec::<Rc<RefCell<Body>>>::with_capacity(2),
+            bodies: Vec::<Body>::with_capacity(2),
             joints: Vec::<Joint>::with_capacity(2),
-            arbiters: HashMap::<ArbiterKey, Arbiter>::new(),
+            pipeline: PhysicsPipeline::new(),
+            body_indices: HashMap::new(),
+            shared_transforms: SharedTransforms::default(),
+            transforms_back_buffer: Arc::default(),
+            joint_removed: None,
+            kept_forces: Vec::new(),
+            substep_forces: Vec::new(),
+            substep_poses: Vec::new(),
         }
     }
 
-    pub fn add_body(&mut self, body: Body) {
-        self.bodies.push(Rc::new(RefCell::new(body)));
+    /// Returns the number of solver iterations per step.
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn gravity(&self) -> Vec2 {
+        self.gravity
+    }
+
+    /// Changes the gravity from the next step on, see `Body::gravity` to
+    /// override it for single bodies.
+    pub fn set_gravity(&mut self, gravity: Vec2) {
+        self.gravity = gravity;
+    }
+
+    /// Adds a body and returns its handle. A body with the id of one already in
+    /// the world, e.g. a clone of it, gets a new id.
+    pub fn add_body(&mut self, mut body: Body) -> BodyHandle {
+        if self.index_of(body.id).is_some() {
+            body.renew_id();
+        }
+        reserve_body_id(body.id);
+        let handle = body.handle();
+        self.body_indices.insert(body.id, self.bodies.len());
+        self.bodies.push(body);
+        handle
     }
 
-    pub fn iter_bodies(&self) -> BodiesIter {
+    fn index_of(&self, id: usize) -> Option<usize> {
+        match self.body_indices.get(&id) {
+            Some(&index) if self.bodies.get(index).is_some_and(|body| body.id == id) => Some(index),
+            _ => self.bodies.iter().position(|body| body.id == id),
+        }
+    }
+
+    /// Returns the body of the handle, or `None` if it was removed.
+    pub fn body(&self, handle: BodyHandle) -> Option<&Body> {
+        self.get_body(handle.id())
+    }
+
+    /// Returns the body of the handle for modification, or `None` if it was
+    /// removed.
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut Body> {
+        self.get_body_mut(handle.id())
+    }
+
+    /// Returns the body with the given id, or `None` if there is none.
+    pub fn get_body(&self, id: usize) -> Option<&Body> {
+        self.index_of(id).map(|index| &self.bodies[index])
+    }
+
+    /// Returns the body with the given id for modification, or `None` if there
+    /// is none.
+    pub fn get_body_mut(&mut self, id: usize) -> Option<&mut Body> {
+        let index = self.index_of(id)?;
+        self.body_indices.insert(id, index);
+        Some(&mut self.bodies[index])
+    }
+
+    /// Moves the body of the handle to `position` and `rotation`. Returns false
+    /// if it was removed.
+    ///
+    /// The contacts of the body are dropped, so the impulses from its old place
+    /// are not warm started against its new neighbours. The broad phase picks up
+    /// the new place in the next step.
+    pub fn set_body_transform(
+        &mut self,
+        handle: BodyHandle,
+        position: Vec2,
+        rotation: f32,
+    ) -> bool {
+        let Some(body) = self.body_mut(handle) else {
+            return false;
+        };
+        body.position = position;
+        body.rotation = rotation;
+        // Do not interpolate across the jump
+        body.previous_pose = Some(Transform::new(position, rotation));
+        self.pipeline.remove_contacts_of(handle.id());
+        true
+    }
+
+    /// Removes the body with the given id together with its contacts and joints,
+    /// see `World::set_joint_removed_callback`. Returns false if no such body
+    /// exists.
+    pub fn remove_body(&mut self, id: usize) -> bool {
+        let Some(index) = self.bodies.iter().position(|body| body.id == id) else {
+            return false;
+        };
+        self.bodies.remove(index);
+        self.pipeline.remove_contacts_of(id);
+        self.pipeline.remove_pair_rules_of(id);
+        let joint_removed = &mut self.joint_removed;
+        self.joints.retain(|joint| {
+            if !joint.involves(id) {
+                return true;
+            }
+            if let Some(callback) = joint_removed {
+                callback(joint);
+            }
+            false
+        });
+        self.body_indices.clear();
+        self.body_indices.extend(
+            self.bodies
+                .iter()
+                .enumerate()
+                .map(|(index, body)| (body.id, index)),
+        );
+        true
+    }
+
+    /// Spawns a fast moving projectile of the given shape and mass and returns
+    /// its handle. The moment of inertia follows from the shape, see
+    /// `Body::set_mass`.
+    ///
+    /// The projectile is flagged as a bullet, never collides with the `shooter`
+    /// and is removed automatically once its `lifetime` (in seconds) runs out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mass` is not positive.
+    pub fn spawn_projectile(
+        &mut self,
+        origin: Vec2,
+        velocity: Vec2,
+        shape: &ShapeGeometry,
+        mass: f32,
+        shooter: Option<BodyHandle>,
+        lifetime: Option<f32>,
+    ) -> BodyHandle {
+        let mut projectile = Body::from_geometry(shape, Transform::new(origin, 0.0));
+        projectile.renew_id();
+        projectile.set_mass(mass);
+        projectile.set_body_type(BodyType::Dynamic);
+        projectile.velocity = velocity;
+        projectile.bullet = true;
+        projectile.ignore_body = shooter.map(|shooter| shooter.id());
+        projectile.lifetime = lifetime;
+        self.add_body(projectile)
+    }
+
+    /// Spawns a chain of `links` boxes of `link_size` and `mass` evenly spaced
+    /// from `start` to `end`, each joined to the next halfway between them.
+    /// Returns the links from the start, attach the ends with more joints to
+    /// hang the chain up.
+    ///
+    /// The joints are springs of 4 Hz with a damping ratio of 0.7 when the world
+    /// is stepped by `time_step`, so long chains stretch a little instead of
+    /// jittering.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mass` is not positive or infinite (`f32::MAX`), or if
+    /// `time_step` is not positive.
+    pub fn create_chain(
+        &mut self,
+        start: Vec2,
+        end: Vec2,
+        links: usize,
+        link_size: Vec2,
+        mass: f32,
+        time_step: f32,
+    ) -> Vec<BodyHandle> {
+        assert!(
+            mass > 0.0 && mass < f32::MAX,
+            "link mass must be positive and finite, got {mass}"
+        );
+        assert!(
+            time_step > 0.0,
+            "time step must be positive, got {time_step}"
+        );
+        let span = end - start;
+        let step = span * (1.0 / links.max(1) as f32);
+        let rotation = span.y.atan2(span.x);
+
+        let omega = 2.0 * std::f32::consts::PI * 4.0;
+        let d = 2.0 * mass * 0.7 * omega;
+        let k = mass * omega * omega;
+
+        let mut handles: Vec<BodyHandle> = Vec::with_capacity(links);
+        for i in 0..links {
+            let mut link = Body::new(link_size, mass);
+            link.position = start + step * (i as f32 + 0.5);
+            link.rotation = rotation;
+            if let Some(&previous) = handles.last() {
+                let previous = self.body(previous).expect("links are not removed");
+                let mut joint = Joint::from_bodies(previous, &link, start + step * i as f32);
+                joint.softness = 1.0 / (d + time_step * k);
+                joint.bias_factor = time_step * k / (d + time_step * k);
+                self.add_joint(joint);
+            }
+            handles.push(self.add_body(link));
+        }
+        handles
+    }
+
+    pub fn iter_bodies(&self) -> BodiesIter<'_> {
         BodiesIter {
             inner: self.bodies.iter(),
         }
     }
 
-    pub fn add_joint(&mut self, joint: Joint) {
+    /// Iterates over the bodies for modification, e.g. to add forces every
+    /// frame.
+    pub fn iter_bodies_mut(&mut self) -> BodiesIterMut<'_> {
+        BodiesIterMut {
+            inner: self.bodies.iter_mut(),
+        }
+    }
+
+    pub fn iter_joints(&self) -> JointsIter<'_> {
+        JointsIter {
+            inner: self.joints.iter(),
+        }
+    }
+
+    /// Iterates over the contacts of the touching pairs, see
+    /// `PhysicsPipeline::iter_arbiters`.
+    pub fn iter_arbiters(&self) -> btree_map::Iter<'_, ArbiterKey, Arbiter> {
+        self.pipeline.iter_arbiters()
+    }
+
+    /// Returns the contact points of two touching bodies, in any order, e.g.
+    /// to check whether the player stands on a platform.
+    pub fn contacts_between(&self, a: BodyHandle, b: BodyHandle) -> Option<&Manifold> {
+        self.pipeline
+            .arbiters
+            .get(&ArbiterKey::from_ids(a.id(), b.id()))
+            .map(|arbiter| &arbiter.manifold)
+    }
+
+    pub fn body_count(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joints.len()
+    }
+
+    /// Returns the number of touching pairs of bodies.
+    pub fn contact_count(&self) -> usize {
+        self.pipeline.arbiters.len()
+    }
+
+    /// Returns the number of bodies that move, i.e. are not static. Bodies do
+    /// not sleep, so these are all simulated every step.
+    pub fn awake_body_count(&self) -> usize {
+        self.bodies
+            .iter()
+            .filter(|body| body.body_type() != BodyType::Static)
+            .count()
+    }
+
+    /// Returns the closest body hit by the ray from `origin` along `direction`
+    /// within `max_distance`, e.g. for line of sight. See `collide::ray_cast`.
+    pub fn ray_cast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<RayHit> {
+        self.ray_hits(origin, direction, max_distance)
+            .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+    }
+
+    /// Returns all bodies hit by the ray, the closest first.
+    pub fn ray_cast_all(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Vec<RayHit> {
+        let mut hits: Vec<RayHit> = self.ray_hits(origin, direction, max_distance).collect();
+        hits.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+        hits
+    }
+
+    fn ray_hits(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+    ) -> impl Iterator<Item = RayHit> + '_ {
+        let length = direction.length();
+        let end = if length > 0.0 {
+            origin + direction * (max_distance / length)
+        } else {
+            origin
+        };
+        let bounds = Aabb::from_points(&[origin, end]);
+        self.bodies
+            .iter()
+            .filter(move |body| body.aabb().overlaps(&bounds))
+            .filter_map(move |body| ray_cast(body, origin, direction, max_distance))
+    }
+
+    /// Sweeps the shape from `transform` along `translation` and returns the
+    /// first body in the way, e.g. to move a character controller. Bodies the
+    /// shape already touches do not block it, so it can slide along the ground.
+    /// The hit normal points from the body towards the shape. See
+    /// `ccd::shape_cast`.
+    pub fn shape_cast(
+        &self,
+        shape: &ShapeGeometry,
+        transform: Transform,
+        translation: Vec2,
+    ) -> Option<RayHit> {
+        let moving = Body::from_geometry(shape, transform);
+        let start = moving.aabb();
+        let mut end = start;
+        end.min = end.min + translation;
+        end.max = end.max + translation;
+        let swept = start.union(&end);
+        self.bodies
+            .iter()
+            .filter(|body| body.aabb().overlaps(&swept))
+            .filter_map(|body| {
+                let (fraction, normal, point) = shape_cast(&moving, translation, body)?;
+                Some(RayHit {
+                    body: body.handle(),
+                    point,
+                    normal,
+                    fraction,
+                })
+            })
+            .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+    }
+
+    /// Returns the bodies whose shape contains the world point, e.g. to pick a
+    /// body with the mouse.
+    pub fn query_point(&self, point: Vec2) -> Vec<BodyHandle> {
+        let bounds = Aabb::new(point, point);
+        self.bodies
+            .iter()
+            .filter(|body| body.aabb().overlaps(&bounds) && body.contains_point(point))
+            .map(Body::handle)
+            .collect()
+    }
+
+    /// Returns the current transforms of all bodies, in storage order.
+    pub fn transforms(&self) -> impl Iterator<Item = BodyTransform> + '_ {
+        self.bodies.iter().map(|body| BodyTransform {
+            id: body.id,
+            position: body.position,
+            rotation: body.rotation,
+        })
+    }
+
+    /// Replaces the content of `transforms` with the current transforms of all
+    /// bodies, reusing its allocation.
+    pub fn write_transforms_into(&self, transforms: &mut Vec<BodyTransform>) {
+        transforms.clear();
+        transforms.extend(self.transforms());
+    }
+
+    /// Returns a handle to the body transforms published at the end of each step,
+    /// which other threads can read while the world is stepping.
+    pub fn shared_transforms(&self) -> SharedTransforms {
+        self.shared_transforms.clone()
+    }
+
+    fn publish_transforms(&mut self) {
+        // Taking the buffer out would allocate an empty `Arc` in its place
+        let back_buffer = Arc::make_mut(&mut self.transforms_back_buffer);
+        back_buffer.clear();
+        back_buffer.extend(self.bodies.iter().map(|body| BodyTransform {
+            id: body.id,
+            position: body.position,
+            rotation: body.rotation,
+        }));
+        self.shared_transforms
+            .swap(&mut self.transforms_back_buffer);
+    }
+
+    /// Returns the distance between two bodies and their closest points, see
+    /// `collide_polygon::distance`. The distance is `None` if a body has no
+    /// vertices.
+    pub fn distance(
+        &self,
+        id_a: usize,
+        id_b: usize,
+    ) -> Result<Option<(f32, Vec2, Vec2)>, Sylt2DErrors> {
+        let find = |id: usize| {
+            self.bodies
+                .iter()
+                .find(|body| body.id == id)
+                .ok_or(Sylt2DErrors::BodyNotFound(id))
+        };
+        Ok(distance(find(id_a)?, find(id_b)?))
+    }
+
+    /// Adds a joint and returns its handle. A joint with the id of one already
+    /// in the world gets a new id, like a body in `World::add_body`.
+    pub fn add_joint(&mut self, mut joint: Joint) -> JointHandle {
+        if self.joint(joint.handle()).is_some() {
+            joint.renew_id();
+        }
+        reserve_joint_id(joint.handle().id());
+        let handle = joint.handle();
         self.joints.push(joint);
+        handle
+    }
+
+    /// Returns the joint of the handle, or `None` if it was removed.
+    pub fn joint(&self, handle: JointHandle) -> Option<&Joint> {
+        self.joints.iter().find(|joint| joint.handle() == handle)
+    }
+
+    /// Returns the joint of the handle for modification, or `None` if it was
+    /// removed.
+    pub fn joint_mut(&mut self, handle: JointHandle) -> Option<&mut Joint> {
+        self.joints
+            .iter_mut()
+            .find(|joint| joint.handle() == handle)
+    }
+
+    /// Removes the joint of the handle. Returns false if it was already removed.
+    pub fn remove_joint(&mut self, handle: JointHandle) -> bool {
+        let Some(index) = self
+            .joints
+            .iter()
+            .position(|joint| joint.handle() == handle)
+        else {
+            return false;
+        };
+        self.joints.remove(index);
+        true
+    }
+
+    /// Calls `callback` with each joint removed because one of its bodies was,
+    /// e.g. to drop game state referring to it.
+    pub fn set_joint_removed_callback(
+        &mut self,
+        callback: impl FnMut(&Joint) + Send + Sync + 'static,
+    ) {
+        self.joint_removed = Some(Box::new(callback));
     }
 
     pub fn clear(&mut self) {
         self.bodies.clear();
+        self.body_indices.clear();
         self.joints.clear();
-        self.arbiters.clear();
+        self.pipeline.clear();
     }
 
     pub fn broad_phase(&mut self) -> Result<(), Sylt2DErrors> {
-        for i in 0..self.bodies.len() {
-            let body_i = self.bodies[i].borrow();
-
-            for j in (i + 1)..self.bodies.len() {
-                let body_j = self.bodies[j].borrow();
-                if body_i.inv_mass == 0.0 && body_j.inv_mass == 0.0 {
-                    continue;
-                };
-                let new_arbiter = Arbiter::new(self.bodies[i].clone(), self.bodies[j].clone());
-                let key = ArbiterKey::new(&body_i, &body_j);
-
-                if new_arbiter.num_contacts > 0 {
-                    match self.arbiters.entry(key) {
-                        std::collections::hash_map::Entry::Occupied(mut entry) => {
-                            let arbiter = entry.get_mut();
-                            arbiter.update(
-                                new_arbiter.contacts.as_ref(),
-                                new_arbiter.num_contacts,
-                                &self.world_context,
-                            )?
-                        }
-                        std::collections::hash_map::Entry::Vacant(entry) => {
-                            entry.insert(new_arbiter);
-                        }
-                    }
-                } else {
-                    self.arbiters.remove(&key);
-                }
+        self.pipeline.broad_phase(&self.bodies, &self.world_context)
+    }
+
+    /// Returns the accumulated contact and joint impulses, which have to be saved
+    /// along with the bodies and joints to restore the world without a pop.
+    pub fn warm_start(&self) -> WarmStart {
+        self.pipeline.warm_start(&self.joints)
+    }
+
+    /// Saves the state that changes while stepping into `state`: the motion of
+    /// the bodies that are not static, the joints and the contacts.
+    pub fn save_state(&self, state: &mut RollbackState) {
+        state.bodies.clear();
+        state.bodies.extend(
+            self.bodies
+                .iter()
+                .filter(|body| body.body_type() != BodyType::Static)
+                .map(|body| BodyState {
+                    id: body.id,
+                    position: body.position,
+                    rotation: body.rotation,
+                    velocity: body.velocity,
+                    angular_velocity: body.angular_velocity,
+                    force: body.force,
+                    torque: body.torque,
+                    previous_pose: body.previous_pose,
+                    lifetime: body.lifetime,
+                }),
+        );
+        state.joints.clone_from(&self.joints);
+        state.contacts.clear();
+        state.contacts.extend(
+            self.pipeline
+                .arbiters
+                .iter()
+                .map(|(key, arbiter)| (*key, *arbiter)),
+        );
+        state.time = self.world_context.time;
+    }
+
+    /// Rewinds the world to a state saved by `save_state`, so stepping it again
+    /// gives exactly the same results.
+    ///
+    /// Bodies and joints added or removed since the save are not, they have to
+    /// be rolled back by the caller. Returns an error without changing the world
+    /// if a saved body is gone.
+    pub fn load_state(&mut self, state: &RollbackState) -> Result<(), Sylt2DErrors> {
+        // Leave the world untouched if the state does not fit
+        if let Some(saved) = state
+            .bodies
+            .iter()
+            .find(|saved| self.index_of(saved.id).is_none())
+        {
+            return Err(Sylt2DErrors::BodyNotFound(saved.id));
+        }
+        for saved in state.bodies.iter() {
+            let Some(index) = self.index_of(saved.id) else {
+                continue;
+            };
+            let body = &mut self.bodies[index];
+            body.position = saved.position;
+            body.rotation = saved.rotation;
+            body.velocity = saved.velocity;
+            body.angular_velocity = saved.angular_velocity;
+            body.force = saved.force;
+            body.torque = saved.torque;
+            body.previous_pose = saved.previous_pose;
+            body.lifetime = saved.lifetime;
+        }
+        for (i, saved) in state.joints.iter().enumerate() {
+            let joint = match self.joints.get_mut(i) {
+                Some(joint) if joint.handle() == saved.handle() => Some(joint),
+                _ => self
+                    .joints
+                    .iter_mut()
+                    .find(|joint| joint.handle() == saved.handle()),
+            };
+            if let Some(joint) = joint {
+                joint.clone_from(saved);
             }
         }
+        self.pipeline.arbiters.clear();
+        self.pipeline
+            .arbiters
+            .extend(state.contacts.iter().map(|(key, arbiter)| (*key, *arbiter)));
+        self.world_context.time = state.time;
+        self.publish_transforms();
         Ok(())
     }
 
+    /// Restores the accumulated contact and joint impulses saved by `warm_start`.
+    /// The bodies and joints have to be restored first.
+    pub fn set_warm_start(&mut self, warm_start: &WarmStart) -> Result<(), Sylt2DErrors> {
+        self.pipeline
+            .set_warm_start(warm_start, &self.bodies, &mut self.joints)
+    }
+
+    /// Returns the statistics of the last step.
+    pub fn last_step_stats(&self) -> StepStats {
+        self.pipeline.last_step_stats()
+    }
+
+    /// Sets the function deciding which pairs of bodies may touch, see
+    /// `PhysicsPipeline::set_contact_filter`.
+    pub fn set_contact_filter(
+        &mut self,
+        filter: impl Fn(&Body, &Body) -> bool + Send + Sync + 'static,
+    ) {
+        self.pipeline.set_contact_filter(filter);
+    }
+
+    /// Sets the function changing the contacts before they are solved, see
+    /// `PhysicsPipeline::set_pre_solve`.
+    pub fn set_pre_solve(
+        &mut self,
+        pre_solve: impl Fn(&Body, &Body, &mut Arbiter) + Send + Sync + 'static,
+    ) {
+        self.pipeline.set_pre_solve(pre_solve);
+    }
+
+    /// Sets how the frictions of two bodies combine when they touch, e.g. `Min`
+    /// for a tyre on ice. See `PhysicsPipeline::set_pair_friction_combine`.
+    pub fn set_pair_friction_combine(
+        &mut self,
+        a: BodyHandle,
+        b: BodyHandle,
+        rule: Option<FrictionCombine>,
+    ) {
+        self.pipeline
+            .set_pair_friction_combine(a.id(), b.id(), rule);
+    }
+
+    /// Returns the impulses applied at the contact points during the last step,
+    /// see `PhysicsPipeline::contact_impulses`.
+    pub fn contact_impulses(&self) -> &[ContactImpulse] {
+        self.pipeline.contact_impulses()
+    }
+
+    /// Returns the pairs of bodies that started or stopped touching since the
+    /// last call, see `PhysicsPipeline::drain_events`.
+    pub fn drain_events(&mut self) -> Drain<'_, ContactEvent> {
+        self.pipeline.drain_events()
+    }
+
+    /// Returns the pairs of bodies considered by the broad phase of the last step,
+    /// before the narrow phase checked them for contacts.
+    pub fn broad_phase_pairs(&self) -> &[ArbiterKey] {
+        self.pipeline.broad_phase_pairs()
+    }
+
+    /// Advances the world by `dt` seconds.
+    ///
+    /// Convenience wrapper running the stages `integrate_forces`, `update_contacts`,
+    /// `solve` and `integrate_velocities`, then removing expired bodies and
+    /// publishing the transforms.
     pub fn step(&mut self, dt: f32) -> Result<(), Sylt2DErrors> {
-        let inv_dt = if dt > 0.0 { 1.0 / dt } else { 0.0 };
-        // Determine overlapping bodies and update contact points.
-        self.broad_phase()?;
+        self.run_stages(dt, self.world_context.warm_starting)?;
+        self.finish_step(dt);
+        Ok(())
+    }
 
-        // Integrate forces.
-        for body in self.bodies.iter() {
-            let mut body = body.borrow_mut();
-            if body.inv_mass == 0.0 {
-                continue;
-            };
-            body.velocity = body.velocity + (self.gravity + body.force * body.inv_mass) * dt;
-            body.angular_velocity += body.inv_moi * body.torque * dt;
+    /// Advances the world by `dt` seconds in `substeps` equal steps, running
+    /// all stages of `step` for each of them.
+    ///
+    /// The contact and joint impulses are carried from one substep to the next
+    /// even without `WorldContext::warm_starting`, so only the first substep
+    /// starts from scratch. Keeps joints together at time steps that make them
+    /// fly apart with `step`.
+    pub fn step_substeps(&mut self, dt: f32, substeps: u32) -> Result<(), Sylt2DErrors> {
+        let substeps = substeps.max(1);
+        let h = dt / substeps as f32;
+        // Every substep applies the forces, which `integrate_velocities` clears
+        self.substep_forces.clear();
+        self.substep_forces
+            .extend(self.bodies.iter().map(|body| (body.force, body.torque)));
+        self.substep_poses.clear();
+        self.substep_poses.extend(
+            self.bodies
+                .iter()
+                .map(|body| Transform::new(body.position, body.rotation)),
+        );
+        let mut result = Ok(());
+        for substep in 0..substeps {
+            for (body, &(force, torque)) in self.bodies.iter_mut().zip(self.substep_forces.iter()) {
+                body.force = force;
+                body.torque = torque;
+            }
+            let warm_starting = self.world_context.warm_starting || substep > 0;
+            result = self.run_stages(h, warm_starting);
+            if result.is_err() {
+                break;
+            }
+        }
+        // The previous pose is the one at the start of the whole step
+        for (body, &pose) in self.bodies.iter_mut().zip(self.substep_poses.iter()) {
+            body.previous_pose = Some(pose);
         }
+        result?;
+        self.finish_step(dt);
+        Ok(())
+    }
+
+    fn run_stages(&mut self, dt: f32, warm_starting: bool) -> Result<(), Sylt2DErrors> {
+        span!("step");
+        let start = Stopwatch::start();
+        self.integrate_forces(dt);
+        let integrate_forces = start.elapsed_ms();
+        self.update_contacts()?;
+        self.solve_with(dt, warm_starting)?;
+        let start = Stopwatch::start();
+        self.integrate_velocities(dt);
+        self.world_context.time += dt;
+        self.pipeline
+            .record_integrate_time(integrate_forces + start.elapsed_ms());
+        Ok(())
+    }
 
-        // Pefrom pre-steps
-        for (_, arbiter) in self.arbiters.iter_mut() {
-            arbiter.pre_step(inv_dt, &self.world_context);
+    // Removes expired bodies and publishes the transforms.
+    fn finish_step(&mut self, dt: f32) {
+        // Remove bodies whose lifetime ran out
+        let mut expired = Vec::new();
+        for body in self.bodies.iter_mut() {
+            if let Some(lifetime) = body.lifetime.as_mut() {
+                *lifetime -= dt;
+                if *lifetime <= 0.0 {
+                    expired.push(body.id);
+                }
+            }
         }
+        for id in expired {
+            self.remove_body(id);
+        }
+
+        self.publish_transforms();
+    }
 
-        for joint in self.joints.iter_mut() {
-            joint.pre_step(&self.world_context, inv_dt)?;
+    /// Applies gravity, wind and the forces added since the last step to the
+    /// velocities.
+    pub fn integrate_forces(&mut self, dt: f32) {
+        if !self.world_context.auto_clear_forces {
+            // Wind and aerodynamics add to the forces for this step only
+            self.kept_forces.clear();
+            self.kept_forces
+                .extend(self.bodies.iter().map(|body| (body.force, body.torque)));
         }
+        PhysicsPipeline::integrate_forces(&mut self.bodies, self.gravity, &self.world_context, dt);
+    }
+
+    /// Determines overlapping bodies and updates the contact points.
+    pub fn update_contacts(&mut self) -> Result<(), Sylt2DErrors> {
+        self.pipeline
+            .update_contacts(&self.bodies, &self.world_context)
+    }
 
-        // Perfrom iterations
-        for _ in 0..self.iterations {
-            for (_, arbiter) in self.arbiters.iter_mut() {
-                arbiter.apply_impulse(&self.world_context);
+    /// Solves contacts and joints, changing the velocities of the bodies. The
+    /// soft and the position solver also correct their positions, see
+    /// `PhysicsPipeline::solve`.
+    pub fn solve(&mut self, dt: f32) -> Result<(), Sylt2DErrors> {
+        self.solve_with(dt, self.world_context.warm_starting)
+    }
+
+    // Solves with warm starting turned on or off for this call only
+    fn solve_with(&mut self, dt: f32, warm_starting: bool) -> Result<(), Sylt2DErrors> {
+        let world_context = WorldContext {
+            warm_starting,
+            ..self.world_context
+        };
+        self.pipeline.solve(
+            &mut self.bodies,
+            &mut self.joints,
+            self.gravity,
+            self.iterations,
+            &world_context,
+            dt,
+        )
+    }
+
+    /// Moves the bodies by their velocities and clears the accumulated forces,
+    /// unless `WorldContext::auto_clear_forces` is off.
+    pub fn integrate_velocities(&mut self, dt: f32) {
+        PhysicsPipeline::integrate_velocities(&mut self.bodies, dt);
+        if !self.world_context.auto_clear_forces {
+            for (body, &(force, torque)) in self.bodies.iter_mut().zip(self.kept_forces.iter()) {
+                body.force = force;
+                body.torque = torque;
             }
+        }
+    }
 
-            for joint in self.joints.iter_mut() {
-                joint.apply_impulse();
+    /// Removes the forces and torques from all bodies, see
+    /// `WorldContext::auto_clear_forces`.
+    pub fn clear_forces(&mut self) {
+        for body in self.bodies.iter_mut() {
+            body.force = Vec2::default();
+            body.torque = 0.0;
+        }
+    }
+}
+
+// Simulation state of a world, see the serde impls of `World`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct WorldStateRef<'a> {
+    gravity: Vec2,
+    iterations: u32,
+    world_context: &'a WorldContext,
+    bodies: &'a [Body],
+    joints: &'a [Joint],
+    warm_start: WarmStart,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct WorldState {
+    gravity: Vec2,
+    iterations: u32,
+    world_context: WorldContext,
+    bodies: Vec<Body>,
+    joints: Vec<Joint>,
+    warm_start: WarmStart,
+}
+
+/// Saves the bodies, joints, settings and the accumulated impulses of the
+/// contacts, so a loaded world continues exactly where it was saved.
+///
+/// The broad phase, callbacks and contact events are not saved. A loaded world
+/// has the default broad phase and no callbacks.
+#[cfg(feature = "serde")]
+impl serde::Serialize for World {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WorldStateRef {
+            gravity: self.gravity,
+            iterations: self.iterations,
+            world_context: &self.world_context,
+            bodies: &self.bodies,
+            joints: &self.joints,
+            warm_start: self.warm_start(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for World {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = WorldState::deserialize(deserializer)?;
+        let mut world = World::new(state.gravity, state.iterations);
+        world.apply_state(state).map_err(serde::de::Error::custom)?;
+        Ok(world)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl World {
+    // Replaces the bodies, joints and settings, keeping the broad phase and the
+    // callbacks.
+    fn apply_state(&mut self, state: WorldState) -> Result<(), Sylt2DErrors> {
+        self.clear();
+        self.gravity = state.gravity;
+        self.iterations = state.iterations;
+        self.world_context = state.world_context;
+        for body in state.bodies {
+            self.add_body(body);
+        }
+        for joint in state.joints {
+            self.add_joint(joint);
+        }
+        self.set_warm_start(&state.warm_start)?;
+        self.publish_transforms();
+        Ok(())
+    }
+}
+
+// Encoding of full and delta snapshots, with variable length integers.
+#[cfg(feature = "snapshot")]
+fn snapshot_options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+#[cfg(feature = "snapshot")]
+#[derive(Debug, PartialEq)]
+pub enum SnapshotErrors {
+    /// The snapshot ends before the whole state was read.
+    Truncated,
+    /// The bytes do not encode a state, e.g. they contain an unknown shape.
+    Corrupted,
+}
+
+#[cfg(feature = "snapshot")]
+impl fmt::Display for SnapshotErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotErrors::Truncated => write!(f, "The snapshot is cut short."),
+            SnapshotErrors::Corrupted => write!(f, "The snapshot does not encode a world."),
+        }
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl std::error::Error for SnapshotErrors {}
+
+#[cfg(feature = "snapshot")]
+impl From<bincode::Error> for SnapshotErrors {
+    fn from(err: bincode::Error) -> Self {
+        match *err {
+            bincode::ErrorKind::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                SnapshotErrors::Truncated
             }
+            _ => SnapshotErrors::Corrupted,
         }
+    }
+}
 
-        // Integrate Velocities
+#[cfg(feature = "snapshot")]
+impl World {
+    /// Encodes the state saved by the serde impls into a compact binary
+    /// snapshot, see `World::restore`.
+    pub fn snapshot(&self) -> Vec<u8> {
+        snapshot_options()
+            .serialize(self)
+            .expect("the world is serializable")
+    }
+
+    /// Replaces the state of the world with a snapshot taken by
+    /// `World::snapshot`. Stepping the world afterwards gives exactly the same
+    /// results as when the snapshot was taken. Keeps the broad phase and the
+    /// callbacks.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), Sylt2DErrors> {
+        let state: WorldState = snapshot_options()
+            .deserialize(snapshot)
+            .map_err(SnapshotErrors::from)?;
+        self.apply_state(state)
+    }
+}
+
+/// Velocities in delta snapshots are rounded to multiples of this. See
+/// `World::delta_snapshot`.
+#[cfg(feature = "snapshot")]
+pub const VELOCITY_STEP: f32 = 1.0 / 128.0;
+
+/// Motion of the bodies as last sent over the network, which delta snapshots
+/// are encoded against, see `World::delta_snapshot`.
+///
+/// The sender keeps the baseline the receiver acknowledged, the receiver saves
+/// its own after applying a delta. An empty baseline makes the delta carry all
+/// bodies.
+#[cfg(feature = "snapshot")]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DeltaBaseline {
+    bodies: HashMap<usize, SyncedBody>,
+}
+
+#[cfg(feature = "snapshot")]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SyncedBody {
+    position: Vec2,
+    rotation: f32,
+    // In multiples of `VELOCITY_STEP`, small ones taking few bytes
+    velocity: (i32, i32),
+    angular_velocity: i32,
+}
+
+#[cfg(feature = "snapshot")]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct DeltaState {
+    time: f32,
+    added: Vec<Body>,
+    removed: Vec<usize>,
+    changed: Vec<(usize, SyncedBody)>,
+}
+
+#[cfg(feature = "snapshot")]
+impl SyncedBody {
+    fn new(body: &Body) -> Self {
+        let quantize = |velocity: f32| (velocity / VELOCITY_STEP).round() as i32;
+        Self {
+            position: body.position,
+            rotation: body.rotation,
+            velocity: (quantize(body.velocity.x), quantize(body.velocity.y)),
+            angular_velocity: quantize(body.angular_velocity),
+        }
+    }
+
+    fn apply(&self, body: &mut Body) {
+        body.position = self.position;
+        body.rotation = self.rotation;
+        body.velocity = Vec2::new(
+            self.velocity.0 as f32 * VELOCITY_STEP,
+            self.velocity.1 as f32 * VELOCITY_STEP,
+        );
+        body.angular_velocity = self.angular_velocity as f32 * VELOCITY_STEP;
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl World {
+    /// Saves the motion of the bodies as a delta snapshot sends it, to encode
+    /// the next deltas against.
+    pub fn save_baseline(&self, baseline: &mut DeltaBaseline) {
+        baseline.bodies.clear();
+        baseline.bodies.extend(
+            self.bodies
+                .iter()
+                .map(|body| (body.id, SyncedBody::new(body))),
+        );
+    }
+
+    /// Encodes the bodies that moved, were added or were removed since
+    /// `baseline`, for syncing a world over the network every tick. Bodies at
+    /// rest are left out, and the velocities are rounded to `VELOCITY_STEP`.
+    ///
+    /// Only bodies are synced, joints and settings need a full snapshot. See
+    /// `World::apply_delta`.
+    pub fn delta_snapshot(&self, baseline: &DeltaBaseline) -> Vec<u8> {
+        let mut delta = DeltaState {
+            time: self.world_context.time,
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
         for body in self.bodies.iter() {
-            let mut body = body.borrow_mut();
-            body.position = body.position + body.velocity * dt;
-            body.rotation += body.angular_velocity * dt;
+            let synced = SyncedBody::new(body);
+            match baseline.bodies.get(&body.id) {
+                None => delta.added.push(body.clone()),
+                Some(old) if *old != synced => delta.changed.push((body.id, synced)),
+                Some(_) => {}
+            }
+        }
+        delta.removed.extend(
+            baseline
+                .bodies
+                .keys()
+                .filter(|&&id| self.index_of(id).is_none()),
+        );
+        // Sorted so the same state always encodes to the same bytes
+        delta.removed.sort_unstable();
+        snapshot_options()
+            .serialize(&delta)
+            .expect("the delta is serializable")
+    }
 
-            body.force = Vec2::default();
-            body.torque = 0.0;
+    /// Applies a delta snapshot taken by `World::delta_snapshot`. The world has
+    /// to be in the state of the baseline the delta was encoded against, e.g.
+    /// the one saved with `save_baseline` after applying the previous delta.
+    /// Returns an error without changing the world if a changed body is
+    /// missing.
+    pub fn apply_delta(&mut self, delta: &[u8]) -> Result<(), Sylt2DErrors> {
+        let delta: DeltaState = snapshot_options()
+            .deserialize(delta)
+            .map_err(SnapshotErrors::from)?;
+        // Leave the world untouched if the delta does not fit
+        for (id, _) in delta.changed.iter() {
+            let added = delta.added.iter().any(|body| body.id == *id);
+            let kept = delta.removed.binary_search(id).is_err() && self.index_of(*id).is_some();
+            if !added && !kept {
+                return Err(Sylt2DErrors::BodyNotFound(*id));
+            }
+        }
+        for id in delta.removed {
+            self.remove_body(id);
+        }
+        for body in delta.added {
+            if let Some(index) = self.index_of(body.id) {
+                self.bodies[index] = body;
+            } else {
+                self.add_body(body);
+            }
+        }
+        for (id, synced) in delta.changed.iter() {
+            if let Some(index) = self.index_of(*id) {
+                synced.apply(&mut self.bodies[index]);
+            }
         }
+        self.world_context.time = delta.time;
+        self.publish_transforms();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::BodyType;
+    use crate::broad_phase::SpatialHash;
+    use crate::joint::{AreaJoint, JointBuilder, JointImpulse, JointKind};
+
+    #[test]
+    fn test_body_handles() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let a = world.add_body(Body::new(Vec2::new(1.0, 1.0), 1.0));
+        let b = world.add_body(Body::new(Vec2::new(2.0, 2.0), 1.0));
+        world.body_mut(b).unwrap().position = Vec2::new(3.0, 0.0);
+        assert_eq!(world.body(b).unwrap().position, Vec2::new(3.0, 0.0));
+
+        assert!(world.remove_body(a.id()));
+        assert!(world.body(a).is_none());
+        assert_eq!(world.body(b).unwrap().width, Vec2::new(2.0, 2.0));
+
+        // Still found after changing the storage directly
+        world.bodies.insert(0, Body::new(Vec2::new(1.0, 1.0), 1.0));
+        assert_eq!(world.body_mut(b).unwrap().width, Vec2::new(2.0, 2.0));
+
+        world.get_body_mut(b.id()).unwrap().velocity = Vec2::new(1.0, 0.0);
+        assert_eq!(
+            world.get_body(b.id()).unwrap().velocity,
+            Vec2::new(1.0, 0.0)
+        );
+        assert!(world.get_body(a.id()).is_none());
+
+        for body in world.iter_bodies_mut() {
+            body.add_force(Vec2::new(0.0, 5.0));
+        }
+        assert!(world
+            .iter_bodies()
+            .all(|body| body.force == Vec2::new(0.0, 5.0)));
+
+        // A clone is added as a body of its own
+        let body = world.body(b).unwrap().clone();
+        let clone = world.add_body(body);
+        assert_ne!(clone, b);
+        world.body_mut(clone).unwrap().position = Vec2::new(-3.0, 0.0);
+        assert_eq!(world.body(b).unwrap().position, Vec2::new(3.0, 0.0));
+        let joint = Joint::from_bodies(
+            world.body(b).unwrap(),
+            world.body(clone).unwrap(),
+            Vec2::default(),
+        );
+        let first = world.add_joint(joint.clone());
+        let second = world.add_joint(joint);
+        assert_ne!(first, second);
+        assert_eq!(world.joint_count(), 2);
+    }
+
+    #[test]
+    fn test_remove_joints() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let removed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = removed.clone();
+        world.set_joint_removed_callback(move |joint| sink.lock().unwrap().push(joint.handle()));
+        let ground = Body::new(Vec2::new(1.0, 1.0), f32::MAX);
+        let mut a = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        a.position = Vec2::new(3.0, 0.0);
+        let mut b = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        b.position = Vec2::new(6.0, 0.0);
+        let first = world.add_joint(Joint::from_bodies(&ground, &a, Vec2::new(1.5, 0.0)));
+        let second = world.add_joint(Joint::from_bodies(&a, &b, Vec2::new(4.5, 0.0)));
+        let third = world.add_joint(Joint::from_bodies(&ground, &b, Vec2::new(3.0, 3.0)));
+        world.add_body(ground);
+        let a = world.add_body(a);
+        world.add_body(b);
+
+        assert!(world.remove_joint(first));
+        assert!(!world.remove_joint(first));
+        assert!(world.joint(first).is_none());
+        assert!(world.remove_body(a.id()));
+        assert_eq!(*removed.lock().unwrap(), vec![second]);
+        assert_eq!(world.joints.len(), 1);
+        assert!(world.joint(third).is_some());
+        world.step(1.0 / 60.0).unwrap();
+    }
+
+    #[test]
+    fn test_create_chain() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let ground = world.add_body(Body::new(Vec2::new(0.2, 0.2), f32::MAX));
+        let links = world.create_chain(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, -8.0),
+            8,
+            Vec2::new(0.75, 0.25),
+            1.0,
+            1.0 / 60.0,
+        );
+        assert_eq!(links.len(), 8);
+        assert_eq!(world.joints.len(), 7);
+        let first = world.body(links[0]).unwrap();
+        assert_eq!(first.position, Vec2::new(0.0, -0.5));
+        assert!((first.rotation + std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        crate::joint::JointBuilder::revolute(ground, links[0])
+            .anchor(Vec2::new(0.0, 0.0))
+            .build(&mut world)
+            .unwrap();
+
+        for _ in 0..120 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        // Hangs from the ground, stretched a little by its weight
+        let last = world.body(links[7]).unwrap();
+        assert!(last.position.x.abs() < 0.01);
+        assert!((last.position.y + 7.5).abs() < 0.2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_create_static_chain() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.create_chain(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, -8.0),
+            8,
+            Vec2::new(0.75, 0.25),
+            f32::MAX,
+            1.0 / 60.0,
+        );
+    }
+
+    #[test]
+    fn test_area_joint() {
+        let squeeze = |keep_area: bool| {
+            let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+            let mut ring = Vec::new();
+            for i in 0..8 {
+                let angle = i as f32 * std::f32::consts::TAU / 8.0;
+                let direction = Vec2::new(angle.cos(), angle.sin());
+                let mut node = Body::new(Vec2::new(0.3, 0.3), 1.0);
+                node.position = direction * 2.0;
+                // Squeezed from the sides
+                node.velocity = Vec2::new(-3.0 * direction.x, 0.0);
+                ring.push(node);
+            }
+            for i in 0..8 {
+                let (a, b) = (&ring[i], &ring[(i + 1) % 8]);
+                world.add_joint(Joint::distance(a, b, a.position, b.position));
+            }
+            let area = Joint::area(&ring.iter().collect::<Vec<_>>());
+            let target_area = match area.kind() {
+                JointKind::Area(area) => area.target_area,
+                _ => unreachable!(),
+            };
+            assert!((target_area - 8.0 * 2.0_f32.sqrt()).abs() < 1e-4);
+            if keep_area {
+                world.add_joint(area);
+            }
+            let ring: Vec<BodyHandle> = ring.into_iter().map(|node| world.add_body(node)).collect();
+
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            let positions: Vec<Vec2> = ring
+                .iter()
+                .map(|&node| world.body(node).unwrap().position)
+                .collect();
+            AreaJoint::area(&positions) / target_area
+        };
+        assert!(squeeze(false) < 0.8);
+        assert!((squeeze(true) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut near = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        near.position = Vec2::new(3.0, 0.0);
+        let mut far = Body::new_circle(0.5, 1.0);
+        far.position = Vec2::new(6.0, 0.2);
+        let mut aside = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        aside.position = Vec2::new(4.0, 3.0);
+        let near = world.add_body(near);
+        let far = world.add_body(far);
+        world.add_body(aside);
+
+        let hit = world
+            .ray_cast(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 10.0)
+            .unwrap();
+        assert_eq!(hit.body, near);
+        assert_eq!(hit.point, Vec2::new(2.5, 0.0));
+        let hits = world.ray_cast_all(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 10.0);
+        assert_eq!(
+            hits.iter().map(|hit| hit.body).collect::<Vec<_>>(),
+            vec![near, far]
+        );
+        // Too short to reach
+        assert!(world
+            .ray_cast(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), 2.0)
+            .is_none());
+
+        assert_eq!(world.query_point(Vec2::new(6.2, 0.5)), vec![far]);
+        assert!(world.query_point(Vec2::new(5.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn test_shape_cast() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        let mut wall = Body::new(Vec2::new(1.0, 4.0), f32::MAX);
+        wall.position = Vec2::new(5.0, 2.0);
+        world.add_body(ground);
+        let wall = world.add_body(wall);
+
+        // Standing on the ground and walking into the wall
+        let player = ShapeGeometry::new_box(Vec2::new(1.0, 2.0));
+        let at = Transform::new(Vec2::new(0.0, 1.0), 0.0);
+        let hit = world.shape_cast(&player, at, Vec2::new(8.0, 0.0)).unwrap();
+        assert_eq!(hit.body, wall);
+        assert!((hit.fraction * 8.0 - 4.0).abs() < 0.02);
+        assert!((hit.normal - Vec2::new(-1.0, 0.0)).length() < 1e-3);
+        assert!((hit.point.x - 4.5).abs() < 1e-3);
+        assert!(world
+            .shape_cast(&player, at, Vec2::new(-8.0, 0.0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_contact_events() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        ground.position = Vec2::new(0.0, -0.5);
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 1.0);
+        let key = ArbiterKey::new(&ground, &box_);
+        world.add_body(ground);
+        let box_ = world.add_body(box_);
+
+        let mut begins = 0;
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+            for event in world.drain_events() {
+                match event {
+                    ContactEvent::Begin {
+                        key: touching,
+                        manifold,
+                    } => {
+                        assert_eq!(touching, key);
+                        assert!(manifold.point_count() > 0);
+                        begins += 1;
+                    }
+                    ContactEvent::End { .. } => panic!("the box keeps resting"),
+                }
+            }
+        }
+        // Only the first touch, not every step of resting
+        assert_eq!(begins, 1);
+
+        world.remove_body(box_.id());
+        let events: Vec<_> = world.drain_events().collect();
+        assert!(matches!(events[..], [ContactEvent::End { key: ended, .. }] if ended == key));
+        assert!(world.drain_events().next().is_none());
+
+        // Undrained events only last for one more step
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 0.45);
+        world.add_body(box_);
+        world.step(1.0 / 60.0).unwrap();
+        world.step(1.0 / 60.0).unwrap();
+        assert!(world.drain_events().next().is_none());
+    }
+
+    #[test]
+    fn test_one_way_platform() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut platform = Body::new(Vec2::new(4.0, 0.2), f32::MAX);
+        platform.position = Vec2::new(0.0, 1.0);
+        let platform_id = platform.id;
+        let mut box_ = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        box_.position = Vec2::new(0.0, -1.0);
+        box_.velocity = Vec2::new(0.0, 8.0);
+        world.set_pre_solve(move |body_1, body_2, arbiter| {
+            let (platform, other) = if body_1.id == platform_id {
+                (body_1, body_2)
+            } else {
+                (body_2, body_1)
+            };
+            // Solid only from above
+            if platform.id == platform_id && other.position.y < platform.position.y + 0.3 {
+                arbiter.modification.enabled = false;
+            }
+            arbiter.modification.friction = Some(0.0);
+        });
+        world.add_body(platform);
+        let box_ = world.add_body(box_);
+
+        for _ in 0..120 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        // Jumped through and landed on top
+        let body = world.body(box_).unwrap();
+        assert!((body.position.y - 1.35).abs() < 0.02);
+        assert!(body.velocity.length() < 0.01);
+    }
+
+    #[test]
+    fn test_pair_friction_combine() {
+        let slide = |rule: Option<FrictionCombine>| {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            let mut ground = Body::new(Vec2::new(100.0, 1.0), f32::MAX);
+            ground.friction = 1.0;
+            let mut ice = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            ice.friction = 0.04;
+            ice.position = Vec2::new(0.0, 1.0);
+            ice.velocity = Vec2::new(3.0, 0.0);
+            let ground = world.add_body(ground);
+            let ice = world.add_body(ice);
+            world.set_pair_friction_combine(ice, ground, rule);
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            world.body(ice).unwrap().position.x
+        };
+        // The geometric mean 0.2 brakes the block much harder than the
+        // friction of the ice alone
+        let stopped = slide(None);
+        assert!(stopped < 2.5);
+        assert!(slide(Some(FrictionCombine::Min)) > stopped + 0.2);
+        assert_eq!(slide(Some(FrictionCombine::GeometricMean)), stopped);
+    }
+
+    #[test]
+    fn test_contact_impulses() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context.solver = solver;
+            world.world_context.warm_starting = true;
+            let mut ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+            ground.position = Vec2::new(0.0, -0.5);
+            let mut box_ = Body::new(Vec2::new(1.0, 1.0), 2.0);
+            box_.position = Vec2::new(0.0, 0.5);
+            world.add_body(ground);
+            world.add_body(box_);
+
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            // Resting, the contacts carry the weight of the box
+            let impulses = world.contact_impulses();
+            assert_eq!(impulses.len(), 2);
+            let total: f32 = impulses.iter().map(|impulse| impulse.normal_impulse).sum();
+            assert!((total - 20.0 / 60.0).abs() < 0.01);
+            assert!(impulses
+                .iter()
+                .all(|impulse| impulse.tangent_impulse.abs() < 1e-3));
+        }
+    }
+
+    #[test]
+    fn test_gravity() {
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.world_context.solver = solver;
+            let falling = world.add_body(Body::new(Vec2::new(1.0, 1.0), 1.0));
+            let mut balloon = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            balloon.position = Vec2::new(5.0, 0.0);
+            balloon.gravity = Some(Vec2::new(0.0, 2.0));
+            let balloon = world.add_body(balloon);
+
+            world.set_gravity(Vec2::new(-10.0, 0.0));
+            assert_eq!(world.gravity(), Vec2::new(-10.0, 0.0));
+            for _ in 0..60 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            let velocity = |handle| world.body(handle).unwrap().velocity;
+            assert!((velocity(falling) - Vec2::new(-10.0, 0.0)).length() < 1e-3);
+            assert!((velocity(balloon) - Vec2::new(0.0, 2.0)).length() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_set_body_transform() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 1.0);
+        let box_ = world.add_body(box_);
+        for _ in 0..30 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        assert_eq!(world.pipeline.arbiters.len(), 1);
+
+        assert!(world.set_body_transform(box_, Vec2::new(3.0, 0.9), 0.0));
+        assert!(world.pipeline.arbiters.is_empty());
+        let body = world.body(box_).unwrap();
+        assert_eq!(body.interpolated_pose(0.0).position, Vec2::new(3.0, 0.9));
+
+        // The new contact starts without the old impulses
+        world.broad_phase().unwrap();
+        let arbiter = world.pipeline.arbiters.values().next().unwrap();
+        assert!(arbiter
+            .manifold
+            .points
+            .iter()
+            .all(|contact| contact.pn == 0.0));
+
+        world.remove_body(box_.id());
+        assert!(!world.set_body_transform(box_, Vec2::default(), 0.0));
+    }
+
+    #[test]
+    fn test_step_substeps() {
+        let make_world = || {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            let mut pivot = Body::new(Vec2::new(0.2, 0.2), f32::MAX);
+            pivot.position = Vec2::new(0.0, 10.0);
+            let mut bob = Body::new(Vec2::new(0.5, 0.5), 1.0);
+            bob.position = Vec2::new(3.0, 10.0);
+            world
+                .joints
+                .push(Joint::from_bodies(&pivot, &bob, pivot.position));
+            world.add_body(pivot);
+            let bob = world.add_body(bob);
+            (world, bob)
+        };
+        let length = |world: &World, bob| {
+            (world.body(bob).unwrap().position - Vec2::new(0.0, 10.0)).length()
+        };
+        let (mut stepped, bob) = make_world();
+        let (mut substepped, substepped_bob) = make_world();
+        let (mut stretch, mut substepped_stretch) = (0.0_f32, 0.0_f32);
+        for _ in 0..50 {
+            stepped.step(0.1).unwrap();
+            substepped.step_substeps(0.1, 8).unwrap();
+            stretch = stretch.max(length(&stepped, bob) - 3.0);
+            substepped_stretch = substepped_stretch.max(length(&substepped, substepped_bob) - 3.0);
+        }
+        assert!(stretch > 0.1);
+        assert!(substepped_stretch < 0.02);
+        // The setting of the caller is left alone
+        assert!(!substepped.world_context.warm_starting);
+    }
+
+    #[test]
+    fn test_interpolated_pose() {
+        let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+        let mut body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        body.velocity = Vec2::new(6.0, 0.0);
+        body.angular_velocity = 3.0;
+        let handle = world.add_body(body);
+        assert_eq!(
+            world.body(handle).unwrap().interpolated_pose(0.5).position,
+            Vec2::default()
+        );
+
+        world.step(1.0 / 60.0).unwrap();
+        world.step_substeps(1.0 / 60.0, 4).unwrap();
+        let body = world.body(handle).unwrap();
+        assert_eq!(body.interpolated_pose(0.0).position, Vec2::new(0.1, 0.0));
+        let halfway = body.interpolated_pose(0.5);
+        assert_eq!(halfway.position, Vec2::new(0.15, 0.0));
+        assert!((halfway.rotation - 0.075).abs() < 1e-6);
+        assert_eq!(body.interpolated_pose(1.0).position, body.position);
+    }
+
+    #[test]
+    fn test_projectile_ignores_shooter() {
+        let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+        let shooter = world.add_body(Body::new(Vec2::new(1.0, 1.0), 1.0));
+
+        world.spawn_projectile(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(50.0, 0.0),
+            &ShapeGeometry::new_box(Vec2::new(0.2, 0.2)),
+            0.1,
+            Some(shooter),
+            None,
+        );
+        world.step(1.0 / 60.0).unwrap();
+        assert!(world.pipeline.arbiters.is_empty());
+        assert_eq!(world.bodies[0].velocity, Vec2::default());
+    }
+
+    #[test]
+    fn test_bullet_does_not_tunnel() {
+        for bullet in [false, true] {
+            let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+            let mut plank = Body::new(Vec2::new(0.1, 4.0), f32::MAX);
+            plank.position = Vec2::new(5.0, 0.0);
+            world.add_body(plank);
+            let mut bomb = Body::new(Vec2::new(0.2, 0.2), 1.0);
+            bomb.velocity = Vec2::new(600.0, 0.0);
+            bomb.bullet = bullet;
+            world.add_body(bomb);
+
+            for _ in 0..3 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            let bomb = &world.bodies[1];
+            assert_eq!(bomb.position.x < 5.0, bullet);
+        }
+    }
+
+    #[test]
+    fn test_bullet_contact() {
+        let shoot = |world: &mut World| {
+            let mut plank = Body::new(Vec2::new(0.1, 4.0), f32::MAX);
+            plank.position = Vec2::new(5.0, 0.0);
+            plank.restitution = 1.0;
+            world.add_body(plank);
+            let mut bomb = Body::new(Vec2::new(0.2, 0.2), 1.0);
+            bomb.velocity = Vec2::new(600.0, 0.0);
+            bomb.restitution = 1.0;
+            bomb.bullet = true;
+            let bomb = world.add_body(bomb);
+            world.step(1.0 / 60.0).unwrap();
+            world.body(bomb).unwrap().clone()
+        };
+
+        // Bounces off with the restitution of the pair
+        for solver in [Solver::Impulse, Solver::Soft { substeps: 4 }] {
+            let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+            world.world_context.solver = solver;
+            let bomb = shoot(&mut world);
+            assert!(bomb.position.x < 5.0);
+            assert!((bomb.velocity.x + 600.0).abs() < 1.0);
+            assert!(world.contact_impulses()[0].normal_impulse > 0.0);
+            assert_eq!(world.contact_count(), 0);
+        }
+
+        // Passes through where the game rules say so
+        let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+        world.set_contact_filter(|body_1, body_2| body_1.bullet == body_2.bullet);
+        assert!(shoot(&mut world).position.x > 5.0);
+        let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+        world.set_pre_solve(|_, _, arbiter| arbiter.modification.enabled = false);
+        assert!(shoot(&mut world).position.x > 5.0);
+    }
+
+    #[test]
+    fn test_collision_filter() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        for x in [0.0, 0.5] {
+            let mut debris = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            debris.position = Vec2::new(x, 0.9);
+            debris.collision_filter.collision_category = 2;
+            debris.collision_filter.collision_mask = !2;
+            world.add_body(debris);
+        }
+        // The pieces of debris overlap but only touch the ground
+        world.broad_phase().unwrap();
+        assert_eq!(world.pipeline.arbiters.len(), 2);
+
+        // A positive group overrides the masks
+        for debris in world.bodies[1..].iter_mut() {
+            debris.collision_filter.group_index = 1;
+        }
+        world.broad_phase().unwrap();
+        assert_eq!(world.pipeline.arbiters.len(), 3);
+
+        // Teammates pass through each other
+        world.set_contact_filter(|a, b| {
+            a.collision_filter.group_index != b.collision_filter.group_index
+        });
+        world.broad_phase().unwrap();
+        assert_eq!(world.pipeline.arbiters.len(), 2);
+    }
+
+    #[test]
+    fn test_projectile_lifetime() {
+        let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+        let projectile = world.spawn_projectile(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            &ShapeGeometry::new_circle(0.1),
+            0.1,
+            None,
+            Some(0.1),
+        );
+        let body = world.body(projectile).unwrap();
+        assert!(body.bullet && body.is_dynamic());
+        assert_eq!(body.velocity, Vec2::new(10.0, 0.0));
+        let same = Body::new_circle(0.1, 0.1);
+        assert_eq!((body.inv_mass, body.inv_moi), (same.inv_mass, same.inv_moi));
+        for _ in 0..10 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        assert_eq!(world.bodies.len(), 0);
+    }
+
+    #[test]
+    fn test_write_transforms_into() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        for x in 0..3 {
+            let mut body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            body.position = Vec2::new(x as f32 * 2.0, 0.0);
+            body.rotation = 0.1;
+            world.add_body(body);
+        }
+        world.step(1.0 / 60.0).unwrap();
+
+        let mut transforms = vec![BodyTransform::default(); 5];
+        world.write_transforms_into(&mut transforms);
+        assert_eq!(transforms.len(), 3);
+        for (transform, body) in transforms.iter().zip(world.iter_bodies()) {
+            assert_eq!(transform.id, body.id);
+            assert_eq!(transform.position, body.position);
+            assert_eq!(transform.rotation, body.rotation);
+        }
+        assert!(world.transforms().eq(transforms.iter().copied()));
+    }
+
+    #[test]
+    fn test_step_stages() {
+        let make_world = || {
+            let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+            world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+            let mut body = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            body.position = Vec2::new(0.0, 0.95);
+            world.add_body(body);
+            world
+        };
+        let mut stepped = make_world();
+        let mut staged = make_world();
+        for _ in 0..30 {
+            stepped.step(1.0 / 60.0).unwrap();
+
+            staged.integrate_forces(1.0 / 60.0);
+            staged.update_contacts().unwrap();
+            staged.solve(1.0 / 60.0).unwrap();
+            staged.integrate_velocities(1.0 / 60.0);
+        }
+        assert_eq!(staged.pipeline.arbiters.len(), 1);
+        assert_eq!(staged.bodies[1].position, stepped.bodies[1].position);
+
+        // Custom logic between the stages is seen by the solver
+        staged.integrate_forces(1.0 / 60.0);
+        staged.update_contacts().unwrap();
+        staged.bodies[1].velocity.y = -5.0;
+        staged.solve(1.0 / 60.0).unwrap();
+        assert!(staged.bodies[1].velocity.y > -1.0);
+    }
+
+    #[test]
+    fn test_broad_phase_pairs() {
+        let mut world = World::new(Vec2::new(0.0, 0.0), 10);
+        let ground = Body::new(Vec2::new(2.0, 1.0), f32::MAX);
+        let mut wall = Body::new(Vec2::new(1.0, 20.0), f32::MAX);
+        wall.position = Vec2::new(-1.0, 0.0);
+        let mut near = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        // Overlapping bounding boxes, but no contact
+        near.position = Vec2::new(1.6, 1.1);
+        near.rotation = std::f32::consts::FRAC_PI_4;
+        let mut far = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        far.position = Vec2::new(5.0, 5.0);
+        let (ground_id, near_id) = (ground.id, near.id);
+        world.add_body(ground);
+        world.add_body(wall);
+        world.add_body(near);
+        world.add_body(far);
+
+        world.broad_phase().unwrap();
+        let pairs = world.broad_phase_pairs();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].ids(), (ground_id, near_id));
+        assert!(world.pipeline.arbiters.is_empty());
+
+        world.world_context.broad_phase = BroadPhaseMode::SpatialHash { cell_size: 1.0 };
+        world.broad_phase().unwrap();
+        let pairs = world.broad_phase_pairs();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].ids(), (ground_id, near_id));
+
+        world.world_context.broad_phase = BroadPhaseMode::Custom;
+        world.pipeline.set_broadphase(SpatialHash::new(1.0));
+        world.broad_phase().unwrap();
+        assert_eq!(world.broad_phase_pairs().len(), 1);
+    }
+
+    #[test]
+    fn test_restore_warm_start() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        let ground = Body::new(Vec2::new(20.0, 1.0), f32::MAX);
+        let mut bob = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        bob.position = Vec2::new(8.0, 5.0);
+        world.add_joint(Joint::from_bodies(&ground, &bob, Vec2::new(5.0, 5.0)));
+        world.add_body(ground);
+        world.add_body(bob);
+        for i in 0..4 {
+            let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            box_.friction = 0.5;
+            box_.position = Vec2::new(0.0, 1.0 + i as f32);
+            world.add_body(box_);
+        }
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+
+        // Restore the bodies and joint definitions into a new world
+        let restore = |warm_start: Option<&WarmStart>| {
+            let mut restored = World::new(Vec2::new(0.0, -10.0), 10);
+            restored.world_context = world.world_context;
+            for body in world.iter_bodies() {
+                restored.add_body(body.clone());
+            }
+            for joint in world.joints.iter() {
+                let mut joint = joint.clone();
+                joint.set_accumulated_impulse(JointImpulse::default());
+                restored.add_joint(joint);
+            }
+            if let Some(warm_start) = warm_start {
+                restored.set_warm_start(warm_start).unwrap();
+            }
+            restored
+        };
+        let warm_start = world.warm_start();
+        assert_eq!(warm_start.contacts.len(), 4);
+        let mut restored = restore(Some(&warm_start));
+        let mut cold = restore(None);
+
+        world.step(1.0 / 60.0).unwrap();
+        restored.step(1.0 / 60.0).unwrap();
+        cold.step(1.0 / 60.0).unwrap();
+        for (restored, body) in restored.iter_bodies().zip(world.iter_bodies()) {
+            assert_eq!(restored.velocity, body.velocity);
+            assert_eq!(restored.position, body.position);
+        }
+        // Without the impulses the stack sinks into itself
+        assert!(cold.bodies[5].velocity.y < world.bodies[5].velocity.y - 0.01);
+    }
+
+    #[test]
+    fn test_restore_joint_warm_start() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        let ground = world.add_body(Body::new(Vec2::new(1.0, 1.0), f32::MAX));
+        let mut arm = Body::new(Vec2::new(2.0, 0.2), 1.0);
+        arm.position = Vec2::new(1.0, 5.0);
+        let arm = world.add_body(arm);
+        let mut weight = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        weight.position = Vec2::new(2.25, 5.0);
+        let weight = world.add_body(weight);
+        // The motor is too weak to hold the arm up, which rests on its limit
+        JointBuilder::revolute(ground, arm)
+            .anchor(Vec2::new(0.0, 5.0))
+            .limits(-0.2, 0.5)
+            .motor(1.0, 5.0)
+            .build(&mut world)
+            .unwrap();
+        JointBuilder::weld(arm, weight)
+            .anchor(Vec2::new(2.0, 5.0))
+            .build(&mut world)
+            .unwrap();
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        let warm_start = world.warm_start();
+        assert!(warm_start.joints[0].limits.0 > 0.0);
+        assert!(warm_start.joints[0].angular != 0.0);
+        assert!(warm_start.joints[1].angular != 0.0);
+
+        let restore = |warm_start: Option<&WarmStart>| {
+            let mut restored = World::new(Vec2::new(0.0, -10.0), 10);
+            restored.world_context = world.world_context;
+            for body in world.iter_bodies() {
+                restored.add_body(body.clone());
+            }
+            for joint in world.joints.iter() {
+                let mut joint = joint.clone();
+                joint.set_accumulated_impulse(JointImpulse::default());
+                restored.add_joint(joint);
+            }
+            if let Some(warm_start) = warm_start {
+                restored.set_warm_start(warm_start).unwrap();
+            }
+            restored
+        };
+        let mut restored = restore(Some(&warm_start));
+        let mut cold = restore(None);
+
+        world.step(1.0 / 60.0).unwrap();
+        restored.step(1.0 / 60.0).unwrap();
+        cold.step(1.0 / 60.0).unwrap();
+        for (restored, body) in restored.iter_bodies().zip(world.iter_bodies()) {
+            assert_eq!(restored.velocity, body.velocity);
+            assert_eq!(restored.angular_velocity, body.angular_velocity);
+        }
+        let body = |world: &World| world.body(arm).unwrap().angular_velocity;
+        assert!((body(&cold) - body(&world)).abs() > 1e-4);
+    }
+
+    #[test]
+    fn test_restore_warm_start_unknown_body() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 0.99);
+        world.add_body(box_);
+        world.step(1.0 / 60.0).unwrap();
+        let warm_start = world.warm_start();
+
+        // A world without the box keeps its own contacts
+        let mut other = World::new(Vec2::new(0.0, -10.0), 10);
+        other.add_body(world.bodies[0].clone());
+        let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        box_.position = Vec2::new(0.0, 0.99);
+        other.add_body(box_);
+        other.step(1.0 / 60.0).unwrap();
+        let contacts = other.contact_count();
+        assert!(matches!(
+            other.set_warm_start(&warm_start),
+            Err(Sylt2DErrors::BodyNotFound(_))
+        ));
+        assert_eq!(other.contact_count(), contacts);
+    }
+
+    #[test]
+    fn test_moving_platform_carries_rider() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let mut platform = Body::new(Vec2::new(10.0, 1.0), f32::MAX);
+        platform.set_body_type(BodyType::Kinematic);
+        platform.carries_riders = true;
+        platform.friction = 1.0;
+        platform.velocity = Vec2::new(2.0, 0.0);
+        world.add_body(platform);
+
+        // Carried by the friction of the platform, not its own
+        let mut rider = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        rider.position = Vec2::new(0.0, 0.99);
+        world.add_body(rider);
+
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        let offset = world.bodies[1].position - world.bodies[0].position;
+        assert!(offset.x.abs() < 0.25);
+        assert!((world.bodies[1].velocity.x - 2.0).abs() < 1e-3);
+
+        // Reversing the platform drags the rider along once friction caught up
+        world.bodies[0].velocity = Vec2::new(-2.0, -1.0);
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        let offset = world.bodies[1].position - world.bodies[0].position;
+        assert!((offset.y - 1.0).abs() < 0.05);
+        assert!((world.bodies[1].velocity.x + 2.0).abs() < 1e-3);
+
+        // A platform without friction lets the rider slide
+        world.bodies[0].friction = 0.0;
+        world.bodies[0].velocity = Vec2::new(2.0, -1.0);
+        for _ in 0..10 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        assert!((world.bodies[1].velocity.x + 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_wind() {
+        let mut world = World::new(Vec2::default(), 10);
+        world.world_context.wind = Some(Wind::constant(Vec2::new(4.0, 0.0), 2.0));
+        let leaf = world.add_body(Body::new(Vec2::new(0.1, 0.1), 0.1));
+        let mut crate_body = Body::new(Vec2::new(1.0, 1.0), 10.0);
+        crate_body.position = Vec2::new(0.0, 5.0);
+        let heavy = world.add_body(crate_body);
+        let mut anchored = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        anchored.position = Vec2::new(0.0, -5.0);
+        anchored.wind_drag = Some(0.0);
+        let calm = world.add_body(anchored);
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        assert!((world.world_context.time - 1.0).abs() < 1e-4);
+        let leaf = world.get_body(leaf.id()).unwrap().velocity.x;
+        let heavy = world.get_body(heavy.id()).unwrap().velocity.x;
+        // The leaf is carried along with the wind, the crate only starts moving
+        assert!((leaf - 4.0).abs() < 0.01);
+        assert!(heavy > 0.0 && heavy < 1.0);
+        assert_eq!(world.get_body(calm.id()).unwrap().velocity, Vec2::default());
+
+        world.step_substeps(1.0 / 60.0, 4).unwrap();
+        assert!((world.world_context.time - 61.0 / 60.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_top_down() {
+        let mut world = World::top_down(10);
+        let mut puck = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        puck.friction = 0.5;
+        puck.velocity = Vec2::new(4.0, 0.0);
+        puck.angular_velocity = -3.0;
+        let puck = world.add_body(puck);
+        let mut rock = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        rock.position = Vec2::new(0.0, 5.0);
+        let rock = world.add_body(rock);
+
+        world.step(0.1).unwrap();
+        let body = world.get_body(puck.id()).unwrap();
+        assert!((body.velocity.x - (4.0 - 0.5 * 9.81 * 0.1)).abs() < 1e-4);
+        assert!(body.angular_velocity < 0.0 && body.angular_velocity > -3.0);
+        // Resting bodies stay put
+        assert_eq!(world.get_body(rock.id()).unwrap().velocity, Vec2::default());
+
+        for _ in 0..20 {
+            world.step(0.1).unwrap();
+        }
+        let body = world.get_body(puck.id()).unwrap();
+        assert_eq!(body.velocity, Vec2::default());
+        assert_eq!(body.angular_velocity, 0.0);
+        assert!(body.position.x > 1.0);
+    }
+
+    #[test]
+    fn test_from_def() {
+        let world = World::from_def(WorldDef {
+            gravity: Vec2::new(0.0, -9.81),
+            velocity_iterations: 8,
+            position_iterations: 3,
+            context: WorldContext {
+                warm_starting: true,
+                ..Default::default()
+            },
+        });
+        assert_eq!(world.gravity(), Vec2::new(0.0, -9.81));
+        assert_eq!(world.iterations, 8);
+        assert!(world.world_context.warm_starting);
+        assert_eq!(
+            world.world_context.position_solver,
+            PositionSolver::NonlinearGaussSeidel { iterations: 3 }
+        );
+
+        // A stiffer bias pushes an overlapping box out faster
+        let overlap = |bias_factor| {
+            let mut world = World::from_def(WorldDef {
+                gravity: Vec2::default(),
+                context: WorldContext {
+                    bias_factor,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            world.add_body(Body::new(Vec2::new(10.0, 1.0), f32::MAX));
+            let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            block.position = Vec2::new(0.0, 0.8);
+            let block = world.add_body(block);
+            world.step(1.0 / 60.0).unwrap();
+            world.get_body(block.id()).unwrap().velocity.y
+        };
+        assert!(overlap(0.8) > overlap(0.2));
+        assert_eq!(overlap(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let ground = world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        block.position = Vec2::new(0.0, 0.95);
+        let block = world.add_body(block);
+        let mut platform = Body::new(Vec2::new(2.0, 0.5), 1.0);
+        platform.set_body_type(BodyType::Kinematic);
+        platform.position = Vec2::new(5.0, 5.0);
+        world.add_body(platform);
+        crate::joint::JointBuilder::revolute(ground, block)
+            .anchor(Vec2::new(0.0, 1.0))
+            .build(&mut world)
+            .unwrap();
+        world.step(1.0 / 60.0).unwrap();
+
+        assert_eq!(world.body_count(), 3);
+        assert_eq!(world.awake_body_count(), 2);
+        assert_eq!(world.joint_count(), 1);
+        assert_eq!(world.iter_joints().count(), 1);
+        assert_eq!(world.contact_count(), 1);
+        let (key, _) = world.iter_arbiters().next().unwrap();
+        let (ground, block) = (world.body(ground).unwrap(), world.body(block).unwrap());
+        assert_eq!(*key, ArbiterKey::new(ground, block));
+    }
+
+    #[test]
+    fn test_contacts_between() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        let platform = world.add_body(Body::new(Vec2::new(4.0, 1.0), f32::MAX));
+        let mut player = Body::new(Vec2::new(1.0, 1.0), 1.0);
+        player.position = Vec2::new(0.0, 0.95);
+        let player = world.add_body(player);
+        let mut other = Body::new(Vec2::new(4.0, 1.0), f32::MAX);
+        other.position = Vec2::new(10.0, 0.0);
+        let other = world.add_body(other);
+        world.step(1.0 / 60.0).unwrap();
+
+        let manifold = world.contacts_between(player, platform).unwrap();
+        assert_eq!(manifold.point_count(), 2);
+        assert!(world.contacts_between(platform, player).is_some());
+        assert!(world.contacts_between(player, other).is_none());
+    }
+
+    #[test]
+    fn test_auto_clear_forces() {
+        let mut world = World::from_def(WorldDef {
+            gravity: Vec2::default(),
+            context: WorldContext {
+                auto_clear_forces: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let handle = world.add_body(Body::new(Vec2::new(1.0, 1.0), 1.0));
+        world
+            .body_mut(handle)
+            .unwrap()
+            .add_force(Vec2::new(6.0, 0.0));
+        world.step(0.5).unwrap();
+        world.step_substeps(0.5, 4).unwrap();
+        assert!((world.body(handle).unwrap().velocity.x - 6.0).abs() < 1e-4);
+
+        // The wind is not kept along with the force
+        world.world_context.wind = Some(Wind::constant(Vec2::new(0.0, 10.0), 1.0));
+        world.step(0.5).unwrap();
+        assert_eq!(world.body(handle).unwrap().force, Vec2::new(6.0, 0.0));
+
+        world.clear_forces();
+        world.world_context.wind = None;
+        let velocity = world.body(handle).unwrap().velocity;
+        world.step(0.5).unwrap();
+        assert_eq!(world.body(handle).unwrap().velocity, velocity);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        let ground = world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut bob = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        bob.position = Vec2::new(8.0, 5.0);
+        let bob = world.add_body(bob);
+        crate::joint::JointBuilder::revolute(ground, bob)
+            .anchor(Vec2::new(5.0, 5.0))
+            .limits(-1.0, 1.0)
+            .build(&mut world)
+            .unwrap();
+        for i in 0..4 {
+            let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            box_.friction = 0.5;
+            box_.position = Vec2::new(0.0, 1.0 + i as f32);
+            world.add_body(box_);
+        }
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+
+        let json = serde_json::to_string(&world).unwrap();
+        let mut loaded: World = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.contact_count(), world.contact_count());
+        for _ in 0..30 {
+            world.step(1.0 / 60.0).unwrap();
+            loaded.step(1.0 / 60.0).unwrap();
+        }
+        for (loaded, body) in loaded.iter_bodies().zip(world.iter_bodies()) {
+            assert_eq!(loaded.id, body.id);
+            assert_eq!(loaded.position, body.position);
+            assert_eq!(loaded.velocity, body.velocity);
+        }
+        // New bodies do not take the ids of the loaded ones
+        let max_id = loaded.iter_bodies().map(|body| body.id).max().unwrap();
+        assert!(Body::new(Vec2::new(1.0, 1.0), 1.0).id > max_id);
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn test_snapshot() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        for i in 0..5 {
+            let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            box_.position = Vec2::new(0.1 * i as f32, 1.0 + i as f32);
+            world.add_body(box_);
+        }
+        for _ in 0..60 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+        let snapshot = world.snapshot();
+        let run = |world: &mut World| {
+            for _ in 0..30 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            world
+                .iter_bodies()
+                .map(|body| (body.position, body.rotation, body.velocity))
+                .collect::<Vec<_>>()
+        };
+        let expected = run(&mut world);
+
+        world.restore(&snapshot).unwrap();
+        assert_eq!(run(&mut world), expected);
+        assert!(matches!(
+            world.restore(&snapshot[..10]),
+            Err(Sylt2DErrors::Snapshot(SnapshotErrors::Truncated))
+        ));
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn test_delta_snapshot() {
+        let mut server = World::new(Vec2::new(0.0, -10.0), 10);
+        server.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        for i in 0..5 {
+            let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            box_.position = Vec2::new(0.1 * i as f32, 1.0 + i as f32);
+            server.add_body(box_);
+        }
+        let mut client = World::new(Vec2::new(0.0, -10.0), 10);
+        let (mut sent, mut received) = (DeltaBaseline::default(), DeltaBaseline::default());
+        let sync = |server: &World,
+                    client: &mut World,
+                    sent: &mut DeltaBaseline,
+                    received: &mut DeltaBaseline| {
+            let delta = server.delta_snapshot(sent);
+            client.apply_delta(&delta).unwrap();
+            server.save_baseline(sent);
+            client.save_baseline(received);
+            assert_eq!(sent, received);
+            delta.len()
+        };
+
+        // The first delta carries all bodies
+        assert!(sync(&server, &mut client, &mut sent, &mut received) > 0);
+        assert_eq!(client.body_count(), 6);
+        let full = server.snapshot().len();
+        for _ in 0..30 {
+            server.step(1.0 / 60.0).unwrap();
+            let size = sync(&server, &mut client, &mut sent, &mut received);
+            assert!(size < full / 4);
+        }
+        for (server_body, client_body) in server.iter_bodies().zip(client.iter_bodies()) {
+            assert_eq!(server_body.position, client_body.position);
+            assert!((server_body.velocity - client_body.velocity).length() <= VELOCITY_STEP);
+        }
+
+        // Fast bodies keep their speed
+        let fast = server.iter_bodies().last().unwrap().handle();
+        server.body_mut(fast).unwrap().velocity = Vec2::new(1000.0, -300.0);
+        sync(&server, &mut client, &mut sent, &mut received);
+        assert_eq!(
+            client.body(fast).unwrap().velocity,
+            Vec2::new(1000.0, -300.0)
+        );
+
+        // Nothing moved since the last delta, only the time is sent
+        let unchanged = sync(&server, &mut client, &mut sent, &mut received);
+        assert!(unchanged <= 8);
+        let removed = server.iter_bodies().last().unwrap().id;
+        server.remove_body(removed);
+        assert!(sync(&server, &mut client, &mut sent, &mut received) > unchanged);
+        assert!(client.get_body(removed).is_none());
+
+        // A client that lost a body rejects the whole delta
+        let lost = server.iter_bodies().last().unwrap().handle();
+        client.remove_body(lost.id());
+        let gone = server.iter_bodies().nth(1).unwrap().id;
+        server.remove_body(gone);
+        let added = server.add_body(Body::new(Vec2::new(1.0, 1.0), 1.0));
+        server.step(1.0 / 60.0).unwrap();
+        let before = client.snapshot();
+        assert!(matches!(
+            client.apply_delta(&server.delta_snapshot(&sent)),
+            Err(Sylt2DErrors::BodyNotFound(id)) if id == lost.id()
+        ));
+        assert_eq!(client.snapshot(), before);
+        assert!(client.get_body(gone).is_some());
+        assert!(client.body(added).is_none());
+        assert!(matches!(
+            client.apply_delta(&[0xff; 3]),
+            Err(Sylt2DErrors::Snapshot(_))
+        ));
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+        world.world_context.warm_starting = true;
+        let ground = world.add_body(Body::new(Vec2::new(20.0, 1.0), f32::MAX));
+        let mut bob = Body::new(Vec2::new(0.5, 0.5), 1.0);
+        bob.position = Vec2::new(8.0, 5.0);
+        let bob = world.add_body(bob);
+        crate::joint::JointBuilder::revolute(ground, bob)
+            .anchor(Vec2::new(5.0, 5.0))
+            .build(&mut world)
+            .unwrap();
+        for i in 0..4 {
+            let mut box_ = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            box_.position = Vec2::new(0.1 * i as f32, 1.0 + i as f32);
+            world.add_body(box_);
+        }
+        for _ in 0..30 {
+            world.step(1.0 / 60.0).unwrap();
+        }
+
+        let mut state = RollbackState::default();
+        world.save_state(&mut state);
+        assert_eq!(state.bodies.len(), 5);
+        let run = |world: &mut World| {
+            for _ in 0..10 {
+                world.step(1.0 / 60.0).unwrap();
+            }
+            world
+                .iter_bodies()
+                .map(|body| (body.position, body.rotation, body.velocity))
+                .collect::<Vec<_>>()
+        };
+        let expected = run(&mut world);
+        world.load_state(&state).unwrap();
+        assert_eq!(run(&mut world), expected);
+
+        // Saving again reuses the buffers
+        let capacity = state.bodies.capacity();
+        world.save_state(&mut state);
+        assert_eq!(state.bodies.capacity(), capacity);
+
+        world.remove_body(bob.id());
+        world.step(1.0 / 60.0).unwrap();
+        let poses: Vec<_> = world
+            .iter_bodies()
+            .map(|body| (body.position, body.velocity))
+            .collect();
+        let time = world.world_context.time;
+        assert!(matches!(
+            world.load_state(&state),
+            Err(Sylt2DErrors::BodyNotFound(_))
+        ));
+        for (body, &(position, velocity)) in world.iter_bodies().zip(poses.iter()) {
+            assert_eq!(body.position, position);
+            assert_eq!(body.velocity, velocity);
+        }
+        assert_eq!(world.world_context.time, time);
+    }
+}
@@ -0,0 +1,102 @@
+//! Checks that stepping a world does not allocate once its buffers have grown.
+//!
+//! The counting allocator is the global allocator of this test binary only, so
+//! the other tests run with the system allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::f32::consts::PI;
+use sylt_2d::body::Body;
+use sylt_2d::joint::Joint;
+use sylt_2d::math_utils::Vec2;
+use sylt_2d::world::World;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// Counts the allocations of each thread
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_step_does_not_allocate() {
+    let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+    let mut ground = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
+    ground.position = Vec2::new(0.0, -10.0);
+    ground.friction = 0.2;
+    world.add_body(ground);
+    let mut blocks = Vec::new();
+    for row in 0..10 {
+        for column in 0..10 - row {
+            let mut block = Body::new(Vec2::new(1.0, 1.0), 1.0);
+            block.friction = 0.2;
+            block.position = Vec2::new(
+                column as f32 * 1.125 + row as f32 * 0.5625 - 5.0,
+                row as f32 * 1.0 + 0.5,
+            );
+            blocks.push(world.add_body(block));
+        }
+    }
+    // The first two blocks of the bottom row and the one on top of them
+    let ring: Vec<&Body> = [blocks[0], blocks[1], blocks[10]]
+        .iter()
+        .map(|&block| world.body(block).unwrap())
+        .collect();
+    let area = Joint::area(&ring);
+    world.add_joint(area);
+    for _ in 0..120 {
+        world.step(1.0 / 60.0).unwrap();
+    }
+    world.step_substeps(1.0 / 60.0, 4).unwrap();
+
+    let before = ALLOCATIONS.with(Cell::get);
+    for _ in 0..10 {
+        world.step(1.0 / 60.0).unwrap();
+        world.step_substeps(1.0 / 60.0, 4).unwrap();
+    }
+    // The job queue of rayon allocates a block every few dozen jobs sent to
+    // its threads
+    #[cfg(not(feature = "parallel"))]
+    assert_eq!(ALLOCATIONS.with(Cell::get), before);
+    #[cfg(feature = "parallel")]
+    assert!(ALLOCATIONS.with(Cell::get) - before <= 1);
+    assert_eq!(world.pipeline.islands().len(), 1);
+}
+
+#[test]
+fn test_polygon_clipping_does_not_allocate() {
+    let mut world = World::new(Vec2::new(0.0, -10.0), 10);
+    let mut ground = Body::new(Vec2::new(100.0, 20.0), f32::MAX);
+    ground.position = Vec2::new(0.0, -10.0);
+    world.add_body(ground);
+    let hexagon: Vec<Vec2> = (0..6)
+        .map(|i| Vec2::new(f32::cos(i as f32 * PI / 3.0), f32::sin(i as f32 * PI / 3.0)))
+        .collect();
+    for i in 0..5 {
+        let mut polygon = Body::new_polygon(hexagon.clone(), 1.0).unwrap();
+        polygon.position = Vec2::new(i as f32 * 3.0, 0.9);
+        world.add_body(polygon);
+    }
+    for _ in 0..60 {
+        world.step(1.0 / 60.0).unwrap();
+    }
+
+    let before = ALLOCATIONS.with(Cell::get);
+    world.step(1.0 / 60.0).unwrap();
+    assert_eq!(ALLOCATIONS.with(Cell::get), before);
+    assert!(world.last_step_stats().contacts >= 5);
+}